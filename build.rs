@@ -1,3 +1,7 @@
 fn main() {
-    println!("cargo:rustc-link-lib=framework=ScreenCaptureKit");
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!("cargo:rustc-link-lib=framework=ScreenCaptureKit");
+        println!("cargo:rustc-link-lib=framework=CoreMedia");
+        println!("cargo:rustc-link-lib=framework=CoreVideo");
+    }
 }
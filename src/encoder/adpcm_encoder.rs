@@ -0,0 +1,193 @@
+use core_foundation::error::CFError;
+
+use crate::{
+    media::{audio_frame::AudioFrame, encoded_packet::EncodedPacket},
+    utils::error::create_sc_error,
+};
+
+use super::{pcm_encoder::to_i16, Encoder};
+
+const STEP_SIZES: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+const INDEX_ADJUST: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ChannelState {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ChannelState {
+    #[allow(clippy::cast_sign_loss)]
+    fn encode(&mut self, sample: i16) -> u8 {
+        let step = STEP_SIZES[self.step_index as usize];
+        let mut diff = i32::from(sample) - self.predictor;
+        let mut nibble = 0;
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+        let mut delta = step >> 3;
+        let mut threshold = step;
+        for bit in [4, 2, 1] {
+            if diff >= threshold {
+                nibble |= bit;
+                diff -= threshold;
+                delta += threshold;
+            }
+            threshold >>= 1;
+        }
+        self.apply(nibble, delta);
+        nibble
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_SIZES[self.step_index as usize];
+        let mut delta = step >> 3;
+        for (bit, shift) in [(4, 0), (2, 1), (1, 2)] {
+            if nibble & bit != 0 {
+                delta += step >> shift;
+            }
+        }
+        self.apply(nibble, delta);
+        self.sample()
+    }
+
+    fn apply(&mut self, nibble: u8, delta: i32) {
+        self.predictor += if nibble & 8 == 0 { delta } else { -delta };
+        self.predictor = self
+            .predictor
+            .clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+        self.step_index = (self.step_index + INDEX_ADJUST[usize::from(nibble & 7)]).clamp(0, 88);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    const fn sample(self) -> i16 {
+        self.predictor as i16
+    }
+}
+
+/// Compresses 16 bit PCM 4:1 with IMA ADPCM, one packet per [`AudioFrame`].
+///
+/// Each packet starts with a four byte header per channel holding the predictor as a
+/// little endian `i16`, the step index and a reserved byte, followed by the interleaved
+/// four bit codes, two per byte with the first sample in the low nibble.
+#[derive(Debug, Clone, Default)]
+pub struct ImaAdpcmEncoder {
+    channels: Vec<ChannelState>,
+}
+
+impl ImaAdpcmEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Encoder<AudioFrame> for ImaAdpcmEncoder {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<EncodedPacket>, CFError> {
+        let channels = usize::from(frame.channels);
+        if channels == 0 {
+            return Err(create_sc_error(
+                "ImaAdpcmEncoder needs at least one channel",
+            ));
+        }
+        self.channels.resize(channels, ChannelState::default());
+
+        let mut data = Vec::with_capacity(channels * 4 + frame.samples.len() / 2 + 1);
+        for state in &self.channels {
+            data.extend_from_slice(&state.sample().to_le_bytes());
+            data.push(state.step_index as u8);
+            data.push(0);
+        }
+        let mut pending = None;
+        for (i, sample) in frame.samples.iter().enumerate() {
+            let nibble = self.channels[i % channels].encode(to_i16(*sample));
+            match pending.take() {
+                None => pending = Some(nibble),
+                Some(low) => data.push(low | (nibble << 4)),
+            }
+        }
+        data.extend(pending);
+        let packet = EncodedPacket::new(data, frame.pts, true).with_duration(frame.duration());
+        Ok(vec![packet])
+    }
+}
+
+/// Decodes a packet written by [`ImaAdpcmEncoder`] back to interleaved 16 bit samples.
+///
+/// # Errors
+///
+/// This function will return an error if the packet is shorter than its headers.
+pub fn decode(packet: &[u8], channels: u16, frame_count: usize) -> Result<Vec<i16>, CFError> {
+    let channels = usize::from(channels);
+    let header_len = channels * 4;
+    if channels == 0 || packet.len() < header_len + (frame_count * channels + 1) / 2 {
+        return Err(create_sc_error("invalid IMA ADPCM packet"));
+    }
+    let mut states: Vec<ChannelState> = packet[..header_len]
+        .chunks_exact(4)
+        .map(|header| ChannelState {
+            predictor: i32::from(i16::from_le_bytes([header[0], header[1]])),
+            step_index: i32::from(header[2]).min(88),
+        })
+        .collect();
+    let nibbles = packet[header_len..]
+        .iter()
+        .flat_map(|byte| [byte & 0x0F, byte >> 4]);
+    Ok(nibbles
+        .take(frame_count * channels)
+        .enumerate()
+        .map(|(i, nibble)| states[i % channels].decode(nibble))
+        .collect())
+}
+
+#[cfg(test)]
+mod adpcm_encoder_test {
+    use std::f32::consts::TAU;
+
+    use crate::{
+        encoder::{pcm_encoder::to_i16, Encoder},
+        media::{audio_frame::AudioFrame, media_time::MediaTime},
+    };
+
+    use super::{decode, ImaAdpcmEncoder};
+
+    #[allow(clippy::cast_precision_loss)]
+    fn sine(frames: usize, offset: usize) -> Vec<f32> {
+        (offset..offset + frames)
+            .flat_map(|i| {
+                let left = (TAU * 440.0 * i as f32 / 48_000.0).sin() * 0.5;
+                [left, -left]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_is_close() {
+        let mut encoder = ImaAdpcmEncoder::new();
+        for block in 0..4 {
+            let samples = sine(1024, block * 1024);
+            let frame = AudioFrame::new(48_000, 2, samples.clone(), MediaTime::ZERO);
+            let packets = encoder.encode(&frame).expect("should encode");
+            assert_eq!(packets[0].data.len(), 8 + 1024);
+            let decoded = decode(&packets[0].data, 2, 1024).expect("should decode");
+            let max_error = samples
+                .iter()
+                .zip(&decoded)
+                .skip(if block == 0 { 64 } else { 0 })
+                .map(|(original, decoded)| {
+                    (i32::from(to_i16(*original)) - i32::from(*decoded)).abs()
+                })
+                .max()
+                .unwrap();
+            assert!(max_error < 600, "error {max_error} in block {block}");
+        }
+    }
+}
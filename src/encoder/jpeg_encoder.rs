@@ -0,0 +1,671 @@
+use core_foundation::error::CFError;
+
+use crate::{
+    media::{
+        encoded_packet::EncodedPacket,
+        video_frame::{PixelFormat, VideoFrame},
+    },
+    utils::error::create_sc_error,
+};
+
+use super::Encoder;
+
+/// The natural order index of each coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// The example quantization tables of ITU-T T.81 Annex K, in natural order.
+const LUMA_QUANTIZATION: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANTIZATION: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// The example Huffman tables of ITU-T T.81 Annex K, as code counts per length and symbols.
+const DC_LUMA_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMA_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_SYMBOLS: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const AC_LUMA_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const AC_LUMA_SYMBOLS: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+const AC_CHROMA_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMA_SYMBOLS: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+/// A Huffman code per symbol, as the code and its length in bits.
+struct HuffmanTable([(u16, u8); 256]);
+
+impl HuffmanTable {
+    /// Assigns canonical codes to `symbols`, ordered by code length.
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(bits: &[u8; 16], symbols: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut symbols = symbols.iter();
+        for (length, &count) in (1..=16).zip(bits) {
+            for _ in 0..count {
+                if let Some(&symbol) = symbols.next() {
+                    codes[usize::from(symbol)] = (code, length);
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Self(codes)
+    }
+}
+
+/// Collects entropy coded bits, stuffing a zero byte after every `0xFF`.
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+impl BitWriter {
+    fn write(&mut self, bits: u16, length: u8) {
+        self.buffer = (self.buffer << length) | u32::from(bits);
+        self.count += u32::from(length);
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.buffer >> self.count) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    /// Pads the last byte with one bits.
+    fn flush(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write((1 << (8 - self.count)) - 1, (8 - self.count) as u8);
+        }
+        self.out
+    }
+}
+
+/// One full range component of a frame, read with the edges repeated.
+struct Component {
+    width: usize,
+    height: usize,
+    samples: Vec<f32>,
+}
+
+impl Component {
+    fn sample(&self, x: usize, y: usize) -> f32 {
+        self.samples[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
+
+/// A baseline JPEG encoder producing one JFIF image per frame, with 4:2:0 chroma
+/// subsampling, e.g. for MJPEG previews.
+///
+/// [`PixelFormat::Nv12`] frames are expanded from video range to the full range JFIF
+/// expects. Every packet is a keyframe.
+#[derive(Debug, Clone)]
+pub struct JpegEncoder {
+    quality: u8,
+    luma_quantization: [u16; 64],
+    chroma_quantization: [u16; 64],
+    cosines: [[f32; 8]; 8],
+}
+
+impl JpegEncoder {
+    /// The codec id used when muxing JPEG frames into Matroska.
+    pub const CODEC_ID: &'static str = "V_MJPEG";
+
+    /// Creates an encoder with a quality from 1 to 100, scaling the quantization tables
+    /// like libjpeg does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quality` is not between 1 and 100.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(quality: u8) -> Self {
+        assert!(
+            (1..=100).contains(&quality),
+            "the quality must be between 1 and 100"
+        );
+        let scale = if quality < 50 {
+            5000 / u32::from(quality)
+        } else {
+            200 - 2 * u32::from(quality)
+        };
+        let scaled = |table: [u16; 64]| {
+            table.map(|value| {
+                u16::try_from(((u32::from(value) * scale + 50) / 100).clamp(1, 255)).unwrap_or(255)
+            })
+        };
+        let mut cosines = [[0.0; 8]; 8];
+        for (u, row) in cosines.iter_mut().enumerate() {
+            let normalization = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            for (x, cosine) in row.iter_mut().enumerate() {
+                let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+                *cosine = normalization * angle.cos() / 2.0;
+            }
+        }
+        Self {
+            quality,
+            luma_quantization: scaled(LUMA_QUANTIZATION),
+            chroma_quantization: scaled(CHROMA_QUANTIZATION),
+            cosines,
+        }
+    }
+
+    pub const fn quality(&self) -> u8 {
+        self.quality
+    }
+
+    /// Encodes `frame` as a JFIF image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is empty or larger than 65535 pixels in either
+    /// dimension.
+    pub fn encode_frame(&self, frame: &VideoFrame) -> Result<Vec<u8>, CFError> {
+        let (Ok(width), Ok(height)) = (u16::try_from(frame.width), u16::try_from(frame.height))
+        else {
+            return Err(create_sc_error("the frame is too large for JPEG"));
+        };
+        if width == 0 || height == 0 {
+            return Err(create_sc_error("the frame is empty"));
+        }
+        let [luma, cb, cr] = components(frame);
+
+        let mut out = Vec::with_capacity(frame.width as usize * frame.height as usize / 4);
+        out.extend_from_slice(&[0xFF, 0xD8]);
+        out.extend_from_slice(&[
+            0xFF, 0xE0, 0, 16, b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0,
+        ]);
+        out.extend_from_slice(&[0xFF, 0xDB, 0, 132]);
+        for (id, table) in [(0, &self.luma_quantization), (1, &self.chroma_quantization)] {
+            out.push(id);
+            out.extend(ZIGZAG.iter().map(|&i| table[i].to_be_bytes()[1]));
+        }
+        out.extend_from_slice(&[0xFF, 0xC0, 0, 17, 8]);
+        out.extend_from_slice(&height.to_be_bytes());
+        out.extend_from_slice(&width.to_be_bytes());
+        out.extend_from_slice(&[3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        let tables: [(u8, &[u8; 16], &[u8]); 4] = [
+            (0x00, &DC_LUMA_BITS, &DC_SYMBOLS),
+            (0x10, &AC_LUMA_BITS, &AC_LUMA_SYMBOLS),
+            (0x01, &DC_CHROMA_BITS, &DC_SYMBOLS),
+            (0x11, &AC_CHROMA_BITS, &AC_CHROMA_SYMBOLS),
+        ];
+        let length: usize = 2 + tables
+            .iter()
+            .map(|(_, _, symbols)| 17 + symbols.len())
+            .sum::<usize>();
+        out.extend_from_slice(&[0xFF, 0xC4]);
+        out.extend_from_slice(&u16::try_from(length).unwrap_or_default().to_be_bytes());
+        for (class_and_id, bits, symbols) in tables {
+            out.push(class_and_id);
+            out.extend_from_slice(bits);
+            out.extend_from_slice(symbols);
+        }
+        out.extend_from_slice(&[0xFF, 0xDA, 0, 12, 3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+
+        let [dc_luma, ac_luma, dc_chroma, ac_chroma] =
+            tables.map(|(_, bits, symbols)| HuffmanTable::new(bits, symbols));
+        let mut bits = BitWriter {
+            out,
+            buffer: 0,
+            count: 0,
+        };
+        let mut predictions = [0; 3];
+        for mcu_y in (0..usize::from(height)).step_by(16) {
+            for mcu_x in (0..usize::from(width)).step_by(16) {
+                for (block_x, block_y) in [(0, 0), (8, 0), (0, 8), (8, 8)] {
+                    let block = self.transform(&luma, mcu_x + block_x, mcu_y + block_y);
+                    let block = quantize(&block, &self.luma_quantization);
+                    encode_block(&mut bits, &block, &mut predictions[0], &dc_luma, &ac_luma);
+                }
+                for (chroma, prediction) in [&cb, &cr].into_iter().zip(&mut predictions[1..]) {
+                    let block = self.transform(chroma, mcu_x / 2, mcu_y / 2);
+                    let block = quantize(&block, &self.chroma_quantization);
+                    encode_block(&mut bits, &block, prediction, &dc_chroma, &ac_chroma);
+                }
+            }
+        }
+        let mut out = bits.flush();
+        out.extend_from_slice(&[0xFF, 0xD9]);
+        Ok(out)
+    }
+
+    /// Returns the DCT coefficients of the 8 by 8 block at `x`, `y`, in natural order.
+    fn transform(&self, component: &Component, x: usize, y: usize) -> [f32; 64] {
+        let mut rows = [0.0; 64];
+        for row in 0..8 {
+            let samples: [f32; 8] =
+                std::array::from_fn(|column| component.sample(x + column, y + row) - 128.0);
+            for (u, cosines) in self.cosines.iter().enumerate() {
+                rows[row * 8 + u] = cosines
+                    .iter()
+                    .zip(samples)
+                    .map(|(cosine, sample)| cosine * sample)
+                    .sum();
+            }
+        }
+        let mut coefficients = [0.0; 64];
+        for (v, cosines) in self.cosines.iter().enumerate() {
+            for u in 0..8 {
+                coefficients[v * 8 + u] = cosines
+                    .iter()
+                    .enumerate()
+                    .map(|(row, cosine)| cosine * rows[row * 8 + u])
+                    .sum();
+            }
+        }
+        coefficients
+    }
+}
+
+impl Default for JpegEncoder {
+    /// Creates an encoder with a quality of 75.
+    fn default() -> Self {
+        Self::new(75)
+    }
+}
+
+impl Encoder<VideoFrame> for JpegEncoder {
+    fn encode(&mut self, frame: &VideoFrame) -> Result<Vec<EncodedPacket>, CFError> {
+        let data = self.encode_frame(frame)?;
+        Ok(vec![EncodedPacket::new(data, frame.pts, true)])
+    }
+}
+
+/// Splits `frame` into the full range BT.601 luma and half resolution chroma of JFIF.
+fn components(frame: &VideoFrame) -> [Component; 3] {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+    let chroma = || Component {
+        width: chroma_width,
+        height: chroma_height,
+        samples: vec![0.0; chroma_width * chroma_height],
+    };
+    let mut luma = Component {
+        width,
+        height,
+        samples: vec![0.0; width * height],
+    };
+    let (mut cb, mut cr) = (chroma(), chroma());
+    let mut counts = vec![0.0f32; chroma_width * chroma_height];
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = rgb(frame, x, y);
+            luma.samples[y * width + x] = 0.114f32.mul_add(b, 0.299f32.mul_add(r, 0.587 * g));
+            let i = (y / 2) * chroma_width + x / 2;
+            cb.samples[i] += 0.5f32.mul_add(b, (-0.168_736f32).mul_add(r, -0.331_264 * g));
+            cr.samples[i] += (-0.081_312f32).mul_add(b, 0.5f32.mul_add(r, -0.418_688 * g));
+            counts[i] += 1.0;
+        }
+    }
+    for ((cb, cr), count) in cb.samples.iter_mut().zip(&mut cr.samples).zip(counts) {
+        *cb = *cb / count + 128.0;
+        *cr = *cr / count + 128.0;
+    }
+    [luma, cb, cr]
+}
+
+/// Returns the color of a pixel, converting [`PixelFormat::Nv12`] from BT.709 video range
+/// like [`bgra_to_ycbcr`](crate::media::video_frame::bgra_to_ycbcr) in reverse.
+fn rgb(frame: &VideoFrame, column: usize, row: usize) -> [f32; 3] {
+    match frame.pixel_format {
+        PixelFormat::Bgra => {
+            let pixel = &frame.planes[0].row(row)[column * 4..column * 4 + 3];
+            [pixel[2], pixel[1], pixel[0]].map(f32::from)
+        }
+        PixelFormat::Nv12 => {
+            let luma = (f32::from(frame.planes[0].row(row)[column]) - 16.0) / 219.0;
+            let chroma = &frame.planes[1].row(row / 2)[column / 2 * 2..column / 2 * 2 + 2];
+            let [cb, cr] = [chroma[0], chroma[1]].map(|value| (f32::from(value) - 128.0) / 224.0);
+            let r = 1.5748f32.mul_add(cr, luma);
+            let b = 1.8556f32.mul_add(cb, luma);
+            let g = 0.0722f32.mul_add(-b, 0.2126f32.mul_add(-r, luma)) / 0.7152;
+            [r, g, b].map(|value| value * 255.0)
+        }
+    }
+}
+
+/// Quantizes natural order coefficients into zigzag order, limited to the 10 bits of
+/// magnitude the Huffman tables have codes for.
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(coefficients: &[f32; 64], table: &[u16; 64]) -> [i16; 64] {
+    ZIGZAG.map(|i| {
+        (coefficients[i] / f32::from(table[i]))
+            .round()
+            .clamp(-1023.0, 1023.0) as i16
+    })
+}
+
+/// Returns the number of bits of `value`'s magnitude and its JPEG bit pattern.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+const fn magnitude(value: i16) -> (u8, u16) {
+    let size = (16 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 {
+        (value - 1) as u16 & ((1 << size) - 1)
+    } else {
+        value as u16
+    };
+    (size, bits)
+}
+
+fn encode_block(
+    bits: &mut BitWriter,
+    block: &[i16; 64],
+    prediction: &mut i16,
+    dc: &HuffmanTable,
+    ac: &HuffmanTable,
+) {
+    let (size, value) = magnitude(block[0] - *prediction);
+    *prediction = block[0];
+    let (code, length) = dc.0[usize::from(size)];
+    bits.write(code, length);
+    bits.write(value, size);
+    let mut zeros = 0;
+    for &coefficient in &block[1..] {
+        if coefficient == 0 {
+            zeros += 1;
+            continue;
+        }
+        while zeros > 15 {
+            let (code, length) = ac.0[0xF0];
+            bits.write(code, length);
+            zeros -= 16;
+        }
+        let (size, value) = magnitude(coefficient);
+        let (code, length) = ac.0[(zeros << 4) | usize::from(size)];
+        bits.write(code, length);
+        bits.write(value, size);
+        zeros = 0;
+    }
+    if zeros > 0 {
+        let (code, length) = ac.0[0x00];
+        bits.write(code, length);
+    }
+}
+
+#[cfg(test)]
+mod jpeg_encoder_test {
+    use std::collections::HashMap;
+
+    use crate::{
+        encoder::Encoder,
+        media::{
+            media_time::MediaTime,
+            video_frame::{bgra_to_ycbcr, VideoFrame},
+        },
+    };
+
+    use super::{JpegEncoder, ZIGZAG};
+
+    /// Reads entropy coded bits, dropping the zero bytes stuffed after `0xFF`.
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+        bit: u8,
+    }
+
+    impl BitReader<'_> {
+        fn read(&mut self, count: u8) -> u16 {
+            (0..count).fold(0, |value, _| {
+                let bit = (self.data[self.position] >> (7 - self.bit)) & 1;
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.bit = 0;
+                    self.position += if self.data[self.position] == 0xFF {
+                        2
+                    } else {
+                        1
+                    };
+                }
+                (value << 1) | u16::from(bit)
+            })
+        }
+
+        fn decode(&mut self, codes: &HashMap<(u8, u16), u8>) -> u8 {
+            let mut code = 0;
+            (1..=16)
+                .find_map(|length| {
+                    code = (code << 1) | self.read(1);
+                    codes.get(&(length, code)).copied()
+                })
+                .expect("invalid Huffman code")
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        fn read_value(&mut self, size: u8) -> i16 {
+            let value = self.read(size) as i16;
+            if size > 0 && value < 1 << (size - 1) {
+                value - (1 << size) + 1
+            } else {
+                value
+            }
+        }
+    }
+
+    /// Decodes the baseline 4:2:0 images [`JpegEncoder`] writes into BGRA pixels.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        clippy::too_many_lines
+    )]
+    fn decode(data: &[u8]) -> (usize, usize, Vec<u8>) {
+        assert_eq!(&data[..2], [0xFF, 0xD8]);
+        assert_eq!(&data[data.len() - 2..], [0xFF, 0xD9]);
+        let mut quantization = [[0u16; 64]; 2];
+        let mut tables = HashMap::new();
+        let (mut width, mut height) = (0, 0);
+        let mut i = 2;
+        let scan = loop {
+            let length = usize::from(u16::from_be_bytes([data[i + 2], data[i + 3]]));
+            let segment = &data[i + 4..i + 2 + length];
+            match data[i + 1] {
+                0xDB => {
+                    for table in segment.chunks(65) {
+                        for (k, &value) in table[1..].iter().enumerate() {
+                            quantization[usize::from(table[0])][ZIGZAG[k]] = value.into();
+                        }
+                    }
+                }
+                0xC0 => {
+                    height = usize::from(u16::from_be_bytes([segment[1], segment[2]]));
+                    width = usize::from(u16::from_be_bytes([segment[3], segment[4]]));
+                }
+                0xC4 => {
+                    let mut rest = segment;
+                    while !rest.is_empty() {
+                        let (mut code, mut symbol) = (0u16, 17);
+                        let mut codes = HashMap::new();
+                        for length in 1..=16u8 {
+                            for _ in 0..rest[usize::from(length)] {
+                                codes.insert((length, code), rest[symbol]);
+                                code += 1;
+                                symbol += 1;
+                            }
+                            code <<= 1;
+                        }
+                        tables.insert(rest[0], codes);
+                        rest = &rest[symbol..];
+                    }
+                }
+                0xDA => break &data[i + 2 + length..],
+                _ => {}
+            }
+            i += 2 + length;
+        };
+
+        let mut bits = BitReader {
+            data: scan,
+            position: 0,
+            bit: 0,
+        };
+        let (padded_width, padded_height) = ((width + 15) / 16 * 16, (height + 15) / 16 * 16);
+        let strides = [padded_width, padded_width / 2, padded_width / 2];
+        let mut planes = [
+            vec![0.0f32; padded_width * padded_height],
+            vec![0.0; padded_width * padded_height / 4],
+            vec![0.0; padded_width * padded_height / 4],
+        ];
+        let mut predictions = [0i16; 3];
+        for mcu_y in (0..height).step_by(16) {
+            for mcu_x in (0..width).step_by(16) {
+                let blocks = [
+                    (0, mcu_x, mcu_y),
+                    (0, mcu_x + 8, mcu_y),
+                    (0, mcu_x, mcu_y + 8),
+                    (0, mcu_x + 8, mcu_y + 8),
+                    (1, mcu_x / 2, mcu_y / 2),
+                    (2, mcu_x / 2, mcu_y / 2),
+                ];
+                for (component, x, y) in blocks {
+                    let table = u8::from(component > 0);
+                    let mut coefficients = [0.0f32; 64];
+                    let size = bits.decode(&tables[&table]);
+                    predictions[component] += bits.read_value(size);
+                    coefficients[0] = f32::from(predictions[component]);
+                    let mut k = 1;
+                    while k < 64 {
+                        let symbol = bits.decode(&tables[&(0x10 | table)]);
+                        if symbol == 0 {
+                            break;
+                        }
+                        k += usize::from(symbol >> 4);
+                        coefficients[ZIGZAG[k]] = f32::from(bits.read_value(symbol & 15));
+                        k += 1;
+                    }
+                    for (coefficient, &step) in coefficients
+                        .iter_mut()
+                        .zip(&quantization[usize::from(table)])
+                    {
+                        *coefficient *= f32::from(step);
+                    }
+                    let basis = |f: usize, x: usize| {
+                        let normalization = if f == 0 { 0.5f32.sqrt() } else { 1.0 };
+                        normalization
+                            * ((2 * x + 1) as f32 * f as f32 * std::f32::consts::PI / 16.0).cos()
+                    };
+                    for row in 0..8 {
+                        for column in 0..8 {
+                            let sum: f32 = (0..64)
+                                .map(|n| coefficients[n] * basis(n % 8, column) * basis(n / 8, row))
+                                .sum();
+                            planes[component][(y + row) * strides[component] + x + column] =
+                                sum / 4.0 + 128.0;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let luma = planes[0][y * strides[0] + x];
+                let cb = planes[1][y / 2 * strides[1] + x / 2] - 128.0;
+                let cr = planes[2][y / 2 * strides[2] + x / 2] - 128.0;
+                let r = 1.402f32.mul_add(cr, luma);
+                let g = (-0.714_136f32).mul_add(cr, (-0.344_136f32).mul_add(cb, luma));
+                let b = 1.772f32.mul_add(cb, luma);
+                pixels.extend([b, g, r, 255.0].map(|value| value.round().clamp(0.0, 255.0) as u8));
+            }
+        }
+        (width, height, pixels)
+    }
+
+    fn mean_error(a: &[u8], b: &[u8]) -> f64 {
+        let total: u64 = a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| u64::from(a.abs_diff(b)))
+            .sum();
+        #[allow(clippy::cast_precision_loss)]
+        let mean = total as f64 / a.len() as f64;
+        mean
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| {
+                (0..width)
+                    .flat_map(move |x| [(x * 6) as u8, (y * 9) as u8, ((x + y) * 3) as u8, 255])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pixels = gradient(37, 23);
+        let frame = VideoFrame::new_bgra(37, 23, pixels.clone(), MediaTime::new(3, 30));
+        let packets = JpegEncoder::new(90).encode(&frame).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].is_keyframe);
+        assert_eq!(packets[0].pts, MediaTime::new(3, 30));
+        let (width, height, decoded) = decode(&packets[0].data);
+        assert_eq!((width, height), (37, 23));
+        assert!(mean_error(&decoded, &pixels) < 2.0);
+
+        // Lower quality trades fidelity for size.
+        let low = JpegEncoder::new(20).encode_frame(&frame).unwrap();
+        assert!(low.len() < packets[0].data.len());
+        let (_, _, decoded) = decode(&low);
+        assert!(mean_error(&decoded, &pixels) < 6.0);
+
+        let empty = VideoFrame::new_bgra(0, 0, Vec::new(), MediaTime::ZERO);
+        assert!(JpegEncoder::default().encode_frame(&empty).is_err());
+    }
+
+    #[test]
+    fn test_nv12() {
+        let bgra = [40, 160, 220, 255];
+        let [y, cb, cr] = bgra_to_ycbcr(bgra);
+        let chroma = [cb, cr].repeat(9 * 5);
+        let frame = VideoFrame::new_nv12(17, 9, vec![y; 17 * 9], chroma, MediaTime::ZERO);
+        let (width, height, decoded) = decode(&JpegEncoder::new(95).encode_frame(&frame).unwrap());
+        assert_eq!((width, height), (17, 9));
+        for pixel in decoded.chunks(4) {
+            for (&actual, &expected) in pixel.iter().zip(&bgra) {
+                assert!(actual.abs_diff(expected) <= 3, "{pixel:?} != {bgra:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Encoders turning captured frames into [`EncodedPacket`]s.
+//!
+//! Hardware encoders implement the same [`Encoder`] trait as the pure Rust reference
+//! encoders in this module, so a capture to muxer pipeline can be exercised without them.
+pub mod adpcm_encoder;
+pub mod jpeg_encoder;
+pub mod pcm_encoder;
+pub mod qoi_encoder;
+
+use core_foundation::error::CFError;
+
+use crate::media::encoded_packet::EncodedPacket;
+
+pub trait Encoder<Frame> {
+    /// Encodes `frame`, returning the packets that became available, in decode order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the frame can not be encoded.
+    fn encode(&mut self, frame: &Frame) -> Result<Vec<EncodedPacket>, CFError>;
+
+    /// Drains packets held back by the encoder at the end of a stream.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the remaining packets can not be encoded.
+    fn flush(&mut self) -> Result<Vec<EncodedPacket>, CFError> {
+        Ok(Vec::new())
+    }
+
+    /// Codec configuration a muxer stores with the track, e.g. an `avcC` record.
+    fn codec_private(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod encoder_test {
+    use std::io::{self, Cursor};
+
+    use crate::{
+        media::{audio_frame::AudioFrame, media_time::MediaTime, video_frame::VideoFrame},
+        sink::matroska::{AudioCodec, AudioTrack, DocType, MatroskaWriter, VideoCodec, VideoTrack},
+    };
+
+    use super::{pcm_encoder::PcmEncoder, qoi_encoder::QoiEncoder, Encoder};
+
+    #[test]
+    fn test_capture_to_muxer() -> io::Result<()> {
+        let mut video_encoder = QoiEncoder::new();
+        let mut audio_encoder = PcmEncoder::float();
+        let mut writer = MatroskaWriter::new(Cursor::new(Vec::new()), DocType::Matroska);
+        let video = writer.add_video_track(VideoTrack::new(
+            VideoCodec::Other(QoiEncoder::CODEC_ID.into()),
+            16,
+            16,
+        ))?;
+        let audio = writer.add_audio_track(AudioTrack::new(AudioCodec::PcmFloat, 48_000.0, 2))?;
+
+        for i in 0..10_u8 {
+            let pts = MediaTime::new(i.into(), 10);
+            let frame = VideoFrame::filled_bgra(16, 16, [i, 0, 255 - i, 255], pts);
+            for packet in video_encoder.encode(&frame).expect("should encode") {
+                assert!(packet.is_keyframe);
+                assert_eq!(packet.pts, pts);
+                writer.write_packet(video, &packet)?;
+            }
+            let samples = AudioFrame::new(48_000, 2, vec![0.5; 9600], pts);
+            for packet in audio_encoder.encode(&samples).expect("should encode") {
+                assert_eq!(packet.data.len(), 9600 * 4);
+                assert_eq!(packet.duration, Some(MediaTime::new(4800, 48_000)));
+                writer.write_packet(audio, &packet)?;
+            }
+        }
+        let file = writer.finish()?.into_inner();
+        assert!(file.len() > 10 * 9600 * 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use core_foundation::error::CFError;
+
+use crate::media::{audio_frame::AudioFrame, encoded_packet::EncodedPacket};
+
+use super::Encoder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmSampleFormat {
+    /// Little endian 32 bit float, the sample format `SCStream` delivers.
+    F32,
+    /// Little endian signed 16 bit integers.
+    S16,
+}
+
+impl PcmSampleFormat {
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::S16 => 2,
+        }
+    }
+}
+
+/// Passes audio through as interleaved PCM, one packet per [`AudioFrame`].
+#[derive(Debug, Clone, Copy)]
+pub struct PcmEncoder {
+    format: PcmSampleFormat,
+}
+
+impl PcmEncoder {
+    pub const fn new(format: PcmSampleFormat) -> Self {
+        Self { format }
+    }
+    pub const fn float() -> Self {
+        Self::new(PcmSampleFormat::F32)
+    }
+    pub const fn format(&self) -> PcmSampleFormat {
+        self.format
+    }
+}
+
+impl Encoder<AudioFrame> for PcmEncoder {
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<EncodedPacket>, CFError> {
+        let mut data = Vec::with_capacity(frame.samples.len() * self.format.bytes_per_sample());
+        match self.format {
+            PcmSampleFormat::F32 => {
+                for sample in &frame.samples {
+                    data.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+            PcmSampleFormat::S16 => {
+                for sample in &frame.samples {
+                    data.extend_from_slice(&to_i16(*sample).to_le_bytes());
+                }
+            }
+        }
+        let packet = EncodedPacket::new(data, frame.pts, true).with_duration(frame.duration());
+        Ok(vec![packet])
+    }
+}
+
+/// Converts a float sample in `-1.0..=1.0` to a signed 16 bit sample, clipping out of range values.
+#[allow(clippy::cast_possible_truncation)]
+pub fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+#[cfg(test)]
+mod pcm_encoder_test {
+    use crate::{
+        encoder::Encoder,
+        media::{audio_frame::AudioFrame, media_time::MediaTime},
+    };
+
+    use super::{PcmEncoder, PcmSampleFormat};
+
+    #[test]
+    fn test_s16() {
+        let mut encoder = PcmEncoder::new(PcmSampleFormat::S16);
+        let frame = AudioFrame::new(16_000, 1, vec![0.0, 1.0, -1.0, 2.0], MediaTime::ZERO);
+        let packets = encoder.encode(&frame).expect("should encode");
+        let samples: Vec<i16> = packets[0]
+            .data
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, vec![0, i16::MAX, -i16::MAX, i16::MAX]);
+        assert_eq!(packets[0].duration, Some(MediaTime::new(4, 16_000)));
+    }
+}
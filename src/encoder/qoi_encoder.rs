@@ -0,0 +1,229 @@
+use core_foundation::error::CFError;
+
+use crate::{
+    media::{
+        encoded_packet::EncodedPacket,
+        video_frame::{PixelFormat, VideoFrame},
+    },
+    utils::error::create_sc_error,
+};
+
+use super::Encoder;
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK: u8 = 0xC0;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const HEADER_LEN: usize = 14;
+
+/// A lossless intra-only encoder producing one [QOI](https://qoiformat.org) image per frame.
+///
+/// Every packet is a keyframe and decode time always equals presentation time.
+#[derive(Debug, Default)]
+pub struct QoiEncoder;
+
+impl QoiEncoder {
+    /// The codec id used when muxing QOI frames into Matroska.
+    pub const CODEC_ID: &'static str = "V_QOI";
+
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Encoder<VideoFrame> for QoiEncoder {
+    fn encode(&mut self, frame: &VideoFrame) -> Result<Vec<EncodedPacket>, CFError> {
+        if frame.pixel_format != PixelFormat::Bgra {
+            return Err(create_sc_error("QoiEncoder only supports BGRA frames"));
+        }
+        let pixels = frame.packed_bgra().unwrap_or_default();
+        let data = encode_bgra(frame.width, frame.height, &pixels);
+        Ok(vec![EncodedPacket::new(data, frame.pts, true)])
+    }
+}
+
+const fn index_position([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+/// Encodes tightly packed BGRA pixels as a QOI image with an alpha channel.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss
+)]
+pub fn encode_bgra(width: u32, height: u32, bgra: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + bgra.len() / 2 + END_MARKER.len());
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.extend_from_slice(&[4, 0]);
+
+    let mut index = [[0_u8; 4]; 64];
+    let mut previous = [0, 0, 0, 255];
+    let mut run = 0_u8;
+    let pixel_count = bgra.len() / 4;
+    for (i, chunk) in bgra.chunks_exact(4).enumerate() {
+        let pixel = [chunk[2], chunk[1], chunk[0], chunk[3]];
+        if pixel == previous {
+            run += 1;
+            if run == 62 || i + 1 == pixel_count {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+        let position = index_position(pixel);
+        if index[position] == pixel {
+            out.push(OP_INDEX | position as u8);
+        } else {
+            index[position] = pixel;
+            if pixel[3] == previous[3] {
+                let dr = pixel[0].wrapping_sub(previous[0]) as i8;
+                let dg = pixel[1].wrapping_sub(previous[1]) as i8;
+                let db = pixel[2].wrapping_sub(previous[2]) as i8;
+                let red_luma = dr.wrapping_sub(dg);
+                let blue_luma = db.wrapping_sub(dg);
+                if (-2..2).contains(&dr) && (-2..2).contains(&dg) && (-2..2).contains(&db) {
+                    out.push(
+                        OP_DIFF
+                            | (((dr + 2) as u8) << 4)
+                            | (((dg + 2) as u8) << 2)
+                            | (db + 2) as u8,
+                    );
+                } else if (-32..32).contains(&dg)
+                    && (-8..8).contains(&red_luma)
+                    && (-8..8).contains(&blue_luma)
+                {
+                    out.push(OP_LUMA | (dg + 32) as u8);
+                    out.push((((red_luma + 8) as u8) << 4) | (blue_luma + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[OP_RGB, pixel[0], pixel[1], pixel[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[OP_RGBA, pixel[0], pixel[1], pixel[2], pixel[3]]);
+            }
+        }
+        previous = pixel;
+    }
+    out.extend_from_slice(&END_MARKER);
+    out
+}
+
+/// Decodes a QOI image into its width, height and tightly packed BGRA pixels.
+///
+/// # Errors
+///
+/// This function will return an error if `data` is not a valid QOI image.
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+pub fn decode_bgra(data: &[u8]) -> Result<(u32, u32, Vec<u8>), CFError> {
+    let invalid = || create_sc_error("invalid QOI image");
+    if data.len() < HEADER_LEN + END_MARKER.len() || &data[..4] != b"qoif" {
+        return Err(invalid());
+    }
+    let width = u32::from_be_bytes(data[4..8].try_into().map_err(|_| invalid())?);
+    let height = u32::from_be_bytes(data[8..12].try_into().map_err(|_| invalid())?);
+    let pixel_count = width as usize * height as usize;
+
+    let mut out = Vec::with_capacity(pixel_count * 4);
+    let mut index = [[0_u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut bytes = data[HEADER_LEN..data.len() - END_MARKER.len()]
+        .iter()
+        .copied();
+    let mut next = || bytes.next().ok_or_else(invalid);
+    while out.len() < pixel_count * 4 {
+        let op = next()?;
+        let mut run = 1;
+        if op == OP_RGB {
+            pixel = [next()?, next()?, next()?, pixel[3]];
+        } else if op == OP_RGBA {
+            pixel = [next()?, next()?, next()?, next()?];
+        } else {
+            match op & MASK {
+                OP_INDEX => pixel = index[usize::from(op)],
+                OP_DIFF => {
+                    pixel[0] = pixel[0].wrapping_add(((op >> 4) & 3).wrapping_sub(2));
+                    pixel[1] = pixel[1].wrapping_add(((op >> 2) & 3).wrapping_sub(2));
+                    pixel[2] = pixel[2].wrapping_add((op & 3).wrapping_sub(2));
+                }
+                OP_LUMA => {
+                    let second = next()?;
+                    let dg = (op & 0x3F).wrapping_sub(32);
+                    pixel[0] = pixel[0].wrapping_add(dg.wrapping_add(second >> 4).wrapping_sub(8));
+                    pixel[1] = pixel[1].wrapping_add(dg);
+                    pixel[2] =
+                        pixel[2].wrapping_add(dg.wrapping_add(second & 0x0F).wrapping_sub(8));
+                }
+                _ => run = usize::from(op & 0x3F) + 1,
+            }
+        }
+        index[index_position(pixel)] = pixel;
+        for _ in 0..run {
+            out.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    out.truncate(pixel_count * 4);
+    Ok((width, height, out))
+}
+
+#[cfg(test)]
+mod qoi_encoder_test {
+    use crate::{
+        encoder::Encoder,
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+    };
+
+    use super::{decode_bgra, encode_bgra, QoiEncoder};
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| {
+                (0..width).flat_map(move |x| {
+                    let alpha = if (x + y) % 7 == 0 { 128 } else { 255 };
+                    [(x * 3) as u8, (y * 5) as u8, ((x * y) % 251) as u8, alpha]
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pixels = gradient(37, 23);
+        let encoded = encode_bgra(37, 23, &pixels);
+        let (width, height, decoded) = decode_bgra(&encoded).expect("should decode");
+        assert_eq!((width, height), (37, 23));
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn test_runs_compress() {
+        let pixels = [10, 20, 30, 255].repeat(64 * 64);
+        let encoded = encode_bgra(64, 64, &pixels);
+        assert!(encoded.len() < 100);
+        assert_eq!(decode_bgra(&encoded).expect("should decode").2, pixels);
+    }
+
+    #[test]
+    fn test_encoder_packets() {
+        let mut encoder = QoiEncoder::new();
+        let frame = VideoFrame::new_bgra(37, 23, gradient(37, 23), MediaTime::new(3, 30));
+        let packets = encoder.encode(&frame).expect("should encode");
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].is_keyframe);
+        assert_eq!(packets[0].dts, MediaTime::new(1, 10));
+        assert_eq!(
+            decode_bgra(&packets[0].data).expect("should decode").2,
+            frame.packed_bgra().unwrap()
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Deterministic random inputs for the property tests of the geometry modules.
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+/// A xorshift generator, so failures reproduce without a property testing dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a value in `low..high`, in whole quarter points so sums stay exact.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn coordinate(&mut self, low: f64, high: f64) -> f64 {
+        let steps = ((high - low) * 4.0) as u64;
+        low + (self.next_u64() % steps.max(1)) as f64 / 4.0
+    }
+
+    pub fn point(&mut self) -> CGPoint {
+        CGPoint::new(
+            self.coordinate(-4000.0, 4000.0),
+            self.coordinate(-4000.0, 4000.0),
+        )
+    }
+
+    pub fn rect(&mut self) -> CGRect {
+        CGRect {
+            origin: self.point(),
+            size: CGSize::new(self.coordinate(0.0, 3000.0), self.coordinate(0.0, 3000.0)),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+use crate::shareable_content::sc_display::SCDisplay;
+
+use super::rect::{bounding_box, RectExt};
+
+/// The frame and scale of a display, for mapping between global points, points local to
+/// the display and the pixels it captures.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayGeometry {
+    pub display_id: u32,
+    /// The frame in global points.
+    pub frame: CGRect,
+    /// Pixels per point.
+    pub scale_factor: f64,
+}
+
+impl From<&SCDisplay> for DisplayGeometry {
+    fn from(display: &SCDisplay) -> Self {
+        Self::new(
+            display.display_id(),
+            display.frame(),
+            display.scale_factor(),
+        )
+    }
+}
+
+impl DisplayGeometry {
+    pub const fn new(display_id: u32, frame: CGRect, scale_factor: f64) -> Self {
+        Self {
+            display_id,
+            frame,
+            scale_factor,
+        }
+    }
+
+    /// Returns the size of the display in pixels.
+    pub fn pixel_size(&self) -> CGSize {
+        self.frame.scaled(self.scale_factor).size
+    }
+
+    /// Maps a global point to a point relative to the top left of the display.
+    pub fn to_local(&self, point: &CGPoint) -> CGPoint {
+        CGPoint::new(point.x - self.frame.origin.x, point.y - self.frame.origin.y)
+    }
+
+    /// Maps a point relative to the top left of the display to a global point.
+    pub fn to_global(&self, point: &CGPoint) -> CGPoint {
+        CGPoint::new(point.x + self.frame.origin.x, point.y + self.frame.origin.y)
+    }
+
+    /// Maps a display-local point to a pixel position in captured frames.
+    pub fn to_pixels(&self, point: &CGPoint) -> CGPoint {
+        CGPoint::new(point.x * self.scale_factor, point.y * self.scale_factor)
+    }
+
+    /// Maps a pixel position in captured frames to a display-local point.
+    pub fn from_pixels(&self, pixel: &CGPoint) -> CGPoint {
+        CGPoint::new(pixel.x / self.scale_factor, pixel.y / self.scale_factor)
+    }
+
+    /// Maps a global rectangle to display-local points.
+    pub fn rect_to_local(&self, rect: &CGRect) -> CGRect {
+        rect.translated(-self.frame.origin.x, -self.frame.origin.y)
+    }
+
+    /// Maps a global rectangle to pixels in captured frames.
+    pub fn rect_to_pixels(&self, rect: &CGRect) -> CGRect {
+        self.rect_to_local(rect).scaled(self.scale_factor)
+    }
+
+    /// Returns the part of a global rectangle, like a window frame, on this display.
+    pub fn clip(&self, rect: &CGRect) -> Option<CGRect> {
+        self.frame.intersection(rect)
+    }
+
+    /// Returns the part of a global rectangle on this display as whole pixels in captured
+    /// frames, rounded outwards and kept within the display.
+    pub fn clip_to_pixels(&self, rect: &CGRect) -> Option<CGRect> {
+        let clipped = self.rect_to_pixels(&self.clip(rect)?).rounded_out();
+        let size = self.pixel_size();
+        clipped.intersection(&CGRect::from_edges(0.0, 0.0, size.width, size.height))
+    }
+}
+
+/// The part of a rectangle shown on one display.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayIntersection {
+    pub display_id: u32,
+    /// The intersection in global points.
+    pub rect: CGRect,
+    /// The share of the rectangle's area on this display, between zero and one.
+    pub fraction: f64,
+}
+
+/// The arrangement of all displays in the global display space.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayLayout {
+    displays: Vec<DisplayGeometry>,
+}
+
+impl DisplayLayout {
+    pub const fn new(displays: Vec<DisplayGeometry>) -> Self {
+        Self { displays }
+    }
+
+    pub fn from_displays(displays: &[SCDisplay]) -> Self {
+        Self::new(displays.iter().map(DisplayGeometry::from).collect())
+    }
+
+    pub fn displays(&self) -> &[DisplayGeometry] {
+        &self.displays
+    }
+
+    pub fn display(&self, display_id: u32) -> Option<&DisplayGeometry> {
+        self.displays
+            .iter()
+            .find(|display| display.display_id == display_id)
+    }
+
+    /// Returns the bounding box of all displays, i.e. the global desktop.
+    pub fn bounds(&self) -> Option<CGRect> {
+        bounding_box(self.displays.iter().map(|display| display.frame))
+    }
+
+    /// Returns the display showing a global point.
+    pub fn display_at(&self, point: &CGPoint) -> Option<&DisplayGeometry> {
+        self.displays
+            .iter()
+            .find(|display| display.frame.contains_point(point))
+    }
+
+    /// Returns the displays a global rectangle intersects in layout order, with how much of
+    /// the rectangle each shows.
+    pub fn intersections(&self, rect: &CGRect) -> Vec<DisplayIntersection> {
+        let area = rect.area();
+        self.displays
+            .iter()
+            .filter_map(|display| {
+                display.clip(rect).map(|clipped| DisplayIntersection {
+                    display_id: display.display_id,
+                    rect: clipped,
+                    fraction: clipped.area() / area,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the display showing the largest part of a global rectangle, e.g. the display
+    /// to capture a window spanning monitors from.
+    pub fn best_display(&self, rect: &CGRect) -> Option<&DisplayGeometry> {
+        self.intersections(rect)
+            .into_iter()
+            .reduce(|best, intersection| {
+                if intersection.fraction > best.fraction {
+                    intersection
+                } else {
+                    best
+                }
+            })
+            .and_then(|best| self.display(best.display_id))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod display_layout_test {
+    use core_graphics::geometry::{CGPoint, CGRect};
+
+    use crate::geometry::{arbitrary::Rng, rect::RectExt};
+
+    use super::{DisplayGeometry, DisplayLayout};
+
+    const CASES: usize = 2000;
+
+    /// A 2x Retina laptop display with a 1x external display to its right, aligned at the
+    /// bottom, and a 1.5x display above.
+    fn layout() -> DisplayLayout {
+        DisplayLayout::new(vec![
+            DisplayGeometry::new(1, CGRect::from_edges(0.0, 0.0, 1512.0, 982.0), 2.0),
+            DisplayGeometry::new(2, CGRect::from_edges(1512.0, -458.0, 3432.0, 982.0), 1.0),
+            DisplayGeometry::new(3, CGRect::from_edges(0.0, -1000.0, 1280.0, 0.0), 1.5),
+        ])
+    }
+
+    fn edges(rect: &CGRect) -> [f64; 4] {
+        [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()]
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(
+            edges(&layout().bounds().unwrap()),
+            [0.0, -1000.0, 3432.0, 982.0]
+        );
+        assert!(DisplayLayout::default().bounds().is_none());
+    }
+
+    #[test]
+    fn test_window_spanning_displays() {
+        let layout = layout();
+        let window = CGRect::from_edges(1312.0, 100.0, 2112.0, 700.0);
+        let intersections = layout.intersections(&window);
+        assert_eq!(intersections.len(), 2);
+        assert_eq!(intersections[0].display_id, 1);
+        assert_eq!(intersections[0].fraction, 0.25);
+        assert_eq!(
+            edges(&intersections[0].rect),
+            [1312.0, 100.0, 1512.0, 700.0]
+        );
+        assert_eq!(intersections[1].display_id, 2);
+        assert_eq!(intersections[1].fraction, 0.75);
+        assert_eq!(layout.best_display(&window).unwrap().display_id, 2);
+
+        let laptop = layout.display(1).unwrap();
+        assert_eq!(
+            edges(&laptop.clip_to_pixels(&window).unwrap()),
+            [2624.0, 200.0, 3024.0, 1400.0]
+        );
+        let external = layout.display(2).unwrap();
+        assert_eq!(
+            edges(&external.clip_to_pixels(&window).unwrap()),
+            [0.0, 558.0, 600.0, 1158.0]
+        );
+        assert!(layout.display(3).unwrap().clip(&window).is_none());
+    }
+
+    #[test]
+    fn test_mapping() {
+        let layout = layout();
+        let point = CGPoint::new(100.0, -10.0);
+        let display = layout.display_at(&point).unwrap();
+        assert_eq!(display.display_id, 3);
+        let local = display.to_local(&point);
+        assert_eq!((local.x, local.y), (100.0, 990.0));
+        let pixel = display.to_pixels(&local);
+        assert_eq!((pixel.x, pixel.y), (150.0, 1485.0));
+        assert!(layout.display_at(&CGPoint::new(2000.0, -900.0)).is_none());
+        assert_eq!(layout.display(1).unwrap().pixel_size().width, 3024.0);
+    }
+
+    #[test]
+    fn test_mapping_round_trips() {
+        let layout = layout();
+        let mut rng = Rng::new(4);
+        for _ in 0..CASES {
+            let point = rng.point();
+            for display in layout.displays() {
+                let local = display.to_local(&point);
+                let back = display.to_global(&display.from_pixels(&display.to_pixels(&local)));
+                assert!((back.x - point.x).abs() < 1e-9 && (back.y - point.y).abs() < 1e-9);
+            }
+            if let Some(display) = layout.display_at(&point) {
+                let local = display.to_local(&point);
+                assert!(local.x >= 0.0 && local.x < display.frame.size.width);
+                assert!(local.y >= 0.0 && local.y < display.frame.size.height);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intersection_properties() {
+        let layout = layout();
+        let bounds = layout.bounds().unwrap();
+        let mut rng = Rng::new(5);
+        for _ in 0..CASES {
+            let rect = rng.rect();
+            let intersections = layout.intersections(&rect);
+            let total: f64 = intersections.iter().map(|i| i.fraction).sum();
+            // The displays don't overlap, so the fractions add up to the part on any display.
+            assert!(total <= 1.0 + 1e-9);
+            if bounds.contains_rect(&rect) && rect.has_area() {
+                let gaps = [
+                    CGRect::from_edges(1280.0, -1000.0, 1512.0, 0.0),
+                    CGRect::from_edges(1512.0, -1000.0, 3432.0, -458.0),
+                ];
+                let in_gaps: f64 = gaps
+                    .iter()
+                    .filter_map(|gap| gap.intersection(&rect))
+                    .map(|r| r.area())
+                    .sum();
+                assert!((total + in_gaps / rect.area() - 1.0).abs() < 1e-9);
+            }
+            for intersection in &intersections {
+                let display = layout.display(intersection.display_id).unwrap();
+                assert!(display.frame.contains_rect(&intersection.rect));
+                assert!(rect.contains_rect(&intersection.rect));
+                let pixels = display.clip_to_pixels(&rect).unwrap();
+                let size = display.pixel_size();
+                assert!(
+                    CGRect::from_edges(0.0, 0.0, size.width, size.height).contains_rect(&pixels)
+                );
+                assert!(pixels.contains_rect(&display.rect_to_pixels(&intersection.rect)));
+            }
+            if let Some(best) = layout.best_display(&rect) {
+                let best = intersections
+                    .iter()
+                    .find(|i| i.display_id == best.display_id)
+                    .unwrap();
+                assert!(intersections.iter().all(|i| i.fraction <= best.fraction));
+            }
+        }
+    }
+}
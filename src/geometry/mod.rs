@@ -0,0 +1,11 @@
+//! Geometry in the global display space `ScreenCaptureKit` reports frames in: points with
+//! the origin at the top left of the main display and y growing downwards.
+#[cfg(test)]
+pub(crate) mod arbitrary;
+pub mod display_layout;
+pub mod occlusion;
+pub mod rect;
+
+pub use display_layout::{DisplayGeometry, DisplayIntersection, DisplayLayout};
+pub use occlusion::{Occlusion, Visibility};
+pub use rect::RectExt;
@@ -0,0 +1,271 @@
+use core_graphics::geometry::CGRect;
+
+use crate::shareable_content::content_snapshot::ContentSnapshot;
+
+use super::rect::RectExt;
+
+/// How much of a window is visible on screen.
+#[derive(Debug, Clone)]
+pub struct Visibility {
+    pub window_id: u32,
+    /// Disjoint rectangles in global points covering the visible part of the window.
+    pub visible_region: Vec<CGRect>,
+    /// The visible share of the window's frame, between zero and one.
+    pub visible_fraction: f64,
+    /// The on-screen windows in front of this one overlapping its frame, frontmost first.
+    pub covered_by: Vec<u32>,
+}
+
+impl Visibility {
+    pub fn visible_area(&self) -> f64 {
+        self.visible_region.iter().map(RectExt::area).sum()
+    }
+
+    pub fn is_fully_occluded(&self) -> bool {
+        self.visible_region.is_empty()
+    }
+}
+
+/// Returns the parts of `rect` outside `hole`, as up to four disjoint rectangles.
+pub fn subtract(rect: &CGRect, hole: &CGRect) -> Vec<CGRect> {
+    let Some(hole) = rect.intersection(hole) else {
+        return if rect.has_area() {
+            vec![*rect]
+        } else {
+            Vec::new()
+        };
+    };
+    [
+        // Full width bands above and below the hole, then the parts beside it.
+        CGRect::from_edges(rect.min_x(), rect.min_y(), rect.max_x(), hole.min_y()),
+        CGRect::from_edges(rect.min_x(), hole.max_y(), rect.max_x(), rect.max_y()),
+        CGRect::from_edges(rect.min_x(), hole.min_y(), hole.min_x(), hole.max_y()),
+        CGRect::from_edges(hole.max_x(), hole.min_y(), rect.max_x(), hole.max_y()),
+    ]
+    .into_iter()
+    .filter(RectExt::has_area)
+    .collect()
+}
+
+/// Returns the parts of `region` not covered by any of `occluders`.
+pub fn subtract_all<'a>(
+    region: Vec<CGRect>,
+    occluders: impl IntoIterator<Item = &'a CGRect>,
+) -> Vec<CGRect> {
+    occluders.into_iter().fold(region, |region, occluder| {
+        region
+            .iter()
+            .flat_map(|rect| subtract(rect, occluder))
+            .collect()
+    })
+}
+
+/// The visibility of every window in a [`ContentSnapshot`].
+///
+/// Windows are stacked by layer and, within a layer, in the order `ScreenCaptureKit` lists
+/// them, frontmost first. Only on-screen windows occlude others, and every window is treated
+/// as opaque across its whole frame. When the snapshot has displays, parts of windows
+/// outside all displays count as hidden.
+#[derive(Debug, Clone, Default)]
+pub struct Occlusion {
+    windows: Vec<Visibility>,
+}
+
+impl Occlusion {
+    pub fn new(snapshot: &ContentSnapshot) -> Self {
+        let mut in_front: Vec<(u32, CGRect)> = Vec::new();
+        let windows = snapshot
+            .windows_in_z_order()
+            .into_iter()
+            .map(|window| {
+                let frame = CGRect::from(window.frame);
+                if !window.is_on_screen || !frame.has_area() {
+                    return Visibility {
+                        window_id: window.window_id,
+                        visible_region: Vec::new(),
+                        visible_fraction: 0.0,
+                        covered_by: Vec::new(),
+                    };
+                }
+                let on_displays = if snapshot.displays.is_empty() {
+                    vec![frame]
+                } else {
+                    snapshot
+                        .displays
+                        .iter()
+                        .filter_map(|display| frame.intersection(&display.frame.into()))
+                        .collect()
+                };
+                let covering: Vec<_> = in_front
+                    .iter()
+                    .filter(|(_, occluder)| occluder.intersection(&frame).is_some())
+                    .collect();
+                let visible_region =
+                    subtract_all(on_displays, covering.iter().map(|(_, occluder)| occluder));
+                let visibility = Visibility {
+                    window_id: window.window_id,
+                    visible_fraction: visible_region.iter().map(RectExt::area).sum::<f64>()
+                        / frame.area(),
+                    visible_region,
+                    covered_by: covering.iter().map(|(window_id, _)| *window_id).collect(),
+                };
+                in_front.push((window.window_id, frame));
+                visibility
+            })
+            .collect();
+        Self { windows }
+    }
+
+    /// Returns the visibility of every window, frontmost first.
+    pub fn windows(&self) -> &[Visibility] {
+        &self.windows
+    }
+
+    pub fn visibility(&self, window_id: u32) -> Option<&Visibility> {
+        self.windows
+            .iter()
+            .find(|visibility| visibility.window_id == window_id)
+    }
+
+    /// Returns the IDs of the windows with at least `min_fraction` of their frame visible,
+    /// frontmost first. A fraction of zero skips only fully occluded windows.
+    pub fn visible_windows(&self, min_fraction: f64) -> Vec<u32> {
+        self.windows
+            .iter()
+            .filter(|visibility| {
+                !visibility.is_fully_occluded() && visibility.visible_fraction >= min_fraction
+            })
+            .map(|visibility| visibility.window_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod occlusion_test {
+    use core_graphics::geometry::{CGPoint, CGRect};
+
+    use crate::{
+        geometry::{arbitrary::Rng, rect::RectExt},
+        shareable_content::content_snapshot::{ContentSnapshot, DisplayInfo, Rect, WindowInfo},
+    };
+
+    use super::{subtract, subtract_all, Occlusion};
+
+    fn window(window_id: u32, frame: Rect, window_layer: u32) -> WindowInfo {
+        WindowInfo {
+            window_id,
+            frame,
+            window_layer,
+            is_on_screen: true,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(windows: Vec<WindowInfo>) -> ContentSnapshot {
+        ContentSnapshot {
+            displays: vec![DisplayInfo {
+                display_id: 1,
+                frame: Rect::new(0.0, 0.0, 1000.0, 1000.0),
+                width: 1000,
+                height: 1000,
+            }],
+            windows,
+            applications: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_subtract() {
+        let rect = CGRect::from_edges(0.0, 0.0, 100.0, 100.0);
+        let pieces = subtract(&rect, &CGRect::from_edges(25.0, 25.0, 75.0, 75.0));
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces.iter().map(RectExt::area).sum::<f64>(), 7500.0);
+        assert_eq!(
+            subtract(&rect, &CGRect::from_edges(50.0, -10.0, 110.0, 110.0)).len(),
+            1
+        );
+        assert!(subtract(&rect, &CGRect::from_edges(-1.0, -1.0, 101.0, 101.0)).is_empty());
+        assert_eq!(
+            subtract(&rect, &CGRect::from_edges(100.0, 0.0, 200.0, 100.0)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_stacking() {
+        let occlusion = Occlusion::new(&snapshot(vec![
+            window(1, Rect::new(0.0, 0.0, 400.0, 400.0), 0),
+            window(2, Rect::new(200.0, 0.0, 400.0, 400.0), 0),
+            window(3, Rect::new(0.0, 0.0, 1000.0, 25.0), 24),
+            window(4, Rect::new(100.0, 100.0, 100.0, 100.0), 0),
+        ]));
+        let ids: Vec<_> = occlusion.windows().iter().map(|v| v.window_id).collect();
+        assert_eq!(ids, vec![3, 1, 2, 4]);
+
+        let menu_bar = occlusion.visibility(3).unwrap();
+        assert_eq!(menu_bar.visible_fraction, 1.0);
+        assert!(menu_bar.covered_by.is_empty());
+
+        let front = occlusion.visibility(1).unwrap();
+        assert_eq!(front.visible_area(), 400.0 * 375.0);
+        assert_eq!(front.covered_by, vec![3]);
+
+        let back = occlusion.visibility(2).unwrap();
+        assert_eq!(back.visible_area(), 200.0 * 375.0);
+        assert_eq!(back.visible_fraction, 200.0 * 375.0 / 160_000.0);
+        assert_eq!(back.covered_by, vec![3, 1]);
+
+        let hidden = occlusion.visibility(4).unwrap();
+        assert!(hidden.is_fully_occluded());
+        assert_eq!(hidden.visible_fraction, 0.0);
+        assert_eq!(hidden.covered_by, vec![1]);
+
+        assert_eq!(occlusion.visible_windows(0.0), vec![3, 1, 2]);
+        assert_eq!(occlusion.visible_windows(0.5), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_off_screen_and_off_display() {
+        let mut minimized = window(1, Rect::new(0.0, 0.0, 1000.0, 1000.0), 0);
+        minimized.is_on_screen = false;
+        let occlusion = Occlusion::new(&snapshot(vec![
+            minimized,
+            window(2, Rect::new(800.0, 0.0, 400.0, 100.0), 0),
+        ]));
+        assert!(occlusion.visibility(1).unwrap().is_fully_occluded());
+        let partly_off_display = occlusion.visibility(2).unwrap();
+        assert_eq!(partly_off_display.visible_fraction, 0.5);
+        assert!(partly_off_display.covered_by.is_empty());
+        assert_eq!(occlusion.visible_windows(0.0), vec![2]);
+    }
+
+    #[test]
+    fn test_region_properties() {
+        let mut rng = Rng::new(6);
+        for _ in 0..300 {
+            let frame = rng.rect();
+            let occluders: Vec<_> = (0..rng.next_u64() % 6).map(|_| rng.rect()).collect();
+            let region = subtract_all(vec![frame], &occluders);
+            for (i, piece) in region.iter().enumerate() {
+                assert!(frame.contains_rect(piece));
+                assert!(occluders.iter().all(|o| o.intersection(piece).is_none()));
+                assert!(region[i + 1..]
+                    .iter()
+                    .all(|other| other.intersection(piece).is_none()));
+            }
+            for _ in 0..50 {
+                let point = CGPoint::new(
+                    rng.coordinate(frame.min_x(), frame.max_x()),
+                    rng.coordinate(frame.min_y(), frame.max_y()),
+                );
+                if !frame.contains_point(&point) {
+                    continue;
+                }
+                let visible = region.iter().any(|piece| piece.contains_point(&point));
+                let covered = occluders.iter().any(|o| o.contains_point(&point));
+                assert_ne!(visible, covered);
+            }
+        }
+    }
+}
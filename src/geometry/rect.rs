@@ -0,0 +1,227 @@
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+/// Pure Rust rectangle math for `CGRect`.
+///
+/// Unlike the inherent `CGRect` methods these call no `CoreGraphics` functions. Rectangles
+/// are treated as half-open, so two displays sharing an edge do not intersect.
+pub trait RectExt: Sized {
+    fn from_edges(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self;
+    fn min_x(&self) -> f64;
+    fn min_y(&self) -> f64;
+    fn max_x(&self) -> f64;
+    fn max_y(&self) -> f64;
+    /// Returns the area, or zero for rectangles with a negative size.
+    fn area(&self) -> f64;
+    fn has_area(&self) -> bool {
+        self.area() > 0.0
+    }
+    /// Returns the same rectangle with a non-negative width and height.
+    #[must_use]
+    fn standardized(&self) -> Self;
+    /// Returns the overlap of both rectangles, or `None` if they share no area.
+    fn intersection(&self, other: &Self) -> Option<Self>;
+    /// Returns the smallest rectangle containing both.
+    #[must_use]
+    fn union(&self, other: &Self) -> Self;
+    fn contains_point(&self, point: &CGPoint) -> bool;
+    fn contains_rect(&self, other: &Self) -> bool;
+    #[must_use]
+    fn translated(&self, dx: f64, dy: f64) -> Self;
+    /// Scales the origin and size, e.g. from points to pixels.
+    #[must_use]
+    fn scaled(&self, factor: f64) -> Self;
+    /// Returns the smallest rectangle with whole number edges containing this one.
+    #[must_use]
+    fn rounded_out(&self) -> Self;
+}
+
+impl RectExt for CGRect {
+    fn from_edges(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Self {
+            origin: CGPoint::new(min_x, min_y),
+            size: CGSize::new(max_x - min_x, max_y - min_y),
+        }
+    }
+
+    fn min_x(&self) -> f64 {
+        self.origin.x.min(self.origin.x + self.size.width)
+    }
+
+    fn min_y(&self) -> f64 {
+        self.origin.y.min(self.origin.y + self.size.height)
+    }
+
+    fn max_x(&self) -> f64 {
+        self.origin.x.max(self.origin.x + self.size.width)
+    }
+
+    fn max_y(&self) -> f64 {
+        self.origin.y.max(self.origin.y + self.size.height)
+    }
+
+    fn area(&self) -> f64 {
+        self.size.width.max(0.0) * self.size.height.max(0.0)
+    }
+
+    fn standardized(&self) -> Self {
+        Self::from_edges(self.min_x(), self.min_y(), self.max_x(), self.max_y())
+    }
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        let rect = Self::from_edges(
+            self.min_x().max(other.min_x()),
+            self.min_y().max(other.min_y()),
+            self.max_x().min(other.max_x()),
+            self.max_y().min(other.max_y()),
+        );
+        rect.has_area().then_some(rect)
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self::from_edges(
+            self.min_x().min(other.min_x()),
+            self.min_y().min(other.min_y()),
+            self.max_x().max(other.max_x()),
+            self.max_y().max(other.max_y()),
+        )
+    }
+
+    fn contains_point(&self, point: &CGPoint) -> bool {
+        (self.min_x()..self.max_x()).contains(&point.x)
+            && (self.min_y()..self.max_y()).contains(&point.y)
+    }
+
+    fn contains_rect(&self, other: &Self) -> bool {
+        self.min_x() <= other.min_x()
+            && self.min_y() <= other.min_y()
+            && other.max_x() <= self.max_x()
+            && other.max_y() <= self.max_y()
+    }
+
+    fn translated(&self, dx: f64, dy: f64) -> Self {
+        Self {
+            origin: CGPoint::new(self.origin.x + dx, self.origin.y + dy),
+            size: self.size,
+        }
+    }
+
+    fn scaled(&self, factor: f64) -> Self {
+        Self {
+            origin: CGPoint::new(self.origin.x * factor, self.origin.y * factor),
+            size: CGSize::new(self.size.width * factor, self.size.height * factor),
+        }
+    }
+
+    fn rounded_out(&self) -> Self {
+        Self::from_edges(
+            self.min_x().floor(),
+            self.min_y().floor(),
+            self.max_x().ceil(),
+            self.max_y().ceil(),
+        )
+    }
+}
+
+/// Returns the smallest rectangle containing all of `rects`.
+pub fn bounding_box(rects: impl IntoIterator<Item = CGRect>) -> Option<CGRect> {
+    rects.into_iter().reduce(|bounds, rect| bounds.union(&rect))
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod rect_test {
+    use core_graphics::geometry::{CGPoint, CGRect};
+
+    use crate::geometry::arbitrary::Rng;
+
+    use super::{bounding_box, RectExt};
+
+    const CASES: usize = 2000;
+
+    fn edges(rect: &CGRect) -> [f64; 4] {
+        [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()]
+    }
+
+    #[test]
+    fn test_edges() {
+        let rect = CGRect::from_edges(10.0, 20.0, 110.0, 70.0);
+        assert_eq!(edges(&rect), [10.0, 20.0, 110.0, 70.0]);
+        assert_eq!(rect.area(), 5000.0);
+        let flipped = CGRect::from_edges(110.0, 70.0, 10.0, 20.0);
+        assert_eq!(flipped.area(), 0.0);
+        assert_eq!(edges(&flipped.standardized()), edges(&rect));
+        assert_eq!(flipped.standardized().area(), 5000.0);
+    }
+
+    #[test]
+    fn test_shared_edges_do_not_intersect() {
+        let left = CGRect::from_edges(0.0, 0.0, 100.0, 100.0);
+        let right = CGRect::from_edges(100.0, 0.0, 200.0, 100.0);
+        assert!(left.intersection(&right).is_none());
+        assert!(!left.contains_point(&CGPoint::new(100.0, 50.0)));
+        assert!(right.contains_point(&CGPoint::new(100.0, 50.0)));
+    }
+
+    #[test]
+    fn test_intersection_properties() {
+        let mut rng = Rng::new(1);
+        for _ in 0..CASES {
+            let (a, b) = (rng.rect(), rng.rect());
+            let ab = a.intersection(&b);
+            let ba = b.intersection(&a);
+            assert_eq!(ab.map(|r| edges(&r)), ba.map(|r| edges(&r)));
+            if let Some(ab) = ab {
+                assert!(a.contains_rect(&ab) && b.contains_rect(&ab));
+                assert!(ab.area() <= a.area().min(b.area()));
+                assert!(ab.has_area());
+            }
+            assert_eq!(
+                a.intersection(&a).map(|r| edges(&r)),
+                a.has_area().then(|| edges(&a))
+            );
+        }
+    }
+
+    #[test]
+    fn test_union_properties() {
+        let mut rng = Rng::new(2);
+        for _ in 0..CASES {
+            let (a, b, c) = (rng.rect(), rng.rect(), rng.rect());
+            let ab = a.union(&b);
+            assert!(ab.contains_rect(&a) && ab.contains_rect(&b));
+            assert_eq!(edges(&ab), edges(&b.union(&a)));
+            assert_eq!(edges(&ab.union(&c)), edges(&a.union(&b.union(&c))));
+            let bounds = bounding_box([a, b, c]).unwrap();
+            assert!([a, b, c].iter().all(|rect| bounds.contains_rect(rect)));
+        }
+        assert!(bounding_box([]).is_none());
+    }
+
+    #[test]
+    fn test_transform_properties() {
+        let mut rng = Rng::new(3);
+        for _ in 0..CASES {
+            let rect = rng.rect();
+            let (dx, dy) = (rng.coordinate(-500.0, 500.0), rng.coordinate(-500.0, 500.0));
+            let moved = rect.translated(dx, dy);
+            assert_eq!(moved.area(), rect.area());
+            assert_eq!(edges(&moved.translated(-dx, -dy)), edges(&rect));
+
+            let scale = [0.5, 1.0, 2.0, 4.0][usize::try_from(rng.next_u64() % 4).unwrap()];
+            let scaled = rect.scaled(scale);
+            assert_eq!(scaled.area(), rect.area() * scale * scale);
+            assert_eq!(edges(&scaled.scaled(1.0 / scale)), edges(&rect));
+
+            let rounded = rect.scaled(1.5).rounded_out();
+            assert!(rounded.contains_rect(&rect.scaled(1.5)));
+            assert!(edges(&rounded).iter().all(|edge| edge.fract() == 0.0));
+            assert!(rounded.size.width < rect.scaled(1.5).size.width + 2.0);
+
+            let point = rng.point();
+            assert_eq!(
+                rect.contains_point(&point),
+                moved.contains_point(&CGPoint::new(point.x + dx, point.y + dy))
+            );
+        }
+    }
+}
@@ -1,7 +1,13 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![allow(clippy::must_use_candidate)]
 
+pub mod encoder;
+pub mod geometry;
+pub mod media;
 pub mod output;
+pub mod pipeline;
+pub mod session;
 pub mod shareable_content;
+pub mod sink;
 pub mod stream;
 pub mod utils;
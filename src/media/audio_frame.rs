@@ -0,0 +1,67 @@
+use super::media_time::MediaTime;
+
+/// An owned block of interleaved 32 bit float audio samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFrame {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Samples interleaved by channel, `frame_count() * channels` long.
+    pub samples: Vec<f32>,
+    pub pts: MediaTime,
+}
+
+impl AudioFrame {
+    pub const fn new(sample_rate: u32, channels: u16, samples: Vec<f32>, pts: MediaTime) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            samples,
+            pts,
+        }
+    }
+
+    /// Interleaves one buffer per channel, the layout `SCStream` delivers audio in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel buffers differ in length.
+    pub fn from_planar(sample_rate: u32, planes: &[&[f32]], pts: MediaTime) -> Self {
+        let frame_count = planes.first().map_or(0, |plane| plane.len());
+        assert!(
+            planes.iter().all(|plane| plane.len() == frame_count),
+            "all channels must have the same number of samples"
+        );
+        let samples = (0..frame_count)
+            .flat_map(|i| planes.iter().map(move |plane| plane[i]))
+            .collect();
+        Self::new(
+            sample_rate,
+            u16::try_from(planes.len()).expect("too many channels"),
+            samples,
+            pts,
+        )
+    }
+
+    /// Returns the number of sample frames, i.e. samples per channel.
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / usize::from(self.channels.max(1))
+    }
+    pub fn duration(&self) -> MediaTime {
+        MediaTime::new(
+            i64::try_from(self.frame_count()).unwrap_or(i64::MAX),
+            i32::try_from(self.sample_rate).unwrap_or(i32::MAX),
+        )
+    }
+    /// Presentation time just after the last sample.
+    pub fn end(&self) -> MediaTime {
+        self.pts + self.duration()
+    }
+    /// Returns the samples of one channel.
+    pub fn channel(&self, channel: u16) -> impl Iterator<Item = f32> + '_ {
+        self.samples
+            .iter()
+            .skip(usize::from(channel))
+            .step_by(usize::from(self.channels.max(1)))
+            .copied()
+    }
+}
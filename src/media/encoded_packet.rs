@@ -0,0 +1,38 @@
+use super::media_time::MediaTime;
+
+/// A compressed access unit as produced by an encoder and consumed by a muxer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedPacket {
+    pub data: Vec<u8>,
+    pub pts: MediaTime,
+    pub dts: MediaTime,
+    pub duration: Option<MediaTime>,
+    pub is_keyframe: bool,
+}
+
+impl EncodedPacket {
+    /// Creates a packet whose decode time equals its presentation time.
+    pub const fn new(data: Vec<u8>, pts: MediaTime, is_keyframe: bool) -> Self {
+        Self {
+            data,
+            pts,
+            dts: pts,
+            duration: None,
+            is_keyframe,
+        }
+    }
+    #[must_use]
+    pub const fn with_dts(mut self, dts: MediaTime) -> Self {
+        self.dts = dts;
+        self
+    }
+    #[must_use]
+    pub const fn with_duration(mut self, duration: MediaTime) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+    /// Presentation time of the end of this packet, if its duration is known.
+    pub fn end(&self) -> Option<MediaTime> {
+        self.duration.map(|d| self.pts + d)
+    }
+}
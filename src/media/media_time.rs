@@ -0,0 +1,210 @@
+use std::{
+    cmp::Ordering,
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+use core_media_rs::cm_time::CMTime;
+
+/// The `kCMTimeFlags_Valid` bit of a `CMTime`.
+const CM_TIME_FLAGS_VALID: u32 = 1 << 0;
+/// The infinity and indefinite bits of a `CMTime`, none of which have a numeric value.
+const CM_TIME_FLAGS_NON_NUMERIC: u32 = 1 << 2 | 1 << 3 | 1 << 4;
+
+/// A rational timestamp with the same `value / timescale` layout as `CMTime`.
+///
+/// Comparisons are exact across timescales, so `1/2` equals `500/1000`.
+#[derive(Debug, Clone, Copy)]
+pub struct MediaTime {
+    pub value: i64,
+    pub timescale: i32,
+}
+
+impl MediaTime {
+    pub const NANOS_PER_SECOND: i32 = 1_000_000_000;
+    pub const ZERO: Self = Self::new(0, Self::NANOS_PER_SECOND);
+
+    pub const fn new(value: i64, timescale: i32) -> Self {
+        Self { value, timescale }
+    }
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Self::new(nanos, Self::NANOS_PER_SECOND)
+    }
+    pub const fn from_millis(millis: i64) -> Self {
+        Self::new(millis, 1000)
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_secs_f64(seconds: f64) -> Self {
+        Self::from_nanos((seconds * f64::from(Self::NANOS_PER_SECOND)).round() as i64)
+    }
+
+    /// Returns `true` if the timescale is positive. `CMTime` uses a zero timescale for invalid times.
+    pub const fn is_valid(&self) -> bool {
+        self.timescale > 0
+    }
+
+    /// Converts this time to another timescale, rounding to the nearest unit. Invalid times
+    /// are returned unchanged.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn rescale(&self, timescale: i32) -> Self {
+        if !self.is_valid() || timescale == self.timescale {
+            return *self;
+        }
+        let numerator = i128::from(self.value) * i128::from(timescale);
+        let denominator = i128::from(self.timescale);
+        let half = denominator / 2;
+        let rounded = if numerator >= 0 {
+            (numerator + half) / denominator
+        } else {
+            (numerator - half) / denominator
+        };
+        Self::new(rounded as i64, timescale)
+    }
+
+    pub fn as_nanos(&self) -> i64 {
+        self.rescale(Self::NANOS_PER_SECOND).value
+    }
+    pub fn as_millis(&self) -> i64 {
+        self.rescale(1000).value
+    }
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_secs_f64(&self) -> f64 {
+        if self.is_valid() {
+            self.value as f64 / f64::from(self.timescale)
+        } else {
+            0.0
+        }
+    }
+    /// Converts this time to a [`Duration`], clamping negative times to zero.
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_nanos(u64::try_from(self.as_nanos()).unwrap_or(0))
+    }
+
+    fn common_timescale(self, other: Self) -> i32 {
+        if self.timescale == other.timescale {
+            return self.timescale;
+        }
+        let (a, b) = (i64::from(self.timescale), i64::from(other.timescale));
+        let (mut x, mut y) = (a, b);
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        i32::try_from(a / x * b).unwrap_or_else(|_| self.timescale.max(other.timescale))
+    }
+}
+
+impl Default for MediaTime {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl PartialEq for MediaTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for MediaTime {}
+
+impl PartialOrd for MediaTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for MediaTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = i128::from(self.value) * i128::from(other.timescale);
+        let rhs = i128::from(other.value) * i128::from(self.timescale);
+        lhs.cmp(&rhs)
+    }
+}
+
+impl Add for MediaTime {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let timescale = self.common_timescale(rhs);
+        Self::new(
+            self.rescale(timescale).value + rhs.rescale(timescale).value,
+            timescale,
+        )
+    }
+}
+impl Sub for MediaTime {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        let timescale = self.common_timescale(rhs);
+        Self::new(
+            self.rescale(timescale).value - rhs.rescale(timescale).value,
+            timescale,
+        )
+    }
+}
+
+impl From<Duration> for MediaTime {
+    fn from(duration: Duration) -> Self {
+        Self::from_nanos(i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<CMTime> for MediaTime {
+    /// Converts a `CMTime`, mapping invalid and indefinite times to an invalid [`MediaTime`].
+    fn from(time: CMTime) -> Self {
+        if time.flags & CM_TIME_FLAGS_VALID == 0 || time.flags & CM_TIME_FLAGS_NON_NUMERIC != 0 {
+            return Self::new(0, 0);
+        }
+        Self::new(time.value, time.timescale)
+    }
+}
+
+#[cfg(test)]
+mod media_time_test {
+    use core_media_rs::cm_time::CMTime;
+
+    use super::MediaTime;
+
+    #[test]
+    fn test_compare_across_timescales() {
+        assert_eq!(MediaTime::new(1, 2), MediaTime::new(500, 1000));
+        assert!(MediaTime::new(1, 48000) < MediaTime::from_nanos(30_000));
+        assert!(MediaTime::new(-1, 600) < MediaTime::ZERO);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let sum = MediaTime::new(1, 600) + MediaTime::new(1, 48000);
+        assert_eq!(sum.timescale, 48000);
+        assert_eq!(sum.value, 81);
+        assert_eq!(
+            MediaTime::from_millis(1500) - MediaTime::new(1, 1),
+            MediaTime::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_rescale_rounds_to_nearest() {
+        assert_eq!(MediaTime::new(1, 3).rescale(1000).value, 333);
+        assert_eq!(MediaTime::new(2, 3).rescale(1000).value, 667);
+        assert_eq!(MediaTime::new(-2, 3).rescale(1000).value, -667);
+        assert_eq!(MediaTime::new(90_000, 90_000).as_millis(), 1000);
+        assert!(!MediaTime::new(5, 0).rescale(1000).is_valid());
+    }
+
+    #[test]
+    fn test_from_cm_time() {
+        let time = CMTime {
+            value: 1_001,
+            timescale: 30_000,
+            flags: 1,
+            epoch: 0,
+        };
+        assert_eq!(MediaTime::from(time), MediaTime::new(1_001, 30_000));
+        let invalid = CMTime { flags: 0, ..time };
+        assert!(!MediaTime::from(invalid).is_valid());
+        let indefinite = CMTime {
+            flags: 1 | 16,
+            ..time
+        };
+        assert!(!MediaTime::from(indefinite).is_valid());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod audio_frame;
+pub mod encoded_packet;
+pub mod media_time;
+pub mod video_frame;
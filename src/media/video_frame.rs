@@ -0,0 +1,305 @@
+use core_graphics::geometry::CGRect;
+
+use crate::{
+    geometry::rect::RectExt,
+    output::sc_stream_frame_info::{SCFrameStatus, SCStreamFrameInfo},
+};
+
+use super::media_time::MediaTime;
+
+/// The pixel layouts `SCStream` can deliver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Packed 8 bit blue, green, red, alpha, i.e. `kCVPixelFormatType_32BGRA`.
+    Bgra,
+    /// A full resolution luma plane followed by an interleaved, half resolution chroma plane,
+    /// i.e. `kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange`.
+    Nv12,
+}
+
+impl PixelFormat {
+    /// Returns the number of planes of a frame in this format.
+    pub const fn plane_count(self) -> usize {
+        match self {
+            Self::Bgra => 1,
+            Self::Nv12 => 2,
+        }
+    }
+    /// Returns the four character code Core Video uses for this format.
+    pub const fn four_char_code(self) -> u32 {
+        match self {
+            Self::Bgra => u32::from_be_bytes(*b"BGRA"),
+            Self::Nv12 => u32::from_be_bytes(*b"420v"),
+        }
+    }
+    /// Returns the format with a Core Video four character code, or `None` for formats
+    /// other than these.
+    pub fn from_four_char_code(code: u32) -> Option<Self> {
+        [Self::Bgra, Self::Nv12]
+            .into_iter()
+            .find(|format| format.four_char_code() == code)
+    }
+}
+
+/// Converts a BGRA color to the BT.709 video range luma and chroma values of
+/// [`PixelFormat::Nv12`] frames, as `[y, cb, cr]`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn bgra_to_ycbcr(bgra: [u8; 4]) -> [u8; 3] {
+    let [b, g, r, _] = bgra.map(f64::from);
+    let y = 0.0722f64.mul_add(b, 0.2126f64.mul_add(r, 0.7152 * g));
+    let cb = (b - y) / 1.8556;
+    let cr = (r - y) / 1.5748;
+    [
+        219.0f64.mul_add(y / 255.0, 16.0),
+        224.0f64.mul_add(cb / 255.0, 128.0),
+        224.0f64.mul_add(cr / 255.0, 128.0),
+    ]
+    .map(|value| value.round().clamp(0.0, 255.0) as u8)
+}
+
+/// A rectangle of whole pixels within a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRect {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the pixels of a `width` by `height` frame that `rect`, in pixels, touches, or
+    /// `None` if it lies outside the frame.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn covering(rect: &CGRect, width: u32, height: u32) -> Option<Self> {
+        let bounds = CGRect::from_edges(0.0, 0.0, f64::from(width), f64::from(height));
+        let rect = rect.rounded_out().intersection(&bounds)?;
+        Some(Self::new(
+            rect.min_x() as u32,
+            rect.min_y() as u32,
+            rect.size.width as u32,
+            rect.size.height as u32,
+        ))
+    }
+}
+
+/// One plane of pixel data. Rows may be padded beyond the visible width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoPlane {
+    pub data: Vec<u8>,
+    pub bytes_per_row: usize,
+}
+
+impl VideoPlane {
+    pub const fn new(data: Vec<u8>, bytes_per_row: usize) -> Self {
+        Self {
+            data,
+            bytes_per_row,
+        }
+    }
+    /// Returns row `y` of this plane, including any padding.
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.data[y * self.bytes_per_row..(y + 1) * self.bytes_per_row]
+    }
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        &mut self.data[y * self.bytes_per_row..(y + 1) * self.bytes_per_row]
+    }
+}
+
+/// The per-frame attachments `SCStream` delivers alongside the pixels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub status: SCFrameStatus,
+    /// The host time the frame was displayed at, in `mach_absolute_time` units.
+    pub display_time: Option<u64>,
+    /// The areas that changed since the previous frame, in frame pixels.
+    pub dirty_rects: Option<Vec<PixelRect>>,
+}
+
+impl FrameInfo {
+    /// Reads the attachments of a `width` by `height` frame. A missing status counts as
+    /// `Complete`.
+    pub fn from_frame_info(info: &SCStreamFrameInfo, width: u32, height: u32) -> Self {
+        Self {
+            status: info.status().unwrap_or_default(),
+            display_time: info.display_time(),
+            dirty_rects: info.dirty_rects().map(|rects| {
+                rects
+                    .iter()
+                    .filter_map(|rect| PixelRect::covering(rect, width, height))
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// An owned video frame, decoupled from the `CMSampleBuffer` it was captured in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    pub planes: Vec<VideoPlane>,
+    pub pts: MediaTime,
+    pub info: FrameInfo,
+}
+
+impl VideoFrame {
+    /// Creates a tightly packed BGRA frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is not `width * height * 4` bytes long.
+    pub fn new_bgra(width: u32, height: u32, data: Vec<u8>, pts: MediaTime) -> Self {
+        let bytes_per_row = width as usize * 4;
+        assert_eq!(
+            data.len(),
+            bytes_per_row * height as usize,
+            "invalid BGRA frame size"
+        );
+        Self {
+            width,
+            height,
+            pixel_format: PixelFormat::Bgra,
+            planes: vec![VideoPlane::new(data, bytes_per_row)],
+            pts,
+            info: FrameInfo::default(),
+        }
+    }
+
+    /// Creates a tightly packed NV12 frame from its luma and interleaved chroma planes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the planes do not match the frame size.
+    pub fn new_nv12(
+        width: u32,
+        height: u32,
+        luma: Vec<u8>,
+        chroma: Vec<u8>,
+        pts: MediaTime,
+    ) -> Self {
+        let (width_px, height_px) = (width as usize, height as usize);
+        let chroma_bytes_per_row = (width_px + 1) / 2 * 2;
+        assert_eq!(luma.len(), width_px * height_px, "invalid NV12 luma size");
+        assert_eq!(
+            chroma.len(),
+            chroma_bytes_per_row * ((height_px + 1) / 2),
+            "invalid NV12 chroma size"
+        );
+        Self {
+            width,
+            height,
+            pixel_format: PixelFormat::Nv12,
+            planes: vec![
+                VideoPlane::new(luma, width_px),
+                VideoPlane::new(chroma, chroma_bytes_per_row),
+            ],
+            pts,
+            info: FrameInfo::default(),
+        }
+    }
+
+    /// Creates a BGRA frame filled with a single color.
+    pub fn filled_bgra(width: u32, height: u32, bgra: [u8; 4], pts: MediaTime) -> Self {
+        Self::new_bgra(
+            width,
+            height,
+            bgra.repeat(width as usize * height as usize),
+            pts,
+        )
+    }
+
+    #[must_use]
+    pub fn with_info(mut self, info: FrameInfo) -> Self {
+        self.info = info;
+        self
+    }
+    #[must_use]
+    pub const fn with_status(mut self, status: SCFrameStatus) -> Self {
+        self.info.status = status;
+        self
+    }
+
+    /// Returns `true` if this frame carries new pixels rather than repeating the previous frame.
+    pub fn has_content(&self) -> bool {
+        self.info.status == SCFrameStatus::Complete || self.info.status == SCFrameStatus::Started
+    }
+
+    /// Returns the BGRA value of the pixel at `x`, `y`, or `None` for other formats or out of bounds.
+    pub fn bgra_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if self.pixel_format != PixelFormat::Bgra || x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = x as usize * 4;
+        let row = self.planes[0].row(y as usize);
+        row.get(offset..offset + 4)?.try_into().ok()
+    }
+
+    /// Copies the visible BGRA pixels into a tightly packed buffer, dropping any row padding.
+    pub fn packed_bgra(&self) -> Option<Vec<u8>> {
+        if self.pixel_format != PixelFormat::Bgra {
+            return None;
+        }
+        let row_len = self.width as usize * 4;
+        let plane = &self.planes[0];
+        Some(
+            (0..self.height as usize)
+                .flat_map(|y| &plane.row(y)[..row_len])
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod video_frame_test {
+    use core_graphics::geometry::CGRect;
+
+    use crate::geometry::rect::RectExt;
+
+    use super::{PixelFormat, PixelRect};
+
+    #[test]
+    fn test_four_char_code() {
+        for format in [PixelFormat::Bgra, PixelFormat::Nv12] {
+            assert_eq!(
+                PixelFormat::from_four_char_code(format.four_char_code()),
+                Some(format)
+            );
+        }
+        assert_eq!(
+            PixelFormat::from_four_char_code(u32::from_be_bytes(*b"420f")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_covering() {
+        let rect = |min_x, min_y, max_x, max_y| CGRect::from_edges(min_x, min_y, max_x, max_y);
+        assert_eq!(
+            PixelRect::covering(&rect(1.5, 2.0, 3.2, 4.0), 10, 10),
+            Some(PixelRect::new(1, 2, 3, 2))
+        );
+        assert_eq!(
+            PixelRect::covering(&rect(-4.0, 8.0, 4.0, 20.0), 10, 10),
+            Some(PixelRect::new(0, 8, 4, 2))
+        );
+        assert_eq!(
+            PixelRect::covering(&rect(10.0, 0.0, 12.0, 5.0), 10, 10),
+            None
+        );
+    }
+}
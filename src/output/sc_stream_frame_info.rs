@@ -1,7 +1,8 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(i32)]
 pub enum SCFrameStatus {
     // A status that indicates the system successfully generated a new frame.
+    #[default]
     Complete,
     // A status that indicates the system didn’t generate a new frame because the display didn’t change.
     Idle,
@@ -23,13 +24,16 @@ mod internal {
     use std::{ffi::c_void, mem};
 
     use core_foundation::{
+        array::{CFArray, CFArrayRef},
         base::{CFTypeID, TCFType},
         declare_TCFType,
+        dictionary::{CFDictionary, CFDictionaryRef},
         error::CFError,
         impl_TCFType,
         number::{CFNumber, CFNumberRef},
-        string::CFString,
+        string::{CFString, CFStringRef},
     };
+    use core_graphics::geometry::CGRect;
 
     use crate::utils::{error::create_cf_error, objc::MessageForTFType};
 
@@ -39,6 +43,11 @@ mod internal {
     pub struct __SCStreamFrameInfoRef(c_void);
     extern "C" {
         pub fn SCStreamFrameInfoGetTypeID() -> CFTypeID;
+        static SCStreamFrameInfoContentRect: CFStringRef;
+        static SCStreamFrameInfoContentScale: CFStringRef;
+        static SCStreamFrameInfoScaleFactor: CFStringRef;
+        static SCStreamFrameInfoDisplayTime: CFStringRef;
+        static SCStreamFrameInfoDirtyRects: CFStringRef;
     }
 
     pub type SCStreamFrameInfoRef = *mut __SCStreamFrameInfoRef;
@@ -72,8 +81,60 @@ mod internal {
             ))
         }
     }
+    fn number(info: &SCStreamFrameInfo, key: CFStringRef) -> Option<f64> {
+        unsafe {
+            let key = CFString::wrap_under_get_rule(key);
+            let raw: CFNumberRef = msg_send![info.as_sendable(), objectForKey: key];
+            if raw.is_null() {
+                return None;
+            }
+            CFNumber::wrap_under_get_rule(raw).to_f64()
+        }
+    }
+    pub fn display_time(info: &SCStreamFrameInfo) -> Option<u64> {
+        unsafe {
+            let key = CFString::wrap_under_get_rule(SCStreamFrameInfoDisplayTime);
+            let raw: CFNumberRef = msg_send![info.as_sendable(), objectForKey: key];
+            if raw.is_null() {
+                return None;
+            }
+            CFNumber::wrap_under_get_rule(raw)
+                .to_i64()
+                .and_then(|time| u64::try_from(time).ok())
+        }
+    }
+    pub fn content_rect(info: &SCStreamFrameInfo) -> Option<CGRect> {
+        unsafe {
+            let key = CFString::wrap_under_get_rule(SCStreamFrameInfoContentRect);
+            let raw: CFDictionaryRef = msg_send![info.as_sendable(), objectForKey: key];
+            if raw.is_null() {
+                return None;
+            }
+            CGRect::from_dict_representation(&CFDictionary::wrap_under_get_rule(raw))
+        }
+    }
+    pub fn dirty_rects(info: &SCStreamFrameInfo) -> Option<Vec<CGRect>> {
+        unsafe {
+            let key = CFString::wrap_under_get_rule(SCStreamFrameInfoDirtyRects);
+            let raw: CFArrayRef = msg_send![info.as_sendable(), objectForKey: key];
+            if raw.is_null() {
+                return None;
+            }
+            CFArray::<CFDictionary>::wrap_under_get_rule(raw)
+                .iter()
+                .map(|rect| CGRect::from_dict_representation(&rect))
+                .collect()
+        }
+    }
+    pub fn content_scale(info: &SCStreamFrameInfo) -> Option<f64> {
+        number(info, unsafe { SCStreamFrameInfoContentScale })
+    }
+    pub fn scale_factor(info: &SCStreamFrameInfo) -> Option<f64> {
+        number(info, unsafe { SCStreamFrameInfoScaleFactor })
+    }
 }
 use core_foundation::error::CFError;
+use core_graphics::geometry::CGRect;
 pub use internal::SCStreamFrameInfo;
 
 impl SCStreamFrameInfo {
@@ -88,6 +149,27 @@ impl SCStreamFrameInfo {
     pub fn status(&self) -> Result<SCFrameStatus, CFError> {
         internal::status(self)
     }
+    /// Returns the host time the frame was displayed at, in `mach_absolute_time` units.
+    pub fn display_time(&self) -> Option<u64> {
+        internal::display_time(self)
+    }
+    /// Returns where the captured content was drawn in the frame, in points. The content is
+    /// smaller than the frame when it was scaled down to fit or letterboxed.
+    pub fn content_rect(&self) -> Option<CGRect> {
+        internal::content_rect(self)
+    }
+    /// Returns the areas of the frame that changed since the previous frame, in pixels.
+    pub fn dirty_rects(&self) -> Option<Vec<CGRect>> {
+        internal::dirty_rects(self)
+    }
+    /// Returns how much the captured content was scaled to fit the frame.
+    pub fn content_scale(&self) -> Option<f64> {
+        internal::content_scale(self)
+    }
+    /// Returns the pixels per point of the frame.
+    pub fn scale_factor(&self) -> Option<f64> {
+        internal::scale_factor(self)
+    }
 }
 
 impl Default for SCStreamFrameInfo {
@@ -0,0 +1,663 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+use core_foundation::error::CFError;
+
+use crate::{
+    media::{audio_frame::AudioFrame, media_time::MediaTime},
+    utils::error::create_sc_error,
+};
+
+use super::resampler::{kaiser, sinc};
+
+/// Loudness is measured in steps of this many seconds, the overlap of the 400 ms blocks of
+/// ITU-R BS.1770.
+const STEP: f64 = 0.1;
+/// Steps per momentary loudness window of 400 ms.
+const MOMENTARY_STEPS: usize = 4;
+/// Steps per short-term loudness window of 3 s.
+const SHORT_TERM_STEPS: usize = 30;
+/// Blocks quieter than this in LUFS don't count towards the integrated loudness.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this many LU below the loudness of the blocks above the absolute gate don't
+/// count towards the integrated loudness.
+const RELATIVE_GATE: f64 = -10.0;
+/// Samples to either side of each interpolated sample of the true-peak oversampler.
+const TRUE_PEAK_REACH: usize = 8;
+/// Differences between a frame's timestamp and the end of the previous frame beyond this,
+/// in seconds, move the meter's clock to the frame's timestamp.
+const GAP_TOLERANCE: f64 = 0.02;
+
+/// Converts a linear amplitude to dBFS, with silence at negative infinity.
+fn to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+/// Converts a channel weighted mean square to LUFS.
+fn to_lufs(energy: f64) -> f64 {
+    10.0f64.mul_add(energy.log10(), -0.691)
+}
+
+/// The peak and RMS level of one channel over a step.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelLevel {
+    /// The largest absolute sample value.
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl ChannelLevel {
+    pub fn peak_db(&self) -> f64 {
+        to_db(f64::from(self.peak))
+    }
+
+    pub fn rms_db(&self) -> f64 {
+        to_db(f64::from(self.rms))
+    }
+}
+
+/// The levels of one 100 ms step of audio, fast enough to drive a VU meter.
+///
+/// Loudness values are in LUFS and negative infinity for digital silence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterReading {
+    pub pts: MediaTime,
+    pub duration: MediaTime,
+    pub channels: Vec<ChannelLevel>,
+    /// The loudness of the last 400 ms.
+    pub momentary: f64,
+    /// The loudness of the last 3 s.
+    pub short_term: f64,
+    /// The highest level between samples of the step in dBTP, or the sample peak when
+    /// true-peak measurement is off.
+    pub true_peak: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeterEvent {
+    Reading(MeterReading),
+    /// Audio has stayed below the silence threshold for the minimum duration since `pts`.
+    SilenceStarted(MediaTime),
+    /// A silence reported with [`MeterEvent::SilenceStarted`] ended.
+    SilenceEnded {
+        start: MediaTime,
+        end: MediaTime,
+    },
+}
+
+/// A biquad filter in transposed direct form II.
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    const fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0].mul_add(x, self.state[0]);
+        self.state[0] = self.b[1].mul_add(x, self.a[0].mul_add(-y, self.state[1]));
+        self.state[1] = self.b[2].mul_add(x, -self.a[1] * y);
+        y
+    }
+}
+
+/// The K-weighting filter of ITU-R BS.1770, a high shelf modelling the head followed by a
+/// high-pass, designed for any sample rate from the analog prototypes of the 48 kHz
+/// coefficients in the standard.
+#[derive(Debug, Clone)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(rate: u32) -> Self {
+        let rate = f64::from(rate);
+        let (frequency, gain, q) = (
+            1_681.974_450_955_533,
+            3.999_843_853_973_347,
+            0.707_175_236_955_419_6,
+        );
+        let k = (PI * frequency / rate).tan();
+        let high = 10.0f64.powf(gain / 20.0);
+        let band = high.powf(0.499_666_774_154_541_6);
+        let a0 = k.mul_add(k, 1.0 + k / q);
+        let shelf = Biquad::new(
+            [
+                k.mul_add(k, high + band * k / q) / a0,
+                2.0 * k.mul_add(k, -high) / a0,
+                k.mul_add(k, high - band * k / q) / a0,
+            ],
+            [
+                2.0 * k.mul_add(k, -1.0) / a0,
+                k.mul_add(k, 1.0 - k / q) / a0,
+            ],
+        );
+        let (frequency, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+        let k = (PI * frequency / rate).tan();
+        let a0 = k.mul_add(k, 1.0 + k / q);
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [
+                2.0 * k.mul_add(k, -1.0) / a0,
+                k.mul_add(k, 1.0 - k / q) / a0,
+            ],
+        );
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+/// Finds peaks between samples by interpolating each channel at a multiple of its rate.
+#[derive(Debug, Clone)]
+struct TruePeak {
+    /// The coefficients of each interpolated position between two samples.
+    phases: Vec<Vec<f64>>,
+    /// The latest samples of the channel, oldest first.
+    history: VecDeque<f64>,
+}
+
+impl TruePeak {
+    /// Oversamples 4 times below 96 kHz and twice below 192 kHz, as BS.1770 recommends.
+    #[allow(clippy::cast_precision_loss)]
+    fn new(rate: u32) -> Self {
+        let factor = match rate {
+            0..=95_999 => 4,
+            96_000..=191_999 => 2,
+            _ => 1,
+        };
+        let reach = TRUE_PEAK_REACH as f64;
+        let phases = (1..factor)
+            .map(|phase| {
+                let offset = f64::from(phase) / f64::from(factor);
+                let weights: Vec<f64> = (0..2 * TRUE_PEAK_REACH)
+                    .map(|tap| {
+                        let distance = tap as f64 - (reach - 1.0) - offset;
+                        sinc(distance) * kaiser(distance / reach)
+                    })
+                    .collect();
+                let sum: f64 = weights.iter().sum();
+                weights.into_iter().map(|weight| weight / sum).collect()
+            })
+            .collect();
+        Self {
+            phases,
+            history: VecDeque::from(vec![0.0; 2 * TRUE_PEAK_REACH]),
+        }
+    }
+
+    /// Adds a sample, returning the peak between the samples now in the middle of the
+    /// history and the sample itself.
+    fn process(&mut self, x: f64) -> f64 {
+        self.history.pop_front();
+        self.history.push_back(x);
+        self.phases
+            .iter()
+            .map(|phase| {
+                phase
+                    .iter()
+                    .zip(&self.history)
+                    .map(|(weight, sample)| weight * sample)
+                    .sum::<f64>()
+                    .abs()
+            })
+            .fold(x.abs(), f64::max)
+    }
+}
+
+/// The running measurement of one input format.
+#[derive(Debug)]
+struct Measurement {
+    rate: u32,
+    channels: u16,
+    /// The presentation time `received` counts samples from.
+    origin: MediaTime,
+    received: u64,
+    step_samples: u64,
+    /// Per channel gains of the channel weighted sum.
+    weights: Vec<f64>,
+    filters: Vec<KWeighting>,
+    true_peaks: Option<Vec<TruePeak>>,
+    /// The samples, peaks, sums of squares and K-weighted sums of squares of the current
+    /// step by channel.
+    step: u64,
+    peaks: Vec<f64>,
+    squares: Vec<f64>,
+    weighted: Vec<f64>,
+    true_peak: f64,
+    /// The channel weighted mean squares of the latest steps, newest last.
+    energies: VecDeque<f64>,
+}
+
+impl Measurement {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn new(frame: &AudioFrame, true_peak: bool) -> Self {
+        let channels = usize::from(frame.channels);
+        // 5.1 in the usual order has a silent LFE and louder surrounds.
+        let weights = if channels == 6 {
+            vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+        } else {
+            vec![1.0; channels]
+        };
+        Self {
+            rate: frame.sample_rate,
+            channels: frame.channels,
+            origin: frame.pts,
+            received: 0,
+            step_samples: (f64::from(frame.sample_rate) * STEP).round().max(1.0) as u64,
+            weights,
+            filters: vec![KWeighting::new(frame.sample_rate); channels],
+            true_peaks: true_peak.then(|| vec![TruePeak::new(frame.sample_rate); channels]),
+            step: 0,
+            peaks: vec![0.0; channels],
+            squares: vec![0.0; channels],
+            weighted: vec![0.0; channels],
+            true_peak: 0.0,
+            energies: VecDeque::with_capacity(SHORT_TERM_STEPS),
+        }
+    }
+
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+    fn time(&self, samples: u64) -> MediaTime {
+        self.origin + MediaTime::new(samples as i64, self.rate as i32)
+    }
+
+    /// Adds one sample per channel, returning the reading and the step's energy when it
+    /// completes a step.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn add(&mut self, samples: &[f32]) -> Option<(MeterReading, f64)> {
+        for (channel, &sample) in samples.iter().enumerate() {
+            let sample = f64::from(sample);
+            self.peaks[channel] = self.peaks[channel].max(sample.abs());
+            self.squares[channel] += sample * sample;
+            let weighted = self.filters[channel].process(sample);
+            self.weighted[channel] += weighted * weighted;
+            let peak = self.true_peaks.as_mut().map_or_else(
+                || sample.abs(),
+                |true_peaks| true_peaks[channel].process(sample),
+            );
+            self.true_peak = self.true_peak.max(peak);
+        }
+        self.received += 1;
+        self.step += 1;
+        if self.step < self.step_samples {
+            return None;
+        }
+        let count = self.step as f64;
+        let energy = self
+            .weighted
+            .iter()
+            .zip(&self.weights)
+            .map(|(sum, weight)| weight * sum / count)
+            .sum::<f64>();
+        if self.energies.len() == SHORT_TERM_STEPS {
+            self.energies.pop_front();
+        }
+        self.energies.push_back(energy);
+        let window =
+            |steps: usize| self.energies.iter().rev().take(steps).sum::<f64>() / steps as f64;
+        let reading = MeterReading {
+            pts: self.time(self.received - self.step),
+            duration: self.time(self.received) - self.time(self.received - self.step),
+            channels: self
+                .peaks
+                .iter()
+                .zip(&self.squares)
+                .map(|(&peak, &squares)| ChannelLevel {
+                    peak: peak as f32,
+                    rms: (squares / count).sqrt() as f32,
+                })
+                .collect(),
+            momentary: to_lufs(window(MOMENTARY_STEPS)),
+            short_term: to_lufs(window(SHORT_TERM_STEPS)),
+            true_peak: to_db(self.true_peak),
+        };
+        self.step = 0;
+        self.peaks.fill(0.0);
+        self.squares.fill(0.0);
+        self.weighted.fill(0.0);
+        self.true_peak = 0.0;
+        let block = (self.energies.len() >= MOMENTARY_STEPS).then(|| window(MOMENTARY_STEPS));
+        Some((reading, block.unwrap_or(f64::NAN)))
+    }
+}
+
+/// Meters audio for level displays and loudness normalization, and watches for silence.
+///
+/// Every 100 ms of audio yields a [`MeterReading`] with per channel peak and RMS levels,
+/// the momentary and short-term loudness and the true peak, measured as EBU R128 and
+/// ITU-R BS.1770-4 specify. The gated integrated loudness and maximum true peak cover
+/// everything since the meter was created or reset.
+///
+/// Silence is audio whose RMS level stays below a threshold in every channel. Silences
+/// lasting the minimum duration are reported once that duration has passed, which catches
+/// captures that are silent because of a misconfiguration, and again when they end, which
+/// marks the stretches to cut.
+///
+/// Timestamps follow the sample count from the first frame and move to a frame's own
+/// timestamp when it's off by more than 20 ms. A change of sample rate or channel count
+/// restarts the measurement.
+#[derive(Debug)]
+pub struct AudioMeter {
+    silence_threshold_db: f64,
+    min_silence: MediaTime,
+    true_peak: bool,
+    measurement: Option<Measurement>,
+    /// The loudness block energies above the absolute gate.
+    blocks: Vec<f64>,
+    max_true_peak: f64,
+    /// The start of the current silence, and whether it has been reported.
+    silence: Option<(MediaTime, bool)>,
+}
+
+impl Default for AudioMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioMeter {
+    pub const fn new() -> Self {
+        Self {
+            silence_threshold_db: -60.0,
+            min_silence: MediaTime::new(2, 1),
+            true_peak: true,
+            measurement: None,
+            blocks: Vec::new(),
+            max_true_peak: f64::NEG_INFINITY,
+            silence: None,
+        }
+    }
+
+    /// Sets the RMS level in dBFS below which audio is silent. Defaults to -60 dB.
+    #[must_use]
+    pub const fn with_silence_threshold(mut self, threshold_db: f64) -> Self {
+        self.silence_threshold_db = threshold_db;
+        self
+    }
+
+    /// Sets how long audio has to be silent to report it. Defaults to 2 s.
+    #[must_use]
+    pub fn with_min_silence(mut self, duration: impl Into<MediaTime>) -> Self {
+        self.min_silence = duration.into();
+        self
+    }
+
+    /// Turns oversampled true-peak measurement on or off, leaving the sample peak. It's
+    /// the most expensive part of metering. Defaults to on.
+    #[must_use]
+    pub const fn with_true_peak(mut self, true_peak: bool) -> Self {
+        self.true_peak = true_peak;
+        self
+    }
+
+    /// Returns the gated integrated loudness in LUFS, or `None` before any audio above
+    /// the absolute gate.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        let mean = |blocks: &mut dyn Iterator<Item = f64>| {
+            let (sum, count) =
+                blocks.fold((0.0, 0), |(sum, count), block| (sum + block, count + 1));
+            (count > 0).then(|| sum / f64::from(count))
+        };
+        let ungated = mean(&mut self.blocks.iter().copied())?;
+        let threshold = to_lufs(ungated) + RELATIVE_GATE;
+        mean(
+            &mut self
+                .blocks
+                .iter()
+                .copied()
+                .filter(|&block| to_lufs(block) > threshold),
+        )
+        .map(to_lufs)
+    }
+
+    /// Returns the highest true peak so far in dBTP.
+    pub const fn max_true_peak(&self) -> f64 {
+        self.max_true_peak
+    }
+
+    /// Forgets all audio so far.
+    pub fn reset(&mut self) {
+        self.measurement = None;
+        self.blocks.clear();
+        self.max_true_peak = f64::NEG_INFINITY;
+        self.silence = None;
+    }
+
+    /// Meters a frame, returning a reading per completed step and any silence events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame's timestamp is invalid or it has no channels or sample
+    /// rate.
+    pub fn push(&mut self, frame: &AudioFrame) -> Result<Vec<MeterEvent>, CFError> {
+        if !frame.pts.is_valid() {
+            return Err(create_sc_error("audio frames need valid timestamps"));
+        }
+        if frame.sample_rate == 0 || frame.channels == 0 {
+            return Err(create_sc_error(
+                "audio frames need a sample rate and channels",
+            ));
+        }
+        let format = (frame.sample_rate, frame.channels);
+        let same_format = self
+            .measurement
+            .as_ref()
+            .is_some_and(|measurement| (measurement.rate, measurement.channels) == format);
+        if !same_format {
+            self.reset();
+        }
+        let measurement = self
+            .measurement
+            .get_or_insert_with(|| Measurement::new(frame, self.true_peak));
+        let expected = measurement.time(measurement.received);
+        if (frame.pts - expected).as_secs_f64().abs() > GAP_TOLERANCE {
+            measurement.origin = measurement.origin + (frame.pts - expected);
+        }
+        let mut events = Vec::new();
+        for samples in frame.samples.chunks_exact(usize::from(frame.channels)) {
+            let Some((reading, block)) = measurement.add(samples) else {
+                continue;
+            };
+            if to_lufs(block) > ABSOLUTE_GATE {
+                self.blocks.push(block);
+            }
+            self.max_true_peak = self.max_true_peak.max(reading.true_peak);
+            let is_silent = reading
+                .channels
+                .iter()
+                .all(|level| level.rms_db() < self.silence_threshold_db);
+            let end = reading.pts + reading.duration;
+            match (self.silence, is_silent) {
+                (None, true) => self.silence = Some((reading.pts, false)),
+                (Some((start, reported)), false) => {
+                    if reported {
+                        events.push(MeterEvent::SilenceEnded {
+                            start,
+                            end: reading.pts,
+                        });
+                    }
+                    self.silence = None;
+                }
+                _ => {}
+            }
+            events.push(MeterEvent::Reading(reading));
+            if let Some((start, false)) = self.silence {
+                if end - start >= self.min_silence {
+                    events.push(MeterEvent::SilenceStarted(start));
+                    self.silence = Some((start, true));
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod audio_meter_test {
+    use std::f64::consts::TAU;
+
+    use crate::media::{audio_frame::AudioFrame, media_time::MediaTime};
+
+    use super::{AudioMeter, MeterEvent, MeterReading};
+
+    const RATE: u32 = 48_000;
+
+    /// Stereo sine segments of the given frequency, level in dBFS and duration in seconds,
+    /// the make-up of the EBU Tech 3341 test signals.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn signal(frequency: f64, phase: f64, segments: &[(f64, f64)]) -> Vec<f32> {
+        segments
+            .iter()
+            .flat_map(|&(level_db, seconds)| {
+                let amplitude = 10f64.powf(level_db / 20.0);
+                (0..(seconds * f64::from(RATE)).round() as u32).flat_map(move |index| {
+                    let time = f64::from(index) / f64::from(RATE);
+                    let sample = (amplitude * TAU.mul_add(frequency * time, phase).sin()) as f32;
+                    [sample, sample]
+                })
+            })
+            .collect()
+    }
+
+    /// Meters `samples` in 20 ms frames starting at `start`.
+    fn meter(meter: &mut AudioMeter, samples: &[f32], start: MediaTime) -> Vec<MeterEvent> {
+        let mut events = Vec::new();
+        for (index, chunk) in (0..).zip(samples.chunks(2 * 960)) {
+            let pts = start + MediaTime::from_millis(20 * index);
+            let frame = AudioFrame::new(RATE, 2, chunk.to_vec(), pts);
+            events.extend(meter.push(&frame).unwrap());
+        }
+        events
+    }
+
+    fn readings(events: &[MeterEvent]) -> Vec<&MeterReading> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                MeterEvent::Reading(reading) => Some(reading),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_near(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{value} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn test_ebu_loudness() {
+        // EBU Tech 3341 tests 1 and 2: constant 1 kHz tones read at their level.
+        for level in [-23.0, -33.0] {
+            let mut audio_meter = AudioMeter::new().with_true_peak(false);
+            let events = meter(
+                &mut audio_meter,
+                &signal(1000.0, 0.0, &[(level, 20.0)]),
+                MediaTime::ZERO,
+            );
+            let readings = readings(&events);
+            assert_eq!(readings.len(), 200);
+            let last = readings.last().unwrap();
+            assert_eq!(last.pts, MediaTime::from_millis(19_900));
+            assert_eq!(last.duration, MediaTime::from_millis(100));
+            assert_near(last.momentary, level, 0.1);
+            assert_near(last.short_term, level, 0.1);
+            assert_near(audio_meter.integrated_loudness().unwrap(), level, 0.1);
+            assert_near(last.channels[0].peak_db(), level, 0.01);
+            assert_near(last.channels[1].rms_db(), level - 3.01, 0.01);
+        }
+
+        // Tests 3 to 5: gating keeps quieter and silent stretches out of -23 LUFS.
+        for segments in [
+            &[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)][..],
+            &[
+                (-72.0, 20.0),
+                (-36.0, 10.0),
+                (-23.0, 60.0),
+                (-36.0, 10.0),
+                (-72.0, 20.0),
+            ],
+            &[(-26.0, 20.0), (-20.0, 20.1), (-26.0, 20.0)],
+        ] {
+            let mut audio_meter = AudioMeter::new().with_true_peak(false);
+            meter(
+                &mut audio_meter,
+                &signal(1000.0, 0.0, segments),
+                MediaTime::ZERO,
+            );
+            assert_near(audio_meter.integrated_loudness().unwrap(), -23.0, 0.1);
+        }
+
+        let mut audio_meter = AudioMeter::new();
+        meter(
+            &mut audio_meter,
+            &signal(1000.0, 0.0, &[(-80.0, 1.0)]),
+            MediaTime::ZERO,
+        );
+        assert_eq!(audio_meter.integrated_loudness(), None);
+    }
+
+    #[test]
+    fn test_true_peak() {
+        // A quarter sample rate tone sampled 45 degrees off its peaks, as in the true-peak
+        // tests of EBU Tech 3341, peaks 3 dB above its samples.
+        let mut audio_meter = AudioMeter::new();
+        let samples = signal(12_000.0, TAU / 8.0, &[(-6.0, 1.0)]);
+        let events = meter(&mut audio_meter, &samples, MediaTime::ZERO);
+        let last = readings(&events).pop().unwrap();
+        assert_near(last.channels[0].peak_db(), -9.01, 0.01);
+        assert_near(last.true_peak, -6.0, 0.2);
+        assert_near(audio_meter.max_true_peak(), -6.0, 0.2);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut audio_meter = AudioMeter::new().with_true_peak(false);
+        let samples = signal(
+            1000.0,
+            0.0,
+            &[(-20.0, 1.0), (-90.0, 3.0), (-20.0, 1.0), (-90.0, 1.0)],
+        );
+        let start = MediaTime::from_millis(5000);
+        let events = meter(&mut audio_meter, &samples, start);
+        let silences: Vec<_> = events
+            .iter()
+            .filter(|event| !matches!(event, MeterEvent::Reading(_)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            silences,
+            [
+                MeterEvent::SilenceStarted(MediaTime::from_millis(6000)),
+                MeterEvent::SilenceEnded {
+                    start: MediaTime::from_millis(6000),
+                    end: MediaTime::from_millis(9000),
+                },
+            ]
+        );
+        // The start is reported once the silence has lasted 2 s.
+        let started = events
+            .iter()
+            .position(|event| matches!(event, MeterEvent::SilenceStarted(_)))
+            .unwrap();
+        let MeterEvent::Reading(reading) = &events[started - 1] else {
+            panic!("a silence starts after a reading");
+        };
+        assert_eq!(reading.pts, MediaTime::from_millis(7900));
+    }
+}
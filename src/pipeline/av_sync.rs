@@ -0,0 +1,450 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    time::Duration,
+};
+
+use core_foundation::error::CFError;
+
+use crate::{media::media_time::MediaTime, utils::error::create_sc_error};
+
+/// Fraction of the remaining difference between the audio sample clock and the host clock
+/// corrected per packet.
+const DRIFT_SMOOTHING: f64 = 0.01;
+/// Differences between the audio sample clock and the host clock beyond this, in seconds,
+/// are dropouts rather than drift and are corrected at once.
+const DRIFT_TOLERANCE: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+impl MediaKind {
+    const fn other(self) -> Self {
+        match self {
+            Self::Video => Self::Audio,
+            Self::Audio => Self::Video,
+        }
+    }
+}
+
+/// A sample mapped onto the session clock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedSample<T> {
+    pub kind: MediaKind,
+    /// Presentation time on the session clock, which starts at zero.
+    pub pts: MediaTime,
+    /// The presentation time the sample was captured with.
+    pub host_pts: MediaTime,
+    pub duration: Option<MediaTime>,
+    /// `true` for the first sample after a gap or discontinuity in the stream's timestamps.
+    pub is_discontinuity: bool,
+    pub payload: T,
+}
+
+/// Orders pending samples by session time, then by arrival.
+#[derive(Debug)]
+struct Pending<T>(SyncedSample<T>, u64);
+
+impl<T> PartialEq for Pending<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<T> Eq for Pending<T> {}
+impl<T> PartialOrd for Pending<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T> Ord for Pending<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.pts, self.1).cmp(&(other.0.pts, other.1))
+    }
+}
+
+#[derive(Debug, Default)]
+struct StreamClock {
+    /// A host time and the session time it maps to.
+    anchor: Option<(MediaTime, MediaTime)>,
+    /// The host time the next sample is expected at.
+    expected_host: MediaTime,
+    /// Session time of the latest sample.
+    latest: MediaTime,
+    /// Session time the latest sample ends at.
+    end: MediaTime,
+    /// The duration of the latest sample, or the time since the one before for samples without one.
+    interval: MediaTime,
+    /// Session time of the next audio sample when laid end to end with the previous ones.
+    sample_clock: Option<MediaTime>,
+    /// Smoothed difference in seconds between the host clock and the sample clock.
+    correction: f64,
+}
+
+/// Maps screen and audio samples, whose timestamps come from unrelated clocks, onto a
+/// common session clock starting at zero and interleaves them in timestamp order.
+///
+/// Each stream is anchored to the session clock by its first sample, which starts where the
+/// other stream currently is. A timestamp jumping backwards or further ahead than the gap
+/// threshold, as after pausing and resuming a stream, re-anchors the stream at the end of
+/// its previous sample or at the other stream's position, whichever is later, so the pause
+/// is cut from the session.
+///
+/// Audio samples with a duration are laid end to end and gradually steered towards their
+/// host timestamps, which removes the jitter of the host timestamps while correcting the
+/// drift of the audio device clock over long sessions.
+#[derive(Debug)]
+pub struct AvSync<T> {
+    gap_threshold: MediaTime,
+    max_latency: MediaTime,
+    corrects_drift: bool,
+    video: StreamClock,
+    audio: StreamClock,
+    pending: BinaryHeap<Reverse<Pending<T>>>,
+    newest: MediaTime,
+    sequence: u64,
+}
+
+impl<T> Default for AvSync<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> AvSync<T> {
+    pub fn new() -> Self {
+        Self {
+            gap_threshold: MediaTime::from_millis(1000),
+            max_latency: MediaTime::from_millis(500),
+            corrects_drift: true,
+            video: StreamClock::default(),
+            audio: StreamClock::default(),
+            pending: BinaryHeap::new(),
+            newest: MediaTime::ZERO,
+            sequence: 0,
+        }
+    }
+
+    /// Sets how far a timestamp may jump ahead before the stream is treated as resumed. Defaults to one second.
+    #[must_use]
+    pub fn with_gap_threshold(mut self, gap_threshold: Duration) -> Self {
+        self.gap_threshold = gap_threshold.into();
+        self
+    }
+
+    /// Sets how long samples wait for the other stream before they are released regardless. Defaults to 500 ms.
+    #[must_use]
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency.into();
+        self
+    }
+
+    /// Enables or disables audio drift correction, which is enabled by default.
+    #[must_use]
+    pub const fn with_drift_correction(mut self, corrects_drift: bool) -> Self {
+        self.corrects_drift = corrects_drift;
+        self
+    }
+
+    /// Returns the current difference between the audio sample clock and the host clock.
+    pub fn audio_drift(&self) -> MediaTime {
+        MediaTime::from_secs_f64(self.audio.correction)
+    }
+
+    /// Adds a video sample captured at `pts`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `pts` is invalid.
+    pub fn push_video(&mut self, pts: impl Into<MediaTime>, payload: T) -> Result<(), CFError> {
+        self.push(MediaKind::Video, pts.into(), None, payload)
+    }
+
+    /// Adds an audio sample captured at `pts` lasting `duration`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `pts` or `duration` is invalid.
+    pub fn push_audio(
+        &mut self,
+        pts: impl Into<MediaTime>,
+        duration: impl Into<MediaTime>,
+        payload: T,
+    ) -> Result<(), CFError> {
+        self.push(MediaKind::Audio, pts.into(), Some(duration.into()), payload)
+    }
+
+    /// Returns the next sample in timestamp order once the other stream has caught up with it
+    /// or it has waited for the maximum latency.
+    pub fn pop(&mut self) -> Option<SyncedSample<T>> {
+        let Reverse(Pending(next, _)) = self.pending.peek()?;
+        let other = self.clock(next.kind.other());
+        let is_ready = other.anchor.is_none()
+            || next.pts <= other.latest
+            || self.newest - next.pts > self.max_latency;
+        if is_ready {
+            self.pending.pop().map(|Reverse(Pending(sample, _))| sample)
+        } else {
+            None
+        }
+    }
+
+    /// Returns all remaining samples in timestamp order, e.g. when the stream stops.
+    pub fn flush(&mut self) -> Vec<SyncedSample<T>> {
+        let mut samples = Vec::with_capacity(self.pending.len());
+        while let Some(Reverse(Pending(sample, _))) = self.pending.pop() {
+            samples.push(sample);
+        }
+        samples
+    }
+
+    const fn clock(&self, kind: MediaKind) -> &StreamClock {
+        match kind {
+            MediaKind::Video => &self.video,
+            MediaKind::Audio => &self.audio,
+        }
+    }
+
+    fn clock_mut(&mut self, kind: MediaKind) -> &mut StreamClock {
+        match kind {
+            MediaKind::Video => &mut self.video,
+            MediaKind::Audio => &mut self.audio,
+        }
+    }
+
+    fn push(
+        &mut self,
+        kind: MediaKind,
+        host_pts: MediaTime,
+        duration: Option<MediaTime>,
+        payload: T,
+    ) -> Result<(), CFError> {
+        if !host_pts.is_valid() || duration.is_some_and(|duration| !duration.is_valid()) {
+            return Err(create_sc_error("AvSync needs valid timestamps"));
+        }
+        let other = self.clock(kind.other());
+        let other_position = other.anchor.map(|_| other.end.max(other.latest));
+        let (gap_threshold, corrects_drift) = (self.gap_threshold, self.corrects_drift);
+        let clock = self.clock_mut(kind);
+
+        let mut is_discontinuity = false;
+        let is_first = clock.anchor.is_none();
+        let anchor = match clock.anchor {
+            None => (host_pts, other_position.unwrap_or(MediaTime::ZERO)),
+            Some(_)
+                if host_pts < clock.expected_host - gap_threshold
+                    || host_pts - clock.expected_host > gap_threshold =>
+            {
+                is_discontinuity = true;
+                clock.sample_clock = None;
+                clock.correction = 0.0;
+                let resumed = clock.end.max(clock.latest + clock.interval);
+                (
+                    host_pts,
+                    other_position.map_or(resumed, |other| other.max(resumed)),
+                )
+            }
+            Some(anchor) => anchor,
+        };
+        clock.anchor = Some(anchor);
+        let host_session = host_pts - anchor.0 + anchor.1;
+
+        let pts = match duration {
+            Some(duration) if kind == MediaKind::Audio && corrects_drift => {
+                let mut sample_pts = clock.sample_clock.unwrap_or(host_session);
+                let error = (host_session - sample_pts).as_secs_f64();
+                if (error - clock.correction).abs() > DRIFT_TOLERANCE {
+                    is_discontinuity = clock.sample_clock.is_some();
+                    sample_pts = host_session;
+                    clock.correction = 0.0;
+                } else {
+                    clock.correction += (error - clock.correction) * DRIFT_SMOOTHING;
+                }
+                clock.sample_clock = Some(sample_pts + duration);
+                (sample_pts + MediaTime::from_secs_f64(clock.correction)).max(clock.latest)
+            }
+            _ => host_session,
+        };
+        clock.interval = match duration {
+            Some(duration) => duration,
+            None if !is_first && !is_discontinuity => host_pts - clock.expected_host,
+            None => clock.interval,
+        };
+        clock.expected_host = host_pts + duration.unwrap_or_default();
+        clock.latest = pts;
+        clock.end = clock.end.max(pts + duration.unwrap_or_default());
+
+        self.newest = self.newest.max(pts);
+        self.sequence += 1;
+        self.pending.push(Reverse(Pending(
+            SyncedSample {
+                kind,
+                pts,
+                host_pts,
+                duration,
+                is_discontinuity,
+                payload,
+            },
+            self.sequence,
+        )));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod av_sync_test {
+    use std::time::Duration;
+
+    use crate::media::media_time::MediaTime;
+
+    use super::{AvSync, MediaKind, SyncedSample};
+
+    const AUDIO_FRAMES: i64 = 1024;
+
+    fn drain(sync: &mut AvSync<usize>, out: &mut Vec<SyncedSample<usize>>) {
+        while let Some(sample) = sync.pop() {
+            out.push(sample);
+        }
+    }
+
+    /// Simulates `seconds` of 30 fps video and 48 kHz audio from a device clock running
+    /// `drift_ppm` fast, with both streams' host clocks starting at unrelated values.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn simulate(
+        sync: &mut AvSync<usize>,
+        seconds: i64,
+        drift_ppm: f64,
+    ) -> Vec<SyncedSample<usize>> {
+        let video_origin = 7_000_000_000_i64;
+        let audio_origin = 123_456_000_000_i64;
+        let audio_interval = AUDIO_FRAMES as f64 / 48_000.0 / (1.0 + drift_ppm / 1e6);
+        let mut out = Vec::new();
+        let (mut video, mut audio) = (0_i64, 0_i64);
+        loop {
+            let video_time = video as f64 / 30.0;
+            let audio_time = audio as f64 * audio_interval;
+            if video_time.min(audio_time) > seconds as f64 {
+                break;
+            }
+            if video_time <= audio_time {
+                let pts = MediaTime::from_nanos(video_origin + (video_time * 1e9) as i64);
+                sync.push_video(pts, video as usize).unwrap();
+                video += 1;
+            } else {
+                // Deterministic jitter of up to 2 ms on the host timestamps.
+                let jitter = ((audio * 7919 + 2) % 5 - 2) as f64 / 1000.0;
+                let pts =
+                    MediaTime::from_nanos(audio_origin + ((audio_time + jitter) * 1e9) as i64);
+                sync.push_audio(pts, MediaTime::new(AUDIO_FRAMES, 48_000), audio as usize)
+                    .unwrap();
+                audio += 1;
+            }
+            drain(sync, &mut out);
+        }
+        out.extend(sync.flush());
+        out
+    }
+
+    /// Returns how far the audio and video session clocks disagree at the end of `samples`.
+    #[allow(clippy::cast_precision_loss)]
+    fn final_offset(samples: &[SyncedSample<usize>]) -> f64 {
+        let last = |kind| samples.iter().rev().find(|s| s.kind == kind).unwrap();
+        let (video, audio) = (last(MediaKind::Video), last(MediaKind::Audio));
+        let video_time = video.payload as f64 / 30.0;
+        let audio_time = audio.payload as f64 * AUDIO_FRAMES as f64 / 48_000.0 / 1.0005;
+        (audio.pts - video.pts).as_secs_f64() - (audio_time - video_time)
+    }
+
+    #[test]
+    fn test_corrects_drift() {
+        let mut sync = AvSync::new();
+        let samples = simulate(&mut sync, 600, 500.0);
+        assert_eq!(samples[0].pts, MediaTime::ZERO);
+        assert!(samples.windows(2).all(|pair| pair[0].pts <= pair[1].pts));
+        assert!(samples.iter().all(|sample| !sample.is_discontinuity));
+        assert!(
+            final_offset(&samples).abs() < 0.005,
+            "offset {}",
+            final_offset(&samples)
+        );
+
+        let audio: Vec<_> = samples
+            .iter()
+            .filter(|s| s.kind == MediaKind::Audio)
+            .collect();
+        let nominal = MediaTime::new(AUDIO_FRAMES, 48_000).as_secs_f64();
+        for pair in audio.windows(2) {
+            let step = (pair[1].pts - pair[0].pts).as_secs_f64();
+            assert!((step - nominal).abs() < nominal * 0.01);
+        }
+        assert!(sync.audio_drift() < MediaTime::from_millis(-250));
+    }
+
+    #[test]
+    fn test_without_drift_correction() {
+        let mut sync = AvSync::new().with_drift_correction(false);
+        let samples = simulate(&mut sync, 60, 500.0);
+        // Host timestamps are followed exactly, including their jitter.
+        assert!(final_offset(&samples).abs() < 0.003);
+        let audio: Vec<_> = samples
+            .iter()
+            .filter(|s| s.kind == MediaKind::Audio)
+            .collect();
+        assert!(audio.windows(2).any(|pair| {
+            (pair[1].pts - pair[0].pts - MediaTime::new(AUDIO_FRAMES, 48_000)).as_secs_f64() > 0.001
+        }));
+    }
+
+    #[test]
+    fn test_pause_is_cut() {
+        let mut sync = AvSync::new().with_gap_threshold(Duration::from_millis(500));
+        let mut out = Vec::new();
+        let frame = |i: i64| MediaTime::new(1_000 + i, 10);
+        for i in 0..10 {
+            sync.push_video(frame(i), 0).unwrap();
+        }
+        for i in 100..110 {
+            sync.push_video(frame(i), 0).unwrap();
+        }
+        sync.push_video(frame(50), 0).unwrap();
+        drain(&mut sync, &mut out);
+        out.extend(sync.flush());
+
+        let times: Vec<i64> = out.iter().map(|s| s.pts.as_millis()).collect();
+        assert_eq!(times[..3], [0, 100, 200]);
+        assert_eq!(times[9..12], [900, 1000, 1100]);
+        assert_eq!(times[20..], [2000]);
+        let discontinuities: Vec<usize> = (0..out.len())
+            .filter(|i| out[*i].is_discontinuity)
+            .collect();
+        assert_eq!(discontinuities, vec![10, 20]);
+    }
+
+    #[test]
+    fn test_interleaves_in_order() {
+        let mut sync = AvSync::new();
+        let mut out = Vec::new();
+        sync.push_video(MediaTime::from_millis(5_000), 0).unwrap();
+        sync.push_audio(MediaTime::new(0, 48_000), MediaTime::new(480, 48_000), 1)
+            .unwrap();
+        sync.push_audio(MediaTime::new(480, 48_000), MediaTime::new(480, 48_000), 2)
+            .unwrap();
+        drain(&mut sync, &mut out);
+        sync.push_video(MediaTime::from_millis(5_015), 3).unwrap();
+        drain(&mut sync, &mut out);
+        sync.push_audio(MediaTime::new(960, 48_000), MediaTime::new(480, 48_000), 4)
+            .unwrap();
+        drain(&mut sync, &mut out);
+
+        let order: Vec<usize> = out.iter().map(|s| s.payload).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+        assert_eq!(out[3].pts, MediaTime::from_millis(15));
+        assert_eq!(sync.flush()[0].pts, MediaTime::from_millis(20));
+    }
+}
@@ -0,0 +1,371 @@
+use std::{borrow::Cow, time::Duration};
+
+use crate::{
+    media::{media_time::MediaTime, video_frame::VideoFrame},
+    pipeline::{
+        overlay::{blend_image, OverlayImage},
+        redaction::FrameMapping,
+    },
+};
+
+use super::CursorTrack;
+
+/// A black arrow with a white fill, pointing at its top left pixel.
+const ARROW: [&str; 19] = [
+    "X           ",
+    "XX          ",
+    "X.X         ",
+    "X..X        ",
+    "X...X       ",
+    "X....X      ",
+    "X.....X     ",
+    "X......X    ",
+    "X.......X   ",
+    "X........X  ",
+    "X.........X ",
+    "X......XXXXX",
+    "X...X..X    ",
+    "X..XX..X    ",
+    "X.X  X..X   ",
+    "XX   X..X   ",
+    "X     X..X  ",
+    "      X..X  ",
+    "       XX   ",
+];
+
+/// The width in pixels of click ripples.
+const RIPPLE_WIDTH: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ring {
+    radius: f64,
+    width: f64,
+    bgra: [u8; 4],
+}
+
+impl Ring {
+    /// Renders the ring with antialiased edges, centered between the four middle pixels.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn render(&self) -> OverlayImage {
+        let half = (self.radius + self.width / 2.0).ceil() as u32 + 1;
+        let mut image = OverlayImage::filled(2 * half, 2 * half, [0; 4]);
+        for y in 0..2 * half {
+            for x in 0..2 * half {
+                let distance = (f64::from(x) + 0.5 - f64::from(half))
+                    .hypot(f64::from(y) + 0.5 - f64::from(half));
+                let coverage =
+                    (self.width / 2.0 + 0.5 - (distance - self.radius).abs()).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    let [blue, green, red, alpha] = self.bgra;
+                    let alpha = (coverage * f64::from(alpha)).round() as u8;
+                    image.set_pixel(x, y, [blue, green, red, alpha]);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// Draws the cursor from a [`CursorTrack`] onto frames captured without it.
+///
+/// The sprite, an arrow unless replaced, is drawn with its hotspot on the cursor position,
+/// optionally above a highlight ring. Presses leave a ripple that grows and fades out over
+/// its duration, and stays visible while the cursor is hidden. Sizes are in frame pixels.
+#[derive(Debug, Clone)]
+pub struct CursorCompositor {
+    sprite: OverlayImage,
+    hotspot: (u32, u32),
+    highlight: Option<Ring>,
+    ripple: Option<(MediaTime, Ring)>,
+}
+
+impl Default for CursorCompositor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CursorCompositor {
+    pub fn new() -> Self {
+        let mut sprite = OverlayImage::filled(12, 19, [0; 4]);
+        for (y, row) in (0..).zip(ARROW) {
+            for (x, pixel) in (0..).zip(row.bytes()) {
+                match pixel {
+                    b'X' => sprite.set_pixel(x, y, [0, 0, 0, 255]),
+                    b'.' => sprite.set_pixel(x, y, [255, 255, 255, 255]),
+                    _ => {}
+                }
+            }
+        }
+        Self {
+            sprite,
+            hotspot: (0, 0),
+            highlight: None,
+            ripple: None,
+        }
+    }
+
+    /// Replaces the arrow with a custom sprite whose pixel at `hotspot_x`, `hotspot_y`
+    /// points at the cursor position.
+    #[must_use]
+    pub fn with_sprite(mut self, sprite: OverlayImage, hotspot_x: u32, hotspot_y: u32) -> Self {
+        self.sprite = sprite;
+        self.hotspot = (hotspot_x, hotspot_y);
+        self
+    }
+
+    /// Draws a ring around the cursor. A `width` of at least twice the `radius` fills it.
+    #[must_use]
+    pub fn with_highlight(mut self, radius: u32, width: u32, bgra: [u8; 4]) -> Self {
+        self.highlight = Some(Ring {
+            radius: f64::from(radius),
+            width: f64::from(width),
+            bgra,
+        });
+        self
+    }
+
+    /// Draws a ring growing to `radius` around each press over `duration`.
+    #[must_use]
+    pub fn with_click_ripple(mut self, duration: Duration, radius: u32, bgra: [u8; 4]) -> Self {
+        self.ripple = Some((
+            duration.into(),
+            Ring {
+                radius: f64::from(radius),
+                width: RIPPLE_WIDTH,
+                bgra,
+            },
+        ));
+        self
+    }
+
+    /// Draws the cursor as it was at `pts` on the session clock onto `frame`, using
+    /// `mapping` to place its global position.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn apply(
+        &self,
+        frame: &mut VideoFrame,
+        track: &CursorTrack,
+        pts: impl Into<MediaTime>,
+        mapping: &FrameMapping,
+    ) {
+        let pts = pts.into();
+        if let Some((duration, ring)) = self.ripple {
+            for click in track.clicks(pts - duration, pts) {
+                let progress = (pts - click.pts).as_secs_f64() / duration.as_secs_f64();
+                let ripple = Ring {
+                    radius: (ring.radius * progress).max(1.0),
+                    ..ring
+                };
+                let opacity = (255.0 * (1.0 - progress)).round() as u8;
+                let center = mapping.point_to_frame(&click.position);
+                draw_centered(frame, &ripple.render(), center.x, center.y, opacity);
+            }
+        }
+        let state = track.state_at(pts);
+        let Some(position) = state.position.filter(|_| state.visible) else {
+            return;
+        };
+        let point = mapping.point_to_frame(&position);
+        if let Some(highlight) = self.highlight {
+            draw_centered(frame, &highlight.render(), point.x, point.y, 255);
+        }
+        blend_at(
+            frame,
+            &self.sprite,
+            point.x.round() as i64 - i64::from(self.hotspot.0),
+            point.y.round() as i64 - i64::from(self.hotspot.1),
+            255,
+        );
+    }
+}
+
+/// Blends an image with an even size centered on the pixel corner nearest to `x`, `y`.
+#[allow(clippy::cast_possible_truncation)]
+fn draw_centered(frame: &mut VideoFrame, image: &OverlayImage, x: f64, y: f64, opacity: u8) {
+    blend_at(
+        frame,
+        image,
+        x.round() as i64 - i64::from(image.width() / 2),
+        y.round() as i64 - i64::from(image.height() / 2),
+        opacity,
+    );
+}
+
+/// Blends an image that may hang off the top or left edge of the frame.
+fn blend_at(frame: &mut VideoFrame, image: &OverlayImage, x: i64, y: i64, opacity: u8) {
+    let skip_x = u32::try_from(-x.min(0)).unwrap_or(u32::MAX);
+    let skip_y = u32::try_from(-y.min(0)).unwrap_or(u32::MAX);
+    let (Ok(x), Ok(y)) = (u32::try_from(x.max(0)), u32::try_from(y.max(0))) else {
+        return;
+    };
+    if skip_x >= image.width() || skip_y >= image.height() {
+        return;
+    }
+    let image = if skip_x == 0 && skip_y == 0 {
+        Cow::Borrowed(image)
+    } else {
+        let mut cropped =
+            OverlayImage::filled(image.width() - skip_x, image.height() - skip_y, [0; 4]);
+        for crop_y in 0..cropped.height() {
+            for crop_x in 0..cropped.width() {
+                cropped.set_pixel(
+                    crop_x,
+                    crop_y,
+                    image.pixel(crop_x + skip_x, crop_y + skip_y),
+                );
+            }
+        }
+        Cow::Owned(cropped)
+    };
+    blend_image(frame, &image, x, y, opacity);
+}
+
+#[cfg(test)]
+mod compositor_test {
+    use std::time::Duration;
+
+    use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+    use crate::{
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+        pipeline::{
+            cursor::{CursorEvent, CursorTrack, MouseButton},
+            overlay::OverlayImage,
+            redaction::FrameMapping,
+        },
+    };
+
+    use super::CursorCompositor;
+
+    const GRAY: [u8; 4] = [128, 128, 128, 255];
+    const RED: [u8; 4] = [0, 0, 255, 255];
+
+    fn frame() -> VideoFrame {
+        VideoFrame::filled_bgra(40, 30, GRAY, MediaTime::ZERO)
+    }
+
+    /// Maps global points with their origin at 100, 100 to pixels at two pixels per point.
+    fn mapping() -> FrameMapping {
+        FrameMapping::new(
+            CGPoint::new(100.0, 100.0),
+            CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(20.0, 15.0)),
+            1.0,
+            2.0,
+        )
+    }
+
+    fn track() -> CursorTrack {
+        let mut track = CursorTrack::new();
+        track
+            .push(
+                MediaTime::ZERO,
+                CursorEvent::Move(CGPoint::new(105.0, 105.0)),
+            )
+            .unwrap();
+        track
+            .push(
+                MediaTime::from_millis(100),
+                CursorEvent::Press(MouseButton::Left),
+            )
+            .unwrap();
+        track
+            .push(MediaTime::from_millis(150), CursorEvent::Hide)
+            .unwrap();
+        track
+    }
+
+    #[test]
+    fn test_sprite() {
+        let compositor = CursorCompositor::new().with_sprite(OverlayImage::filled(3, 3, RED), 1, 1);
+        let mut frame = frame();
+        compositor.apply(&mut frame, &track(), MediaTime::from_millis(50), &mapping());
+        for y in 0..30 {
+            for x in 0..40 {
+                let expected = if (9..=11).contains(&x) && (9..=11).contains(&y) {
+                    RED
+                } else {
+                    GRAY
+                };
+                assert_eq!(frame.bgra_pixel(x, y), Some(expected), "at {x}, {y}");
+            }
+        }
+
+        // Hidden cursors are not drawn, and sprites may hang off the frame.
+        let mut hidden = self::frame();
+        compositor.apply(
+            &mut hidden,
+            &track(),
+            MediaTime::from_millis(150),
+            &mapping(),
+        );
+        assert_eq!(hidden, self::frame());
+        let mut corner = self::frame();
+        let corner_mapping = FrameMapping {
+            source_origin: CGPoint::new(105.0, 105.0),
+            ..mapping()
+        };
+        compositor.apply(&mut corner, &track(), MediaTime::ZERO, &corner_mapping);
+        assert_eq!(corner.bgra_pixel(0, 0), Some(RED));
+        assert_eq!(corner.bgra_pixel(1, 1), Some(RED));
+        assert_eq!(corner.bgra_pixel(2, 2), Some(GRAY));
+
+        // The default arrow points with its tip.
+        let mut arrow = self::frame();
+        CursorCompositor::new().apply(&mut arrow, &track(), MediaTime::ZERO, &mapping());
+        assert_eq!(arrow.bgra_pixel(10, 10), Some([0, 0, 0, 255]));
+        assert_eq!(arrow.bgra_pixel(9, 10), Some(GRAY));
+        assert_eq!(arrow.bgra_pixel(11, 12), Some([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_highlight_and_ripple() {
+        let compositor = CursorCompositor::new()
+            .with_sprite(OverlayImage::filled(1, 1, [0; 4]), 0, 0)
+            .with_highlight(4, 2, RED)
+            .with_click_ripple(Duration::from_millis(100), 8, [255, 0, 0, 255]);
+
+        let mut highlighted = frame();
+        compositor.apply(&mut highlighted, &track(), MediaTime::ZERO, &mapping());
+        assert_eq!(highlighted.bgra_pixel(13, 9), Some(RED));
+        assert_eq!(highlighted.bgra_pixel(10, 10), Some(GRAY));
+        assert_eq!(highlighted.bgra_pixel(17, 9), Some(GRAY));
+
+        // Half way through, the ripple is at half its radius and opacity, and outlives the
+        // hidden cursor.
+        let mut rippled = frame();
+        compositor.apply(
+            &mut rippled,
+            &track(),
+            MediaTime::from_millis(150),
+            &mapping(),
+        );
+        assert_eq!(rippled.bgra_pixel(14, 9), Some([192, 64, 64, 255]));
+        assert_eq!(rippled.bgra_pixel(10, 10), Some(GRAY));
+        assert_eq!(rippled.bgra_pixel(19, 9), Some(GRAY));
+
+        let mut faded = frame();
+        compositor.apply(
+            &mut faded,
+            &track(),
+            MediaTime::from_millis(200),
+            &mapping(),
+        );
+        assert_eq!(faded, frame());
+
+        let mut nv12 = VideoFrame::new_nv12(
+            40,
+            30,
+            vec![100; 40 * 30],
+            vec![128; 40 * 15],
+            MediaTime::ZERO,
+        );
+        compositor.apply(&mut nv12, &track(), MediaTime::ZERO, &mapping());
+        assert_ne!(nv12.planes[0].row(9)[13], 100);
+        assert_eq!(nv12.planes[0].row(10)[10], 100);
+    }
+}
@@ -0,0 +1,354 @@
+//! Cursor tracks recorded next to streams captured without the cursor, and compositing of
+//! a cursor sprite back onto their frames.
+mod compositor;
+mod recorder;
+
+pub use compositor::CursorCompositor;
+pub use recorder::CursorRecorder;
+
+use std::fmt::Write;
+
+use core_foundation::error::CFError;
+use core_graphics::geometry::CGPoint;
+
+use crate::{media::media_time::MediaTime, utils::error::create_sc_error};
+
+/// The first line of the text format of cursor tracks.
+const TEXT_HEADER: &str = "# cursor track";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    pub const ALL: [Self; 3] = [Self::Left, Self::Right, Self::Middle];
+
+    const fn mask(self) -> u8 {
+        match self {
+            Self::Left => 1,
+            Self::Right => 2,
+            Self::Middle => 4,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Middle => "middle",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CursorEvent {
+    /// The cursor moved to a position in global points.
+    Move(CGPoint),
+    Show,
+    Hide,
+    Press(MouseButton),
+    Release(MouseButton),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorSample {
+    /// Time on the session clock, which starts at zero.
+    pub pts: MediaTime,
+    pub event: CursorEvent,
+}
+
+/// `CGPoint` has no `PartialEq`, so events compare positions by their coordinates.
+impl PartialEq for CursorEvent {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Move(position), Self::Move(other)) => same_point(*position, *other),
+            (Self::Press(button), Self::Press(other))
+            | (Self::Release(button), Self::Release(other)) => button == other,
+            (Self::Show, Self::Show) | (Self::Hide, Self::Hide) => true,
+            _ => false,
+        }
+    }
+}
+
+fn same_point(point: CGPoint, other: CGPoint) -> bool {
+    (point.x, point.y) == (other.x, other.y)
+}
+
+/// The cursor at a point in time.
+#[derive(Debug, Clone, Copy)]
+pub struct CursorState {
+    /// The position in global points, or `None` before the first move.
+    pub position: Option<CGPoint>,
+    pub visible: bool,
+    pressed: u8,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            position: None,
+            visible: true,
+            pressed: 0,
+        }
+    }
+}
+
+impl PartialEq for CursorState {
+    fn eq(&self, other: &Self) -> bool {
+        let same_position = match (self.position, other.position) {
+            (Some(position), Some(other)) => same_point(position, other),
+            (position, other) => position.is_none() && other.is_none(),
+        };
+        same_position && self.visible == other.visible && self.pressed == other.pressed
+    }
+}
+
+impl CursorState {
+    pub const fn is_pressed(&self, button: MouseButton) -> bool {
+        self.pressed & button.mask() != 0
+    }
+
+    #[must_use]
+    const fn after(mut self, event: CursorEvent) -> Self {
+        match event {
+            CursorEvent::Move(position) => self.position = Some(position),
+            CursorEvent::Show => self.visible = true,
+            CursorEvent::Hide => self.visible = false,
+            CursorEvent::Press(button) => self.pressed |= button.mask(),
+            CursorEvent::Release(button) => self.pressed &= !button.mask(),
+        }
+        self
+    }
+}
+
+/// A mouse button press and where it happened.
+#[derive(Debug, Clone, Copy)]
+pub struct Click {
+    pub pts: MediaTime,
+    pub button: MouseButton,
+    pub position: CGPoint,
+}
+
+/// Cursor positions, visibility changes and button events in timestamp order.
+///
+/// Tracks convert to and from a line based text format with a nanosecond timestamp and an
+/// event per line, e.g. `16666667 move 120.5 80` or `33333333 press left`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CursorTrack {
+    samples: Vec<CursorSample>,
+    /// The state after each sample, so lookups don't replay the whole track.
+    states: Vec<CursorState>,
+}
+
+impl CursorTrack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an event at `pts` on the session clock.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pts` is invalid or earlier than the last event.
+    pub fn push(&mut self, pts: impl Into<MediaTime>, event: CursorEvent) -> Result<(), CFError> {
+        let pts = pts.into();
+        if !pts.is_valid() {
+            return Err(create_sc_error("cursor events need valid timestamps"));
+        }
+        if self.samples.last().is_some_and(|last| pts < last.pts) {
+            return Err(create_sc_error(format!(
+                "cursor event at {} ms is earlier than the last one",
+                pts.as_millis()
+            )));
+        }
+        let state = self.states.last().copied().unwrap_or_default().after(event);
+        self.samples.push(CursorSample { pts, event });
+        self.states.push(state);
+        Ok(())
+    }
+
+    pub fn samples(&self) -> &[CursorSample] {
+        &self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the time of the last event, or zero for empty tracks.
+    pub fn duration(&self) -> MediaTime {
+        self.samples.last().map_or(MediaTime::ZERO, |last| last.pts)
+    }
+
+    /// Returns the cursor after all events at or before `pts`.
+    pub fn state_at(&self, pts: impl Into<MediaTime>) -> CursorState {
+        let pts = pts.into();
+        let count = self.samples.partition_point(|sample| sample.pts <= pts);
+        count
+            .checked_sub(1)
+            .map_or_else(CursorState::default, |index| self.states[index])
+    }
+
+    /// Returns the presses after `start` and at or before `end`, skipping presses before the
+    /// cursor's first position.
+    pub fn clicks(&self, start: impl Into<MediaTime>, end: impl Into<MediaTime>) -> Vec<Click> {
+        let (start, end) = (start.into(), end.into());
+        let first = self.samples.partition_point(|sample| sample.pts <= start);
+        let last = self.samples.partition_point(|sample| sample.pts <= end);
+        (first..last.max(first))
+            .filter_map(|index| match self.samples[index].event {
+                CursorEvent::Press(button) => Some(Click {
+                    pts: self.samples[index].pts,
+                    button,
+                    position: self.states[index].position?,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{TEXT_HEADER}\n");
+        for sample in &self.samples {
+            let pts = sample.pts.as_nanos();
+            // Writing to a `String` cannot fail.
+            let _ = match sample.event {
+                CursorEvent::Move(position) => {
+                    writeln!(text, "{pts} move {} {}", position.x, position.y)
+                }
+                CursorEvent::Show => writeln!(text, "{pts} show"),
+                CursorEvent::Hide => writeln!(text, "{pts} hide"),
+                CursorEvent::Press(button) => writeln!(text, "{pts} press {}", button.name()),
+                CursorEvent::Release(button) => {
+                    writeln!(text, "{pts} release {}", button.name())
+                }
+            };
+        }
+        text
+    }
+
+    /// Parses a track written by [`CursorTrack::to_text`]. Blank lines and lines starting
+    /// with `#` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line of the first malformed or out of order event.
+    pub fn from_text(text: &str) -> Result<Self, CFError> {
+        let mut track = Self::new();
+        for (number, line) in (1..).zip(text.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pts, event) = parse_line(line)
+                .ok_or_else(|| create_sc_error(format!("invalid cursor event on line {number}")))?;
+            track.push(pts, event).map_err(|_| {
+                create_sc_error(format!("cursor event on line {number} is out of order"))
+            })?;
+        }
+        Ok(track)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(MediaTime, CursorEvent)> {
+    let mut fields = line.split_whitespace();
+    let pts = MediaTime::from_nanos(fields.next()?.parse().ok()?);
+    let event = match fields.next()? {
+        "move" => CursorEvent::Move(CGPoint::new(
+            fields.next()?.parse().ok()?,
+            fields.next()?.parse().ok()?,
+        )),
+        "show" => CursorEvent::Show,
+        "hide" => CursorEvent::Hide,
+        "press" => CursorEvent::Press(MouseButton::from_name(fields.next()?)?),
+        "release" => CursorEvent::Release(MouseButton::from_name(fields.next()?)?),
+        _ => return None,
+    };
+    fields.next().is_none().then_some((pts, event))
+}
+
+#[cfg(test)]
+mod cursor_test {
+    use core_graphics::geometry::CGPoint;
+
+    use crate::media::media_time::MediaTime;
+
+    use super::{CursorEvent, CursorTrack, MouseButton};
+
+    fn track() -> CursorTrack {
+        let mut track = CursorTrack::new();
+        let events = [
+            (0, CursorEvent::Move(CGPoint::new(10.0, 20.0))),
+            (100, CursorEvent::Press(MouseButton::Left)),
+            (150, CursorEvent::Move(CGPoint::new(12.5, 20.25))),
+            (200, CursorEvent::Release(MouseButton::Left)),
+            (300, CursorEvent::Hide),
+            (300, CursorEvent::Press(MouseButton::Right)),
+        ];
+        for (millis, event) in events {
+            track
+                .push(MediaTime::from_millis(millis), event)
+                .expect("should push in order");
+        }
+        track
+    }
+
+    #[test]
+    fn test_state_at() {
+        let track = track();
+        assert!(track
+            .clone()
+            .push(MediaTime::from_millis(299), CursorEvent::Show)
+            .is_err());
+        assert_eq!(track.duration(), MediaTime::from_millis(300));
+
+        let before = track.state_at(MediaTime::from_nanos(-1));
+        assert!(before.position.is_none());
+        assert!(before.visible);
+
+        let pressed = track.state_at(MediaTime::from_millis(199));
+        assert_eq!(pressed.position.map(|p| (p.x, p.y)), Some((12.5, 20.25)));
+        assert!(pressed.is_pressed(MouseButton::Left));
+
+        let last = track.state_at(MediaTime::from_millis(300));
+        assert!(!last.visible);
+        assert!(!last.is_pressed(MouseButton::Left));
+        assert!(last.is_pressed(MouseButton::Right));
+
+        let clicks = track.clicks(MediaTime::ZERO, MediaTime::from_millis(300));
+        assert_eq!(clicks.len(), 2);
+        assert_eq!(clicks[0].button, MouseButton::Left);
+        assert_eq!((clicks[0].position.x, clicks[0].position.y), (10.0, 20.0));
+        assert_eq!(clicks[1].pts, MediaTime::from_millis(300));
+        assert!(track
+            .clicks(MediaTime::from_millis(100), MediaTime::from_millis(299))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_text_format() {
+        let track = track();
+        let text = track.to_text();
+        assert_eq!(
+            text.lines().take(4).collect::<Vec<_>>(),
+            [
+                "# cursor track",
+                "0 move 10 20",
+                "100000000 press left",
+                "150000000 move 12.5 20.25"
+            ]
+        );
+        assert_eq!(CursorTrack::from_text(&text).expect("should parse"), track);
+
+        assert!(CursorTrack::from_text("0 show\n\n10 wiggle\n").is_err());
+        assert!(CursorTrack::from_text("10 show\n5 hide").is_err());
+        assert!(CursorTrack::from_text("10 press left extra").is_err());
+    }
+}
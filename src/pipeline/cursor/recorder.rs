@@ -0,0 +1,221 @@
+use core_foundation::error::CFError;
+use core_graphics::geometry::CGPoint;
+
+use crate::{media::media_time::MediaTime, utils::error::create_sc_error};
+
+use super::{same_point, CursorEvent, CursorState, CursorTrack, MouseButton};
+
+/// Records the cursor of a stream captured with `shows_cursor` turned off into a
+/// [`CursorTrack`].
+///
+/// Samples of the whole cursor state are turned into the events that changed since the
+/// previous sample. Host timestamps are mapped to the session clock by subtracting the
+/// origin, which should be the host presentation time of the session's first frame so the
+/// track lines up with the frames. Without an origin, the first sample starts the session.
+///
+/// Sample at least as often as frames arrive; clicks shorter than the sampling interval
+/// are missed.
+#[derive(Debug, Clone, Default)]
+pub struct CursorRecorder {
+    origin: Option<MediaTime>,
+    /// The session time and state of the previous sample.
+    last: Option<(MediaTime, CursorState)>,
+    track: CursorTrack,
+}
+
+impl CursorRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the host time the session clock starts at.
+    #[must_use]
+    pub fn with_origin(mut self, origin: impl Into<MediaTime>) -> Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    pub const fn origin(&self) -> Option<MediaTime> {
+        self.origin
+    }
+
+    pub const fn track(&self) -> &CursorTrack {
+        &self.track
+    }
+
+    pub fn finish(self) -> CursorTrack {
+        self.track
+    }
+
+    /// Records the cursor at a host time, with its position in global points and the
+    /// buttons held down. Samples before the origin are recorded at zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `host_pts` is invalid or earlier than the previous sample.
+    pub fn record(
+        &mut self,
+        host_pts: impl Into<MediaTime>,
+        position: CGPoint,
+        visible: bool,
+        pressed: &[MouseButton],
+    ) -> Result<(), CFError> {
+        let host_pts = host_pts.into();
+        if !host_pts.is_valid() {
+            return Err(create_sc_error("cursor samples need valid timestamps"));
+        }
+        let origin = *self.origin.get_or_insert(host_pts);
+        let pts = (host_pts - origin).max(MediaTime::ZERO);
+        if self.last.is_some_and(|(last_pts, _)| pts < last_pts) {
+            return Err(create_sc_error(format!(
+                "cursor sample at {} ms is earlier than the previous one",
+                pts.as_millis()
+            )));
+        }
+        let last = self.last.map(|(_, state)| state).unwrap_or_default();
+        let mut events = Vec::new();
+        if !last
+            .position
+            .is_some_and(|last_position| same_point(last_position, position))
+        {
+            events.push(CursorEvent::Move(position));
+        }
+        if last.visible != visible {
+            events.push(if visible {
+                CursorEvent::Show
+            } else {
+                CursorEvent::Hide
+            });
+        }
+        for button in MouseButton::ALL {
+            match (last.is_pressed(button), pressed.contains(&button)) {
+                (false, true) => events.push(CursorEvent::Press(button)),
+                (true, false) => events.push(CursorEvent::Release(button)),
+                _ => {}
+            }
+        }
+        for event in events {
+            self.track.push(pts, event)?;
+        }
+        self.last = Some((pts, self.track.state_at(pts)));
+        Ok(())
+    }
+
+    /// Records the system cursor now, timestamped with the host clock `SCStream` uses for
+    /// presentation times.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cursor position can't be read or time went backwards.
+    #[cfg(target_os = "macos")]
+    pub fn record_system_cursor(&mut self) -> Result<(), CFError> {
+        let (host_pts, position, visible, pressed) = system::sample()?;
+        self.record(host_pts, position, visible, &pressed)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod system {
+    use core_foundation::error::CFError;
+    use core_graphics::{
+        event::CGEvent,
+        event_source::{CGEventSource, CGEventSourceStateID},
+        geometry::CGPoint,
+    };
+
+    use crate::{
+        media::media_time::MediaTime,
+        pipeline::cursor::MouseButton,
+        utils::{error::create_sc_error, host_time},
+    };
+
+    extern "C" {
+        fn CGEventSourceButtonState(state: CGEventSourceStateID, button: u32) -> bool;
+        fn CGCursorIsVisible() -> u32;
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn sample() -> Result<(MediaTime, CGPoint, bool, Vec<MouseButton>), CFError> {
+        let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+            .map_err(|()| create_sc_error("could not create an event source"))?;
+        let position = CGEvent::new(source)
+            .map_err(|()| create_sc_error("could not read the cursor position"))?
+            .location();
+        let nanos = host_time::host_time_to_nanos(host_time::now());
+        let (visible, pressed) = unsafe {
+            let pressed = [
+                (MouseButton::Left, 0),
+                (MouseButton::Right, 1),
+                (MouseButton::Middle, 2),
+            ]
+            .into_iter()
+            .filter(|&(_, number)| {
+                CGEventSourceButtonState(CGEventSourceStateID::CombinedSessionState, number)
+            })
+            .map(|(button, _)| button)
+            .collect();
+            (CGCursorIsVisible() != 0, pressed)
+        };
+        Ok((
+            MediaTime::from_nanos(nanos as i64),
+            position,
+            visible,
+            pressed,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod recorder_test {
+    use core_graphics::geometry::CGPoint;
+
+    use crate::{
+        media::media_time::MediaTime,
+        pipeline::cursor::{CursorEvent, MouseButton},
+    };
+
+    use super::CursorRecorder;
+
+    #[test]
+    fn test_record() {
+        let mut recorder = CursorRecorder::new().with_origin(MediaTime::from_millis(5000));
+        let at = |x| CGPoint::new(x, 10.0);
+        recorder
+            .record(MediaTime::from_millis(4990), at(1.0), true, &[])
+            .unwrap();
+        recorder
+            .record(MediaTime::from_millis(5010), at(1.0), true, &[])
+            .unwrap();
+        recorder
+            .record(
+                MediaTime::from_millis(5020),
+                at(2.0),
+                false,
+                &[MouseButton::Left],
+            )
+            .unwrap();
+        recorder
+            .record(MediaTime::from_millis(5030), at(2.0), false, &[])
+            .unwrap();
+        assert!(recorder
+            .record(MediaTime::from_millis(5025), at(3.0), true, &[])
+            .is_err());
+
+        let events: Vec<_> = recorder
+            .finish()
+            .samples()
+            .iter()
+            .map(|sample| (sample.pts.as_millis(), sample.event))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (0, CursorEvent::Move(at(1.0))),
+                (20, CursorEvent::Move(at(2.0))),
+                (20, CursorEvent::Hide),
+                (20, CursorEvent::Press(MouseButton::Left)),
+                (30, CursorEvent::Release(MouseButton::Left)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use std::hash::{DefaultHasher, Hasher};
+
+use crate::{
+    media::{
+        media_time::MediaTime,
+        video_frame::{PixelFormat, PixelRect, VideoFrame},
+    },
+    utils::hash::hash,
+};
+
+/// How the change between frames is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChangeMetric {
+    /// The fraction of tiles whose pixels differ in any way.
+    #[default]
+    Exact,
+    /// The fraction of differing bits of a 64 bit difference hash of the downscaled luma,
+    /// which ignores noise and small shifts in brightness.
+    Perceptual,
+}
+
+/// How much a frame changed compared to the last frame that was kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameChange {
+    pub pts: MediaTime,
+    /// From `0.0` for identical frames to `1.0` for frames that changed everywhere.
+    pub score: f64,
+    /// A hash of the frame: of every pixel for [`ChangeMetric::Exact`], or the perceptual
+    /// hash for [`ChangeMetric::Perceptual`].
+    pub hash: u64,
+    /// `true` if the frame was dropped for changing too little.
+    pub is_dropped: bool,
+}
+
+/// A frame that changed enough to be kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredFrame {
+    pub frame: VideoFrame,
+    pub change: FrameChange,
+}
+
+/// The tile hashes of the latest frame and of the last frame that was kept.
+#[derive(Debug)]
+struct Reference {
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    columns: u32,
+    current: Vec<u64>,
+    kept: Vec<u64>,
+    current_perceptual: u64,
+    kept_perceptual: u64,
+}
+
+/// Drops frames that barely differ from the last frame kept and scores how much each frame
+/// changed, e.g. for picking thumbnails.
+///
+/// Frames are split into square tiles that are hashed separately. With dirty rects enabled,
+/// frames that carry them only rehash the tiles they touch, and frames with no dirty rects
+/// score zero without looking at their pixels. Frames are compared to the last frame that
+/// was kept rather than the previous one, so slow changes still add up. Frames without new
+/// content, like `Idle` frames, are always dropped.
+#[derive(Debug)]
+pub struct FrameDeduplicator {
+    metric: ChangeMetric,
+    threshold: f64,
+    tile_size: u32,
+    uses_dirty_rects: bool,
+    reference: Option<Reference>,
+    dropped: u64,
+}
+
+impl Default for FrameDeduplicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDeduplicator {
+    pub const fn new() -> Self {
+        Self {
+            metric: ChangeMetric::Exact,
+            threshold: 0.0,
+            tile_size: 32,
+            uses_dirty_rects: true,
+            reference: None,
+            dropped: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_metric(mut self, metric: ChangeMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Drops frames scoring at most `threshold`. Defaults to zero, which only drops
+    /// identical frames.
+    #[must_use]
+    pub const fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the size of the tiles hashed separately, rounded up to an even number of pixels.
+    /// Defaults to 32.
+    #[must_use]
+    pub const fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = if tile_size < 2 {
+            2
+        } else {
+            tile_size + tile_size % 2
+        };
+        self
+    }
+
+    /// Sets whether the dirty rects of frames limit what is hashed. Defaults to `true`.
+    #[must_use]
+    pub const fn with_dirty_rects(mut self, uses_dirty_rects: bool) -> Self {
+        self.uses_dirty_rects = uses_dirty_rects;
+        self
+    }
+
+    /// Returns the number of frames dropped so far.
+    pub const fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Scores a frame against the last frame kept, keeping it as the new reference unless it
+    /// is dropped.
+    pub fn measure(&mut self, frame: &VideoFrame) -> FrameChange {
+        let change = self.score(frame);
+        if change.is_dropped {
+            self.dropped += 1;
+        } else if let Some(reference) = &mut self.reference {
+            reference.kept.clone_from(&reference.current);
+            reference.kept_perceptual = reference.current_perceptual;
+        }
+        change
+    }
+
+    /// Returns the frame with its score, or `None` if it was dropped.
+    pub fn push(&mut self, frame: VideoFrame) -> Option<ScoredFrame> {
+        let change = self.measure(&frame);
+        (!change.is_dropped).then_some(ScoredFrame { frame, change })
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn score(&mut self, frame: &VideoFrame) -> FrameChange {
+        let mut change = FrameChange {
+            pts: frame.pts,
+            score: 0.0,
+            hash: 0,
+            is_dropped: true,
+        };
+        if !frame.has_content() {
+            change.hash = self.reference.as_ref().map_or(0, |reference| {
+                summary(
+                    self.metric,
+                    &reference.current,
+                    reference.current_perceptual,
+                )
+            });
+            return change;
+        }
+        let tile_size = self.tile_size;
+        let matches = self.reference.as_ref().is_some_and(|reference| {
+            (reference.width, reference.height, reference.pixel_format)
+                == (frame.width, frame.height, frame.pixel_format)
+        });
+        if !matches {
+            let columns = (frame.width + tile_size - 1) / tile_size;
+            let rows = (frame.height + tile_size - 1) / tile_size;
+            let current: Vec<u64> = (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .map(|(column, row)| tile_hash(frame, column, row, tile_size))
+                .collect();
+            let perceptual = perceptual_hash(frame);
+            change.score = 1.0;
+            change.hash = summary(self.metric, &current, perceptual);
+            change.is_dropped = false;
+            self.reference = Some(Reference {
+                width: frame.width,
+                height: frame.height,
+                pixel_format: frame.pixel_format,
+                columns,
+                kept: current.clone(),
+                current,
+                current_perceptual: perceptual,
+                kept_perceptual: perceptual,
+            });
+            return change;
+        }
+        let dirty_rects = frame
+            .info
+            .dirty_rects
+            .as_deref()
+            .filter(|_| self.uses_dirty_rects);
+        let Some(reference) = &mut self.reference else {
+            unreachable!("the reference was checked above");
+        };
+        let columns = reference.columns;
+        let dirty_tiles = dirty_rects.map(|rects| {
+            let mut dirty = vec![false; reference.current.len()];
+            for rect in rects {
+                for index in tiles_in(rect, frame.width, frame.height, tile_size, columns) {
+                    dirty[index] = true;
+                }
+            }
+            dirty
+        });
+        for (index, tile) in (0..).zip(reference.current.iter_mut()) {
+            if dirty_tiles
+                .as_ref()
+                .map_or(true, |dirty| dirty[index as usize])
+            {
+                *tile = tile_hash(frame, index % columns, index / columns, tile_size);
+            }
+        }
+        if dirty_tiles.map_or(true, |dirty| dirty.contains(&true)) {
+            reference.current_perceptual = perceptual_hash(frame);
+        }
+        change.score = match self.metric {
+            ChangeMetric::Exact => {
+                let changed = reference
+                    .current
+                    .iter()
+                    .zip(&reference.kept)
+                    .filter(|(current, kept)| current != kept)
+                    .count();
+                changed as f64 / reference.current.len().max(1) as f64
+            }
+            ChangeMetric::Perceptual => {
+                let distance =
+                    (reference.current_perceptual ^ reference.kept_perceptual).count_ones();
+                f64::from(distance) / 64.0
+            }
+        };
+        change.hash = summary(
+            self.metric,
+            &reference.current,
+            reference.current_perceptual,
+        );
+        change.is_dropped = change.score <= self.threshold;
+        change
+    }
+}
+
+fn summary(metric: ChangeMetric, tiles: &[u64], perceptual: u64) -> u64 {
+    match metric {
+        ChangeMetric::Exact => hash(tiles),
+        ChangeMetric::Perceptual => perceptual,
+    }
+}
+
+/// Returns the indices of the tiles `rect` overlaps.
+#[allow(clippy::cast_possible_truncation)]
+fn tiles_in(
+    rect: &PixelRect,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    columns: u32,
+) -> impl Iterator<Item = usize> {
+    let right = rect.x.saturating_add(rect.width).min(width);
+    let bottom = rect.y.saturating_add(rect.height).min(height);
+    let (columns_start, rows_start) = (rect.x / tile_size, rect.y / tile_size);
+    let (columns_end, rows_end) = if rect.x < right && rect.y < bottom {
+        ((right - 1) / tile_size + 1, (bottom - 1) / tile_size + 1)
+    } else {
+        (columns_start, rows_start)
+    };
+    (rows_start..rows_end).flat_map(move |row| {
+        (columns_start..columns_end).map(move |column| (row * columns + column) as usize)
+    })
+}
+
+fn tile_hash(frame: &VideoFrame, column: u32, row: u32, tile_size: u32) -> u64 {
+    let (left, top) = (column * tile_size, row * tile_size);
+    let right = (left + tile_size).min(frame.width) as usize;
+    let bottom = (top + tile_size).min(frame.height) as usize;
+    let (left, top) = (left as usize, top as usize);
+    let mut hasher = DefaultHasher::new();
+    match frame.pixel_format {
+        PixelFormat::Bgra => {
+            for y in top..bottom {
+                hasher.write(&frame.planes[0].row(y)[left * 4..right * 4]);
+            }
+        }
+        PixelFormat::Nv12 => {
+            for y in top..bottom {
+                hasher.write(&frame.planes[0].row(y)[left..right]);
+            }
+            for y in top / 2..(bottom + 1) / 2 {
+                hasher.write(&frame.planes[1].row(y)[left / 2 * 2..(right + 1) / 2 * 2]);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+/// Returns the difference hash of a frame: its luma is averaged down to 9 by 8 blocks, and
+/// each bit tells whether a block is brighter than its right neighbour.
+#[allow(clippy::cast_possible_truncation)]
+pub fn perceptual_hash(frame: &VideoFrame) -> u64 {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let mut blocks = [[0u32; 9]; 8];
+    for (block_y, block_row) in blocks.iter_mut().enumerate() {
+        let top = block_y * height / 8;
+        let bottom = ((block_y + 1) * height / 8).max(top + 1).min(height);
+        for (block_x, block) in block_row.iter_mut().enumerate() {
+            let left = block_x * width / 9;
+            let right = ((block_x + 1) * width / 9).max(left + 1).min(width);
+            let mut sum = 0u64;
+            for y in top..bottom {
+                let row = frame.planes[0].row(y);
+                for x in left..right {
+                    sum += u64::from(match frame.pixel_format {
+                        PixelFormat::Bgra => luma(&row[x * 4..x * 4 + 3]),
+                        PixelFormat::Nv12 => row[x],
+                    });
+                }
+            }
+            let count = ((bottom.saturating_sub(top)) * (right.saturating_sub(left))).max(1);
+            *block = (sum / count as u64) as u32;
+        }
+    }
+    let mut bits = 0;
+    for (index, block_row) in blocks.iter().enumerate() {
+        for x in 0..8 {
+            if block_row[x] > block_row[x + 1] {
+                bits |= 1 << (index * 8 + x);
+            }
+        }
+    }
+    bits
+}
+
+/// Approximates BT.709 luma of a BGR pixel with integer weights summing to 256.
+#[allow(clippy::cast_possible_truncation)]
+fn luma(bgr: &[u8]) -> u8 {
+    ((u32::from(bgr[0]) * 18 + u32::from(bgr[1]) * 183 + u32::from(bgr[2]) * 55) >> 8) as u8
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod dedup_test {
+    use crate::{
+        media::{
+            media_time::MediaTime,
+            video_frame::{FrameInfo, PixelRect, VideoFrame},
+        },
+        output::sc_stream_frame_info::SCFrameStatus,
+        session::compositor::fill_rect,
+    };
+
+    use super::{perceptual_hash, ChangeMetric, FrameDeduplicator};
+
+    const GRAY: [u8; 4] = [128, 128, 128, 255];
+
+    /// A 64x64 frame, which has four tiles of 32 pixels.
+    fn frame(millis: i64, marks: &[(u32, u32)]) -> VideoFrame {
+        let mut frame = VideoFrame::filled_bgra(64, 64, GRAY, MediaTime::from_millis(millis));
+        for &(x, y) in marks {
+            fill_rect(
+                &mut frame,
+                PixelRect::new(x, y, 2, 2),
+                [0, 0, 255, 255],
+                255,
+            );
+        }
+        frame
+    }
+
+    fn with_dirty_rects(frame: VideoFrame, dirty_rects: Vec<PixelRect>) -> VideoFrame {
+        frame.with_info(FrameInfo {
+            dirty_rects: Some(dirty_rects),
+            ..FrameInfo::default()
+        })
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let mut deduplicator = FrameDeduplicator::new();
+        let first = deduplicator.measure(&frame(0, &[]));
+        assert_eq!(first.score, 1.0);
+        assert!(!first.is_dropped);
+
+        let same = deduplicator.measure(&frame(10, &[]));
+        assert_eq!(same.score, 0.0);
+        assert!(same.is_dropped);
+        assert_eq!(same.hash, first.hash);
+
+        let idle = frame(15, &[(40, 40)]).with_status(SCFrameStatus::Idle);
+        assert!(deduplicator.push(idle).is_none());
+
+        let caret = deduplicator
+            .push(frame(20, &[(40, 40)]))
+            .expect("should keep a changed frame");
+        assert_eq!(caret.change.score, 0.25);
+        assert_ne!(caret.change.hash, first.hash);
+        assert_eq!(deduplicator.dropped(), 2);
+
+        // Small changes add up against the last frame kept.
+        let mut deduplicator = FrameDeduplicator::new().with_threshold(0.3);
+        let kept: Vec<_> = [
+            frame(0, &[]),
+            frame(10, &[(0, 0)]),
+            frame(20, &[(0, 0), (40, 0)]),
+            frame(30, &[(0, 0), (40, 0)]),
+        ]
+        .into_iter()
+        .filter_map(|frame| deduplicator.push(frame))
+        .map(|scored| (scored.frame.pts.as_millis(), scored.change.score))
+        .collect();
+        assert_eq!(kept, [(0, 1.0), (20, 0.5)]);
+
+        // A new size starts over.
+        let resized = VideoFrame::filled_bgra(32, 32, GRAY, MediaTime::from_millis(40));
+        assert_eq!(deduplicator.measure(&resized).score, 1.0);
+    }
+
+    #[test]
+    fn test_dirty_rects() {
+        let mut deduplicator = FrameDeduplicator::new();
+        deduplicator.measure(&frame(0, &[]));
+
+        // Only the dirty tiles are looked at.
+        let clean = with_dirty_rects(frame(10, &[(40, 40)]), vec![]);
+        assert!(deduplicator.measure(&clean).is_dropped);
+        let elsewhere = with_dirty_rects(frame(20, &[(40, 40)]), vec![PixelRect::new(0, 0, 8, 8)]);
+        assert!(deduplicator.measure(&elsewhere).is_dropped);
+        let dirty = with_dirty_rects(frame(30, &[(40, 40)]), vec![PixelRect::new(30, 30, 20, 20)]);
+        assert_eq!(deduplicator.measure(&dirty).score, 0.25);
+
+        let mut ignoring = FrameDeduplicator::new().with_dirty_rects(false);
+        ignoring.measure(&frame(0, &[]));
+        assert_eq!(ignoring.measure(&clean).score, 0.25);
+    }
+
+    #[test]
+    fn test_perceptual() {
+        let gradient = |millis, offset: u8| {
+            let data = (0..48 * 32)
+                .flat_map(|index| {
+                    let level = u8::try_from(index % 48 * 4).unwrap() + offset;
+                    [level, level, level, 255]
+                })
+                .collect();
+            VideoFrame::new_bgra(48, 32, data, MediaTime::from_millis(millis))
+        };
+        let mut deduplicator = FrameDeduplicator::new()
+            .with_metric(ChangeMetric::Perceptual)
+            .with_threshold(0.1);
+        let first = deduplicator.measure(&gradient(0, 0));
+        assert_eq!(first.hash, perceptual_hash(&gradient(0, 0)));
+
+        // A brighter frame differs in every tile but looks the same.
+        assert!(deduplicator.measure(&gradient(10, 2)).is_dropped);
+        let mut exact = FrameDeduplicator::new();
+        exact.measure(&gradient(0, 0));
+        assert_eq!(exact.measure(&gradient(10, 2)).score, 1.0);
+
+        let mut mirrored = gradient(20, 0);
+        for y in 0..32 {
+            let row = mirrored.planes[0].row_mut(y);
+            let pixels: Vec<u8> = row.chunks(4).rev().flatten().copied().collect();
+            row.copy_from_slice(&pixels);
+        }
+        assert_eq!(deduplicator.measure(&mirrored).score, 1.0);
+
+        let luma: Vec<u8> = (0..48 * 32)
+            .map(|index| u8::try_from(index % 48 * 4).unwrap())
+            .collect();
+        let nv12 = VideoFrame::new_nv12(48, 32, luma, vec![128; 48 * 16], MediaTime::ZERO);
+        assert_eq!(perceptual_hash(&nv12), 0);
+        assert_eq!(perceptual_hash(&mirrored), u64::MAX);
+    }
+}
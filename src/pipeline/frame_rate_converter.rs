@@ -0,0 +1,264 @@
+use crate::media::{media_time::MediaTime, video_frame::VideoFrame};
+
+/// A frame placed on the constant frame rate timeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertedFrame {
+    /// The source frame with its presentation time moved to the output slot.
+    pub frame: VideoFrame,
+    /// The presentation time the source frame was captured with.
+    pub source_pts: MediaTime,
+    /// `true` if the source frame was already output for an earlier slot.
+    pub is_duplicate: bool,
+}
+
+/// Turns the variable frame rate output of `SCStream`, which only delivers new frames when
+/// the content changes, into a constant frame rate.
+///
+/// Each output slot shows the latest complete frame captured up to half a frame after the
+/// slot starts. Idle periods repeat that frame, and of several frames arriving within one
+/// slot only the last is kept. Frames without new content, like `Idle` frames, only advance
+/// the clock, so their slots are filled once they arrive rather than when the next complete
+/// frame does.
+#[derive(Debug)]
+pub struct FrameRateConverter {
+    frame_duration: MediaTime,
+    origin: Option<MediaTime>,
+    next_slot: i64,
+    current: Option<(VideoFrame, bool)>,
+    dropped: u64,
+    duplicated: u64,
+}
+
+impl FrameRateConverter {
+    /// Creates a converter with one slot every `frame_duration`, e.g. `1001/30000` for 29.97 fps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_duration` is not positive.
+    pub fn new(frame_duration: MediaTime) -> Self {
+        assert!(
+            frame_duration.is_valid() && frame_duration > MediaTime::ZERO,
+            "the frame duration must be positive"
+        );
+        Self {
+            frame_duration,
+            origin: None,
+            next_slot: 0,
+            current: None,
+            dropped: 0,
+            duplicated: 0,
+        }
+    }
+
+    /// Creates a converter producing `fps` frames per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is zero or exceeds `i32::MAX`.
+    pub fn with_fps(fps: u32) -> Self {
+        Self::new(MediaTime::new(
+            1,
+            i32::try_from(fps).expect("fps should fit a timescale"),
+        ))
+    }
+
+    /// Returns the number of complete frames replaced by a later frame in the same slot.
+    pub const fn dropped_frames(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the number of slots that repeated an earlier frame.
+    pub const fn duplicated_frames(&self) -> u64 {
+        self.duplicated
+    }
+
+    /// Adds a captured frame, returning the slots that can be filled up to its timestamp.
+    pub fn push(&mut self, frame: VideoFrame) -> Vec<ConvertedFrame> {
+        let pts = frame.pts;
+        let origin = *self.origin.get_or_insert(pts);
+        if pts < origin {
+            return Vec::new();
+        }
+        let converted = self.fill_until(pts);
+        if frame.has_content() {
+            if let Some((_, false)) = self.current {
+                self.dropped += 1;
+            }
+            self.current = Some((frame, false));
+        }
+        converted
+    }
+
+    /// Fills the remaining slots starting before `end`, e.g. the time capture stopped.
+    pub fn finish(&mut self, end: MediaTime) -> Vec<ConvertedFrame> {
+        let mut converted = Vec::new();
+        while self.origin.is_some() && self.slot_time(self.next_slot) < end {
+            match self.emit() {
+                Some(frame) => converted.push(frame),
+                None => break,
+            }
+        }
+        converted
+    }
+
+    fn slot_time(&self, slot: i64) -> MediaTime {
+        self.origin.unwrap_or_default()
+            + MediaTime::new(
+                slot * self.frame_duration.value,
+                self.frame_duration.timescale,
+            )
+    }
+
+    /// Returns whether a frame arriving at `pts` decides the next slot, i.e. is past its
+    /// middle.
+    fn slot_is_due(&self, pts: MediaTime) -> bool {
+        let slot_time = self.slot_time(self.next_slot);
+        // Compares doubled times rather than halving the frame duration, which could need a
+        // timescale beyond `i32`.
+        slot_time + slot_time + self.frame_duration < pts + pts
+    }
+
+    /// Emits every slot whose frame is decided by a frame arriving at `pts`.
+    fn fill_until(&mut self, pts: MediaTime) -> Vec<ConvertedFrame> {
+        let mut converted = Vec::new();
+        while self.slot_is_due(pts) {
+            match self.emit() {
+                Some(frame) => converted.push(frame),
+                None => break,
+            }
+        }
+        converted
+    }
+
+    fn emit(&mut self) -> Option<ConvertedFrame> {
+        let slot_time = self.slot_time(self.next_slot);
+        let (frame, was_output) = self.current.as_mut()?;
+        self.next_slot += 1;
+        let is_duplicate = *was_output;
+        if is_duplicate {
+            self.duplicated += 1;
+        }
+        *was_output = true;
+        let source_pts = frame.pts;
+        let mut frame = frame.clone();
+        frame.pts = slot_time;
+        Some(ConvertedFrame {
+            frame,
+            source_pts,
+            is_duplicate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod frame_rate_converter_test {
+    use crate::{
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+        output::sc_stream_frame_info::SCFrameStatus,
+    };
+
+    use super::FrameRateConverter;
+
+    fn frame(micros: i64, status: SCFrameStatus) -> VideoFrame {
+        VideoFrame::filled_bgra(2, 2, [0, 0, 0, 255], MediaTime::new(micros, 1_000_000))
+            .with_status(status)
+    }
+
+    fn run(
+        converter: &mut FrameRateConverter,
+        schedule: &[(i64, SCFrameStatus)],
+        end: i64,
+    ) -> Vec<(i64, i64, bool)> {
+        let mut frames = Vec::new();
+        for (micros, status) in schedule {
+            frames.extend(converter.push(frame(*micros, *status)));
+        }
+        frames.extend(converter.finish(MediaTime::new(end, 1_000_000)));
+        frames
+            .into_iter()
+            .map(|c| {
+                (
+                    c.frame.pts.as_millis(),
+                    c.source_pts.as_millis(),
+                    c.is_duplicate,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn test_drops_frames_arriving_too_fast() {
+        let mut converter = FrameRateConverter::with_fps(30);
+        let schedule: Vec<_> = (0..60)
+            .map(|i| (i * 16_667, SCFrameStatus::Complete))
+            .collect();
+        let output = run(&mut converter, &schedule, 1_000_000);
+        assert_eq!(output.len(), 30);
+        for (slot, (pts, source_pts, is_duplicate)) in output.into_iter().enumerate() {
+            assert_eq!(pts, (slot as i64 * 1000 + 15) / 30);
+            assert!((source_pts - pts).abs() <= 17);
+            assert!(!is_duplicate);
+        }
+        assert_eq!(converter.dropped_frames(), 29);
+        assert_eq!(converter.duplicated_frames(), 0);
+    }
+
+    #[test]
+    fn test_duplicates_idle_periods() {
+        let mut converter = FrameRateConverter::with_fps(10);
+        let mut schedule = vec![
+            (0, SCFrameStatus::Started),
+            (100_000, SCFrameStatus::Complete),
+        ];
+        schedule.extend((2..6).map(|i| (i * 100_000, SCFrameStatus::Idle)));
+        schedule.push((600_000, SCFrameStatus::Complete));
+        let output = run(&mut converter, &schedule, 800_000);
+        assert_eq!(
+            output,
+            vec![
+                (0, 0, false),
+                (100, 100, false),
+                (200, 100, true),
+                (300, 100, true),
+                (400, 100, true),
+                (500, 100, true),
+                (600, 600, false),
+                (700, 600, true),
+            ]
+        );
+        assert_eq!(converter.duplicated_frames(), 5);
+        assert_eq!(converter.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn test_fine_timescale() {
+        let mut converter = FrameRateConverter::new(MediaTime::new(200_000_000, 2_000_000_000));
+        let schedule = [
+            (0, SCFrameStatus::Complete),
+            (160_000, SCFrameStatus::Complete),
+        ];
+        let output = run(&mut converter, &schedule, 300_000);
+        assert_eq!(
+            output,
+            vec![(0, 0, false), (100, 0, true), (200, 160, false)]
+        );
+    }
+
+    #[test]
+    fn test_jitter_is_absorbed() {
+        let mut converter = FrameRateConverter::new(MediaTime::new(1001, 30_000));
+        let schedule: Vec<_> = (0..300)
+            .map(|i| {
+                (
+                    i * 1_001_000 / 30 + ((i * 7919 + 10) % 21 - 10) * 1000,
+                    SCFrameStatus::Complete,
+                )
+            })
+            .collect();
+        let output = run(&mut converter, &schedule, 10_010_000);
+        assert_eq!(output.len(), 300);
+        assert!(output.iter().all(|(_, _, is_duplicate)| !is_duplicate));
+        assert_eq!(converter.dropped_frames(), 0);
+    }
+}
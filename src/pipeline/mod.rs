@@ -0,0 +1,11 @@
+//! Processing stages between a stream's output handlers and its sinks.
+pub mod audio_meter;
+pub mod av_sync;
+pub mod cursor;
+pub mod dedup;
+pub mod frame_rate_converter;
+pub mod overlay;
+pub mod redaction;
+pub mod resampler;
+pub mod scaler;
+pub mod timeline;
@@ -0,0 +1,139 @@
+/// Maps host times, like the display time attachment of frames, to wall-clock time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayClock {
+    /// The `mach_timebase_info` ratio from host time units to nanoseconds.
+    numer: u32,
+    denom: u32,
+    host_time: u64,
+    unix_nanos: i128,
+    /// Seconds east of UTC to show times in.
+    utc_offset: i32,
+}
+
+impl DisplayClock {
+    /// Creates a clock from a host time and the wall-clock time, in nanoseconds since the
+    /// Unix epoch, it was taken at. Host time units are `numer / denom` nanoseconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `denom` is zero.
+    pub fn new(numer: u32, denom: u32, host_time: u64, unix_nanos: i128) -> Self {
+        assert!(denom > 0, "the timebase denominator must be positive");
+        Self {
+            numer,
+            denom,
+            host_time,
+            unix_nanos,
+            utc_offset: 0,
+        }
+    }
+
+    /// Creates a clock for the current process from `mach_absolute_time`.
+    #[cfg(target_os = "macos")]
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn now() -> Self {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        use crate::utils::host_time;
+
+        let (numer, denom) = host_time::timebase();
+        let host_time = host_time::now();
+        let unix_nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_nanos() as i128);
+        Self::new(numer, denom, host_time, unix_nanos)
+    }
+
+    /// Shows times at a fixed offset from UTC, e.g. `3600` for UTC+1.
+    #[must_use]
+    pub const fn with_utc_offset(mut self, seconds: i32) -> Self {
+        self.utc_offset = seconds;
+        self
+    }
+
+    /// Returns the wall-clock time of a host time, in nanoseconds since the Unix epoch.
+    pub fn unix_nanos(&self, host_time: u64) -> i128 {
+        let elapsed = i128::from(host_time) - i128::from(self.host_time);
+        self.unix_nanos + elapsed * i128::from(self.numer) / i128::from(self.denom)
+    }
+
+    /// Formats the wall-clock time of a host time as `YYYY-MM-DD HH:MM:SS.mmm` followed by
+    /// the UTC offset, e.g. `2024-05-01 09:30:00.250 +01:00`.
+    pub fn format(&self, host_time: u64) -> String {
+        format_unix_millis(
+            self.unix_nanos(host_time).div_euclid(1_000_000) + i128::from(self.utc_offset) * 1000,
+            self.utc_offset,
+        )
+    }
+}
+
+impl Default for DisplayClock {
+    /// A clock where host times are nanoseconds since the Unix epoch.
+    fn default() -> Self {
+        Self::new(1, 1, 0, 0)
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn format_unix_millis(millis: i128, utc_offset: i32) -> String {
+    let days = millis.div_euclid(86_400_000) as i64;
+    let millis_of_day = millis.rem_euclid(86_400_000) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let offset_minutes = utc_offset.unsigned_abs() / 60;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03} {}{:02}:{:02}",
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000,
+        if utc_offset < 0 { '-' } else { '+' },
+        offset_minutes / 60,
+        offset_minutes % 60,
+    )
+}
+
+/// Returns the proleptic Gregorian date of a day number counted from 1970-01-01.
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod clock_test {
+    use super::DisplayClock;
+
+    #[test]
+    fn test_format() {
+        // 2024-02-29 23:59:59.999 UTC.
+        let unix_nanos = 1_709_251_199_999_000_000;
+        let clock = DisplayClock::new(125, 3, 3_000, unix_nanos);
+        assert_eq!(clock.format(3_000), "2024-02-29 23:59:59.999 +00:00");
+        // 24,000 host time units are a millisecond.
+        assert_eq!(clock.format(27_000), "2024-03-01 00:00:00.000 +00:00");
+        assert_eq!(
+            clock.with_utc_offset(-5 * 3600 - 1800).format(3_000),
+            "2024-02-29 18:29:59.999 -05:30"
+        );
+        assert_eq!(
+            DisplayClock::default().format(0),
+            "1970-01-01 00:00:00.000 +00:00"
+        );
+        assert_eq!(
+            DisplayClock::new(1, 1, 1_000_000_000, 0).format(0),
+            "1969-12-31 23:59:59.000 +00:00"
+        );
+    }
+}
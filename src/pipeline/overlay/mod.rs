@@ -0,0 +1,570 @@
+//! Burns text, timestamps and images like logos into frames.
+pub mod clock;
+pub mod font;
+
+use crate::media::video_frame::{bgra_to_ycbcr, PixelFormat, VideoFrame};
+
+use self::clock::DisplayClock;
+
+/// The placeholder in overlay text replaced with the wall-clock time a frame was displayed.
+pub const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+
+/// Where an overlay item is placed within the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Returns the top left of a `width` by `height` item anchored in a `frame_width` by
+    /// `frame_height` frame, kept `margin_x` and `margin_y` pixels from the anchored edges.
+    pub const fn place(
+        self,
+        frame_width: u32,
+        frame_height: u32,
+        width: u32,
+        height: u32,
+        margin_x: u32,
+        margin_y: u32,
+    ) -> (u32, u32) {
+        let left = margin_x;
+        let center_x = frame_width.saturating_sub(width) / 2;
+        let right = frame_width.saturating_sub(width.saturating_add(margin_x));
+        let top = margin_y;
+        let center_y = frame_height.saturating_sub(height) / 2;
+        let bottom = frame_height.saturating_sub(height.saturating_add(margin_y));
+        match self {
+            Self::TopLeft => (left, top),
+            Self::Top => (center_x, top),
+            Self::TopRight => (right, top),
+            Self::Left => (left, center_y),
+            Self::Center => (center_x, center_y),
+            Self::Right => (right, center_y),
+            Self::BottomLeft => (left, bottom),
+            Self::Bottom => (center_x, bottom),
+            Self::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// A tightly packed BGRA image with straight, not premultiplied, alpha.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl OverlayImage {
+    /// # Panics
+    ///
+    /// Panics if `data` is not `width * height * 4` bytes long.
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * 4,
+            "invalid overlay image size"
+        );
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn filled(width: u32, height: u32, bgra: [u8; 4]) -> Self {
+        Self::new(width, height, bgra.repeat(width as usize * height as usize))
+    }
+
+    /// Renders text in the built-in bitmap font, each font pixel `scale` pixels wide, on a
+    /// transparent or `background` colored box with `padding` pixels around the text.
+    pub fn text(
+        text: &str,
+        scale: u32,
+        color: [u8; 4],
+        background: Option<[u8; 4]>,
+        padding: u32,
+    ) -> Self {
+        let scale = scale.max(1);
+        let (text_width, text_height) = font::text_size(text);
+        let mut image = Self::filled(
+            text_width * scale + 2 * padding,
+            text_height * scale + 2 * padding,
+            background.unwrap_or([0; 4]),
+        );
+        for (line_index, line) in (0..).zip(text.lines()) {
+            for (column, character) in (0..).zip(line.chars()) {
+                let (left, top) = (column * font::ADVANCE, line_index * font::LINE_HEIGHT);
+                for y in 0..font::GLYPH_HEIGHT {
+                    for x in (0..font::GLYPH_WIDTH).filter(|&x| font::is_set(character, x, y)) {
+                        for dy in 0..scale {
+                            for dx in 0..scale {
+                                image.set_pixel(
+                                    padding + (left + x) * scale + dx,
+                                    padding + (top + y) * scale + dy,
+                                    color,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `x`, `y` is outside the image.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = self.offset(x, y);
+        [
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ]
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `x`, `y` is outside the image.
+    pub fn set_pixel(&mut self, x: u32, y: u32, bgra: [u8; 4]) {
+        let offset = self.offset(x, y);
+        self.data[offset..offset + 4].copy_from_slice(&bgra);
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn mix(from: u8, to: u8, alpha: u32) -> u8 {
+    ((to as u32 * alpha + from as u32 * (255 - alpha) + 127) / 255) as u8
+}
+
+/// Blends `image` onto a BGRA or NV12 frame with its top left at `x`, `y`, scaling its
+/// alpha by `opacity`. Parts outside the frame are skipped.
+///
+/// In NV12 frames, each chroma sample takes the average of the blends of the pixels it
+/// covers.
+pub fn blend_image(frame: &mut VideoFrame, image: &OverlayImage, x: u32, y: u32, opacity: u8) {
+    let x_end = x.saturating_add(image.width).min(frame.width);
+    let y_end = y.saturating_add(image.height).min(frame.height);
+    let alpha_at = |pixel: [u8; 4]| u32::from(pixel[3]) * u32::from(opacity) / 255;
+    match frame.pixel_format {
+        PixelFormat::Bgra => {
+            for frame_y in y..y_end {
+                let row = frame.planes[0].row_mut(frame_y as usize);
+                for frame_x in x..x_end {
+                    let pixel = image.pixel(frame_x - x, frame_y - y);
+                    let alpha = alpha_at(pixel);
+                    let offset = frame_x as usize * 4;
+                    let target = &mut row[offset..offset + 4];
+                    for channel in 0..3 {
+                        target[channel] = mix(target[channel], pixel[channel], alpha);
+                    }
+                    target[3] = mix(target[3], 255, alpha);
+                }
+            }
+        }
+        PixelFormat::Nv12 => {
+            for frame_y in y..y_end {
+                let row = frame.planes[0].row_mut(frame_y as usize);
+                for frame_x in x..x_end {
+                    let pixel = image.pixel(frame_x - x, frame_y - y);
+                    let luma = &mut row[frame_x as usize];
+                    *luma = mix(*luma, bgra_to_ycbcr(pixel)[0], alpha_at(pixel));
+                }
+            }
+            for chroma_y in y / 2..(y_end + 1) / 2 {
+                let row = frame.planes[1].row_mut(chroma_y as usize);
+                for chroma_x in x / 2..(x_end + 1) / 2 {
+                    let offset = chroma_x as usize * 2;
+                    let mut blended = [0; 2];
+                    for (pixel_x, pixel_y) in [(0, 0), (1, 0), (0, 1), (1, 1)]
+                        .map(|(dx, dy)| (chroma_x * 2 + dx, chroma_y * 2 + dy))
+                    {
+                        let covered =
+                            (x..x_end).contains(&pixel_x) && (y..y_end).contains(&pixel_y);
+                        for (channel, sum) in blended.iter_mut().enumerate() {
+                            let current = row[offset + channel];
+                            *sum += u32::from(if covered {
+                                let pixel = image.pixel(pixel_x - x, pixel_y - y);
+                                mix(current, bgra_to_ycbcr(pixel)[channel + 1], alpha_at(pixel))
+                            } else {
+                                current
+                            });
+                        }
+                    }
+                    for (channel, sum) in blended.into_iter().enumerate() {
+                        row[offset + channel] = u8::try_from((sum + 2) / 4).unwrap_or(u8::MAX);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OverlayContent {
+    Text {
+        text: String,
+        scale: u32,
+        color: [u8; 4],
+        background: Option<[u8; 4]>,
+        padding: u32,
+    },
+    Image(OverlayImage),
+}
+
+/// Text or an image burned into frames at an anchored position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayItem {
+    content: OverlayContent,
+    anchor: Anchor,
+    margin_x: u32,
+    margin_y: u32,
+    opacity: u8,
+}
+
+impl OverlayItem {
+    const fn new(content: OverlayContent) -> Self {
+        Self {
+            content,
+            anchor: Anchor::TopLeft,
+            margin_x: 0,
+            margin_y: 0,
+            opacity: u8::MAX,
+        }
+    }
+
+    /// Creates white text without a background. Every [`TIMESTAMP_PLACEHOLDER`] in it is
+    /// replaced with the frame's wall-clock time.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(OverlayContent::Text {
+            text: text.into(),
+            scale: 1,
+            color: [255; 4],
+            background: None,
+            padding: 0,
+        })
+    }
+
+    pub const fn image(image: OverlayImage) -> Self {
+        Self::new(OverlayContent::Image(image))
+    }
+
+    #[must_use]
+    pub const fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_margin(mut self, margin_x: u32, margin_y: u32) -> Self {
+        self.margin_x = margin_x;
+        self.margin_y = margin_y;
+        self
+    }
+
+    /// Sets the opacity from 0 for invisible to 255, the default, for opaque.
+    #[must_use]
+    pub const fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Sets how many pixels wide each font pixel of text is drawn. Ignored for images.
+    #[must_use]
+    pub fn with_scale(mut self, new_scale: u32) -> Self {
+        if let OverlayContent::Text { scale, .. } = &mut self.content {
+            *scale = new_scale.max(1);
+        }
+        self
+    }
+
+    /// Sets the BGRA color of text. Ignored for images.
+    #[must_use]
+    pub fn with_color(mut self, bgra: [u8; 4]) -> Self {
+        if let OverlayContent::Text { color, .. } = &mut self.content {
+            *color = bgra;
+        }
+        self
+    }
+
+    /// Draws text on a box of a BGRA color extending `padding` pixels around it. Ignored for
+    /// images.
+    #[must_use]
+    pub fn with_background(mut self, bgra: [u8; 4], new_padding: u32) -> Self {
+        if let OverlayContent::Text {
+            background,
+            padding,
+            ..
+        } = &mut self.content
+        {
+            *background = Some(bgra);
+            *padding = new_padding;
+        }
+        self
+    }
+}
+
+/// An overlay stage burning text, timestamps and images into BGRA and NV12 frames.
+///
+/// Timestamps come from the display time attachment of each frame, mapped to wall-clock
+/// time with a [`DisplayClock`]. Frames without the attachment, or overlays without a clock,
+/// show dashes instead.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    items: Vec<OverlayItem>,
+    clock: Option<DisplayClock>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_item(mut self, item: OverlayItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_clock(mut self, clock: DisplayClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Returns the wall-clock time a frame was displayed at, as it is shown in overlays.
+    pub fn timestamp(&self, frame: &VideoFrame) -> String {
+        match (self.clock, frame.info.display_time) {
+            (Some(clock), Some(display_time)) => clock.format(display_time),
+            _ => "---------- --:--:--.---".to_string(),
+        }
+    }
+
+    /// Draws every item onto `frame`, in the order they were added.
+    pub fn apply(&self, frame: &mut VideoFrame) {
+        let mut timestamp = None;
+        for item in &self.items {
+            let rendered;
+            let image = match &item.content {
+                OverlayContent::Image(image) => image,
+                OverlayContent::Text {
+                    text,
+                    scale,
+                    color,
+                    background,
+                    padding,
+                } => {
+                    let text = if text.contains(TIMESTAMP_PLACEHOLDER) {
+                        let timestamp = timestamp.get_or_insert_with(|| self.timestamp(frame));
+                        text.replace(TIMESTAMP_PLACEHOLDER, timestamp)
+                    } else {
+                        text.clone()
+                    };
+                    rendered = OverlayImage::text(&text, *scale, *color, *background, *padding);
+                    &rendered
+                }
+            };
+            let (x, y) = item.anchor.place(
+                frame.width,
+                frame.height,
+                image.width(),
+                image.height(),
+                item.margin_x,
+                item.margin_y,
+            );
+            blend_image(frame, image, x, y, item.opacity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod overlay_test {
+    use std::{fmt::Write, fs, path::Path};
+
+    use crate::media::{
+        media_time::MediaTime,
+        video_frame::{PixelFormat, VideoFrame},
+    };
+
+    use super::{blend_image, clock::DisplayClock, Anchor, Overlay, OverlayImage, OverlayItem};
+
+    const PALETTE: &str = ".#o+*=%@&$abcdefghijklmnopqrstuvwxyz";
+
+    /// Writes a plane as one character per sample, with a legend of the sample values.
+    fn plane_to_text(text: &mut String, frame: &VideoFrame, plane: usize, width: usize) {
+        let channels = match (frame.pixel_format, plane) {
+            (PixelFormat::Bgra, _) => 4,
+            (PixelFormat::Nv12, 0) => 1,
+            (PixelFormat::Nv12, _) => 2,
+        };
+        let height = if plane == 0 {
+            frame.height as usize
+        } else {
+            (frame.height as usize + 1) / 2
+        };
+        let mut values: Vec<&[u8]> = Vec::new();
+        let mut rows = String::new();
+        for y in 0..height {
+            for sample in frame.planes[plane].row(y)[..width * channels].chunks_exact(channels) {
+                let index = values.iter().position(|v| *v == sample).unwrap_or_else(|| {
+                    values.push(sample);
+                    values.len() - 1
+                });
+                rows.push(PALETTE.chars().nth(index).expect("too many colors"));
+            }
+            rows.push('\n');
+        }
+        for (symbol, value) in PALETTE.chars().zip(&values) {
+            text.push(symbol);
+            text.push(' ');
+            for byte in *value {
+                write!(text, "{byte:02x}").unwrap();
+            }
+            text.push('\n');
+        }
+        text.push_str(&rows);
+    }
+
+    fn to_text(frame: &VideoFrame) -> String {
+        let mut text = String::new();
+        plane_to_text(&mut text, frame, 0, frame.width as usize);
+        if frame.pixel_format == PixelFormat::Nv12 {
+            text.push('\n');
+            plane_to_text(&mut text, frame, 1, (frame.width as usize + 1) / 2);
+        }
+        text
+    }
+
+    /// Compares a frame with a golden file, or rewrites the file if `UPDATE_GOLDEN` is set.
+    fn assert_golden(name: &str, frame: &VideoFrame) {
+        let source = Path::new(file!());
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(source)
+            .with_file_name("golden")
+            .join(name);
+        let actual = to_text(frame);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|error| panic!("could not read {}: {error}", path.display()));
+        assert!(
+            expected == actual,
+            "{name} differs from the golden file:\n{actual}"
+        );
+    }
+
+    fn logo() -> OverlayImage {
+        // A red square with a half transparent green border.
+        let mut logo = OverlayImage::filled(6, 6, [0, 255, 0, 128]);
+        for y in 1..5 {
+            for x in 1..5 {
+                logo.set_pixel(x, y, [0, 0, 255, 255]);
+            }
+        }
+        logo
+    }
+
+    fn overlay() -> Overlay {
+        Overlay::new()
+            .with_clock(DisplayClock::new(1, 1, 0, 1_714_555_800_250_000_000).with_utc_offset(3600))
+            .with_item(
+                OverlayItem::text("{timestamp}")
+                    .with_anchor(Anchor::Bottom)
+                    .with_background([0, 0, 0, 255], 1)
+                    .with_margin(0, 1),
+            )
+            .with_item(
+                OverlayItem::text("ID 42")
+                    .with_color([0, 255, 255, 255])
+                    .with_scale(2)
+                    .with_margin(2, 2),
+            )
+            .with_item(
+                OverlayItem::image(logo())
+                    .with_anchor(Anchor::TopRight)
+                    .with_margin(2, 2),
+            )
+    }
+
+    #[test]
+    fn test_anchor() {
+        assert_eq!(Anchor::TopLeft.place(100, 50, 20, 10, 4, 2), (4, 2));
+        assert_eq!(Anchor::Center.place(100, 50, 20, 10, 4, 2), (40, 20));
+        assert_eq!(Anchor::BottomRight.place(100, 50, 20, 10, 4, 2), (76, 38));
+        assert_eq!(Anchor::Bottom.place(10, 10, 20, 20, 4, 2), (0, 0));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let frame = VideoFrame::filled_bgra(1, 1, [0; 4], MediaTime::ZERO);
+        assert_eq!(overlay().timestamp(&frame), "---------- --:--:--.---");
+        let mut frame = frame;
+        frame.info.display_time = Some(1_000_000);
+        assert_eq!(
+            overlay().timestamp(&frame),
+            "2024-05-01 10:30:00.251 +01:00"
+        );
+        assert_eq!(Overlay::new().timestamp(&frame), "---------- --:--:--.---");
+    }
+
+    #[test]
+    fn test_blend_image() {
+        let mut frame = VideoFrame::filled_bgra(4, 1, [200, 100, 0, 255], MediaTime::ZERO);
+        let image = OverlayImage::new(2, 1, vec![0, 0, 0, 255, 0, 100, 200, 51]);
+        blend_image(&mut frame, &image, 1, 0, 255);
+        assert_eq!(frame.bgra_pixel(0, 0), Some([200, 100, 0, 255]));
+        assert_eq!(frame.bgra_pixel(1, 0), Some([0, 0, 0, 255]));
+        assert_eq!(frame.bgra_pixel(2, 0), Some([160, 100, 40, 255]));
+        blend_image(&mut frame, &image, 3, 0, 0);
+        assert_eq!(frame.bgra_pixel(3, 0), Some([200, 100, 0, 255]));
+    }
+
+    #[test]
+    fn test_golden_bgra() {
+        let mut frame = VideoFrame::filled_bgra(200, 26, [80, 40, 20, 255], MediaTime::ZERO);
+        frame.info.display_time = Some(0);
+        overlay().apply(&mut frame);
+        assert_golden("overlay_bgra.txt", &frame);
+    }
+
+    #[test]
+    fn test_golden_nv12() {
+        let mut frame = VideoFrame::new_nv12(
+            200,
+            26,
+            vec![41; 200 * 26],
+            [110u8, 120].repeat(100 * 13),
+            MediaTime::ZERO,
+        );
+        frame.info.display_time = Some(0);
+        overlay().apply(&mut frame);
+        assert_golden("overlay_nv12.txt", &frame);
+    }
+}
@@ -0,0 +1,556 @@
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+use crate::{
+    geometry::{display_layout::DisplayGeometry, rect::RectExt},
+    media::video_frame::{bgra_to_ycbcr, PixelFormat, PixelRect, VideoFrame, VideoPlane},
+    output::sc_stream_frame_info::SCStreamFrameInfo,
+    shareable_content::{
+        content_query::WindowQuery,
+        content_snapshot::{ContentSnapshot, WindowInfo},
+    },
+};
+
+/// Maps global points to the pixels of captured frames.
+///
+/// `SCStream` draws the captured area into the frame's content rect, scaled by the content
+/// scale, so a global point `p` ends up at
+/// `(content_rect.origin + (p - source_origin) * content_scale) * scale_factor`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameMapping {
+    /// The top left of the captured area in global points, e.g. the captured display's origin.
+    pub source_origin: CGPoint,
+    /// Where the captured content was drawn in the frame, in points.
+    pub content_rect: CGRect,
+    /// How much the captured content was scaled to fit the frame.
+    pub content_scale: f64,
+    /// The pixels per point of the frame.
+    pub scale_factor: f64,
+}
+
+impl FrameMapping {
+    pub const fn new(
+        source_origin: CGPoint,
+        content_rect: CGRect,
+        content_scale: f64,
+        scale_factor: f64,
+    ) -> Self {
+        Self {
+            source_origin,
+            content_rect,
+            content_scale,
+            scale_factor,
+        }
+    }
+
+    /// Returns the mapping of a display captured at its full pixel size.
+    pub fn for_display(display: &DisplayGeometry) -> Self {
+        Self::new(
+            display.frame.origin,
+            CGRect::from_edges(
+                0.0,
+                0.0,
+                display.frame.size.width,
+                display.frame.size.height,
+            ),
+            1.0,
+            display.scale_factor,
+        )
+    }
+
+    /// Reads the mapping from the content rect, content scale and scale factor attachments of
+    /// a frame. Returns `None` if any of them is missing.
+    pub fn from_frame_info(source_origin: CGPoint, info: &SCStreamFrameInfo) -> Option<Self> {
+        Some(Self::new(
+            source_origin,
+            info.content_rect()?,
+            info.content_scale()?,
+            info.scale_factor()?,
+        ))
+    }
+
+    /// Maps a global rectangle to frame pixels.
+    pub fn rect_to_frame(&self, rect: &CGRect) -> CGRect {
+        rect.translated(-self.source_origin.x, -self.source_origin.y)
+            .scaled(self.content_scale)
+            .translated(self.content_rect.origin.x, self.content_rect.origin.y)
+            .scaled(self.scale_factor)
+    }
+
+    /// Maps a global point to frame pixels.
+    pub fn point_to_frame(&self, point: &CGPoint) -> CGPoint {
+        CGPoint::new(
+            (point.x - self.source_origin.x)
+                .mul_add(self.content_scale, self.content_rect.origin.x)
+                * self.scale_factor,
+            (point.y - self.source_origin.y)
+                .mul_add(self.content_scale, self.content_rect.origin.y)
+                * self.scale_factor,
+        )
+    }
+
+    /// Returns the frame pixels showing captured content, without any letterboxing.
+    pub fn content_in_frame(&self) -> CGRect {
+        self.content_rect.scaled(self.scale_factor)
+    }
+}
+
+/// How redacted regions are hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactionStyle {
+    /// Paints over regions with a BGRA color.
+    Fill([u8; 4]),
+    /// Replaces regions with blocks of their average color, this many pixels wide.
+    Pixelate(u32),
+    /// Blurs regions with a box blur of this radius in pixels.
+    Blur(u32),
+}
+
+/// Hides the contents of sensitive windows in captured frames, while keeping them in the
+/// layout unlike excluding them with `SCContentFilter`.
+///
+/// Windows are matched with [`WindowQuery`] rules, e.g. by bundle ID or title pattern, and a
+/// window is redacted if any rule matches. The whole frame of matched on-screen windows is
+/// redacted, including parts covered by other windows, so stale snapshots err on the side
+/// of hiding too much.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<WindowQuery>,
+    style: RedactionStyle,
+}
+
+impl Redactor {
+    pub const fn new(style: RedactionStyle) -> Self {
+        Self {
+            rules: Vec::new(),
+            style,
+        }
+    }
+
+    #[must_use]
+    pub fn with_rule(mut self, rule: WindowQuery) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub const fn style(&self) -> RedactionStyle {
+        self.style
+    }
+
+    /// Returns the on-screen windows of a snapshot matching any rule.
+    pub fn windows<'a>(&self, snapshot: &'a ContentSnapshot) -> Vec<&'a WindowInfo> {
+        snapshot
+            .windows
+            .iter()
+            .filter(|window| {
+                window.is_on_screen && self.rules.iter().any(|rule| rule.matches(snapshot, window))
+            })
+            .collect()
+    }
+
+    /// Returns the pixels of a `width` by `height` frame to redact, rounded outwards to whole
+    /// pixels and clipped to the captured content.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn regions(
+        &self,
+        snapshot: &ContentSnapshot,
+        mapping: &FrameMapping,
+        width: u32,
+        height: u32,
+    ) -> Vec<PixelRect> {
+        let bounds = CGRect {
+            origin: CGPoint::new(0.0, 0.0),
+            size: CGSize::new(f64::from(width), f64::from(height)),
+        };
+        let Some(visible) = mapping.content_in_frame().intersection(&bounds) else {
+            return Vec::new();
+        };
+        self.windows(snapshot)
+            .into_iter()
+            .filter_map(|window| {
+                let rect = mapping
+                    .rect_to_frame(&window.frame.into())
+                    .rounded_out()
+                    .intersection(&visible.rounded_out())?;
+                Some(PixelRect::new(
+                    rect.min_x() as u32,
+                    rect.min_y() as u32,
+                    rect.size.width as u32,
+                    rect.size.height as u32,
+                ))
+            })
+            .collect()
+    }
+
+    /// Redacts the matching windows of `snapshot` in `frame`, returning the number of regions
+    /// redacted.
+    pub fn apply(
+        &self,
+        frame: &mut VideoFrame,
+        snapshot: &ContentSnapshot,
+        mapping: &FrameMapping,
+    ) -> usize {
+        let regions = self.regions(snapshot, mapping, frame.width, frame.height);
+        for region in &regions {
+            redact(frame, *region, self.style);
+        }
+        regions.len()
+    }
+}
+
+/// A rectangle of samples within one plane.
+struct PlaneRegion {
+    plane: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// Bytes per sample.
+    channels: usize,
+}
+
+fn plane_regions(format: PixelFormat, rect: PixelRect) -> Vec<PlaneRegion> {
+    let (x, y) = (rect.x as usize, rect.y as usize);
+    let (width, height) = (rect.width as usize, rect.height as usize);
+    match format {
+        PixelFormat::Bgra => vec![PlaneRegion {
+            plane: 0,
+            x,
+            y,
+            width,
+            height,
+            channels: 4,
+        }],
+        PixelFormat::Nv12 => {
+            // Chroma samples cover two by two pixels, so partly covered ones are included.
+            let (chroma_x, chroma_y) = (x / 2, y / 2);
+            vec![
+                PlaneRegion {
+                    plane: 0,
+                    x,
+                    y,
+                    width,
+                    height,
+                    channels: 1,
+                },
+                PlaneRegion {
+                    plane: 1,
+                    x: chroma_x,
+                    y: chroma_y,
+                    width: (x + width + 1) / 2 - chroma_x,
+                    height: (y + height + 1) / 2 - chroma_y,
+                    channels: 2,
+                },
+            ]
+        }
+    }
+}
+
+/// Hides `rect` of a frame, which must lie within it.
+pub fn redact(frame: &mut VideoFrame, rect: PixelRect, style: RedactionStyle) {
+    if rect.is_empty() {
+        return;
+    }
+    let [y, cb, cr] = match style {
+        RedactionStyle::Fill(bgra) => bgra_to_ycbcr(bgra),
+        _ => [0; 3],
+    };
+    for region in plane_regions(frame.pixel_format, rect) {
+        let plane = &mut frame.planes[region.plane];
+        // Chroma planes have half the resolution, so their blocks and radius are halved too.
+        let subsampling = if region.channels == 2 { 2 } else { 1 };
+        match style {
+            RedactionStyle::Fill(bgra) => {
+                let color = match (frame.pixel_format, region.channels) {
+                    (PixelFormat::Bgra, _) => bgra.to_vec(),
+                    (PixelFormat::Nv12, 1) => vec![y],
+                    (PixelFormat::Nv12, _) => vec![cb, cr],
+                };
+                fill(plane, &region, &color);
+            }
+            RedactionStyle::Pixelate(block) => {
+                pixelate(plane, &region, (block as usize / subsampling).max(1));
+            }
+            RedactionStyle::Blur(radius) => blur(plane, &region, radius as usize / subsampling),
+        }
+    }
+}
+
+fn fill(plane: &mut VideoPlane, region: &PlaneRegion, color: &[u8]) {
+    let start = region.x * region.channels;
+    for y in region.y..region.y + region.height {
+        let row = &mut plane.row_mut(y)[start..start + region.width * region.channels];
+        for sample in row.chunks_exact_mut(region.channels) {
+            sample.copy_from_slice(color);
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn pixelate(plane: &mut VideoPlane, region: &PlaneRegion, block: usize) {
+    let channels = region.channels;
+    for block_y in (region.y..region.y + region.height).step_by(block) {
+        let y_end = (block_y + block).min(region.y + region.height);
+        for block_x in (region.x..region.x + region.width).step_by(block) {
+            let x_end = (block_x + block).min(region.x + region.width);
+            let (start, end) = (block_x * channels, x_end * channels);
+            let mut sums = [0u64; 4];
+            for y in block_y..y_end {
+                for sample in plane.row(y)[start..end].chunks_exact(channels) {
+                    for (sum, &value) in sums.iter_mut().zip(sample) {
+                        *sum += u64::from(value);
+                    }
+                }
+            }
+            let count = ((y_end - block_y) * (x_end - block_x)) as u64;
+            let average = sums.map(|sum| ((sum + count / 2) / count) as u8);
+            for y in block_y..y_end {
+                for sample in plane.row_mut(y)[start..end].chunks_exact_mut(channels) {
+                    sample.copy_from_slice(&average[..channels]);
+                }
+            }
+        }
+    }
+}
+
+fn blur(plane: &mut VideoPlane, region: &PlaneRegion, radius: usize) {
+    if radius == 0 {
+        return;
+    }
+    let channels = region.channels;
+    let start = region.x * channels;
+    let mut line = vec![0; region.width.max(region.height) * channels];
+    for y in region.y..region.y + region.height {
+        let row = &mut plane.row_mut(y)[start..start + region.width * channels];
+        let source = &mut line[..row.len()];
+        source.copy_from_slice(row);
+        blur_line(source, row, channels, radius);
+    }
+    let mut column = vec![0; region.height * channels];
+    for x in region.x..region.x + region.width {
+        let offset = x * channels;
+        for (y, sample) in (region.y..).zip(column.chunks_exact_mut(channels)) {
+            sample.copy_from_slice(&plane.row(y)[offset..offset + channels]);
+        }
+        let blurred = &mut line[..column.len()];
+        blur_line(&column, blurred, channels, radius);
+        for (y, sample) in (region.y..).zip(blurred.chunks_exact(channels)) {
+            plane.row_mut(y)[offset..offset + channels].copy_from_slice(sample);
+        }
+    }
+}
+
+/// Averages every sample of `source` with the samples up to `radius` away on either side,
+/// clamped to the line, into `target`.
+#[allow(clippy::cast_possible_truncation)]
+fn blur_line(source: &[u8], target: &mut [u8], channels: usize, radius: usize) {
+    let count = source.len() / channels;
+    let mut prefix = vec![0u32; count + 1];
+    for channel in 0..channels {
+        for i in 0..count {
+            prefix[i + 1] = prefix[i] + u32::from(source[i * channels + channel]);
+        }
+        for i in 0..count {
+            let (low, high) = (i.saturating_sub(radius), (i + radius + 1).min(count));
+            let taps = (high - low) as u32;
+            target[i * channels + channel] = ((prefix[high] - prefix[low] + taps / 2) / taps) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod redaction_test {
+    use core_graphics::geometry::{CGPoint, CGRect};
+
+    use crate::{
+        geometry::{display_layout::DisplayGeometry, rect::RectExt},
+        media::{
+            media_time::MediaTime,
+            video_frame::{PixelRect, VideoFrame},
+        },
+        shareable_content::{
+            content_query::{TextMatch, WindowQuery},
+            content_snapshot::{ApplicationInfo, ContentSnapshot, Rect, WindowInfo},
+        },
+    };
+
+    use super::{redact, FrameMapping, RedactionStyle, Redactor};
+
+    const WHITE: [u8; 4] = [255; 4];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn window(window_id: u32, title: &str, bundle_identifier: &str, frame: Rect) -> WindowInfo {
+        WindowInfo {
+            window_id,
+            title: title.to_string(),
+            frame,
+            is_on_screen: true,
+            owning_application: ApplicationInfo {
+                bundle_identifier: bundle_identifier.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn snapshot() -> ContentSnapshot {
+        let mut hidden = window(
+            4,
+            "Vault",
+            "com.example.vault",
+            Rect::new(0.0, 0.0, 50.0, 50.0),
+        );
+        hidden.is_on_screen = false;
+        ContentSnapshot {
+            windows: vec![
+                window(
+                    1,
+                    "Inbox",
+                    "com.apple.mail",
+                    Rect::new(100.0, 50.0, 200.0, 100.0),
+                ),
+                window(
+                    2,
+                    "Vault",
+                    "com.example.vault",
+                    Rect::new(900.0, 500.0, 200.0, 200.0),
+                ),
+                window(
+                    3,
+                    "Notes",
+                    "com.apple.Notes",
+                    Rect::new(0.0, 0.0, 100.0, 100.0),
+                ),
+                hidden,
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn redactor(style: RedactionStyle) -> Redactor {
+        Redactor::new(style)
+            .with_rule(WindowQuery::new().bundle_identifier("com.apple.mail"))
+            .with_rule(WindowQuery::new().title(TextMatch::glob("Va*")))
+    }
+
+    #[test]
+    fn test_mapping() {
+        let display = DisplayGeometry::new(1, CGRect::from_edges(-1000.0, 0.0, 0.0, 600.0), 2.0);
+        let full = FrameMapping::for_display(&display);
+        let rect = full.rect_to_frame(&CGRect::from_edges(-900.0, 50.0, -700.0, 150.0));
+        assert_eq!(
+            [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()],
+            [200.0, 100.0, 600.0, 300.0]
+        );
+        // The same display scaled down into a 4:3 frame of 800x800 pixels, letterboxed.
+        let letterboxed = FrameMapping::new(
+            CGPoint::new(-1000.0, 0.0),
+            CGRect::from_edges(0.0, 50.0, 400.0, 290.0),
+            0.4,
+            2.0,
+        );
+        let rect = letterboxed.rect_to_frame(&CGRect::from_edges(-900.0, 50.0, -700.0, 150.0));
+        assert_eq!(
+            [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()],
+            [80.0, 140.0, 240.0, 220.0]
+        );
+        let content = letterboxed.content_in_frame();
+        assert_eq!([content.min_y(), content.max_y()], [100.0, 580.0]);
+    }
+
+    #[test]
+    fn test_regions() {
+        let mapping = FrameMapping::for_display(&DisplayGeometry::new(
+            1,
+            CGRect::from_edges(0.0, 0.0, 1000.0, 600.0),
+            1.0,
+        ));
+        let redactor = redactor(RedactionStyle::Fill(BLACK));
+        let ids: Vec<_> = redactor
+            .windows(&snapshot())
+            .iter()
+            .map(|w| w.window_id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(
+            redactor.regions(&snapshot(), &mapping, 1000, 600),
+            vec![
+                PixelRect::new(100, 50, 200, 100),
+                PixelRect::new(900, 500, 100, 100)
+            ]
+        );
+        // Frames smaller than the content, e.g. a cropped stream, clip the regions too.
+        assert_eq!(
+            redactor.regions(&snapshot(), &mapping, 150, 600),
+            vec![PixelRect::new(100, 50, 50, 100)]
+        );
+    }
+
+    #[test]
+    fn test_fill() {
+        let mapping = FrameMapping::for_display(&DisplayGeometry::new(
+            1,
+            CGRect::from_edges(0.0, 0.0, 320.0, 200.0),
+            0.5,
+        ));
+        let redactor = redactor(RedactionStyle::Fill(BLACK));
+        let mut frame = VideoFrame::filled_bgra(160, 100, WHITE, MediaTime::ZERO);
+        assert_eq!(redactor.apply(&mut frame, &snapshot(), &mapping), 1);
+        assert_eq!(frame.bgra_pixel(49, 25), Some(WHITE));
+        assert_eq!(frame.bgra_pixel(50, 25), Some(BLACK));
+        assert_eq!(frame.bgra_pixel(149, 74), Some(BLACK));
+        assert_eq!(frame.bgra_pixel(150, 74), Some(WHITE));
+        assert_eq!(frame.bgra_pixel(149, 75), Some(WHITE));
+
+        let mut nv12 = VideoFrame::new_nv12(8, 4, vec![235; 32], vec![128; 16], MediaTime::ZERO);
+        redact(
+            &mut nv12,
+            PixelRect::new(1, 1, 4, 2),
+            RedactionStyle::Fill([0, 0, 255, 255]),
+        );
+        assert_eq!(nv12.planes[0].row(1), &[235, 63, 63, 63, 63, 235, 235, 235]);
+        assert_eq!(nv12.planes[0].row(0), &[235; 8]);
+        assert_eq!(
+            nv12.planes[1].row(0),
+            &[102, 240, 102, 240, 102, 240, 128, 128]
+        );
+        assert_eq!(
+            nv12.planes[1].row(1),
+            &[102, 240, 102, 240, 102, 240, 128, 128]
+        );
+    }
+
+    #[test]
+    fn test_pixelate_and_blur() {
+        // Vertical black and white stripes, one pixel wide.
+        let pixels: Vec<u8> = (0..8 * 4)
+            .flat_map(|i| if i % 2 == 0 { BLACK } else { WHITE })
+            .collect();
+        let striped = VideoFrame::new_bgra(8, 4, pixels, MediaTime::ZERO);
+
+        let mut frame = striped.clone();
+        redact(
+            &mut frame,
+            PixelRect::new(0, 0, 4, 4),
+            RedactionStyle::Pixelate(2),
+        );
+        assert_eq!(frame.bgra_pixel(0, 0), Some([128, 128, 128, 255]));
+        assert_eq!(frame.bgra_pixel(3, 3), Some([128, 128, 128, 255]));
+        assert_eq!(frame.bgra_pixel(4, 0), Some(BLACK));
+        assert_eq!(frame.bgra_pixel(5, 0), Some(WHITE));
+
+        let mut frame = striped.clone();
+        redact(
+            &mut frame,
+            PixelRect::new(2, 1, 4, 2),
+            RedactionStyle::Blur(1),
+        );
+        // Each pixel averages itself and its neighbours within the region.
+        assert_eq!(frame.bgra_pixel(2, 1), Some([128, 128, 128, 255]));
+        assert_eq!(frame.bgra_pixel(3, 1), Some([85, 85, 85, 255]));
+        assert_eq!(frame.bgra_pixel(4, 2), Some([170, 170, 170, 255]));
+        assert_eq!(frame.bgra_pixel(2, 0), Some(BLACK));
+        assert_eq!(frame.bgra_pixel(6, 1), Some(BLACK));
+        assert_eq!(frame.planes[0].row(3), striped.planes[0].row(3));
+    }
+}
@@ -0,0 +1,591 @@
+use std::{borrow::Cow, f64::consts::PI};
+
+use core_foundation::error::CFError;
+
+use crate::{
+    media::{audio_frame::AudioFrame, media_time::MediaTime},
+    utils::error::create_sc_error,
+};
+
+/// Differences between a frame's timestamp and the end of the previous frame beyond this,
+/// in seconds, are gaps rather than jitter and start a new segment.
+const GAP_TOLERANCE: f64 = 0.02;
+/// The Kaiser window shape, trading transition width for roughly 80 dB of stopband
+/// attenuation.
+const KAISER_BETA: f64 = 8.0;
+/// The passband as a fraction of the lower Nyquist frequency, leaving room for the
+/// transition band below it.
+const PASSBAND: f64 = 0.95;
+/// Up to this many filter phases are computed ahead; rates with a larger ratio compute
+/// their coefficients per output sample.
+const MAX_PHASES: u64 = 1024;
+
+/// Mixes input channels into output channels, one row of input gains per output channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMatrix {
+    inputs: u16,
+    /// `outputs * inputs` gains, row by row.
+    gains: Vec<f32>,
+}
+
+impl ChannelMatrix {
+    /// Creates a matrix from one row of gains per output channel, each as long as there are
+    /// input channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no rows or columns, or rows differ in length.
+    pub fn new(rows: &[&[f32]]) -> Self {
+        let inputs = rows.first().map_or(0, |row| row.len());
+        assert!(
+            inputs > 0 && rows.iter().all(|row| row.len() == inputs),
+            "a channel matrix needs rows of the same, non-zero length"
+        );
+        Self {
+            inputs: u16::try_from(inputs).expect("too many input channels"),
+            gains: rows.concat(),
+        }
+    }
+
+    /// Passes `channels` channels through unchanged.
+    pub fn identity(channels: u16) -> Self {
+        Self::mix(channels, channels)
+    }
+
+    /// The default mix from `inputs` to `outputs` channels. Mono is copied to every output,
+    /// and every output of a mono mix averages all inputs. Otherwise each input goes to the
+    /// output with the same index, with inputs beyond the outputs folded in by index
+    /// modulo the output count and each output scaled to keep its level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either channel count is zero.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mix(inputs: u16, outputs: u16) -> Self {
+        assert!(inputs > 0 && outputs > 0, "channel counts must not be zero");
+        let rows: Vec<Vec<f32>> = (0..outputs)
+            .map(|output| {
+                let feeds = |input: u16| inputs == 1 || input % outputs == output;
+                let count = (0..inputs).filter(|&input| feeds(input)).count();
+                (0..inputs)
+                    .map(|input| {
+                        if feeds(input) {
+                            1.0 / count as f32
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self::new(&rows.iter().map(Vec::as_slice).collect::<Vec<_>>())
+    }
+
+    pub const fn inputs(&self) -> u16 {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> u16 {
+        u16::try_from(self.gains.len() / usize::from(self.inputs)).unwrap_or(u16::MAX)
+    }
+
+    /// Returns the gain of `input` in `output`.
+    pub fn gain(&self, output: u16, input: u16) -> f32 {
+        self.gains[usize::from(output) * usize::from(self.inputs) + usize::from(input)]
+    }
+
+    /// Mixes interleaved samples with [`Self::inputs`] channels, appending the mixed samples
+    /// to `output`.
+    fn apply(&self, samples: &[f32], output: &mut Vec<f32>) {
+        let inputs = usize::from(self.inputs);
+        for frame in samples.chunks_exact(inputs) {
+            output.extend(self.gains.chunks_exact(inputs).map(|row| {
+                row.iter()
+                    .zip(frame)
+                    .map(|(gain, sample)| gain * sample)
+                    .sum::<f32>()
+            }));
+        }
+    }
+}
+
+/// A windowed sinc low-pass filter split into the phases of a rational rate ratio.
+#[derive(Debug)]
+struct Kernel {
+    /// The output rate divided by the greatest common divisor of both rates.
+    up: u64,
+    /// The input rate divided by the greatest common divisor of both rates.
+    down: u64,
+    /// The cutoff frequency as a fraction of the input Nyquist frequency.
+    cutoff: f64,
+    /// How many input samples the filter reaches to either side.
+    reach: usize,
+    /// How many input samples make up each output sample, usually `2 * reach`.
+    taps: usize,
+    /// The coefficients of each phase when there are at most [`MAX_PHASES`].
+    phases: Vec<Vec<f32>>,
+}
+
+impl Kernel {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn new(input_rate: u32, output_rate: u32, zero_crossings: usize) -> Self {
+        let divisor = gcd(u64::from(input_rate), u64::from(output_rate));
+        let (up, down) = (
+            u64::from(output_rate) / divisor,
+            u64::from(input_rate) / divisor,
+        );
+        let mut kernel = Self {
+            up,
+            down,
+            cutoff: (up as f64 / down as f64).min(1.0) * PASSBAND,
+            reach: 0,
+            taps: 0,
+            phases: Vec::new(),
+        };
+        if up == down {
+            // The same rate, which a filter would only blur.
+            kernel.reach = 1;
+            kernel.taps = 1;
+            kernel.phases = vec![vec![1.0]];
+        } else {
+            kernel.reach = (zero_crossings as f64 / kernel.cutoff).ceil() as usize;
+            kernel.taps = 2 * kernel.reach;
+            if up <= MAX_PHASES {
+                kernel.phases = (0..up).map(|phase| kernel.coefficients(phase)).collect();
+            }
+        }
+        kernel
+    }
+
+    /// Returns the coefficients for the `taps` input samples around an output sample
+    /// `phase / up` of an input sample after the last input sample before it, starting
+    /// `reach - 1` samples before that one.
+    #[allow(clippy::cast_precision_loss)]
+    fn coefficients(&self, phase: u64) -> Vec<f32> {
+        let offset = phase as f64 / self.up as f64;
+        let reach = self.reach as f64;
+        let weights: Vec<f64> = (0..self.taps)
+            .map(|tap| {
+                let distance = tap as f64 - (reach - 1.0) - offset;
+                self.cutoff * sinc(self.cutoff * distance) * kaiser(distance / reach)
+            })
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        #[allow(clippy::cast_possible_truncation)]
+        weights.iter().map(|weight| (weight / sum) as f32).collect()
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+pub(super) fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The Kaiser window at `x` from -1 to 1.
+pub(super) fn kaiser(x: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(KAISER_BETA * x.mul_add(-x, 1.0).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+/// The zeroth order modified Bessel function of the first kind, from its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..64 {
+        term *= half / f64::from(k);
+        let squared = term * term;
+        sum += squared;
+        if squared < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
+}
+
+/// The audio of one continuous stretch of input.
+#[derive(Debug)]
+struct Segment {
+    input_rate: u32,
+    input_channels: u16,
+    /// The presentation time of the segment's first input sample.
+    origin: MediaTime,
+    kernel: Kernel,
+    /// Mixed input samples, interleaved, starting at input sample `offset`.
+    buffer: Vec<f32>,
+    offset: i64,
+    /// Input samples received.
+    received: u64,
+    /// Output samples produced.
+    produced: u64,
+}
+
+/// Converts audio to a fixed sample rate and channel layout, e.g. the 16 kHz mono
+/// speech-to-text models expect.
+///
+/// Channels are mixed first, then each is resampled with a Kaiser windowed sinc filter
+/// evaluated at the phases of the rational ratio between the rates, with its cutoff below
+/// the lower of the two Nyquist frequencies so downsampling doesn't alias.
+///
+/// Output timestamps follow the output sample count from the first input frame, so output
+/// frames are contiguous and line up with the input despite the filter's lookahead; output
+/// lags input by that lookahead until [`Self::finish`] flushes it. Gaps in the input
+/// timestamps and changes of input format flush the current segment and start a new one at
+/// the new timestamp.
+#[derive(Debug)]
+pub struct Resampler {
+    output_rate: u32,
+    matrix: Option<ChannelMatrix>,
+    output_channels: u16,
+    zero_crossings: usize,
+    segment: Option<Segment>,
+}
+
+impl Resampler {
+    /// Creates a resampler producing `output_rate` Hz audio with `output_channels` channels,
+    /// mixed from the input with [`ChannelMatrix::mix`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `output_rate` or `output_channels` is zero.
+    pub fn new(output_rate: u32, output_channels: u16) -> Self {
+        assert!(
+            output_rate > 0 && output_channels > 0,
+            "the output format must have samples"
+        );
+        Self {
+            output_rate,
+            matrix: None,
+            output_channels,
+            zero_crossings: 16,
+            segment: None,
+        }
+    }
+
+    /// Mixes channels with `matrix`, whose rows set the output channel count. Input frames
+    /// must then have as many channels as the matrix has columns.
+    #[must_use]
+    pub fn with_matrix(mut self, matrix: ChannelMatrix) -> Self {
+        self.output_channels = matrix.outputs();
+        self.matrix = Some(matrix);
+        self
+    }
+
+    /// Sets how many zero crossings of the sinc filter are used to either side of each
+    /// output sample. More give a sharper cutoff at the cost of lookahead and time.
+    /// Defaults to 16.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `zero_crossings` is zero.
+    #[must_use]
+    pub fn with_zero_crossings(mut self, zero_crossings: usize) -> Self {
+        assert!(
+            zero_crossings > 0,
+            "the filter needs at least one zero crossing"
+        );
+        self.zero_crossings = zero_crossings;
+        self
+    }
+
+    pub const fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub const fn output_channels(&self) -> u16 {
+        self.output_channels
+    }
+
+    /// Converts a frame, returning the output that's ready: usually one frame, none while
+    /// the filter fills, or two when the frame starts a new segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame's timestamp is invalid, it has no channels or sample
+    /// rate, or its channels don't match the matrix set with [`Self::with_matrix`].
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn push(&mut self, frame: &AudioFrame) -> Result<Vec<AudioFrame>, CFError> {
+        if !frame.pts.is_valid() {
+            return Err(create_sc_error("audio frames need valid timestamps"));
+        }
+        if frame.sample_rate == 0 || frame.channels == 0 {
+            return Err(create_sc_error(
+                "audio frames need a sample rate and channels",
+            ));
+        }
+        if let Some(matrix) = &self.matrix {
+            if matrix.inputs() != frame.channels {
+                return Err(create_sc_error(format!(
+                    "the channel matrix mixes {} channels, not {}",
+                    matrix.inputs(),
+                    frame.channels
+                )));
+            }
+        }
+        let mut output = Vec::new();
+        let continues = self.segment.as_ref().is_some_and(|segment| {
+            segment.input_rate == frame.sample_rate
+                && segment.input_channels == frame.channels
+                && (frame.pts - segment.input_end()).as_secs_f64().abs() <= GAP_TOLERANCE
+        });
+        if !continues {
+            output.extend(self.finish());
+            self.segment = Some(self.start(frame));
+        }
+        let default;
+        let matrix = if let Some(matrix) = &self.matrix {
+            matrix
+        } else {
+            default = ChannelMatrix::mix(frame.channels, self.output_channels);
+            &default
+        };
+        if let Some(segment) = &mut self.segment {
+            matrix.apply(&frame.samples, &mut segment.buffer);
+            segment.received += frame.frame_count() as u64;
+            output.extend(segment.produce(self.output_rate, self.output_channels, false));
+        }
+        Ok(output)
+    }
+
+    /// Flushes the filter's lookahead, returning the rest of the current segment. The next
+    /// frame starts a new segment at its own timestamp.
+    pub fn finish(&mut self) -> Option<AudioFrame> {
+        let mut segment = self.segment.take()?;
+        let padding = segment.kernel.reach * usize::from(self.output_channels);
+        segment.buffer.resize(segment.buffer.len() + padding, 0.0);
+        segment.produce(self.output_rate, self.output_channels, true)
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn start(&self, frame: &AudioFrame) -> Segment {
+        let kernel = Kernel::new(frame.sample_rate, self.output_rate, self.zero_crossings);
+        // The samples before the first one are silent.
+        let lead = kernel.reach - 1;
+        Segment {
+            input_rate: frame.sample_rate,
+            input_channels: frame.channels,
+            origin: frame.pts,
+            buffer: vec![0.0; lead * usize::from(self.output_channels)],
+            offset: -(lead as i64),
+            kernel,
+            received: 0,
+            produced: 0,
+        }
+    }
+}
+
+impl Segment {
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+    fn input_end(&self) -> MediaTime {
+        self.origin + MediaTime::new(self.received as i64, self.input_rate as i32)
+    }
+
+    /// Filters the output samples whose input is buffered, or with `flush` every output
+    /// sample up to the end of the input once the buffer is padded past it.
+    #[allow(
+        clippy::cast_possible_wrap,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn produce(&mut self, output_rate: u32, channels: u16, flush: bool) -> Option<AudioFrame> {
+        let channels = usize::from(channels);
+        let kernel = &self.kernel;
+        let reach = kernel.reach as i64;
+        let available = self.offset + (self.buffer.len() / channels) as i64;
+        let first = self.produced;
+        let mut samples = Vec::new();
+        loop {
+            let position = self.produced * kernel.down;
+            let base = (position / kernel.up) as i64;
+            if base - (reach - 1) + kernel.taps as i64 > available
+                || (flush && position >= self.received * kernel.up)
+            {
+                break;
+            }
+            let phase = position % kernel.up;
+            let coefficients = kernel.phases.get(phase as usize).map_or_else(
+                || Cow::Owned(kernel.coefficients(phase)),
+                |coefficients| Cow::Borrowed(coefficients.as_slice()),
+            );
+            let start = (base - (reach - 1) - self.offset) as usize * channels;
+            let window = &self.buffer[start..start + coefficients.len() * channels];
+            for channel in 0..channels {
+                samples.push(
+                    coefficients
+                        .iter()
+                        .zip(window[channel..].iter().step_by(channels))
+                        .map(|(coefficient, sample)| coefficient * sample)
+                        .sum::<f32>(),
+                );
+            }
+            self.produced += 1;
+        }
+        // Drops the input no later output sample reaches.
+        let next = (self.produced * kernel.down / kernel.up) as i64 - (reach - 1);
+        let unused = (next - self.offset).clamp(0, available - self.offset);
+        self.buffer.drain(..unused as usize * channels);
+        self.offset += unused;
+        if samples.is_empty() {
+            return None;
+        }
+        Some(AudioFrame::new(
+            output_rate,
+            channels as u16,
+            samples,
+            self.origin + MediaTime::new(first as i64, output_rate as i32),
+        ))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+mod resampler_test {
+    use std::f64::consts::TAU;
+
+    use crate::media::{audio_frame::AudioFrame, media_time::MediaTime};
+
+    use super::{ChannelMatrix, Resampler};
+
+    /// One second of a sine tone in `channels` identical channels.
+    #[allow(clippy::cast_possible_truncation)]
+    fn tone(rate: u32, channels: u16, frequency: f64) -> Vec<f32> {
+        (0..rate)
+            .flat_map(|index| {
+                let value = (TAU * frequency * f64::from(index) / f64::from(rate)).sin();
+                vec![value as f32 * 0.5; usize::from(channels)]
+            })
+            .collect()
+    }
+
+    /// Feeds `samples` in frames of varying length from `start`, returning the output.
+    fn convert(
+        resampler: &mut Resampler,
+        rate: u32,
+        channels: u16,
+        samples: &[f32],
+        start: MediaTime,
+    ) -> Vec<AudioFrame> {
+        let mut output = Vec::new();
+        let mut received = 0;
+        for (index, chunk) in samples.chunks(usize::from(channels) * 997).enumerate() {
+            // Timestamps jitter around the sample clock without breaking the segment.
+            let jitter = MediaTime::from_millis([0, 3, -2][index % 3]);
+            let pts = start + MediaTime::new(received, i32::try_from(rate).unwrap()) + jitter;
+            let frame = AudioFrame::new(rate, channels, chunk.to_vec(), pts);
+            received += i64::try_from(frame.frame_count()).unwrap();
+            output.extend(resampler.push(&frame).unwrap());
+        }
+        output.extend(resampler.finish());
+        output
+    }
+
+    fn contiguous(frames: &[AudioFrame], start: MediaTime) -> Vec<f32> {
+        let mut end = start;
+        for frame in frames {
+            assert_eq!(frame.pts, end);
+            end = frame.end();
+        }
+        frames
+            .iter()
+            .flat_map(|frame| frame.samples.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_matrix() {
+        let down = ChannelMatrix::mix(2, 1);
+        assert_eq!((down.inputs(), down.outputs()), (2, 1));
+        assert_eq!((down.gain(0, 0), down.gain(0, 1)), (0.5, 0.5));
+        let up = ChannelMatrix::mix(1, 2);
+        assert_eq!((up.gain(0, 0), up.gain(1, 0)), (1.0, 1.0));
+        let surround = ChannelMatrix::mix(6, 2);
+        assert_eq!(surround.gain(0, 4), 1.0 / 3.0);
+        assert_eq!(surround.gain(1, 4), 0.0);
+        assert_eq!(ChannelMatrix::identity(3).gain(2, 2), 1.0);
+
+        let swap = ChannelMatrix::new(&[&[0.0, 1.0], &[1.0, 0.0], &[0.5, 0.5]]);
+        let mut resampler = Resampler::new(48_000, 1).with_matrix(swap);
+        assert_eq!(resampler.output_channels(), 3);
+        let frame = AudioFrame::new(48_000, 2, vec![0.25, 0.75], MediaTime::ZERO);
+        // Without resampling there's no lookahead, so the output is ready at once.
+        let output = resampler.push(&frame).unwrap();
+        assert_eq!(output[0].samples, [0.75, 0.25, 0.5]);
+        assert!(resampler.finish().is_none());
+        let mono = AudioFrame::new(48_000, 1, vec![0.0], MediaTime::ZERO);
+        assert!(resampler.push(&mono).is_err());
+    }
+
+    #[test]
+    fn test_resample() {
+        for rate in [48_000, 44_100, 8_000] {
+            let start = MediaTime::from_millis(10_000);
+            let mut resampler = Resampler::new(16_000, 1);
+            let output = convert(&mut resampler, rate, 2, &tone(rate, 2, 440.0), start);
+            let samples = contiguous(&output, start);
+            assert_eq!(samples.len(), 16_000, "{rate} Hz");
+            // Away from the edges, where the filter sees silence, the tone comes through
+            // unchanged.
+            for (index, &sample) in samples.iter().enumerate().skip(200).take(15_600) {
+                let expected = (TAU * 440.0 * index as f64 / 16_000.0).sin() * 0.5;
+                assert!(
+                    (f64::from(sample) - expected).abs() < 2e-3,
+                    "{rate} Hz at {index}: {sample} instead of {expected}"
+                );
+            }
+        }
+
+        // A tone above the output Nyquist frequency is filtered out instead of aliasing.
+        let mut resampler = Resampler::new(16_000, 1);
+        let output = convert(
+            &mut resampler,
+            48_000,
+            1,
+            &tone(48_000, 1, 11_000.0),
+            MediaTime::ZERO,
+        );
+        let samples = contiguous(&output, MediaTime::ZERO);
+        let peak = samples[200..15_800]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak < 1e-3, "aliased at {peak}");
+    }
+
+    #[test]
+    fn test_gaps() {
+        let mut resampler = Resampler::new(16_000, 1);
+        let frame =
+            |millis| AudioFrame::new(16_000, 1, vec![0.5; 160], MediaTime::from_millis(millis));
+        let mut output = resampler.push(&frame(0)).unwrap();
+        output.extend(resampler.push(&frame(10)).unwrap());
+        // A gap flushes the first segment and starts another at the new timestamp.
+        output.extend(resampler.push(&frame(100)).unwrap());
+        output.extend(resampler.finish());
+        let spans: Vec<_> = output
+            .iter()
+            .map(|frame| (frame.pts.as_millis(), frame.end().as_millis()))
+            .collect();
+        assert_eq!(spans, [(0, 10), (10, 20), (100, 110)]);
+        assert!(output
+            .iter()
+            .all(|frame| frame.samples.iter().all(|&sample| sample == 0.5)));
+        assert!(resampler
+            .push(&AudioFrame::new(16_000, 1, vec![], MediaTime::new(0, 0)))
+            .is_err());
+    }
+}
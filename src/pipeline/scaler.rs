@@ -0,0 +1,744 @@
+use core_graphics::geometry::CGRect;
+
+use crate::{
+    geometry::rect::RectExt,
+    media::video_frame::{
+        bgra_to_ycbcr, FrameInfo, PixelFormat, PixelRect, VideoFrame, VideoPlane,
+    },
+};
+
+use super::resampler::sinc;
+
+/// How source pixels are weighted into each output pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleFilter {
+    /// The source pixel under the output pixel's center.
+    Nearest,
+    /// Linear interpolation between the two nearest pixels.
+    #[default]
+    Bilinear,
+    /// Catmull-Rom cubic interpolation of the four nearest pixels.
+    Bicubic,
+    /// A three lobe Lanczos window over the six nearest pixels, the sharpest of the modes.
+    Lanczos,
+}
+
+impl ScaleFilter {
+    /// Returns how many source pixels the kernel reaches to either side.
+    const fn radius(self) -> f64 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Self::Nearest => f64::from(u8::from(x < 0.5)),
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                if x < 1.0 {
+                    1.5f64.mul_add(x, -2.5).mul_add(x * x, 1.0)
+                } else if x < 2.0 {
+                    (-0.5f64).mul_add(x, 2.5).mul_add(x, -4.0).mul_add(x, 2.0)
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// How the source is fitted into an output of a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AspectMode {
+    /// Stretches the source over the whole output.
+    #[default]
+    Stretch,
+    /// Scales the whole source to fit, adding bars of the background color above and below
+    /// (letterbox) or to the sides (pillarbox).
+    Fit,
+    /// Scales the source to cover the output, cropping what sticks out on either side.
+    Fill,
+}
+
+/// Scales and crops BGRA and NV12 frames on the CPU, e.g. to make a preview next to a
+/// full resolution recording.
+///
+/// Downscaling widens the filter to cover every source pixel, so fine detail averages out
+/// rather than aliasing. Edges repeat the outermost pixels. Alpha is filtered like the color
+/// channels, which is exact for the opaque frames `SCStream` delivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scaler {
+    width: u32,
+    height: u32,
+    filter: ScaleFilter,
+    crop: Option<PixelRect>,
+    aspect: AspectMode,
+    background: [u8; 4],
+}
+
+/// The source pixels scaled into an output rectangle.
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Scaler {
+    /// Creates a scaler producing frames of `width` by `height` pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `height` is zero.
+    pub fn new(width: u32, height: u32) -> Self {
+        assert!(width > 0 && height > 0, "the output size must not be empty");
+        Self {
+            width,
+            height,
+            filter: ScaleFilter::default(),
+            crop: None,
+            aspect: AspectMode::default(),
+            background: [0, 0, 0, 255],
+        }
+    }
+
+    #[must_use]
+    pub const fn with_filter(mut self, filter: ScaleFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Only scales the part of the source inside `crop`, clipped to the frame.
+    #[must_use]
+    pub const fn with_crop(mut self, crop: PixelRect) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_aspect(mut self, aspect: AspectMode) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// Sets the BGRA color of letterbox and pillarbox bars. Defaults to opaque black.
+    #[must_use]
+    pub const fn with_background(mut self, bgra: [u8; 4]) -> Self {
+        self.background = bgra;
+        self
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns where the source lands in the output, which is smaller than the output with
+    /// [`AspectMode::Fit`]. In NV12 output it starts on even pixels to keep chroma aligned.
+    pub fn content_rect(&self, frame: &VideoFrame) -> PixelRect {
+        self.layout(frame).1
+    }
+
+    /// Returns `frame` scaled to the output size, keeping its timestamp and attachments. Dirty
+    /// rects are mapped to the output pixels they affect.
+    pub fn scale(&self, frame: &VideoFrame) -> VideoFrame {
+        let (source, target) = self.layout(frame);
+        let mut output = match frame.pixel_format {
+            PixelFormat::Bgra => {
+                let plane = VideoPlane::new(
+                    self.background
+                        .repeat(self.width as usize * self.height as usize),
+                    self.width as usize * 4,
+                );
+                VideoFrame {
+                    planes: vec![plane],
+                    ..self.empty_frame(frame, source, target)
+                }
+            }
+            PixelFormat::Nv12 => {
+                let [luma, cb, cr] = bgra_to_ycbcr(self.background);
+                let (width, height) = (self.width as usize, self.height as usize);
+                let chroma_width = (width + 1) / 2;
+                VideoFrame {
+                    planes: vec![
+                        VideoPlane::new(vec![luma; width * height], width),
+                        VideoPlane::new(
+                            [cb, cr].repeat(chroma_width * ((height + 1) / 2)),
+                            chroma_width * 2,
+                        ),
+                    ],
+                    ..self.empty_frame(frame, source, target)
+                }
+            }
+        };
+        if target.is_empty() || source.width <= 0.0 || source.height <= 0.0 {
+            return output;
+        }
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        match frame.pixel_format {
+            PixelFormat::Bgra => {
+                resample(
+                    &frame.planes[0],
+                    (width, height),
+                    source,
+                    &mut output.planes[0],
+                    target,
+                    4,
+                    self.filter,
+                );
+            }
+            PixelFormat::Nv12 => {
+                resample(
+                    &frame.planes[0],
+                    (width, height),
+                    source,
+                    &mut output.planes[0],
+                    target,
+                    1,
+                    self.filter,
+                );
+                let chroma_source = Area {
+                    x: source.x / 2.0,
+                    y: source.y / 2.0,
+                    width: source.width / 2.0,
+                    height: source.height / 2.0,
+                };
+                let chroma_target = PixelRect::new(
+                    target.x / 2,
+                    target.y / 2,
+                    (target.width + 1) / 2,
+                    (target.height + 1) / 2,
+                );
+                resample(
+                    &frame.planes[1],
+                    ((width + 1) / 2, (height + 1) / 2),
+                    chroma_source,
+                    &mut output.planes[1],
+                    chroma_target,
+                    2,
+                    self.filter,
+                );
+            }
+        }
+        output
+    }
+
+    fn empty_frame(&self, frame: &VideoFrame, source: Area, target: PixelRect) -> VideoFrame {
+        VideoFrame {
+            width: self.width,
+            height: self.height,
+            pixel_format: frame.pixel_format,
+            planes: Vec::new(),
+            pts: frame.pts,
+            info: FrameInfo {
+                dirty_rects: frame
+                    .info
+                    .dirty_rects
+                    .as_ref()
+                    .map(|rects| self.dirty_rects(frame.pixel_format, rects, source, target)),
+                ..frame.info.clone()
+            },
+        }
+    }
+
+    /// Maps dirty rects of the source to the output pixels whose filter reaches them.
+    fn dirty_rects(
+        &self,
+        format: PixelFormat,
+        rects: &[PixelRect],
+        source: Area,
+        target: PixelRect,
+    ) -> Vec<PixelRect> {
+        if target.is_empty() || source.width <= 0.0 || source.height <= 0.0 {
+            return Vec::new();
+        }
+        let scale_x = f64::from(target.width) / source.width;
+        let scale_y = f64::from(target.height) / source.height;
+        let reach = |scale: f64| {
+            let reach = self.filter.radius() * scale.recip().max(1.0);
+            match format {
+                PixelFormat::Bgra => reach,
+                // Half resolution chroma samples span two pixels and reach twice as far.
+                PixelFormat::Nv12 => reach.mul_add(2.0, 1.0),
+            }
+        };
+        let (reach_x, reach_y) = (reach(scale_x), reach(scale_y));
+        rects
+            .iter()
+            .filter_map(|rect| {
+                let min_x = (f64::from(rect.x) - reach_x).max(source.x);
+                let min_y = (f64::from(rect.y) - reach_y).max(source.y);
+                let max_x = (f64::from(rect.x.saturating_add(rect.width)) + reach_x)
+                    .min(source.x + source.width);
+                let max_y = (f64::from(rect.y.saturating_add(rect.height)) + reach_y)
+                    .min(source.y + source.height);
+                if rect.is_empty() || min_x >= max_x || min_y >= max_y {
+                    return None;
+                }
+                let mapped = CGRect::from_edges(
+                    (min_x - source.x).mul_add(scale_x, f64::from(target.x)),
+                    (min_y - source.y).mul_add(scale_y, f64::from(target.y)),
+                    (max_x - source.x).mul_add(scale_x, f64::from(target.x)),
+                    (max_y - source.y).mul_add(scale_y, f64::from(target.y)),
+                );
+                PixelRect::covering(&mapped, self.width, self.height)
+            })
+            .collect()
+    }
+
+    /// Returns the source area to scale and the output rectangle to scale it into.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn layout(&self, frame: &VideoFrame) -> (Area, PixelRect) {
+        let crop = self
+            .crop
+            .unwrap_or_else(|| PixelRect::new(0, 0, frame.width, frame.height));
+        let x = crop.x.min(frame.width);
+        let y = crop.y.min(frame.height);
+        let mut source = Area {
+            x: f64::from(x),
+            y: f64::from(y),
+            width: f64::from(crop.width.min(frame.width - x)),
+            height: f64::from(crop.height.min(frame.height - y)),
+        };
+        let full = PixelRect::new(0, 0, self.width, self.height);
+        if source.width <= 0.0 || source.height <= 0.0 {
+            return (source, full);
+        }
+        let (width, height) = (f64::from(self.width), f64::from(self.height));
+        let source_aspect = source.width / source.height;
+        let target_aspect = width / height;
+        match self.aspect {
+            AspectMode::Stretch => (source, full),
+            AspectMode::Fill => {
+                if source_aspect > target_aspect {
+                    let cropped = source.height * target_aspect;
+                    source.x += (source.width - cropped) / 2.0;
+                    source.width = cropped;
+                } else {
+                    let cropped = source.width / target_aspect;
+                    source.y += (source.height - cropped) / 2.0;
+                    source.height = cropped;
+                }
+                (source, full)
+            }
+            AspectMode::Fit => {
+                let align = match frame.pixel_format {
+                    PixelFormat::Bgra => 1,
+                    PixelFormat::Nv12 => 2,
+                };
+                let fitted = |size: f64, limit: u32| (size.round() as u32).clamp(1, limit);
+                let (fit_width, fit_height) = if source_aspect > target_aspect {
+                    (self.width, fitted(width / source_aspect, self.height))
+                } else {
+                    (fitted(height * source_aspect, self.width), self.height)
+                };
+                let offset = |free: u32| free / 2 / align * align;
+                let target = PixelRect::new(
+                    offset(self.width - fit_width),
+                    offset(self.height - fit_height),
+                    fit_width,
+                    fit_height,
+                );
+                (source, target)
+            }
+        }
+    }
+}
+
+/// The source pixels and their weights making up one output pixel along one axis.
+#[derive(Debug)]
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Computes the contributions to `length` output pixels from the source span starting at
+/// `start` and `span` pixels long, within a source `limit` pixels long.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn contributions(
+    filter: ScaleFilter,
+    start: f64,
+    span: f64,
+    limit: usize,
+    length: u32,
+) -> Vec<Contribution> {
+    let ratio = span / f64::from(length);
+    let last = limit.saturating_sub(1) as f64;
+    (0..length)
+        .map(|index| {
+            let center = (f64::from(index) + 0.5).mul_add(ratio, start);
+            if filter == ScaleFilter::Nearest {
+                return Contribution {
+                    start: center.floor().clamp(0.0, last) as usize,
+                    weights: vec![1.0],
+                };
+            }
+            let stretch = ratio.max(1.0);
+            let support = filter.radius() * stretch;
+            let first = (center - support).floor() as i64;
+            let end = (center + support).ceil() as i64;
+            let clamp = |pixel: i64| pixel.clamp(0, last as i64) as usize;
+            let start = clamp(first);
+            let mut weights = vec![0.0; clamp(end) - start + 1];
+            for pixel in first..=end {
+                let weight = filter.weight((pixel as f64 + 0.5 - center) / stretch);
+                weights[clamp(pixel) - start] += weight;
+            }
+            let sum: f64 = weights.iter().sum();
+            Contribution {
+                start,
+                weights: weights
+                    .into_iter()
+                    .map(|weight| (weight / sum) as f32)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Scales the `source` area of a plane `size` pixels large, with `channels` interleaved
+/// bytes per pixel, into `target` of another plane, first horizontally and then vertically.
+/// Padding beyond the width of the source rows is never read.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn resample(
+    input: &VideoPlane,
+    (source_width, source_height): (usize, usize),
+    source: Area,
+    output: &mut VideoPlane,
+    target: PixelRect,
+    channels: usize,
+    filter: ScaleFilter,
+) {
+    let target_width = target
+        .width
+        .min((output.bytes_per_row / channels) as u32 - target.x);
+    let target_height = target
+        .height
+        .min((output.data.len() / output.bytes_per_row.max(1)) as u32 - target.y);
+    let columns = contributions(filter, source.x, source.width, source_width, target.width);
+    let rows = contributions(
+        filter,
+        source.y,
+        source.height,
+        source_height,
+        target.height,
+    );
+    let Some(first_row) = rows.iter().map(|row| row.start).min() else {
+        return;
+    };
+    let last_row = rows
+        .iter()
+        .map(|row| row.start + row.weights.len())
+        .max()
+        .unwrap_or(first_row);
+    let row_len = target_width as usize * channels;
+    // Every needed source row scaled horizontally.
+    let mut horizontal = vec![0.0f32; (last_row - first_row) * row_len];
+    for (y, scaled) in (first_row..last_row).zip(horizontal.chunks_exact_mut(row_len)) {
+        let row = input.row(y);
+        for (column, pixel) in columns.iter().zip(scaled.chunks_exact_mut(channels)) {
+            for (offset, weight) in column.weights.iter().enumerate() {
+                let source = &row[(column.start + offset) * channels..];
+                for (value, &sample) in pixel.iter_mut().zip(source) {
+                    *value += weight * f32::from(sample);
+                }
+            }
+        }
+    }
+    for (y, row) in (target.y..target.y + target_height).zip(&rows) {
+        let output_row = &mut output.row_mut(y as usize)
+            [target.x as usize * channels..target.x as usize * channels + row_len];
+        let mut sums = vec![0.0f32; row_len];
+        for (offset, weight) in row.weights.iter().enumerate() {
+            let start = (row.start + offset - first_row) * row_len;
+            for (sum, &value) in sums.iter_mut().zip(&horizontal[start..start + row_len]) {
+                *sum += weight * value;
+            }
+        }
+        for (output, sum) in output_row.iter_mut().zip(sums) {
+            *output = sum.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+mod scaler_test {
+    use crate::media::{
+        media_time::MediaTime,
+        video_frame::{FrameInfo, PixelRect, VideoFrame, VideoPlane},
+    };
+
+    use super::{AspectMode, ScaleFilter, Scaler};
+
+    const FILTERS: [ScaleFilter; 4] = [
+        ScaleFilter::Nearest,
+        ScaleFilter::Bilinear,
+        ScaleFilter::Bicubic,
+        ScaleFilter::Lanczos,
+    ];
+
+    /// A gray BGRA frame whose pixels are given by `level`.
+    fn gray(width: u32, height: u32, level: impl Fn(u32, u32) -> u8) -> VideoFrame {
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let level = level(x, y);
+                [level, level, level, 255]
+            })
+            .collect();
+        VideoFrame::new_bgra(width, height, data, MediaTime::ZERO)
+    }
+
+    fn levels(frame: &VideoFrame) -> Vec<Vec<u8>> {
+        (0..frame.height)
+            .map(|y| {
+                (0..frame.width)
+                    .map(|x| frame.bgra_pixel(x, y).unwrap()[0])
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Computes output pixels directly from the kernel definitions, sampling the source
+    /// with clamped edges.
+    fn reference(frame: &VideoFrame, width: u32, height: u32, filter: ScaleFilter) -> Vec<Vec<u8>> {
+        let source = levels(frame);
+        let axis = |index: u32, length: u32, source_length: u32| {
+            let ratio = f64::from(source_length) / f64::from(length);
+            let stretch = ratio.max(1.0);
+            let center = (f64::from(index) + 0.5) * ratio;
+            if filter == ScaleFilter::Nearest {
+                return vec![((center as u32).min(source_length - 1) as usize, 1.0)];
+            }
+            let reach = (filter.radius() * stretch).ceil() as i64 + 1;
+            let pixel = center.floor() as i64;
+            let taps: Vec<(usize, f64)> = (pixel - reach..=pixel + reach)
+                .map(|tap| {
+                    let weight = filter.weight((tap as f64 + 0.5 - center) / stretch);
+                    (tap.clamp(0, i64::from(source_length) - 1) as usize, weight)
+                })
+                .collect();
+            let sum: f64 = taps.iter().map(|(_, weight)| weight).sum();
+            taps.into_iter()
+                .map(|(tap, weight)| (tap, weight / sum))
+                .collect::<Vec<_>>()
+        };
+        (0..height)
+            .map(|y| {
+                let rows = axis(y, height, frame.height);
+                (0..width)
+                    .map(|x| {
+                        let columns = axis(x, width, frame.width);
+                        let mut value = 0.0;
+                        for &(row, row_weight) in &rows {
+                            for &(column, column_weight) in &columns {
+                                value +=
+                                    row_weight * column_weight * f64::from(source[row][column]);
+                            }
+                        }
+                        value.round().clamp(0.0, 255.0) as u8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        let pattern = gray(12, 9, |x, y| ((x * 37 + y * 91) % 256) as u8);
+        for filter in FILTERS {
+            // Same size is a copy, and flat areas stay flat.
+            let scaler = Scaler::new(12, 9).with_filter(filter);
+            assert_eq!(scaler.scale(&pattern), pattern, "{filter:?}");
+            let flat = gray(7, 5, |_, _| 77);
+            let stretched = Scaler::new(16, 3).with_filter(filter).scale(&flat);
+            assert_eq!(stretched, gray(16, 3, |_, _| 77), "{filter:?}");
+
+            for (width, height) in [(5, 4), (25, 17), (8, 20)] {
+                let resized = Scaler::new(width, height)
+                    .with_filter(filter)
+                    .scale(&pattern);
+                let expected = reference(&pattern, width, height, filter);
+                for (row, expected_row) in levels(&resized).iter().zip(&expected) {
+                    for (&value, &expected) in row.iter().zip(expected_row) {
+                        assert!(
+                            value.abs_diff(expected) <= 1,
+                            "{filter:?} at {width}x{height}: {value} instead of {expected}"
+                        );
+                    }
+                }
+            }
+        }
+
+        let row = gray(2, 1, |x, _| [0, 100][x as usize]);
+        let upscaled = |filter| levels(&Scaler::new(4, 1).with_filter(filter).scale(&row));
+        assert_eq!(upscaled(ScaleFilter::Nearest), [[0, 0, 100, 100]]);
+        assert_eq!(upscaled(ScaleFilter::Bilinear), [[0, 25, 75, 100]]);
+        // Catmull-Rom overshoots at the edges, clamped to zero on the dark side.
+        assert_eq!(upscaled(ScaleFilter::Bicubic), [[0, 20, 80, 107]]);
+    }
+
+    #[test]
+    fn test_crop_and_aspect() {
+        let pattern = gray(4, 2, |x, y| (y * 4 + x) as u8 * 10);
+        let nearest = Scaler::new(2, 2).with_filter(ScaleFilter::Nearest);
+        let crop = nearest.with_crop(PixelRect::new(1, 0, 2, 10));
+        assert_eq!(levels(&crop.scale(&pattern)), [[10, 20], [50, 60]]);
+        let fill = nearest.with_aspect(AspectMode::Fill);
+        assert_eq!(levels(&fill.scale(&pattern)), [[10, 20], [50, 60]]);
+
+        let letterbox = Scaler::new(4, 4)
+            .with_filter(ScaleFilter::Nearest)
+            .with_aspect(AspectMode::Fit)
+            .with_background([9, 9, 9, 255]);
+        assert_eq!(letterbox.content_rect(&pattern), PixelRect::new(0, 1, 4, 2));
+        assert_eq!(
+            levels(&letterbox.scale(&pattern)),
+            [
+                [9, 9, 9, 9],
+                [0, 10, 20, 30],
+                [40, 50, 60, 70],
+                [9, 9, 9, 9]
+            ]
+        );
+        let pillarbox = Scaler::new(4, 2)
+            .with_filter(ScaleFilter::Nearest)
+            .with_aspect(AspectMode::Fit)
+            .with_background([9, 9, 9, 255]);
+        let square = gray(2, 2, |x, _| x as u8 + 1);
+        assert_eq!(pillarbox.content_rect(&square), PixelRect::new(1, 0, 2, 2));
+        assert_eq!(
+            levels(&pillarbox.scale(&square)),
+            [[9, 1, 2, 9], [9, 1, 2, 9]]
+        );
+    }
+
+    #[test]
+    fn test_padded_rows() {
+        let pattern = gray(5, 3, |x, y| (x * 40 + y * 7) as u8);
+        let mut padded = pattern.clone();
+        padded.planes[0] = VideoPlane::new(
+            pattern.planes[0]
+                .data
+                .chunks_exact(5 * 4)
+                .flat_map(|row| [row, &[255; 3 * 4]].concat())
+                .collect(),
+            8 * 4,
+        );
+        for filter in FILTERS {
+            for (width, height) in [(5, 3), (3, 2), (9, 7)] {
+                let scaler = Scaler::new(width, height).with_filter(filter);
+                assert_eq!(
+                    scaler.scale(&padded),
+                    scaler.scale(&pattern),
+                    "{filter:?} at {width}x{height}"
+                );
+            }
+        }
+
+        let nv12 = VideoFrame::new_nv12(
+            3,
+            2,
+            vec![10, 20, 30, 40, 50, 60],
+            vec![100, 200, 110, 210],
+            MediaTime::ZERO,
+        );
+        let padded = VideoFrame {
+            planes: vec![
+                VideoPlane::new([[10, 20, 30, 0, 0], [40, 50, 60, 0, 0]].concat(), 5),
+                VideoPlane::new(vec![100, 200, 110, 210, 0, 0], 6),
+            ],
+            ..nv12.clone()
+        };
+        for filter in FILTERS {
+            let scaler = Scaler::new(7, 3).with_filter(filter);
+            assert_eq!(scaler.scale(&padded), scaler.scale(&nv12), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_dirty_rects() {
+        let dirty = |rects: Vec<PixelRect>| {
+            gray(8, 4, |_, _| 0).with_info(FrameInfo {
+                dirty_rects: Some(rects),
+                ..FrameInfo::default()
+            })
+        };
+        let frame = dirty(vec![PixelRect::new(2, 1, 1, 1), PixelRect::new(6, 0, 2, 4)]);
+        let nearest = Scaler::new(4, 2).with_filter(ScaleFilter::Nearest);
+        assert_eq!(
+            nearest.scale(&frame).info.dirty_rects,
+            Some(vec![PixelRect::new(0, 0, 2, 2), PixelRect::new(2, 0, 2, 2)])
+        );
+        // Wider filters reach further, and rects outside the crop are dropped.
+        let cropped = Scaler::new(4, 2)
+            .with_filter(ScaleFilter::Bilinear)
+            .with_crop(PixelRect::new(0, 0, 4, 4));
+        assert_eq!(
+            cropped.scale(&frame).info.dirty_rects,
+            Some(vec![PixelRect::new(1, 0, 3, 2)])
+        );
+        let letterbox = Scaler::new(8, 8)
+            .with_filter(ScaleFilter::Nearest)
+            .with_aspect(AspectMode::Fit);
+        assert_eq!(
+            letterbox
+                .scale(&dirty(vec![PixelRect::new(0, 0, 1, 1)]))
+                .info
+                .dirty_rects,
+            Some(vec![PixelRect::new(0, 2, 2, 2)])
+        );
+        assert_eq!(nearest.scale(&gray(8, 4, |_, _| 0)).info.dirty_rects, None);
+    }
+
+    #[test]
+    fn test_nv12() {
+        let luma: Vec<u8> = (0..8 * 4).map(|index| (index % 8 * 30) as u8).collect();
+        let chroma = [100, 200].repeat(4 * 2);
+        let frame = VideoFrame::new_nv12(8, 4, luma, chroma, MediaTime::from_millis(5));
+        let scaled = Scaler::new(8, 8)
+            .with_filter(ScaleFilter::Nearest)
+            .with_aspect(AspectMode::Fit)
+            .scale(&frame);
+        assert_eq!(scaled.pts, MediaTime::from_millis(5));
+        assert_eq!(scaled.planes[0].row(0), [16; 8]);
+        assert_eq!(scaled.planes[0].row(2), [0, 30, 60, 90, 120, 150, 180, 210]);
+        assert_eq!(scaled.planes[0].row(6), [16; 8]);
+        assert_eq!(scaled.planes[1].row(0), [128; 8]);
+        assert_eq!(scaled.planes[1].row(1), [100, 200].repeat(4));
+        assert_eq!(scaled.planes[1].row(3), [128; 8]);
+    }
+}
@@ -0,0 +1,495 @@
+use std::{fmt::Write, time::Duration};
+
+use crate::{
+    media::media_time::MediaTime,
+    pipeline::{cursor::CursorTrack, dedup::FrameChange},
+    shareable_content::content_snapshot::{ContentChange, ContentSnapshot, WindowInfo},
+};
+
+/// How far audio has to fall below the onset threshold before the next onset counts, in dB.
+const ONSET_HYSTERESIS_DB: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MomentKind {
+    /// The picture changed a lot at once.
+    SceneChange,
+    /// Another window became active.
+    FocusChange,
+    /// Audio became loud after being quiet.
+    AudioOnset,
+    /// Nothing happened on screen, in the audio or with the cursor for a while.
+    Idle,
+}
+
+impl MomentKind {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::SceneChange => "scene_change",
+            Self::FocusChange => "focus_change",
+            Self::AudioOnset => "audio_onset",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+/// An interesting point in a recording, on the session clock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Moment {
+    pub kind: MomentKind,
+    pub start: MediaTime,
+    /// The end of idle stretches, or `start` for moments without a duration.
+    pub end: MediaTime,
+    pub title: String,
+}
+
+/// A section of a recording, starting at a moment and ending where the next one starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub kind: Option<MomentKind>,
+    pub start: MediaTime,
+    pub end: MediaTime,
+    pub title: String,
+}
+
+/// Collects the statistics of a recording and extracts a [`Timeline`] of interesting
+/// moments from them.
+///
+/// Scene changes come from the change scores of a
+/// [`FrameDeduplicator`](crate::pipeline::dedup::FrameDeduplicator), focus changes from
+/// diffs between content snapshots, and audio onsets from audio levels. Every changed
+/// frame, snapshot change, loud stretch of audio and cursor event counts as activity, and
+/// long enough gaps between activity become idle stretches. Each source must be pushed in
+/// timestamp order, but sources may be interleaved freely.
+#[derive(Debug)]
+pub struct ActivityTimeline {
+    scene_threshold: f64,
+    onset_threshold_db: f32,
+    min_idle: MediaTime,
+    min_spacing: MediaTime,
+    moments: Vec<Moment>,
+    activity: Vec<MediaTime>,
+    snapshot: Option<ContentSnapshot>,
+    is_loud: bool,
+}
+
+impl Default for ActivityTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActivityTimeline {
+    pub const fn new() -> Self {
+        Self {
+            scene_threshold: 0.5,
+            onset_threshold_db: -30.0,
+            min_idle: MediaTime::from_millis(10_000),
+            min_spacing: MediaTime::from_millis(2000),
+            moments: Vec::new(),
+            activity: Vec::new(),
+            snapshot: None,
+            is_loud: false,
+        }
+    }
+
+    /// Sets the change score from which a frame is a scene change. Defaults to 0.5.
+    #[must_use]
+    pub const fn with_scene_threshold(mut self, scene_threshold: f64) -> Self {
+        self.scene_threshold = scene_threshold;
+        self
+    }
+
+    /// Sets the level in dBFS audio has to reach to start an onset. Defaults to -30 dB.
+    #[must_use]
+    pub const fn with_onset_threshold(mut self, onset_threshold_db: f32) -> Self {
+        self.onset_threshold_db = onset_threshold_db;
+        self
+    }
+
+    /// Sets how long a stretch without activity has to be to count as idle. Defaults to
+    /// ten seconds.
+    #[must_use]
+    pub fn with_min_idle(mut self, min_idle: Duration) -> Self {
+        self.min_idle = min_idle.into();
+        self
+    }
+
+    /// Sets how close moments of the same kind may follow each other before the later ones
+    /// are skipped. Defaults to two seconds.
+    #[must_use]
+    pub fn with_min_spacing(mut self, min_spacing: Duration) -> Self {
+        self.min_spacing = min_spacing.into();
+        self
+    }
+
+    /// Adds the change score of a frame, whether or not it was dropped.
+    pub fn push_frame_change(&mut self, change: &FrameChange) {
+        if change.score > 0.0 {
+            self.activity.push(change.pts);
+        }
+        if change.score >= self.scene_threshold {
+            self.add_moment(
+                MomentKind::SceneChange,
+                change.pts,
+                "Scene change".to_string(),
+            );
+        }
+    }
+
+    /// Adds a content snapshot taken at `pts`. Windows that became active, or opened
+    /// active, since the previous snapshot are focus changes.
+    pub fn push_snapshot(&mut self, pts: impl Into<MediaTime>, snapshot: ContentSnapshot) {
+        let pts = pts.into();
+        let changes = self.snapshot.take().unwrap_or_default().diff(&snapshot);
+        if !changes.is_empty() {
+            self.activity.push(pts);
+        }
+        for change in changes {
+            let focused = match change {
+                ContentChange::WindowActivated { window_id } => snapshot.window(window_id),
+                ContentChange::WindowOpened(window) if window.is_active => {
+                    snapshot.window(window.window_id)
+                }
+                _ => None,
+            };
+            if let Some(window) = focused {
+                self.add_moment(MomentKind::FocusChange, pts, window_title(window));
+            }
+        }
+        self.snapshot = Some(snapshot);
+    }
+
+    /// Adds the level in dBFS of the audio starting at `pts`, e.g. the RMS level of a
+    /// 100 ms block.
+    pub fn push_audio_level(&mut self, pts: impl Into<MediaTime>, level_db: f32) {
+        let pts = pts.into();
+        if level_db >= self.onset_threshold_db {
+            self.activity.push(pts);
+            if !self.is_loud {
+                self.is_loud = true;
+                self.add_moment(MomentKind::AudioOnset, pts, "Audio".to_string());
+            }
+        } else if level_db < self.onset_threshold_db - ONSET_HYSTERESIS_DB {
+            self.is_loud = false;
+        }
+    }
+
+    /// Counts every event of a cursor track as activity.
+    pub fn push_cursor_track(&mut self, track: &CursorTrack) {
+        self.activity
+            .extend(track.samples().iter().map(|sample| sample.pts));
+    }
+
+    /// Adds activity from any other source at `pts`.
+    pub fn push_activity(&mut self, pts: impl Into<MediaTime>) {
+        self.activity.push(pts.into());
+    }
+
+    /// Returns the moments of the recording ending at `end`, in timestamp order.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn finish(mut self, end: impl Into<MediaTime>) -> Timeline {
+        let end = end.into();
+        self.activity.sort();
+        let mut previous = MediaTime::ZERO;
+        for &pts in self.activity.iter().chain([&end]) {
+            if pts - previous >= self.min_idle {
+                let seconds = (pts - previous).as_secs_f64().round() as i64;
+                self.moments.push(Moment {
+                    kind: MomentKind::Idle,
+                    start: previous,
+                    end: pts,
+                    title: format!("Idle for {seconds} s"),
+                });
+            }
+            previous = previous.max(pts);
+        }
+        self.moments.sort_by_key(|moment| moment.start);
+        Timeline {
+            moments: self.moments,
+            duration: end,
+        }
+    }
+
+    fn add_moment(&mut self, kind: MomentKind, pts: MediaTime, title: String) {
+        let too_close = self
+            .moments
+            .iter()
+            .rev()
+            .find(|moment| moment.kind == kind)
+            .is_some_and(|last| pts - last.start < self.min_spacing);
+        if !too_close {
+            self.moments.push(Moment {
+                kind,
+                start: pts,
+                end: pts,
+                title,
+            });
+        }
+    }
+}
+
+fn window_title(window: &WindowInfo) -> String {
+    let application = &window.owning_application.application_name;
+    match (application.is_empty(), window.title.is_empty()) {
+        (true, true) => format!("Window {}", window.window_id),
+        (false, true) => application.clone(),
+        (true, false) => window.title.clone(),
+        (false, false) => format!("{application}: {}", window.title),
+    }
+}
+
+/// The interesting moments of a recording, exportable as a chapter list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeline {
+    pub moments: Vec<Moment>,
+    pub duration: MediaTime,
+}
+
+impl Timeline {
+    /// Splits the recording into chapters at each moment, with a chapter starting where
+    /// each idle stretch ends. Moments at the same time share a chapter.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut starts: Vec<(MediaTime, Option<MomentKind>, String)> = Vec::new();
+        for moment in &self.moments {
+            let start = moment.start.min(self.duration);
+            match starts.last_mut() {
+                Some((last, _, title)) if *last == start => {
+                    title.push_str(" / ");
+                    title.push_str(&moment.title);
+                }
+                _ => starts.push((start, Some(moment.kind), moment.title.clone())),
+            }
+            if moment.kind == MomentKind::Idle
+                && moment.end < self.duration
+                && !self.moments.iter().any(|other| other.start == moment.end)
+            {
+                starts.push((moment.end, None, "Activity".to_string()));
+            }
+        }
+        starts.sort_by_key(|(start, ..)| *start);
+        if starts
+            .first()
+            .map_or(true, |(start, ..)| *start > MediaTime::ZERO)
+        {
+            starts.insert(0, (MediaTime::ZERO, None, "Start".to_string()));
+        }
+        let ends: Vec<MediaTime> = starts
+            .iter()
+            .skip(1)
+            .map(|(start, ..)| *start)
+            .chain([self.duration])
+            .collect();
+        starts
+            .into_iter()
+            .zip(ends)
+            .filter(|((start, ..), end)| start < end)
+            .map(|((start, kind, title), end)| Chapter {
+                kind,
+                start,
+                end,
+                title,
+            })
+            .collect()
+    }
+
+    /// Returns the chapters as JSON, with times in seconds, e.g.
+    /// `{"duration":12.5,"chapters":[{"start":0,"end":4,"kind":"scene_change","title":"Scene change"}]}`.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"duration\":{},\"chapters\":[",
+            self.duration.as_secs_f64()
+        );
+        for (index, chapter) in self.chapters().iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            // Writing to a `String` cannot fail.
+            let _ = write!(
+                json,
+                "{{\"start\":{},\"end\":{},\"kind\":{},\"title\":\"{}\"}}",
+                chapter.start.as_secs_f64(),
+                chapter.end.as_secs_f64(),
+                chapter
+                    .kind
+                    .map_or_else(|| "null".to_string(), |kind| format!("\"{}\"", kind.name())),
+                escape_json(&chapter.title)
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// Returns the chapters as a `WebVTT` chapters file.
+    pub fn to_webvtt(&self) -> String {
+        let mut vtt = "WEBVTT\n".to_string();
+        for (number, chapter) in (1..).zip(self.chapters()) {
+            let title = chapter.title.replace("-->", "->").replace('\n', " ");
+            // Writing to a `String` cannot fail.
+            let _ = write!(
+                vtt,
+                "\n{number}\n{} --> {}\n{title}\n",
+                vtt_time(chapter.start),
+                vtt_time(chapter.end)
+            );
+        }
+        vtt
+    }
+}
+
+fn vtt_time(time: MediaTime) -> String {
+    let millis = time.as_millis().max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if character.is_control() => {
+                // Writing to a `String` cannot fail.
+                let _ = write!(escaped, "\\u{:04x}", u32::from(character));
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod timeline_test {
+    use std::time::Duration;
+
+    use core_graphics::geometry::CGPoint;
+
+    use crate::{
+        media::media_time::MediaTime,
+        pipeline::{
+            cursor::{CursorEvent, CursorTrack},
+            dedup::FrameChange,
+        },
+        shareable_content::content_snapshot::{ApplicationInfo, ContentSnapshot, WindowInfo},
+    };
+
+    use super::{ActivityTimeline, MomentKind};
+
+    fn change(seconds: i64, score: f64) -> FrameChange {
+        FrameChange {
+            pts: MediaTime::from_millis(seconds * 1000),
+            score,
+            hash: 0,
+            is_dropped: false,
+        }
+    }
+
+    fn snapshot(active: Option<u32>) -> ContentSnapshot {
+        let window = |window_id, title: &str, application: &str| WindowInfo {
+            window_id,
+            title: title.to_string(),
+            is_active: active == Some(window_id),
+            owning_application: ApplicationInfo {
+                application_name: application.to_string(),
+                ..ApplicationInfo::default()
+            },
+            ..WindowInfo::default()
+        };
+        ContentSnapshot {
+            windows: vec![
+                window(1, "main.rs", "Editor"),
+                window(2, "Docs \"draft\"", "Browser"),
+            ],
+            ..ContentSnapshot::default()
+        }
+    }
+
+    fn timeline() -> ActivityTimeline {
+        let mut timeline = ActivityTimeline::new().with_min_idle(Duration::from_secs(10));
+        timeline.push_snapshot(MediaTime::ZERO, snapshot(Some(1)));
+        timeline.push_frame_change(&change(1, 0.2));
+        timeline.push_frame_change(&change(3, 0.8));
+        timeline.push_frame_change(&change(4, 0.9));
+        timeline.push_snapshot(MediaTime::from_millis(5000), snapshot(Some(1)));
+        timeline.push_snapshot(MediaTime::from_millis(6000), snapshot(Some(2)));
+        for (millis, level) in [(6500, -60.0), (7000, -20.0), (7500, -28.0), (8000, -40.0)] {
+            timeline.push_audio_level(MediaTime::from_millis(millis), level);
+        }
+        timeline.push_audio_level(MediaTime::from_millis(30_000), -10.0);
+        let mut track = CursorTrack::new();
+        track
+            .push(
+                MediaTime::from_millis(18_000),
+                CursorEvent::Move(CGPoint::new(1.0, 1.0)),
+            )
+            .unwrap();
+        timeline.push_cursor_track(&track);
+        timeline
+    }
+
+    #[test]
+    fn test_moments() {
+        let timeline = timeline().finish(MediaTime::from_millis(45_000));
+        let moments: Vec<_> = timeline
+            .moments
+            .iter()
+            .map(|moment| {
+                (
+                    moment.kind,
+                    moment.start.as_millis(),
+                    moment.end.as_millis(),
+                    moment.title.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            moments,
+            [
+                (MomentKind::FocusChange, 0, 0, "Editor: main.rs"),
+                (MomentKind::SceneChange, 3000, 3000, "Scene change"),
+                (
+                    MomentKind::FocusChange,
+                    6000,
+                    6000,
+                    "Browser: Docs \"draft\""
+                ),
+                (MomentKind::AudioOnset, 7000, 7000, "Audio"),
+                (MomentKind::Idle, 7500, 18_000, "Idle for 11 s"),
+                (MomentKind::Idle, 18_000, 30_000, "Idle for 12 s"),
+                (MomentKind::AudioOnset, 30_000, 30_000, "Audio"),
+                (MomentKind::Idle, 30_000, 45_000, "Idle for 15 s"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let mut timeline = timeline().with_min_idle(Duration::from_secs(8));
+        timeline.push_activity(MediaTime::from_millis(19_000));
+        let timeline = timeline.finish(MediaTime::from_millis(40_000));
+        assert_eq!(
+            timeline.to_webvtt(),
+            "WEBVTT\n\
+             \n1\n00:00:00.000 --> 00:00:03.000\nEditor: main.rs\n\
+             \n2\n00:00:03.000 --> 00:00:06.000\nScene change\n\
+             \n3\n00:00:06.000 --> 00:00:07.000\nBrowser: Docs \"draft\"\n\
+             \n4\n00:00:07.000 --> 00:00:07.500\nAudio\n\
+             \n5\n00:00:07.500 --> 00:00:18.000\nIdle for 11 s\n\
+             \n6\n00:00:18.000 --> 00:00:19.000\nActivity\n\
+             \n7\n00:00:19.000 --> 00:00:30.000\nIdle for 11 s\n\
+             \n8\n00:00:30.000 --> 00:00:40.000\nAudio / Idle for 10 s\n"
+        );
+        let json = timeline.to_json();
+        assert!(json.starts_with(
+            "{\"duration\":40,\"chapters\":[{\"start\":0,\"end\":3,\"kind\":\"focus_change\",\"title\":\"Editor: main.rs\"},"
+        ));
+        assert!(json.contains("\"title\":\"Browser: Docs \\\"draft\\\"\""));
+        assert!(json.ends_with("\"kind\":\"audio_onset\",\"title\":\"Audio / Idle for 10 s\"}]}"));
+    }
+}
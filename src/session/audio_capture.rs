@@ -0,0 +1,311 @@
+use core_foundation::error::CFError;
+
+use crate::{
+    shareable_content::{
+        content_query::ApplicationQuery,
+        content_snapshot::ContentSnapshot,
+        content_watcher::{ContentProvider, ShareableContentProvider},
+    },
+    utils::error::create_sc_error,
+};
+
+use super::backend::{AudioCaptureBackend, AudioHandler, AudioTarget};
+
+/// Captures the audio of applications picked by bundle identifier, e.g. only Zoom's with
+/// `AudioCapture::for_applications(["us.zoom.xos"])`.
+///
+/// The applications are resolved to their running processes in the shareable content, and
+/// the stream's filter includes them on a display, which `ScreenCaptureKit` requires even
+/// for audio. Applications that aren't running yet are picked up by
+/// [`AudioCaptureSession::refresh`] once they are, and so are applications that restarted
+/// with a new process ID.
+#[derive(Debug, Clone)]
+pub struct AudioCapture<P = ShareableContentProvider> {
+    bundle_identifiers: Vec<String>,
+    display_id: Option<u32>,
+    sample_rate: u32,
+    channel_count: u8,
+    provider: P,
+}
+
+impl AudioCapture {
+    /// Creates a capture of the given applications' audio at 48 kHz in stereo, resolved
+    /// with [`ShareableContentProvider`].
+    pub fn for_applications<I>(bundle_identifiers: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            bundle_identifiers: bundle_identifiers.into_iter().map(Into::into).collect(),
+            display_id: None,
+            sample_rate: 48_000,
+            channel_count: 2,
+            provider: ShareableContentProvider::default(),
+        }
+    }
+}
+
+impl<P: ContentProvider> AudioCapture<P> {
+    /// Resolves the applications with `provider` instead, e.g. to test without
+    /// `ScreenCaptureKit`.
+    pub fn with_provider<Q: ContentProvider>(self, provider: Q) -> AudioCapture<Q> {
+        AudioCapture {
+            bundle_identifiers: self.bundle_identifiers,
+            display_id: self.display_id,
+            sample_rate: self.sample_rate,
+            channel_count: self.channel_count,
+            provider,
+        }
+    }
+
+    /// Builds the filter on this display while it's attached. Defaults to the first
+    /// display of the shareable content.
+    #[must_use]
+    pub const fn with_display(mut self, display_id: u32) -> Self {
+        self.display_id = Some(display_id);
+        self
+    }
+
+    /// Sets the sample rate, one of the 8, 16, 24 and 48 kHz `ScreenCaptureKit` supports.
+    #[must_use]
+    pub const fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Sets the channel count, 1 or 2.
+    #[must_use]
+    pub const fn with_channel_count(mut self, channel_count: u8) -> Self {
+        self.channel_count = channel_count;
+        self
+    }
+
+    pub fn bundle_identifiers(&self) -> &[String] {
+        &self.bundle_identifiers
+    }
+
+    /// Returns what to capture in `snapshot`: the processes of the applications running
+    /// in it, on the configured display or its first one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot has no displays.
+    pub fn resolve(&self, snapshot: &ContentSnapshot) -> Result<AudioTarget, CFError> {
+        let display = self
+            .display_id
+            .and_then(|display_id| snapshot.display(display_id))
+            .or_else(|| snapshot.displays.first())
+            .ok_or_else(|| create_sc_error("there is no display to capture audio on"))?;
+        let mut process_ids: Vec<i32> = self
+            .bundle_identifiers
+            .iter()
+            .flat_map(|bundle_identifier| {
+                ApplicationQuery::new()
+                    .bundle_identifier(bundle_identifier.as_str())
+                    .run(snapshot)
+            })
+            .map(|application| application.process_id)
+            .collect();
+        process_ids.sort_unstable();
+        process_ids.dedup();
+        Ok(AudioTarget {
+            display_id: display.display_id,
+            process_ids,
+            sample_rate: self.sample_rate,
+            channel_count: self.channel_count,
+        })
+    }
+
+    /// Starts capturing with `backend`, passing every audio frame to `handler`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content can't be fetched, has no displays, or the stream
+    /// fails to start.
+    pub fn start<B: AudioCaptureBackend>(
+        mut self,
+        mut backend: B,
+        handler: AudioHandler,
+    ) -> Result<AudioCaptureSession<B, P>, CFError> {
+        let snapshot = self.provider.snapshot()?;
+        let target = self.resolve(&snapshot)?;
+        let stream = backend.start_audio(&target, handler)?;
+        Ok(AudioCaptureSession {
+            capture: self,
+            backend,
+            target,
+            stream: Some(stream),
+        })
+    }
+}
+
+/// A running [`AudioCapture`].
+pub struct AudioCaptureSession<B: AudioCaptureBackend, P = ShareableContentProvider> {
+    capture: AudioCapture<P>,
+    backend: B,
+    target: AudioTarget,
+    stream: Option<B::Stream>,
+}
+
+impl<B: AudioCaptureBackend, P> std::fmt::Debug for AudioCaptureSession<B, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioCaptureSession")
+            .field("bundle_identifiers", &self.capture.bundle_identifiers)
+            .field("target", &self.target)
+            .field("is_running", &self.stream.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: AudioCaptureBackend, P: ContentProvider> AudioCaptureSession<B, P> {
+    /// Returns what the stream captures.
+    pub const fn target(&self) -> &AudioTarget {
+        &self.target
+    }
+
+    pub const fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Resolves the applications again with the capture's provider, see [`Self::update`].
+    /// Call it periodically, or use [`Self::update`] with the snapshots a
+    /// [`ContentWatcher`](crate::shareable_content::content_watcher::ContentWatcher)
+    /// reports.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content can't be fetched or the update fails.
+    pub fn refresh(&mut self) -> Result<bool, CFError> {
+        let snapshot = self.capture.provider.snapshot()?;
+        self.update(&snapshot)
+    }
+
+    /// Resolves the applications in `snapshot` and switches the stream's filter to them if
+    /// their processes or display changed, which happens when they start, quit or restart.
+    /// Returns `true` if the filter changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the capture was stopped, the snapshot has no displays, or the
+    /// stream rejects the new filter, in which case it keeps capturing the old target.
+    pub fn update(&mut self, snapshot: &ContentSnapshot) -> Result<bool, CFError> {
+        let Some(stream) = &mut self.stream else {
+            return Err(create_sc_error("the audio capture was stopped"));
+        };
+        let target = self.capture.resolve(snapshot)?;
+        if target == self.target {
+            return Ok(false);
+        }
+        self.backend.update_audio(stream, &target)?;
+        self.target = target;
+        Ok(true)
+    }
+
+    /// Stops the stream. Stopping a stopped capture does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream fails to stop.
+    pub fn stop(&mut self) -> Result<(), CFError> {
+        self.stream
+            .take()
+            .map_or(Ok(()), |stream| self.backend.stop_audio(stream))
+    }
+}
+
+#[cfg(test)]
+mod audio_capture_test {
+    use std::sync::{Arc, Mutex};
+
+    use core_foundation::error::CFError;
+
+    use crate::{
+        media::{audio_frame::AudioFrame, media_time::MediaTime},
+        session::synthetic::SyntheticBackend,
+        shareable_content::content_snapshot::{ApplicationInfo, ContentSnapshot, DisplayInfo},
+    };
+
+    use super::AudioCapture;
+
+    fn snapshot(applications: &[(i32, &str)]) -> ContentSnapshot {
+        ContentSnapshot {
+            displays: vec![
+                DisplayInfo {
+                    display_id: 1,
+                    ..DisplayInfo::default()
+                },
+                DisplayInfo {
+                    display_id: 2,
+                    ..DisplayInfo::default()
+                },
+            ],
+            windows: Vec::new(),
+            applications: applications
+                .iter()
+                .map(|&(process_id, bundle_identifier)| ApplicationInfo {
+                    process_id,
+                    application_name: bundle_identifier.to_string(),
+                    bundle_identifier: bundle_identifier.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_follows_restarts() {
+        let content = Arc::new(Mutex::new(snapshot(&[
+            (10, "us.zoom.xos"),
+            (11, "com.apple.Music"),
+        ])));
+        let provider = {
+            let content = Arc::clone(&content);
+            move || -> Result<ContentSnapshot, CFError> { Ok(content.lock().unwrap().clone()) }
+        };
+        let backend = SyntheticBackend::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let received = Arc::clone(&received);
+            Arc::new(move |frame: AudioFrame| received.lock().unwrap().push(frame.pts))
+        };
+        let mut session = AudioCapture::for_applications(["us.zoom.xos"])
+            .with_provider(provider)
+            .with_display(2)
+            .with_sample_rate(16_000)
+            .with_channel_count(1)
+            .start(backend.clone(), handler)
+            .unwrap();
+        let target = backend.audio_target().unwrap();
+        assert_eq!(
+            (target.display_id, target.process_ids.as_slice()),
+            (2, &[10][..])
+        );
+        assert_eq!((target.sample_rate, target.channel_count), (16_000, 1));
+        let frame = AudioFrame::new(16_000, 1, vec![0.0; 160], MediaTime::from_millis(5));
+        assert!(backend.emit_audio(frame.clone()));
+        assert_eq!(*received.lock().unwrap(), [MediaTime::from_millis(5)]);
+
+        assert!(!session.refresh().unwrap());
+        // Zoom quits, then comes back with a new process ID.
+        *content.lock().unwrap() = snapshot(&[(11, "com.apple.Music")]);
+        assert!(session.refresh().unwrap());
+        assert!(backend.audio_target().unwrap().process_ids.is_empty());
+        *content.lock().unwrap() = snapshot(&[(11, "com.apple.Music"), (12, "us.zoom.xos")]);
+        assert!(session.refresh().unwrap());
+        assert_eq!(backend.audio_target().unwrap().process_ids, [12]);
+        assert_eq!(session.target().process_ids, [12]);
+
+        // A missing display falls back to the first one, and no display is an error.
+        let mut single = snapshot(&[(12, "us.zoom.xos")]);
+        single.displays.remove(1);
+        assert!(session.update(&single).unwrap());
+        assert_eq!(session.target().display_id, 1);
+        assert!(session.update(&ContentSnapshot::default()).is_err());
+        assert_eq!(session.target().display_id, 1);
+
+        session.stop().unwrap();
+        assert!(!session.is_running());
+        assert!(!backend.emit_audio(frame));
+        assert!(session.refresh().is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use core_foundation::error::CFError;
+
+use crate::{
+    geometry::display_layout::DisplayGeometry,
+    media::{audio_frame::AudioFrame, video_frame::VideoFrame},
+};
+
+/// Receives the frames of one capture stream, on whatever thread the stream delivers them.
+pub type FrameHandler = Arc<dyn Fn(VideoFrame) + Send + Sync>;
+
+/// Receives the audio of a capture stream, on whatever thread the stream delivers it.
+pub type AudioHandler = Arc<dyn Fn(AudioFrame) + Send + Sync>;
+
+/// Starts and stops the per display capture streams of a session.
+///
+/// [`ScreenCaptureBackend`](super::screen_capture::ScreenCaptureBackend) captures with
+/// `SCStream`, while [`SyntheticBackend`](super::synthetic::SyntheticBackend) lets tests
+/// deliver frames by hand.
+pub trait CaptureBackend {
+    /// Keeps a running stream alive until it is passed back to [`Self::stop_display`].
+    type Stream;
+
+    /// Starts capturing `display` at `width` by `height` pixels, passing every frame to
+    /// `handler`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the display is not available or the stream fails to start.
+    fn start_display(
+        &mut self,
+        display: &DisplayGeometry,
+        width: u32,
+        height: u32,
+        handler: FrameHandler,
+    ) -> Result<Self::Stream, CFError>;
+
+    /// Stops a stream started by [`Self::start_display`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream fails to stop.
+    fn stop_display(&mut self, stream: Self::Stream) -> Result<(), CFError>;
+}
+
+/// The applications an audio stream captures, and the format it captures them in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTarget {
+    /// The display the stream's content filter is built on. Audio doesn't depend on it,
+    /// but `ScreenCaptureKit` only filters applications on a display.
+    pub display_id: u32,
+    /// The process IDs of the applications to capture, in ascending order. Empty while none
+    /// of them are running, which captures silence.
+    pub process_ids: Vec<i32>,
+    pub sample_rate: u32,
+    pub channel_count: u8,
+}
+
+/// Starts, retargets and stops the audio stream of an
+/// [`AudioCapture`](super::audio_capture::AudioCapture).
+pub trait AudioCaptureBackend {
+    /// Keeps a running stream alive until it is passed back to [`Self::stop_audio`].
+    type Stream;
+
+    /// Starts capturing the audio of `target`, passing every frame to `handler`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the display or applications are not available or the stream
+    /// fails to start.
+    fn start_audio(
+        &mut self,
+        target: &AudioTarget,
+        handler: AudioHandler,
+    ) -> Result<Self::Stream, CFError>;
+
+    /// Switches a running stream to the applications of `target` without restarting it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the display or applications are not available or the stream
+    /// rejects the new filter.
+    fn update_audio(
+        &mut self,
+        stream: &mut Self::Stream,
+        target: &AudioTarget,
+    ) -> Result<(), CFError>;
+
+    /// Stops a stream started by [`Self::start_audio`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream fails to stop.
+    fn stop_audio(&mut self, stream: Self::Stream) -> Result<(), CFError>;
+}
@@ -0,0 +1,253 @@
+use crate::{
+    geometry::{display_layout::DisplayLayout, rect::RectExt},
+    media::{
+        media_time::MediaTime,
+        video_frame::{PixelFormat, PixelRect, VideoFrame},
+    },
+};
+
+/// Draws a BGRA `frame` scaled to fill `rect` of a BGRA `canvas`, sampling the nearest
+/// source pixel. Parts of `rect` outside the canvas are skipped.
+///
+/// Returns `false`, leaving the canvas untouched, if either frame is not BGRA.
+pub fn draw_scaled(canvas: &mut VideoFrame, frame: &VideoFrame, rect: PixelRect) -> bool {
+    blend_scaled(canvas, frame, rect, u8::MAX)
+}
+
+/// Like [`draw_scaled`], but mixes the frame with the canvas, from fully transparent at an
+/// `opacity` of 0 to opaque at 255.
+#[allow(clippy::cast_possible_truncation)]
+pub fn blend_scaled(
+    canvas: &mut VideoFrame,
+    frame: &VideoFrame,
+    rect: PixelRect,
+    opacity: u8,
+) -> bool {
+    if canvas.pixel_format != PixelFormat::Bgra || frame.pixel_format != PixelFormat::Bgra {
+        return false;
+    }
+    if rect.is_empty() || frame.width == 0 || frame.height == 0 || opacity == 0 {
+        return true;
+    }
+    let (src_width, src_height) = (u64::from(frame.width), u64::from(frame.height));
+    let (dst_width, dst_height) = (u64::from(rect.width), u64::from(rect.height));
+    let x_end = rect.x.saturating_add(rect.width).min(canvas.width);
+    let y_end = rect.y.saturating_add(rect.height).min(canvas.height);
+    if rect.x >= x_end {
+        return true;
+    }
+    // Source columns for each destination column, sampled at pixel centers.
+    let columns: Vec<usize> = (rect.x..x_end)
+        .map(|x| ((2 * u64::from(x - rect.x) + 1) * src_width / (2 * dst_width)) as usize * 4)
+        .collect();
+    for y in rect.y..y_end {
+        let src_y = (2 * u64::from(y - rect.y) + 1) * src_height / (2 * dst_height);
+        let src_row = frame.planes[0].row(src_y as usize);
+        let dst_row = &mut canvas.planes[0].row_mut(y as usize)[rect.x as usize * 4..];
+        for (dst, &src) in dst_row.chunks_exact_mut(4).zip(&columns) {
+            blend_pixel(dst, &src_row[src..src + 4], opacity);
+        }
+    }
+    true
+}
+
+/// Fills `rect` of a BGRA `canvas` with a color at the given opacity. Returns `false` if the
+/// canvas is not BGRA.
+pub fn fill_rect(canvas: &mut VideoFrame, rect: PixelRect, bgra: [u8; 4], opacity: u8) -> bool {
+    if canvas.pixel_format != PixelFormat::Bgra {
+        return false;
+    }
+    let x_end = rect.x.saturating_add(rect.width).min(canvas.width);
+    let y_end = rect.y.saturating_add(rect.height).min(canvas.height);
+    if rect.x >= x_end || opacity == 0 {
+        return true;
+    }
+    for y in rect.y..y_end {
+        let row =
+            &mut canvas.planes[0].row_mut(y as usize)[rect.x as usize * 4..x_end as usize * 4];
+        for dst in row.chunks_exact_mut(4) {
+            blend_pixel(dst, &bgra, opacity);
+        }
+    }
+    true
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn blend_pixel(dst: &mut [u8], src: &[u8], opacity: u8) {
+    if opacity == u8::MAX {
+        dst.copy_from_slice(src);
+        return;
+    }
+    let (alpha, inverse) = (u32::from(opacity), u32::from(u8::MAX - opacity));
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = ((u32::from(src) * alpha + u32::from(*dst) * inverse + 127) / 255) as u8;
+    }
+}
+
+/// Composites the frames of several displays into one canvas arranged like the displays
+/// are in the global display space.
+///
+/// The canvas covers the bounding box of the layout at `scale` pixels per point, so a scale
+/// of 1 keeps every display at its size in points and 2 matches Retina displays. Each
+/// display's frame is scaled to its place on the canvas, and gaps between displays are
+/// filled with the background color. Only BGRA frames are drawn.
+#[derive(Debug, Clone)]
+pub struct DisplayCompositor {
+    width: u32,
+    height: u32,
+    placements: Vec<(u32, PixelRect)>,
+    background: [u8; 4],
+}
+
+impl DisplayCompositor {
+    /// # Panics
+    ///
+    /// Panics if `scale` is not positive.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(layout: &DisplayLayout, scale: f64) -> Self {
+        assert!(scale > 0.0, "the canvas scale must be positive");
+        let Some(bounds) = layout.bounds() else {
+            return Self {
+                width: 0,
+                height: 0,
+                placements: Vec::new(),
+                background: [0, 0, 0, 255],
+            };
+        };
+        // Rounding both edges keeps displays that share an edge adjacent on the canvas.
+        let to_canvas_x = |x: f64| ((x - bounds.min_x()) * scale).round().max(0.0) as u32;
+        let to_canvas_y = |y: f64| ((y - bounds.min_y()) * scale).round().max(0.0) as u32;
+        let placements = layout
+            .displays()
+            .iter()
+            .map(|display| {
+                let (x, y) = (
+                    to_canvas_x(display.frame.min_x()),
+                    to_canvas_y(display.frame.min_y()),
+                );
+                let rect = PixelRect::new(
+                    x,
+                    y,
+                    to_canvas_x(display.frame.max_x()) - x,
+                    to_canvas_y(display.frame.max_y()) - y,
+                );
+                (display.display_id, rect)
+            })
+            .collect();
+        Self {
+            width: to_canvas_x(bounds.max_x()),
+            height: to_canvas_y(bounds.max_y()),
+            placements,
+            background: [0, 0, 0, 255],
+        }
+    }
+
+    #[must_use]
+    pub const fn with_background(mut self, bgra: [u8; 4]) -> Self {
+        self.background = bgra;
+        self
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns where a display is drawn on the canvas.
+    pub fn placement(&self, display_id: u32) -> Option<PixelRect> {
+        self.placements
+            .iter()
+            .find(|(id, _)| *id == display_id)
+            .map(|(_, rect)| *rect)
+    }
+
+    /// Draws the latest frame of each display onto a new canvas. Displays without a frame,
+    /// or not in the layout, are left as background.
+    pub fn compose<'a>(
+        &self,
+        frames: impl IntoIterator<Item = (u32, &'a VideoFrame)>,
+        pts: MediaTime,
+    ) -> VideoFrame {
+        let mut canvas = VideoFrame::filled_bgra(self.width, self.height, self.background, pts);
+        for (display_id, frame) in frames {
+            if let Some(rect) = self.placement(display_id) {
+                draw_scaled(&mut canvas, frame, rect);
+            }
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod compositor_test {
+    use core_graphics::geometry::CGRect;
+
+    use crate::{
+        geometry::{
+            display_layout::{DisplayGeometry, DisplayLayout},
+            rect::RectExt,
+        },
+        media::{
+            media_time::MediaTime,
+            video_frame::{PixelRect, VideoFrame},
+        },
+    };
+
+    use super::{draw_scaled, DisplayCompositor};
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+
+    #[test]
+    fn test_draw_scaled() {
+        let mut canvas = VideoFrame::filled_bgra(8, 4, [0; 4], MediaTime::ZERO);
+        // A 2x2 checkerboard scaled up to 4x4 at the right edge, half of it off the canvas.
+        let mut frame = VideoFrame::filled_bgra(2, 2, RED, MediaTime::ZERO);
+        frame.planes[0].row_mut(0)[4..8].copy_from_slice(&BLUE);
+        frame.planes[0].row_mut(1)[..4].copy_from_slice(&BLUE);
+        assert!(draw_scaled(&mut canvas, &frame, PixelRect::new(6, 0, 4, 4)));
+        assert_eq!(canvas.bgra_pixel(5, 0), Some([0; 4]));
+        assert_eq!(canvas.bgra_pixel(6, 0), Some(RED));
+        assert_eq!(canvas.bgra_pixel(7, 1), Some(RED));
+        assert_eq!(canvas.bgra_pixel(6, 2), Some(BLUE));
+        assert_eq!(canvas.bgra_pixel(7, 3), Some(BLUE));
+
+        let nv12 = VideoFrame::new_nv12(2, 2, vec![0; 4], vec![0; 2], MediaTime::ZERO);
+        assert!(!draw_scaled(&mut canvas, &nv12, PixelRect::new(0, 0, 2, 2)));
+    }
+
+    #[test]
+    fn test_follows_layout() {
+        // A laptop with an external display to its right, aligned at the bottom.
+        let layout = DisplayLayout::new(vec![
+            DisplayGeometry::new(1, CGRect::from_edges(0.0, 0.0, 1440.0, 900.0), 2.0),
+            DisplayGeometry::new(2, CGRect::from_edges(1440.0, -180.0, 3360.0, 900.0), 1.0),
+        ]);
+        let compositor = DisplayCompositor::new(&layout, 0.25).with_background([9; 4]);
+        assert_eq!((compositor.width(), compositor.height()), (840, 270));
+        assert_eq!(
+            compositor.placement(1),
+            Some(PixelRect::new(0, 45, 360, 225))
+        );
+        assert_eq!(
+            compositor.placement(2),
+            Some(PixelRect::new(360, 0, 480, 270))
+        );
+
+        let laptop = VideoFrame::filled_bgra(2880, 1800, RED, MediaTime::ZERO);
+        let external = VideoFrame::filled_bgra(1920, 1080, BLUE, MediaTime::ZERO);
+        let canvas = compositor.compose([(1, &laptop), (2, &external)], MediaTime::from_millis(5));
+        assert_eq!(canvas.pts, MediaTime::from_millis(5));
+        assert_eq!(canvas.bgra_pixel(0, 0), Some([9; 4]));
+        assert_eq!(canvas.bgra_pixel(0, 45), Some(RED));
+        assert_eq!(canvas.bgra_pixel(359, 269), Some(RED));
+        assert_eq!(canvas.bgra_pixel(360, 0), Some(BLUE));
+        assert_eq!(canvas.bgra_pixel(839, 269), Some(BLUE));
+
+        let empty = DisplayCompositor::new(&DisplayLayout::default(), 1.0);
+        assert_eq!((empty.width(), empty.height()), (0, 0));
+    }
+}
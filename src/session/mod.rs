@@ -0,0 +1,10 @@
+//! Capture sessions spanning several streams, with a synthetic backend to run them without
+//! `ScreenCaptureKit`.
+pub mod audio_capture;
+pub mod backend;
+pub mod compositor;
+pub mod multi_display;
+pub mod picture_in_picture;
+pub mod sample_buffer;
+pub mod screen_capture;
+pub mod synthetic;
@@ -0,0 +1,551 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use core_foundation::error::CFError;
+
+use crate::{
+    geometry::display_layout::DisplayLayout,
+    media::{media_time::MediaTime, video_frame::VideoFrame},
+};
+
+use super::{
+    backend::{CaptureBackend, FrameHandler},
+    compositor::DisplayCompositor,
+};
+
+type Inbox = Arc<Mutex<Vec<(u32, VideoFrame)>>>;
+
+#[derive(Debug, Default)]
+struct DisplayFrames {
+    /// Frames not yet shown, in capture order.
+    pending: VecDeque<VideoFrame>,
+    /// The frame shown at the last composed tick.
+    current: Option<VideoFrame>,
+    /// The newest timestamp the display's stream reached, including frames without content.
+    latest: Option<MediaTime>,
+}
+
+/// Captures several displays at once, one stream each, and composites their frames into a
+/// single canvas arranged like the displays.
+///
+/// Frames of all streams are placed on one timeline starting at the first captured frame.
+/// The session outputs a canvas every frame duration, showing the latest frame each display
+/// captured up to that time. A canvas is composed once every display has delivered a frame
+/// at or after its time, or once another display is `max_latency` ahead, since
+/// `ScreenCaptureKit` stops delivering frames for displays whose content does not change.
+///
+/// Displays attached or detached while capturing are picked up with
+/// [`Self::update_layout`], e.g. from the changes a
+/// [`ContentWatcher`](crate::shareable_content::content_watcher::ContentWatcher) reports.
+pub struct MultiDisplaySession<B: CaptureBackend> {
+    backend: B,
+    layout: DisplayLayout,
+    selection: Option<Vec<u32>>,
+    scale: f64,
+    background: [u8; 4],
+    frame_duration: MediaTime,
+    max_latency: MediaTime,
+    compositor: DisplayCompositor,
+    is_running: bool,
+    streams: Vec<(u32, B::Stream)>,
+    frames: HashMap<u32, DisplayFrames>,
+    inbox: Inbox,
+    origin: Option<MediaTime>,
+    newest: Option<MediaTime>,
+    next_tick: i64,
+}
+
+impl<B: CaptureBackend> std::fmt::Debug for MultiDisplaySession<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiDisplaySession")
+            .field("layout", &self.layout)
+            .field("selection", &self.selection)
+            .field("compositor", &self.compositor)
+            .field("active_displays", &self.active_displays())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: CaptureBackend> MultiDisplaySession<B> {
+    /// Creates a session capturing every display of `layout` at 30 fps, one canvas pixel
+    /// per point.
+    pub fn new(backend: B, layout: DisplayLayout) -> Self {
+        let compositor = DisplayCompositor::new(&DisplayLayout::default(), 1.0);
+        let mut session = Self {
+            backend,
+            layout,
+            selection: None,
+            scale: 1.0,
+            background: [0, 0, 0, 255],
+            frame_duration: MediaTime::new(1, 30),
+            max_latency: MediaTime::from_millis(100),
+            compositor,
+            is_running: false,
+            streams: Vec::new(),
+            frames: HashMap::new(),
+            inbox: Arc::default(),
+            origin: None,
+            newest: None,
+            next_tick: 0,
+        };
+        session.update_compositor();
+        session
+    }
+
+    /// Captures only the given displays instead of all of them. Selected displays that are
+    /// not attached are captured once they are.
+    #[must_use]
+    pub fn with_displays(mut self, display_ids: Vec<u32>) -> Self {
+        self.selection = Some(display_ids);
+        self.update_compositor();
+        self
+    }
+
+    /// Sets the canvas pixels per point, e.g. `0.5` for a half size canvas.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is not positive.
+    #[must_use]
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        assert!(scale > 0.0, "the canvas scale must be positive");
+        self.scale = scale;
+        self.update_compositor();
+        self
+    }
+
+    #[must_use]
+    pub fn with_background(mut self, bgra: [u8; 4]) -> Self {
+        self.background = bgra;
+        self.update_compositor();
+        self
+    }
+
+    /// Sets the output frame rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is zero or exceeds `i32::MAX`.
+    #[must_use]
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        assert!(fps > 0, "the frame rate must be positive");
+        self.frame_duration =
+            MediaTime::new(1, i32::try_from(fps).expect("fps should fit a timescale"));
+        self
+    }
+
+    /// Sets how far one display may run ahead before canvases are composed without waiting
+    /// for the others.
+    #[must_use]
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency.into();
+        self
+    }
+
+    pub const fn layout(&self) -> &DisplayLayout {
+        &self.layout
+    }
+
+    pub const fn compositor(&self) -> &DisplayCompositor {
+        &self.compositor
+    }
+
+    pub const fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub const fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    /// Returns the IDs of the displays being captured, in layout order.
+    pub fn active_displays(&self) -> Vec<u32> {
+        self.streams
+            .iter()
+            .map(|(display_id, _)| *display_id)
+            .collect()
+    }
+
+    fn selected_layout(&self) -> DisplayLayout {
+        DisplayLayout::new(
+            self.layout
+                .displays()
+                .iter()
+                .filter(|display| {
+                    self.selection
+                        .as_ref()
+                        .map_or(true, |selection| selection.contains(&display.display_id))
+                })
+                .copied()
+                .collect(),
+        )
+    }
+
+    fn update_compositor(&mut self) {
+        self.compositor = DisplayCompositor::new(&self.selected_layout(), self.scale)
+            .with_background(self.background);
+    }
+
+    /// Starts a stream for every selected display.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error starting a stream, after stopping the streams already started.
+    pub fn start(&mut self) -> Result<(), CFError> {
+        if self.is_running {
+            return Ok(());
+        }
+        self.is_running = true;
+        if let Err(error) = self.sync_streams() {
+            // Stopping is best effort, the start error is the one worth reporting.
+            let _ = self.stop();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Stops all streams. Frames captured so far can still be composed with [`Self::finish`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error stopping a stream. All streams are stopped regardless.
+    pub fn stop(&mut self) -> Result<(), CFError> {
+        self.is_running = false;
+        self.sync_streams()
+    }
+
+    /// Replaces the display layout after displays were attached, detached or rearranged.
+    ///
+    /// Streams of detached displays are stopped and streams of newly attached selected
+    /// displays started. Canvases composed afterwards follow the new layout, so their size
+    /// may change.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error starting or stopping a stream. The other displays are updated
+    /// regardless, and a display that failed to start is left out until the next update.
+    pub fn update_layout(&mut self, layout: DisplayLayout) -> Result<(), CFError> {
+        self.layout = layout;
+        self.update_compositor();
+        self.sync_streams()
+    }
+
+    /// Starts and stops streams until exactly the selected displays of the layout are
+    /// captured while running, and none otherwise. The frames of detached displays are
+    /// dropped, while those of stopped streams are kept for [`Self::finish`].
+    fn sync_streams(&mut self) -> Result<(), CFError> {
+        let selected = self.selected_layout();
+        self.frames
+            .retain(|display_id, _| selected.display(*display_id).is_some());
+        let wanted = if self.is_running {
+            selected
+        } else {
+            DisplayLayout::default()
+        };
+        let mut result = Ok(());
+        let (kept, removed): (Vec<_>, Vec<_>) = mem::take(&mut self.streams)
+            .into_iter()
+            .partition(|(display_id, _)| wanted.display(*display_id).is_some());
+        for (_, stream) in removed {
+            if let Err(error) = self.backend.stop_display(stream) {
+                result = result.and(Err(error));
+            }
+        }
+        self.streams = kept;
+        for display in wanted.displays() {
+            let display_id = display.display_id;
+            if self.streams.iter().any(|(id, _)| *id == display_id) {
+                continue;
+            }
+            let Some(rect) = self.compositor.placement(display_id) else {
+                continue;
+            };
+            let inbox = Arc::clone(&self.inbox);
+            let handler: FrameHandler = Arc::new(move |frame| {
+                inbox
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push((display_id, frame));
+            });
+            match self.backend.start_display(
+                display,
+                rect.width.max(1),
+                rect.height.max(1),
+                handler,
+            ) {
+                Ok(stream) => {
+                    self.frames.entry(display_id).or_default();
+                    self.streams.push((display_id, stream));
+                }
+                Err(error) => result = result.and(Err(error)),
+            }
+        }
+        let order = |display_id: &u32| {
+            wanted
+                .displays()
+                .iter()
+                .position(|display| display.display_id == *display_id)
+        };
+        self.streams
+            .sort_by_key(|(display_id, _)| order(display_id));
+        result
+    }
+
+    fn receive(&mut self) {
+        let received = mem::take(&mut *self.inbox.lock().unwrap_or_else(PoisonError::into_inner));
+        for (display_id, frame) in received {
+            // Frames a stream delivered before it was stopped are dropped with it.
+            let Some(frames) = self.frames.get_mut(&display_id) else {
+                continue;
+            };
+            let pts = frame.pts;
+            if !pts.is_valid() {
+                continue;
+            }
+            self.origin.get_or_insert(pts);
+            frames.latest = Some(frames.latest.map_or(pts, |latest| latest.max(pts)));
+            self.newest = Some(self.newest.map_or(pts, |newest| newest.max(pts)));
+            if frame.has_content() {
+                frames.pending.push_back(frame);
+            }
+        }
+    }
+
+    fn tick_time(&self, origin: MediaTime, tick: i64) -> MediaTime {
+        origin
+            + MediaTime::new(
+                self.frame_duration.value * tick,
+                self.frame_duration.timescale,
+            )
+    }
+
+    fn is_ready(&self, time: MediaTime, newest: MediaTime) -> bool {
+        newest >= time
+            && (newest >= time + self.max_latency
+                || self
+                    .frames
+                    .values()
+                    .all(|frames| frames.latest.is_some_and(|latest| latest >= time)))
+    }
+
+    fn compose_ticks(&mut self, flush: bool) -> Vec<VideoFrame> {
+        self.receive();
+        let (Some(origin), Some(newest)) = (self.origin, self.newest) else {
+            return Vec::new();
+        };
+        let mut canvases = Vec::new();
+        loop {
+            let time = self.tick_time(origin, self.next_tick);
+            if newest < time || !(flush || self.is_ready(time, newest)) {
+                break;
+            }
+            for frames in self.frames.values_mut() {
+                while frames
+                    .pending
+                    .front()
+                    .is_some_and(|frame| frame.pts <= time)
+                {
+                    frames.current = frames.pending.pop_front();
+                }
+            }
+            let shown = self
+                .frames
+                .iter()
+                .filter_map(|(display_id, frames)| Some((*display_id, frames.current.as_ref()?)));
+            canvases.push(self.compositor.compose(shown, time - origin));
+            self.next_tick += 1;
+        }
+        canvases
+    }
+
+    /// Returns the canvases that can be composed from the frames captured so far, with
+    /// timestamps starting at zero.
+    pub fn poll(&mut self) -> Vec<VideoFrame> {
+        self.compose_ticks(false)
+    }
+
+    /// Returns the canvases up to the newest captured frame without waiting for slower
+    /// displays, e.g. after [`Self::stop`].
+    pub fn finish(&mut self) -> Vec<VideoFrame> {
+        self.compose_ticks(true)
+    }
+}
+
+impl<B: CaptureBackend> Drop for MultiDisplaySession<B> {
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, and the streams are released either way.
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod multi_display_test {
+    use std::time::Duration;
+
+    use core_graphics::geometry::CGRect;
+
+    use crate::{
+        geometry::{
+            display_layout::{DisplayGeometry, DisplayLayout},
+            rect::RectExt,
+        },
+        media::{media_time::MediaTime, video_frame::PixelRect},
+        output::sc_stream_frame_info::SCFrameStatus,
+        session::synthetic::SyntheticBackend,
+    };
+
+    use super::MultiDisplaySession;
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn main_display() -> DisplayGeometry {
+        DisplayGeometry::new(1, CGRect::from_edges(0.0, 0.0, 800.0, 600.0), 2.0)
+    }
+
+    fn side_display() -> DisplayGeometry {
+        DisplayGeometry::new(2, CGRect::from_edges(800.0, 0.0, 1600.0, 400.0), 1.0)
+    }
+
+    fn session(backend: &SyntheticBackend) -> MultiDisplaySession<SyntheticBackend> {
+        MultiDisplaySession::new(
+            backend.clone(),
+            DisplayLayout::new(vec![main_display(), side_display()]),
+        )
+        .with_scale(0.25)
+        .with_fps(10)
+    }
+
+    #[test]
+    fn test_composes_on_shared_timeline() {
+        let backend = SyntheticBackend::new();
+        let mut session = session(&backend);
+        assert!(backend.active_displays().is_empty());
+        session.start().unwrap();
+        assert_eq!(backend.active_displays(), vec![1, 2]);
+        assert_eq!(backend.stream_size(1), Some((200, 150)));
+        assert_eq!(backend.stream_size(2), Some((200, 100)));
+
+        assert!(backend.emit(1, RED, MediaTime::from_millis(1000)));
+        // The side display hasn't caught up yet.
+        assert!(session.poll().is_empty());
+        assert!(backend.emit(2, GREEN, MediaTime::from_millis(1020)));
+        assert!(backend.emit(1, BLUE, MediaTime::from_millis(1150)));
+        assert!(backend.emit(2, GREEN, MediaTime::from_millis(1210)));
+        let canvases = session.poll();
+        let times: Vec<_> = canvases.iter().map(|c| c.pts.as_millis()).collect();
+        assert_eq!(times, vec![0, 100]);
+
+        let first = &canvases[0];
+        assert_eq!((first.width, first.height), (400, 150));
+        assert_eq!(first.bgra_pixel(0, 0), Some(RED));
+        // The side display's first frame came 20 ms after the first tick.
+        assert_eq!(first.bgra_pixel(200, 0), Some(BLACK));
+        let second = &canvases[1];
+        assert_eq!(second.bgra_pixel(199, 149), Some(RED));
+        assert_eq!(second.bgra_pixel(200, 0), Some(GREEN));
+        assert_eq!(second.bgra_pixel(200, 100), Some(BLACK));
+
+        session.stop().unwrap();
+        assert!(backend.active_displays().is_empty());
+        let rest: Vec<_> = session.finish().iter().map(|c| c.pts.as_millis()).collect();
+        assert_eq!(rest, vec![200]);
+    }
+
+    #[test]
+    fn test_does_not_wait_for_idle_displays() {
+        let backend = SyntheticBackend::new();
+        let mut session = session(&backend).with_max_latency(Duration::from_millis(250));
+        session.start().unwrap();
+        backend.emit(1, RED, MediaTime::from_millis(0));
+        backend.emit(2, GREEN, MediaTime::from_millis(0));
+        assert_eq!(session.poll().len(), 1);
+        backend.emit(1, BLUE, MediaTime::from_millis(200));
+        assert!(session.poll().is_empty());
+        backend.emit(1, BLUE, MediaTime::from_millis(350));
+        let canvases = session.poll();
+        assert_eq!(canvases.len(), 1);
+        assert_eq!(canvases[0].bgra_pixel(0, 0), Some(RED));
+        assert_eq!(canvases[0].bgra_pixel(200, 0), Some(GREEN));
+        // An idle frame moves the side display's clock without new pixels.
+        let idle = backend.stream_size(2).map(|(width, height)| {
+            crate::media::video_frame::VideoFrame::filled_bgra(
+                width,
+                height,
+                BLACK,
+                MediaTime::from_millis(350),
+            )
+            .with_status(SCFrameStatus::Idle)
+        });
+        backend.emit_frame(2, idle.unwrap());
+        let canvases = session.poll();
+        assert_eq!(canvases.len(), 2);
+        assert_eq!(canvases[1].bgra_pixel(0, 0), Some(BLUE));
+        assert_eq!(canvases[1].bgra_pixel(200, 0), Some(GREEN));
+    }
+
+    #[test]
+    fn test_displays_attached_and_detached() {
+        let backend = SyntheticBackend::new();
+        let mut session =
+            MultiDisplaySession::new(backend.clone(), DisplayLayout::new(vec![main_display()]))
+                .with_displays(vec![1, 2])
+                .with_scale(0.25);
+        session.start().unwrap();
+        assert_eq!(backend.active_displays(), vec![1]);
+        assert_eq!(session.compositor().width(), 200);
+
+        session
+            .update_layout(DisplayLayout::new(vec![side_display(), main_display()]))
+            .unwrap();
+        assert_eq!(backend.active_displays(), vec![1, 2]);
+        assert_eq!(session.active_displays(), vec![2, 1]);
+        assert_eq!(
+            session.compositor().placement(2),
+            Some(PixelRect::new(200, 0, 200, 100))
+        );
+
+        session
+            .update_layout(DisplayLayout::new(vec![side_display()]))
+            .unwrap();
+        assert_eq!(backend.active_displays(), vec![2]);
+        assert!(!backend.emit(1, RED, MediaTime::ZERO));
+        assert!(backend.emit(2, GREEN, MediaTime::ZERO));
+        let canvases = session.poll();
+        assert_eq!((canvases[0].width, canvases[0].height), (200, 100));
+        assert_eq!(canvases[0].bgra_pixel(0, 0), Some(GREEN));
+
+        // Displays outside the selection are never captured.
+        let third = DisplayGeometry::new(3, CGRect::from_edges(-800.0, 0.0, 0.0, 600.0), 1.0);
+        session
+            .update_layout(DisplayLayout::new(vec![third, side_display()]))
+            .unwrap();
+        assert_eq!(backend.active_displays(), vec![2]);
+    }
+
+    #[test]
+    fn test_start_failure() {
+        let backend = SyntheticBackend::new();
+        backend.fail_display(2);
+        let mut session = session(&backend);
+        assert!(session.start().is_err());
+        assert!(!session.is_running());
+        assert!(backend.active_displays().is_empty());
+
+        let mut session =
+            MultiDisplaySession::new(backend.clone(), DisplayLayout::new(vec![main_display()]));
+        session.start().unwrap();
+        assert!(session
+            .update_layout(DisplayLayout::new(vec![main_display(), side_display()]))
+            .is_err());
+        assert_eq!(backend.active_displays(), vec![1]);
+        drop(session);
+        assert!(backend.active_displays().is_empty());
+    }
+}
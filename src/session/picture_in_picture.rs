@@ -0,0 +1,450 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::media::{
+    media_time::MediaTime,
+    video_frame::{PixelRect, VideoFrame},
+};
+
+use super::{
+    backend::FrameHandler,
+    compositor::{blend_scaled, fill_rect},
+};
+
+type Inbox = Arc<Mutex<Vec<(u32, VideoFrame)>>>;
+
+/// A corner of the canvas to pin an overlay to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Returns a `width` by `height` rectangle `margin` pixels away from this corner of a
+    /// `canvas_width` by `canvas_height` canvas.
+    pub const fn place(
+        self,
+        canvas_width: u32,
+        canvas_height: u32,
+        width: u32,
+        height: u32,
+        margin: u32,
+    ) -> PixelRect {
+        let right = canvas_width.saturating_sub(width.saturating_add(margin));
+        let bottom = canvas_height.saturating_sub(height.saturating_add(margin));
+        let (x, y) = match self {
+            Self::TopLeft => (margin, margin),
+            Self::TopRight => (right, margin),
+            Self::BottomLeft => (margin, bottom),
+            Self::BottomRight => (right, bottom),
+        };
+        PixelRect::new(x, y, width, height)
+    }
+}
+
+/// A solid frame drawn around the outside of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Border {
+    pub width: u32,
+    pub bgra: [u8; 4],
+}
+
+/// Where and how the frames of one source are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipLayer {
+    pub source_id: u32,
+    /// The canvas pixels the source's frames are scaled to fill.
+    pub rect: PixelRect,
+    /// Layers with a higher z-index are drawn on top. Equal ones are drawn in the order they
+    /// were added.
+    pub z_index: i32,
+    /// From 0 for invisible to 255 for opaque.
+    pub opacity: u8,
+    pub border: Option<Border>,
+}
+
+impl PipLayer {
+    pub const fn new(source_id: u32, rect: PixelRect) -> Self {
+        Self {
+            source_id,
+            rect,
+            z_index: 0,
+            opacity: u8::MAX,
+            border: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_z_index(mut self, z_index: i32) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_border(mut self, width: u32, bgra: [u8; 4]) -> Self {
+        self.border = Some(Border { width, bgra });
+        self
+    }
+
+    fn draw_border(&self, canvas: &mut VideoFrame) {
+        let Some(border) = self.border else {
+            return;
+        };
+        // Clipped to the canvas, so placements near `u32::MAX` don't overflow.
+        let rect = self.rect;
+        let right = rect.x.saturating_add(rect.width).min(canvas.width);
+        let bottom = rect.y.saturating_add(rect.height).min(canvas.height);
+        let outer_x = rect.x.saturating_sub(border.width);
+        let outer_y = rect.y.saturating_sub(border.width);
+        let outer_width = right.saturating_add(border.width).saturating_sub(outer_x);
+        let height = bottom.saturating_sub(rect.y);
+        let sides = [
+            PixelRect::new(outer_x, outer_y, outer_width, rect.y - outer_y),
+            PixelRect::new(outer_x, bottom, outer_width, border.width),
+            PixelRect::new(outer_x, rect.y, rect.x - outer_x, height),
+            PixelRect::new(right, rect.y, border.width, height),
+        ];
+        for side in sides {
+            fill_rect(canvas, side, border.bgra, self.opacity);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SourceFrames {
+    pending: VecDeque<VideoFrame>,
+    current: Option<VideoFrame>,
+}
+
+/// Overlays the frames of several sources on one canvas, e.g. a window capture in the
+/// corner of a full display capture.
+///
+/// Frames are pushed per source as they are captured, and [`Self::poll`] outputs a canvas
+/// for every tick of a constant frame rate, showing the latest frame each source captured up
+/// to that time. Sources that go idle keep showing their last frame. Only BGRA frames are
+/// drawn.
+#[derive(Debug)]
+pub struct PipCompositor {
+    width: u32,
+    height: u32,
+    background: [u8; 4],
+    layers: Vec<PipLayer>,
+    sources: HashMap<u32, SourceFrames>,
+    inbox: Inbox,
+    frame_duration: MediaTime,
+    origin: Option<MediaTime>,
+    next_tick: i64,
+}
+
+impl PipCompositor {
+    /// Creates a black `width` by `height` canvas without layers, composed at 30 fps.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            background: [0, 0, 0, 255],
+            layers: Vec::new(),
+            sources: HashMap::new(),
+            inbox: Arc::default(),
+            frame_duration: MediaTime::new(1, 30),
+            origin: None,
+            next_tick: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_background(mut self, bgra: [u8; 4]) -> Self {
+        self.background = bgra;
+        self
+    }
+
+    /// Sets the output frame rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is zero or exceeds `i32::MAX`.
+    #[must_use]
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        assert!(fps > 0, "the frame rate must be positive");
+        self.frame_duration =
+            MediaTime::new(1, i32::try_from(fps).expect("fps should fit a timescale"));
+        self
+    }
+
+    #[must_use]
+    pub fn with_layer(mut self, layer: PipLayer) -> Self {
+        self.set_layer(layer);
+        self
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the layers in the order they were added.
+    pub fn layers(&self) -> &[PipLayer] {
+        &self.layers
+    }
+
+    /// Adds a layer, or replaces the layer of the same source in place.
+    pub fn set_layer(&mut self, layer: PipLayer) {
+        match self
+            .layers
+            .iter_mut()
+            .find(|existing| existing.source_id == layer.source_id)
+        {
+            Some(existing) => *existing = layer,
+            None => self.layers.push(layer),
+        }
+    }
+
+    /// Removes the layer of a source along with its frames.
+    pub fn remove_layer(&mut self, source_id: u32) -> Option<PipLayer> {
+        self.sources.remove(&source_id);
+        let index = self
+            .layers
+            .iter()
+            .position(|layer| layer.source_id == source_id)?;
+        Some(self.layers.remove(index))
+    }
+
+    /// Adds a captured frame of a source.
+    pub fn push(&mut self, source_id: u32, frame: VideoFrame) {
+        let pts = frame.pts;
+        if !pts.is_valid() || !frame.has_content() {
+            return;
+        }
+        self.origin.get_or_insert(pts);
+        self.sources
+            .entry(source_id)
+            .or_default()
+            .pending
+            .push_back(frame);
+    }
+
+    /// Returns a handler that queues the frames of a source for the next [`Self::poll`], for
+    /// passing to a [`CaptureBackend`](super::backend::CaptureBackend) or calling from a
+    /// stream's output handler.
+    pub fn frame_handler(&self, source_id: u32) -> FrameHandler {
+        let inbox = Arc::clone(&self.inbox);
+        Arc::new(move |frame| {
+            inbox
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((source_id, frame));
+        })
+    }
+
+    /// Returns a canvas for every tick up to `until`, with timestamps starting at zero at the
+    /// first frame pushed.
+    pub fn poll(&mut self, until: MediaTime) -> Vec<VideoFrame> {
+        let received = mem::take(&mut *self.inbox.lock().unwrap_or_else(PoisonError::into_inner));
+        for (source_id, frame) in received {
+            self.push(source_id, frame);
+        }
+        let Some(origin) = self.origin else {
+            return Vec::new();
+        };
+        let mut canvases = Vec::new();
+        loop {
+            let time = origin
+                + MediaTime::new(
+                    self.frame_duration.value * self.next_tick,
+                    self.frame_duration.timescale,
+                );
+            if time > until {
+                break;
+            }
+            canvases.push(self.compose(time, time - origin));
+            self.next_tick += 1;
+        }
+        canvases
+    }
+
+    fn compose(&mut self, time: MediaTime, pts: MediaTime) -> VideoFrame {
+        for source in self.sources.values_mut() {
+            while source
+                .pending
+                .front()
+                .is_some_and(|frame| frame.pts <= time)
+            {
+                source.current = source.pending.pop_front();
+            }
+        }
+        let mut canvas = VideoFrame::filled_bgra(self.width, self.height, self.background, pts);
+        let mut layers: Vec<_> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z_index);
+        for layer in layers {
+            let Some(frame) = self
+                .sources
+                .get(&layer.source_id)
+                .and_then(|source| source.current.as_ref())
+            else {
+                continue;
+            };
+            blend_scaled(&mut canvas, frame, layer.rect, layer.opacity);
+            layer.draw_border(&mut canvas);
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod picture_in_picture_test {
+    use core_graphics::geometry::CGRect;
+
+    use crate::{
+        geometry::{display_layout::DisplayGeometry, rect::RectExt},
+        media::{
+            media_time::MediaTime,
+            video_frame::{PixelRect, VideoFrame},
+        },
+        output::sc_stream_frame_info::SCFrameStatus,
+        session::{backend::CaptureBackend, synthetic::SyntheticBackend},
+    };
+
+    use super::{Corner, PipCompositor, PipLayer};
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+    const WHITE: [u8; 4] = [255; 4];
+
+    fn frame(bgra: [u8; 4], millis: i64) -> VideoFrame {
+        VideoFrame::filled_bgra(16, 9, bgra, MediaTime::from_millis(millis))
+    }
+
+    #[test]
+    fn test_corner_placement() {
+        assert_eq!(
+            Corner::TopLeft.place(1920, 1080, 480, 270, 20),
+            PixelRect::new(20, 20, 480, 270)
+        );
+        assert_eq!(
+            Corner::BottomRight.place(1920, 1080, 480, 270, 20),
+            PixelRect::new(1420, 790, 480, 270)
+        );
+        assert_eq!(
+            Corner::TopRight.place(100, 100, 200, 50, 10),
+            PixelRect::new(0, 10, 200, 50)
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        let overlay = Corner::BottomRight.place(160, 90, 40, 30, 10);
+        let mut compositor = PipCompositor::new(160, 90)
+            .with_fps(10)
+            .with_layer(
+                PipLayer::new(2, overlay)
+                    .with_z_index(1)
+                    .with_border(2, WHITE),
+            )
+            .with_layer(PipLayer::new(1, PixelRect::new(0, 0, 160, 90)))
+            .with_layer(PipLayer::new(3, overlay).with_z_index(1).with_opacity(128));
+        compositor.push(1, frame(RED, 0));
+        compositor.push(2, frame(GREEN, 0));
+        compositor.push(3, frame(BLUE, 0));
+        let canvases = compositor.poll(MediaTime::ZERO);
+        assert_eq!(canvases.len(), 1);
+        let canvas = &canvases[0];
+        assert_eq!((canvas.width, canvas.height), (160, 90));
+        assert_eq!(canvas.bgra_pixel(0, 0), Some(RED));
+        // The half transparent blue layer is drawn over the green one it ties with.
+        assert_eq!(canvas.bgra_pixel(110, 50), Some([128, 127, 0, 255]));
+        assert_eq!(canvas.bgra_pixel(149, 79), Some([128, 127, 0, 255]));
+        assert_eq!(canvas.bgra_pixel(108, 48), Some(WHITE));
+        assert_eq!(canvas.bgra_pixel(151, 81), Some(WHITE));
+        assert_eq!(canvas.bgra_pixel(152, 82), Some(RED));
+        assert_eq!(canvas.bgra_pixel(107, 50), Some(RED));
+
+        compositor.set_layer(PipLayer::new(1, PixelRect::new(0, 0, 160, 90)).with_z_index(2));
+        assert_eq!(compositor.layers().len(), 3);
+        let canvas = &compositor.poll(MediaTime::from_millis(100))[0];
+        assert_eq!(canvas.bgra_pixel(110, 50), Some(RED));
+        assert_eq!(canvas.bgra_pixel(108, 48), Some(RED));
+
+        assert!(compositor.remove_layer(1).is_some());
+        assert!(compositor.remove_layer(1).is_none());
+        let canvas = &compositor.poll(MediaTime::from_millis(200))[0];
+        assert_eq!(canvas.bgra_pixel(0, 0), Some([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_placement_beyond_canvas() {
+        let mut compositor = PipCompositor::new(20, 10)
+            .with_fps(10)
+            .with_layer(PipLayer::new(1, PixelRect::new(0, 0, 20, 10)))
+            .with_layer(
+                PipLayer::new(2, PixelRect::new(15, 4, u32::MAX, u32::MAX))
+                    .with_z_index(1)
+                    .with_border(3, WHITE),
+            )
+            .with_layer(
+                PipLayer::new(3, PixelRect::new(u32::MAX - 1, 2, 10, 4))
+                    .with_z_index(1)
+                    .with_border(2, WHITE),
+            );
+        compositor.push(1, frame(RED, 0));
+        compositor.push(2, frame(GREEN, 0));
+        compositor.push(3, frame(BLUE, 0));
+        let canvas = &compositor.poll(MediaTime::ZERO)[0];
+        assert_eq!(canvas.bgra_pixel(16, 6), Some(GREEN));
+        assert_eq!(canvas.bgra_pixel(19, 9), Some(GREEN));
+        assert_eq!(canvas.bgra_pixel(13, 5), Some(WHITE));
+        assert_eq!(canvas.bgra_pixel(19, 1), Some(WHITE));
+        assert_eq!(canvas.bgra_pixel(11, 8), Some(RED));
+    }
+
+    #[test]
+    fn test_holds_idle_sources() {
+        let mut compositor = PipCompositor::new(20, 10)
+            .with_fps(10)
+            .with_layer(PipLayer::new(1, PixelRect::new(0, 0, 10, 10)))
+            .with_layer(PipLayer::new(2, PixelRect::new(10, 0, 10, 10)));
+        compositor.push(1, frame(RED, 1000));
+        compositor.push(2, frame(GREEN, 1000));
+        compositor.push(1, frame(BLUE, 1150));
+        compositor.push(2, frame(BLUE, 1300).with_status(SCFrameStatus::Idle));
+        compositor.push(1, frame(RED, 1300));
+        let canvases = compositor.poll(MediaTime::from_millis(1350));
+        let times: Vec<_> = canvases.iter().map(|c| c.pts.as_millis()).collect();
+        assert_eq!(times, vec![0, 100, 200, 300]);
+        let left: Vec<_> = canvases.iter().map(|c| c.bgra_pixel(0, 0)).collect();
+        assert_eq!(left, vec![Some(RED), Some(RED), Some(BLUE), Some(RED)]);
+        assert!(canvases.iter().all(|c| c.bgra_pixel(10, 0) == Some(GREEN)));
+        assert!(compositor.poll(MediaTime::from_millis(1350)).is_empty());
+    }
+
+    #[test]
+    fn test_frame_handler() {
+        let mut backend = SyntheticBackend::new();
+        let display = DisplayGeometry::new(1, CGRect::from_edges(0.0, 0.0, 64.0, 36.0), 1.0);
+        let mut compositor =
+            PipCompositor::new(64, 36).with_layer(PipLayer::new(1, PixelRect::new(0, 0, 64, 36)));
+        let stream = backend
+            .start_display(&display, 64, 36, compositor.frame_handler(1))
+            .unwrap();
+        backend.emit(1, GREEN, MediaTime::from_millis(500));
+        let canvases = compositor.poll(MediaTime::from_millis(500));
+        assert_eq!(canvases.len(), 1);
+        assert_eq!(canvases[0].bgra_pixel(63, 35), Some(GREEN));
+        backend.stop_display(stream).unwrap();
+    }
+}
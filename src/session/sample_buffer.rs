@@ -0,0 +1,190 @@
+//! Copies the pixels and samples `SCStream` delivers out of their `CMSampleBuffer`.
+//!
+//! [`video_frame`] and [`audio_frame`] are the default
+//! [`SampleBufferConverter`](super::screen_capture::SampleBufferConverter) and
+//! [`AudioBufferConverter`](super::screen_capture::AudioBufferConverter).
+use std::slice;
+
+use core_foundation::{
+    array::{CFArray, CFArrayRef},
+    base::{Boolean, CFTypeRef, TCFType},
+};
+use core_media_rs::{cm_sample_buffer::CMSampleBuffer, cm_time::CMTime};
+
+use crate::{
+    media::{
+        audio_frame::AudioFrame,
+        media_time::MediaTime,
+        video_frame::{FrameInfo, PixelFormat, VideoFrame, VideoPlane},
+    },
+    output::sc_stream_frame_info::SCStreamFrameInfo,
+};
+
+/// `kCVPixelBufferLock_ReadOnly`.
+const LOCK_READ_ONLY: u64 = 1;
+/// `kAudioFormatLinearPCM`.
+const LINEAR_PCM: u32 = u32::from_be_bytes(*b"lpcm");
+/// `kAudioFormatFlagIsFloat`.
+const FLAG_IS_FLOAT: u32 = 1;
+
+#[repr(C)]
+struct AudioStreamBasicDescription {
+    sample_rate: f64,
+    format_id: u32,
+    format_flags: u32,
+    bytes_per_packet: u32,
+    frames_per_packet: u32,
+    bytes_per_frame: u32,
+    channels_per_frame: u32,
+    bits_per_channel: u32,
+    reserved: u32,
+}
+
+extern "C" {
+    fn CMSampleBufferGetImageBuffer(sample_buffer: CFTypeRef) -> CFTypeRef;
+    fn CMSampleBufferGetPresentationTimeStamp(sample_buffer: CFTypeRef) -> CMTime;
+    fn CMSampleBufferGetSampleAttachmentsArray(
+        sample_buffer: CFTypeRef,
+        create_if_necessary: Boolean,
+    ) -> CFArrayRef;
+    fn CMSampleBufferGetFormatDescription(sample_buffer: CFTypeRef) -> CFTypeRef;
+    fn CMAudioFormatDescriptionGetStreamBasicDescription(
+        description: CFTypeRef,
+    ) -> *const AudioStreamBasicDescription;
+    fn CVPixelBufferLockBaseAddress(pixel_buffer: CFTypeRef, flags: u64) -> i32;
+    fn CVPixelBufferUnlockBaseAddress(pixel_buffer: CFTypeRef, flags: u64) -> i32;
+    fn CVPixelBufferGetPixelFormatType(pixel_buffer: CFTypeRef) -> u32;
+    fn CVPixelBufferGetWidth(pixel_buffer: CFTypeRef) -> usize;
+    fn CVPixelBufferGetHeight(pixel_buffer: CFTypeRef) -> usize;
+    fn CVPixelBufferGetBaseAddress(pixel_buffer: CFTypeRef) -> *const u8;
+    fn CVPixelBufferGetBytesPerRow(pixel_buffer: CFTypeRef) -> usize;
+    fn CVPixelBufferGetBaseAddressOfPlane(pixel_buffer: CFTypeRef, plane: usize) -> *const u8;
+    fn CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer: CFTypeRef, plane: usize) -> usize;
+    fn CVPixelBufferGetHeightOfPlane(pixel_buffer: CFTypeRef, plane: usize) -> usize;
+}
+
+/// Returns the `SCStreamFrameInfo` attachments of a screen sample buffer.
+pub fn frame_info(sample_buffer: &CMSampleBuffer) -> Option<SCStreamFrameInfo> {
+    unsafe {
+        let attachments = CMSampleBufferGetSampleAttachmentsArray(sample_buffer.as_CFTypeRef(), 0);
+        if attachments.is_null() {
+            return None;
+        }
+        let attachments = CFArray::<SCStreamFrameInfo>::wrap_under_get_rule(attachments);
+        let info = attachments.get(0).map(|info| info.clone());
+        info
+    }
+}
+
+/// Copies a BGRA or NV12 screen sample buffer into a [`VideoFrame`], keeping the row
+/// padding of the pixel buffer, with its [`FrameInfo`] read from the sample's attachments.
+///
+/// Returns `None` for sample buffers without an image, like those of `Idle` frames, and for
+/// other pixel formats.
+pub fn video_frame(sample_buffer: &CMSampleBuffer) -> Option<VideoFrame> {
+    let pixel_buffer = unsafe { CMSampleBufferGetImageBuffer(sample_buffer.as_CFTypeRef()) };
+    if pixel_buffer.is_null() {
+        return None;
+    }
+    let pixel_format =
+        PixelFormat::from_four_char_code(unsafe { CVPixelBufferGetPixelFormatType(pixel_buffer) })?;
+    let width = u32::try_from(unsafe { CVPixelBufferGetWidth(pixel_buffer) }).ok()?;
+    let height = u32::try_from(unsafe { CVPixelBufferGetHeight(pixel_buffer) }).ok()?;
+    if unsafe { CVPixelBufferLockBaseAddress(pixel_buffer, LOCK_READ_ONLY) } != 0 {
+        return None;
+    }
+    let planes: Option<Vec<_>> = (0..pixel_format.plane_count())
+        .map(|plane| unsafe { copy_plane(pixel_buffer, pixel_format, plane) })
+        .collect();
+    unsafe { CVPixelBufferUnlockBaseAddress(pixel_buffer, LOCK_READ_ONLY) };
+    let pts = unsafe { CMSampleBufferGetPresentationTimeStamp(sample_buffer.as_CFTypeRef()) };
+    let info = frame_info(sample_buffer).map_or_else(FrameInfo::default, |info| {
+        FrameInfo::from_frame_info(&info, width, height)
+    });
+    Some(VideoFrame {
+        width,
+        height,
+        pixel_format,
+        planes: planes?,
+        pts: pts.into(),
+        info,
+    })
+}
+
+/// Copies one plane of a locked pixel buffer, rows and padding alike.
+unsafe fn copy_plane(
+    pixel_buffer: CFTypeRef,
+    pixel_format: PixelFormat,
+    plane: usize,
+) -> Option<VideoPlane> {
+    let (base, bytes_per_row, rows) = if pixel_format.plane_count() == 1 {
+        (
+            CVPixelBufferGetBaseAddress(pixel_buffer),
+            CVPixelBufferGetBytesPerRow(pixel_buffer),
+            CVPixelBufferGetHeight(pixel_buffer),
+        )
+    } else {
+        (
+            CVPixelBufferGetBaseAddressOfPlane(pixel_buffer, plane),
+            CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer, plane),
+            CVPixelBufferGetHeightOfPlane(pixel_buffer, plane),
+        )
+    };
+    if base.is_null() {
+        return None;
+    }
+    let data = slice::from_raw_parts(base, bytes_per_row.checked_mul(rows)?);
+    Some(VideoPlane::new(data.to_vec(), bytes_per_row))
+}
+
+/// Copies an audio sample buffer of 32 bit float PCM, interleaved or with one buffer per
+/// channel as `SCStream` delivers it, into an [`AudioFrame`].
+///
+/// Returns `None` for sample buffers without audio and for other sample formats.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn audio_frame(sample_buffer: &CMSampleBuffer) -> Option<AudioFrame> {
+    let description = unsafe {
+        let format = CMSampleBufferGetFormatDescription(sample_buffer.as_CFTypeRef());
+        if format.is_null() {
+            return None;
+        }
+        CMAudioFormatDescriptionGetStreamBasicDescription(format).as_ref()?
+    };
+    if description.format_id != LINEAR_PCM
+        || description.format_flags & FLAG_IS_FLOAT == 0
+        || description.bits_per_channel != 32
+    {
+        return None;
+    }
+    let sample_rate = description.sample_rate.round() as u32;
+    let pts: MediaTime =
+        unsafe { CMSampleBufferGetPresentationTimeStamp(sample_buffer.as_CFTypeRef()) }.into();
+    let buffers = sample_buffer.get_audio_buffer_list().ok()?;
+    let planes: Vec<Vec<f32>> = (0..buffers.num_buffers())
+        .filter_map(|index| buffers.get(index))
+        .map(|buffer| samples(buffer.data()))
+        .collect();
+    match planes.as_slice() {
+        [] => None,
+        [interleaved] => Some(AudioFrame::new(
+            sample_rate,
+            u16::try_from(description.channels_per_frame).ok()?,
+            interleaved.clone(),
+            pts,
+        )),
+        [first, rest @ ..] => {
+            if rest.iter().any(|plane| plane.len() != first.len()) {
+                return None;
+            }
+            let planes: Vec<_> = planes.iter().map(Vec::as_slice).collect();
+            Some(AudioFrame::from_planar(sample_rate, &planes, pts))
+        }
+    }
+}
+
+/// Reads native endian 32 bit floats.
+fn samples(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
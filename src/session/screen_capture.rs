@@ -0,0 +1,208 @@
+use core_foundation::error::CFError;
+use core_media_rs::{cm_sample_buffer::CMSampleBuffer, cm_time::CMTime};
+
+use crate::{
+    geometry::display_layout::DisplayGeometry,
+    media::{audio_frame::AudioFrame, video_frame::VideoFrame},
+    shareable_content::{
+        sc_running_application::SCRunningApplication, sc_shareable_content::SCShareableContent,
+    },
+    stream::{
+        sc_content_filter::SCContentFilter, sc_stream::SCStream,
+        sc_stream_configuration::SCStreamConfiguration,
+        sc_stream_output_trait::SCStreamOutputTrait, sc_stream_output_type::SCStreamOutputType,
+    },
+    utils::error::create_sc_error,
+};
+
+use super::{
+    backend::{AudioCaptureBackend, AudioHandler, AudioTarget, CaptureBackend, FrameHandler},
+    sample_buffer,
+};
+
+/// Copies the pixels of a screen sample buffer into a [`VideoFrame`], or returns `None` for
+/// sample buffers without an image, like those of `Idle` frames.
+///
+/// [`sample_buffer::video_frame`] converts BGRA and NV12 sample buffers.
+pub type SampleBufferConverter = fn(&CMSampleBuffer) -> Option<VideoFrame>;
+
+/// Copies the samples of an audio sample buffer into an [`AudioFrame`], or returns `None`
+/// for sample buffers without audio.
+///
+/// [`sample_buffer::audio_frame`] converts float PCM sample buffers.
+pub type AudioBufferConverter = fn(&CMSampleBuffer) -> Option<AudioFrame>;
+
+/// Captures every display with its own `SCStream`, excluding no windows.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenCaptureBackend {
+    convert: SampleBufferConverter,
+}
+
+impl ScreenCaptureBackend {
+    pub const fn new(convert: SampleBufferConverter) -> Self {
+        Self { convert }
+    }
+}
+
+impl Default for ScreenCaptureBackend {
+    fn default() -> Self {
+        Self::new(sample_buffer::video_frame)
+    }
+}
+
+struct FrameOutput {
+    convert: SampleBufferConverter,
+    handler: FrameHandler,
+}
+
+impl SCStreamOutputTrait for FrameOutput {
+    fn did_output_sample_buffer(&self, sample_buffer: CMSampleBuffer, of_type: SCStreamOutputType) {
+        if of_type != SCStreamOutputType::Screen {
+            return;
+        }
+        if let Some(frame) = (self.convert)(&sample_buffer) {
+            (self.handler)(frame);
+        }
+    }
+}
+
+impl CaptureBackend for ScreenCaptureBackend {
+    type Stream = SCStream;
+
+    fn start_display(
+        &mut self,
+        display: &DisplayGeometry,
+        width: u32,
+        height: u32,
+        handler: FrameHandler,
+    ) -> Result<SCStream, CFError> {
+        let sc_display = SCShareableContent::get()?
+            .displays()
+            .into_iter()
+            .find(|candidate| candidate.display_id() == display.display_id)
+            .ok_or_else(|| {
+                create_sc_error(format!("display {} is not available", display.display_id))
+            })?;
+        let config = SCStreamConfiguration::new()
+            .set_width(width)?
+            .set_height(height)?;
+        let filter = SCContentFilter::new().with_display_excluding_windows(&sc_display, &[]);
+        let mut stream = SCStream::new(&filter, &config);
+        stream.add_output_handler(
+            FrameOutput {
+                convert: self.convert,
+                handler,
+            },
+            SCStreamOutputType::Screen,
+        );
+        stream.start_capture()?;
+        Ok(stream)
+    }
+
+    fn stop_display(&mut self, stream: SCStream) -> Result<(), CFError> {
+        stream.stop_capture()
+    }
+}
+
+/// Captures the audio of applications with an `SCStream` whose video is as cheap as it gets:
+/// 2 by 2 pixels at one frame per second, with no output handler to receive it.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenCaptureAudioBackend {
+    convert: AudioBufferConverter,
+}
+
+impl ScreenCaptureAudioBackend {
+    pub const fn new(convert: AudioBufferConverter) -> Self {
+        Self { convert }
+    }
+
+    /// Builds a filter of the target's applications on its display.
+    fn filter(target: &AudioTarget) -> Result<SCContentFilter, CFError> {
+        let content = SCShareableContent::get()?;
+        let display = content
+            .displays()
+            .into_iter()
+            .find(|candidate| candidate.display_id() == target.display_id)
+            .ok_or_else(|| {
+                create_sc_error(format!("display {} is not available", target.display_id))
+            })?;
+        let applications: Vec<SCRunningApplication> = content
+            .applications()
+            .into_iter()
+            .filter(|application| target.process_ids.contains(&application.process_id()))
+            .collect();
+        let applications: Vec<_> = applications.iter().collect();
+        Ok(
+            SCContentFilter::new().with_display_including_application_excepting_windows(
+                &display,
+                &applications,
+                &[],
+            ),
+        )
+    }
+}
+
+impl Default for ScreenCaptureAudioBackend {
+    fn default() -> Self {
+        Self::new(sample_buffer::audio_frame)
+    }
+}
+
+struct AudioOutput {
+    convert: AudioBufferConverter,
+    handler: AudioHandler,
+}
+
+impl SCStreamOutputTrait for AudioOutput {
+    fn did_output_sample_buffer(&self, sample_buffer: CMSampleBuffer, of_type: SCStreamOutputType) {
+        if of_type != SCStreamOutputType::Audio {
+            return;
+        }
+        if let Some(frame) = (self.convert)(&sample_buffer) {
+            (self.handler)(frame);
+        }
+    }
+}
+
+impl AudioCaptureBackend for ScreenCaptureAudioBackend {
+    type Stream = SCStream;
+
+    fn start_audio(
+        &mut self,
+        target: &AudioTarget,
+        handler: AudioHandler,
+    ) -> Result<SCStream, CFError> {
+        let filter = Self::filter(target)?;
+        let config = SCStreamConfiguration::new()
+            .set_width(2)?
+            .set_height(2)?
+            .set_minimum_frame_interval(CMTime {
+                value: 1,
+                timescale: 1,
+                flags: 1,
+                epoch: 0,
+            })?
+            .set_captures_audio(true)?
+            .set_excludes_current_process_audio(true)?
+            .set_sample_rate(target.sample_rate)?
+            .set_channel_count(target.channel_count)?;
+        let mut stream = SCStream::new(&filter, &config);
+        stream.add_output_handler(
+            AudioOutput {
+                convert: self.convert,
+                handler,
+            },
+            SCStreamOutputType::Audio,
+        );
+        stream.start_capture()?;
+        Ok(stream)
+    }
+
+    fn update_audio(&mut self, stream: &mut SCStream, target: &AudioTarget) -> Result<(), CFError> {
+        stream.update_content_filter(&Self::filter(target)?)
+    }
+
+    fn stop_audio(&mut self, stream: SCStream) -> Result<(), CFError> {
+        stream.stop_capture()
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use core_foundation::error::CFError;
+
+use crate::{
+    geometry::display_layout::DisplayGeometry,
+    media::{audio_frame::AudioFrame, media_time::MediaTime, video_frame::VideoFrame},
+    utils::error::create_sc_error,
+};
+
+use super::backend::{
+    AudioCaptureBackend, AudioHandler, AudioTarget, CaptureBackend, FrameHandler,
+};
+
+struct SyntheticStream {
+    width: u32,
+    height: u32,
+    handler: FrameHandler,
+}
+
+#[derive(Default)]
+struct SyntheticState {
+    streams: HashMap<u32, SyntheticStream>,
+    failing_displays: Vec<u32>,
+    audio: Option<(AudioTarget, AudioHandler)>,
+}
+
+/// A capture backend without `ScreenCaptureKit`, which delivers the frames and audio its
+/// owner emits.
+///
+/// Clones share their streams, so a test can keep a clone to drive a session that owns the
+/// other.
+#[derive(Clone, Default)]
+pub struct SyntheticBackend {
+    state: Arc<Mutex<SyntheticState>>,
+}
+
+impl std::fmt::Debug for SyntheticBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyntheticBackend")
+            .field("active_displays", &self.active_displays())
+            .finish()
+    }
+}
+
+impl SyntheticBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SyntheticState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the IDs of the displays being captured, in ascending order.
+    pub fn active_displays(&self) -> Vec<u32> {
+        let mut displays: Vec<_> = self.lock().streams.keys().copied().collect();
+        displays.sort_unstable();
+        displays
+    }
+
+    /// Returns the frame size a display is captured at.
+    pub fn stream_size(&self, display_id: u32) -> Option<(u32, u32)> {
+        self.lock()
+            .streams
+            .get(&display_id)
+            .map(|stream| (stream.width, stream.height))
+    }
+
+    /// Makes starting a stream for `display_id` fail, e.g. to simulate a display that was
+    /// detached before its stream started.
+    pub fn fail_display(&self, display_id: u32) {
+        self.lock().failing_displays.push(display_id);
+    }
+
+    /// Delivers a frame of the display's stream size filled with `bgra`. Returns `false` if
+    /// the display is not being captured.
+    pub fn emit(&self, display_id: u32, bgra: [u8; 4], pts: MediaTime) -> bool {
+        let Some((width, height)) = self.stream_size(display_id) else {
+            return false;
+        };
+        self.emit_frame(
+            display_id,
+            VideoFrame::filled_bgra(width, height, bgra, pts),
+        )
+    }
+
+    /// Delivers `frame` as if the display's stream had captured it. Returns `false` if the
+    /// display is not being captured.
+    pub fn emit_frame(&self, display_id: u32, frame: VideoFrame) -> bool {
+        let handler = self
+            .lock()
+            .streams
+            .get(&display_id)
+            .map(|stream| Arc::clone(&stream.handler));
+        let Some(handler) = handler else {
+            return false;
+        };
+        // The handler runs unlocked, so it may start or stop streams itself.
+        handler(frame);
+        true
+    }
+
+    /// Returns what the audio stream captures, or `None` while none is running.
+    pub fn audio_target(&self) -> Option<AudioTarget> {
+        self.lock().audio.as_ref().map(|(target, _)| target.clone())
+    }
+
+    /// Delivers `frame` as if the audio stream had captured it. Returns `false` if no audio
+    /// stream is running.
+    pub fn emit_audio(&self, frame: AudioFrame) -> bool {
+        let handler = self
+            .lock()
+            .audio
+            .as_ref()
+            .map(|(_, handler)| Arc::clone(handler));
+        let Some(handler) = handler else {
+            return false;
+        };
+        handler(frame);
+        true
+    }
+}
+
+impl CaptureBackend for SyntheticBackend {
+    type Stream = u32;
+
+    fn start_display(
+        &mut self,
+        display: &DisplayGeometry,
+        width: u32,
+        height: u32,
+        handler: FrameHandler,
+    ) -> Result<u32, CFError> {
+        let mut state = self.lock();
+        if state.failing_displays.contains(&display.display_id) {
+            return Err(create_sc_error(format!(
+                "display {} is not available",
+                display.display_id
+            )));
+        }
+        state.streams.insert(
+            display.display_id,
+            SyntheticStream {
+                width,
+                height,
+                handler,
+            },
+        );
+        drop(state);
+        Ok(display.display_id)
+    }
+
+    fn stop_display(&mut self, stream: u32) -> Result<(), CFError> {
+        self.lock().streams.remove(&stream);
+        Ok(())
+    }
+}
+
+impl AudioCaptureBackend for SyntheticBackend {
+    type Stream = ();
+
+    fn start_audio(&mut self, target: &AudioTarget, handler: AudioHandler) -> Result<(), CFError> {
+        let mut state = self.lock();
+        if state.failing_displays.contains(&target.display_id) {
+            return Err(create_sc_error(format!(
+                "display {} is not available",
+                target.display_id
+            )));
+        }
+        state.audio = Some((target.clone(), handler));
+        drop(state);
+        Ok(())
+    }
+
+    fn update_audio(&mut self, _stream: &mut (), target: &AudioTarget) -> Result<(), CFError> {
+        match &mut self.lock().audio {
+            Some((current, _)) => {
+                *current = target.clone();
+                Ok(())
+            }
+            None => Err(create_sc_error("the audio stream is not running")),
+        }
+    }
+
+    fn stop_audio(&mut self, _stream: ()) -> Result<(), CFError> {
+        self.lock().audio = None;
+        Ok(())
+    }
+}
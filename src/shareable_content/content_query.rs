@@ -0,0 +1,526 @@
+//! Selecting windows and applications from a [`ContentSnapshot`].
+//!
+//! Finding the frontmost Safari window on display 2:
+//!
+//! ```ignore
+//! let window = WindowQuery::new()
+//!     .bundle_identifier("com.apple.Safari")
+//!     .on_display(2)
+//!     .on_screen(true)
+//!     .sort_by(WindowOrder::ZOrder)
+//!     .first(&snapshot);
+//! ```
+
+use crate::geometry::occlusion::Occlusion;
+
+use super::content_snapshot::{ApplicationInfo, ContentSnapshot, WindowInfo};
+
+/// How a name or title is matched.
+#[derive(Debug, Clone)]
+pub enum TextMatch {
+    Exact(String),
+    /// A glob where `*` matches any run of characters and `?` any single character.
+    Glob(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl TextMatch {
+    pub fn exact(text: impl Into<String>) -> Self {
+        Self::Exact(text.into())
+    }
+
+    pub fn glob(pattern: impl Into<String>) -> Self {
+        Self::Glob(pattern.into())
+    }
+
+    /// Matches anywhere in the text unless the pattern is anchored with `^` and `$`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(Self::Regex)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Self::Exact(expected) => expected == text,
+            Self::Glob(pattern) => glob_matches(
+                &pattern.chars().collect::<Vec<_>>(),
+                &text.chars().collect::<Vec<_>>(),
+            ),
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Matches with a single backtracking point at the last `*` seen, which is enough as a
+/// later `*` can absorb anything an earlier one would.
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    t = matched + 1;
+                    star = Some((after_star, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowOrder {
+    /// Frontmost first: higher layers before lower ones, and within a layer in the order
+    /// `ScreenCaptureKit` lists the windows.
+    ZOrder,
+    /// Largest frame first.
+    Area,
+}
+
+/// Selects the windows of a [`ContentSnapshot`] matching every configured condition.
+#[derive(Debug, Clone, Default)]
+pub struct WindowQuery {
+    bundle_identifier: Option<String>,
+    application_name: Option<TextMatch>,
+    title: Option<TextMatch>,
+    process_id: Option<i32>,
+    window_layer: Option<u32>,
+    is_on_screen: Option<bool>,
+    is_active: Option<bool>,
+    display_id: Option<u32>,
+    min_size: Option<(f64, f64)>,
+    min_visible_fraction: Option<f64>,
+    order: Option<WindowOrder>,
+}
+
+impl WindowQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn bundle_identifier(mut self, bundle_identifier: impl Into<String>) -> Self {
+        self.bundle_identifier = Some(bundle_identifier.into());
+        self
+    }
+
+    #[must_use]
+    pub fn application_name(mut self, name: TextMatch) -> Self {
+        self.application_name = Some(name);
+        self
+    }
+
+    #[must_use]
+    pub fn title(mut self, title: TextMatch) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    #[must_use]
+    pub const fn process_id(mut self, process_id: i32) -> Self {
+        self.process_id = Some(process_id);
+        self
+    }
+
+    #[must_use]
+    pub const fn window_layer(mut self, window_layer: u32) -> Self {
+        self.window_layer = Some(window_layer);
+        self
+    }
+
+    #[must_use]
+    pub const fn on_screen(mut self, is_on_screen: bool) -> Self {
+        self.is_on_screen = Some(is_on_screen);
+        self
+    }
+
+    #[must_use]
+    pub const fn active(mut self, is_active: bool) -> Self {
+        self.is_active = Some(is_active);
+        self
+    }
+
+    /// Only matches windows intersecting the display. Windows never match a display missing
+    /// from the snapshot.
+    #[must_use]
+    pub const fn on_display(mut self, display_id: u32) -> Self {
+        self.display_id = Some(display_id);
+        self
+    }
+
+    /// Only matches windows at least `width` by `height` points large.
+    #[must_use]
+    pub const fn min_size(mut self, width: f64, height: f64) -> Self {
+        self.min_size = Some((width, height));
+        self
+    }
+
+    /// Only returns windows with at least `fraction` of their frame visible, as computed by
+    /// [`Occlusion`]. Zero skips fully occluded windows. This is checked by
+    /// [`WindowQuery::run`] and [`WindowQuery::first`], but not [`WindowQuery::matches`].
+    #[must_use]
+    pub const fn min_visible_fraction(mut self, fraction: f64) -> Self {
+        self.min_visible_fraction = Some(fraction);
+        self
+    }
+
+    /// Sorts the results. Without an order they are returned as listed in the snapshot.
+    #[must_use]
+    pub const fn sort_by(mut self, order: WindowOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn matches(&self, snapshot: &ContentSnapshot, window: &WindowInfo) -> bool {
+        let application = &window.owning_application;
+        self.bundle_identifier
+            .as_ref()
+            .map_or(true, |bundle_identifier| {
+                *bundle_identifier == application.bundle_identifier
+            })
+            && self
+                .application_name
+                .as_ref()
+                .map_or(true, |name| name.matches(&application.application_name))
+            && self
+                .title
+                .as_ref()
+                .map_or(true, |title| title.matches(&window.title))
+            && self
+                .process_id
+                .map_or(true, |process_id| process_id == application.process_id)
+            && self
+                .window_layer
+                .map_or(true, |window_layer| window_layer == window.window_layer)
+            && self
+                .is_on_screen
+                .map_or(true, |is_on_screen| is_on_screen == window.is_on_screen)
+            && self
+                .is_active
+                .map_or(true, |is_active| is_active == window.is_active)
+            && self.display_id.map_or(true, |display_id| {
+                snapshot
+                    .display(display_id)
+                    .is_some_and(|display| display.frame.intersects(&window.frame))
+            })
+            && self.min_size.map_or(true, |(width, height)| {
+                window.frame.width >= width && window.frame.height >= height
+            })
+    }
+
+    /// Returns the matching windows.
+    pub fn run<'a>(&self, snapshot: &'a ContentSnapshot) -> Vec<&'a WindowInfo> {
+        let mut windows: Vec<_> = match self.order {
+            Some(WindowOrder::ZOrder) => snapshot.windows_in_z_order(),
+            _ => snapshot.windows.iter().collect(),
+        };
+        windows.retain(|window| self.matches(snapshot, window));
+        if let Some(fraction) = self.min_visible_fraction {
+            let visible = Occlusion::new(snapshot).visible_windows(fraction);
+            windows.retain(|window| visible.contains(&window.window_id));
+        }
+        if self.order == Some(WindowOrder::Area) {
+            windows.sort_by(|a, b| b.frame.area().total_cmp(&a.frame.area()));
+        }
+        windows
+    }
+
+    /// Returns the first matching window in the configured order.
+    pub fn first<'a>(&self, snapshot: &'a ContentSnapshot) -> Option<&'a WindowInfo> {
+        self.run(snapshot).into_iter().next()
+    }
+}
+
+/// Selects the applications of a [`ContentSnapshot`] matching every configured condition.
+#[derive(Debug, Clone, Default)]
+pub struct ApplicationQuery {
+    bundle_identifier: Option<String>,
+    application_name: Option<TextMatch>,
+    process_id: Option<i32>,
+}
+
+impl ApplicationQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn bundle_identifier(mut self, bundle_identifier: impl Into<String>) -> Self {
+        self.bundle_identifier = Some(bundle_identifier.into());
+        self
+    }
+
+    #[must_use]
+    pub fn application_name(mut self, name: TextMatch) -> Self {
+        self.application_name = Some(name);
+        self
+    }
+
+    #[must_use]
+    pub const fn process_id(mut self, process_id: i32) -> Self {
+        self.process_id = Some(process_id);
+        self
+    }
+
+    pub fn matches(&self, application: &ApplicationInfo) -> bool {
+        self.bundle_identifier
+            .as_ref()
+            .map_or(true, |bundle_identifier| {
+                *bundle_identifier == application.bundle_identifier
+            })
+            && self
+                .application_name
+                .as_ref()
+                .map_or(true, |name| name.matches(&application.application_name))
+            && self
+                .process_id
+                .map_or(true, |process_id| process_id == application.process_id)
+    }
+
+    /// Returns the matching applications in the order of the snapshot.
+    pub fn run<'a>(&self, snapshot: &'a ContentSnapshot) -> Vec<&'a ApplicationInfo> {
+        snapshot
+            .applications
+            .iter()
+            .filter(|application| self.matches(application))
+            .collect()
+    }
+
+    /// Returns the windows owned by the matching applications, in the order of the snapshot.
+    pub fn windows<'a>(&self, snapshot: &'a ContentSnapshot) -> Vec<&'a WindowInfo> {
+        snapshot
+            .windows
+            .iter()
+            .filter(|window| self.matches(&window.owning_application))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod content_query_test {
+    use crate::shareable_content::content_snapshot::{
+        ApplicationInfo, ContentSnapshot, DisplayInfo, Rect, WindowInfo,
+    };
+
+    use super::{ApplicationQuery, TextMatch, WindowOrder, WindowQuery};
+
+    fn application(process_id: i32, name: &str, bundle_identifier: &str) -> ApplicationInfo {
+        ApplicationInfo {
+            process_id,
+            application_name: name.to_owned(),
+            bundle_identifier: bundle_identifier.to_owned(),
+        }
+    }
+
+    fn window(
+        window_id: u32,
+        title: &str,
+        frame: Rect,
+        window_layer: u32,
+        owning_application: &ApplicationInfo,
+    ) -> WindowInfo {
+        WindowInfo {
+            window_id,
+            title: title.to_owned(),
+            frame,
+            window_layer,
+            is_on_screen: true,
+            is_active: false,
+            owning_application: owning_application.clone(),
+        }
+    }
+
+    /// Two side by side displays with Safari and Terminal windows on both.
+    fn snapshot() -> ContentSnapshot {
+        let safari = application(100, "Safari", "com.apple.Safari");
+        let terminal = application(200, "Terminal", "com.apple.Terminal");
+        let mut windows = vec![
+            window(
+                1,
+                "Apple",
+                Rect::new(100.0, 100.0, 800.0, 600.0),
+                0,
+                &safari,
+            ),
+            window(2, "", Rect::new(0.0, 0.0, 1920.0, 25.0), 24, &safari),
+            window(
+                3,
+                "zsh — 80×24",
+                Rect::new(2000.0, 50.0, 640.0, 400.0),
+                0,
+                &terminal,
+            ),
+            window(
+                4,
+                "GitHub",
+                Rect::new(2100.0, 0.0, 1000.0, 900.0),
+                0,
+                &safari,
+            ),
+            window(5, "Rust", Rect::new(1800.0, 0.0, 300.0, 200.0), 0, &safari),
+            window(
+                6,
+                "vim — 120×40",
+                Rect::new(0.0, 0.0, 10.0, 10.0),
+                0,
+                &terminal,
+            ),
+        ];
+        windows[0].is_active = true;
+        windows[5].is_on_screen = false;
+        ContentSnapshot {
+            displays: vec![
+                DisplayInfo {
+                    display_id: 1,
+                    frame: Rect::new(0.0, 0.0, 1920.0, 1080.0),
+                    width: 1920,
+                    height: 1080,
+                },
+                DisplayInfo {
+                    display_id: 2,
+                    frame: Rect::new(1920.0, 0.0, 2560.0, 1440.0),
+                    width: 2560,
+                    height: 1440,
+                },
+            ],
+            windows,
+            applications: vec![safari, terminal],
+        }
+    }
+
+    fn ids(windows: &[&WindowInfo]) -> Vec<u32> {
+        windows.iter().map(|window| window.window_id).collect()
+    }
+
+    #[test]
+    fn test_glob() {
+        let glob = |pattern: &str, text: &str| TextMatch::glob(pattern).matches(text);
+        assert!(glob("*", ""));
+        assert!(glob("Saf*", "Safari"));
+        assert!(glob("*a*i", "Safari"));
+        assert!(glob("S?f?r?", "Safari"));
+        assert!(glob("*—*×*", "zsh — 80×24"));
+        assert!(glob("a*b*c", "aXbYbZc"));
+        assert!(!glob("a*b*c", "aXbYbZ"));
+        assert!(!glob("Saf", "Safari"));
+        assert!(!glob("?", ""));
+        assert!(TextMatch::exact("Safari").matches("Safari"));
+        assert!(!TextMatch::exact("Safari").matches("safari"));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        let regex = TextMatch::regex(r"^\w+ — \d+×\d+$").unwrap();
+        assert!(regex.matches("zsh — 80×24"));
+        assert!(!regex.matches("GitHub"));
+        assert!(TextMatch::regex("(").is_err());
+    }
+
+    #[test]
+    fn test_filters() {
+        let snapshot = snapshot();
+        let run = |query: WindowQuery| ids(&query.run(&snapshot));
+        assert_eq!(
+            run(WindowQuery::new().bundle_identifier("com.apple.Terminal")),
+            vec![3, 6]
+        );
+        assert_eq!(
+            run(WindowQuery::new().application_name(TextMatch::glob("Term*"))),
+            vec![3, 6]
+        );
+        assert_eq!(
+            run(WindowQuery::new().title(TextMatch::glob("*×*"))),
+            vec![3, 6]
+        );
+        assert_eq!(run(WindowQuery::new().process_id(100)), vec![1, 2, 4, 5]);
+        assert_eq!(run(WindowQuery::new().window_layer(24)), vec![2]);
+        assert_eq!(run(WindowQuery::new().on_screen(false)), vec![6]);
+        assert_eq!(run(WindowQuery::new().active(true)), vec![1]);
+        assert_eq!(run(WindowQuery::new().on_display(2)), vec![3, 4, 5]);
+        assert_eq!(run(WindowQuery::new().on_display(3)), Vec::<u32>::new());
+        assert_eq!(
+            run(WindowQuery::new().min_size(640.0, 400.0)),
+            vec![1, 3, 4]
+        );
+        assert_eq!(
+            run(WindowQuery::new()
+                .bundle_identifier("com.apple.Safari")
+                .on_display(1)
+                .window_layer(0)),
+            vec![1, 5]
+        );
+    }
+
+    #[test]
+    fn test_sorting() {
+        let snapshot = snapshot();
+        let query = WindowQuery::new().bundle_identifier("com.apple.Safari");
+        assert_eq!(
+            ids(&query.clone().sort_by(WindowOrder::ZOrder).run(&snapshot)),
+            vec![2, 1, 4, 5]
+        );
+        assert_eq!(
+            ids(&query.sort_by(WindowOrder::Area).run(&snapshot)),
+            vec![4, 1, 5, 2]
+        );
+        let frontmost = WindowQuery::new()
+            .bundle_identifier("com.apple.Safari")
+            .on_display(2)
+            .window_layer(0)
+            .sort_by(WindowOrder::ZOrder)
+            .first(&snapshot);
+        assert_eq!(frontmost.map(|window| window.window_id), Some(4));
+    }
+
+    #[test]
+    fn test_skips_occluded() {
+        let snapshot = snapshot();
+        let visible = WindowQuery::new().min_visible_fraction(0.0).run(&snapshot);
+        // Window 6 is off screen, windows 4 and 5 are partly covered by window 3.
+        assert_eq!(ids(&visible), vec![1, 2, 3, 4, 5]);
+        let mostly_visible = WindowQuery::new()
+            .min_visible_fraction(0.9)
+            .sort_by(WindowOrder::ZOrder)
+            .run(&snapshot);
+        assert_eq!(ids(&mostly_visible), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_applications() {
+        let snapshot = snapshot();
+        let query = ApplicationQuery::new().application_name(TextMatch::glob("*ari"));
+        assert_eq!(query.run(&snapshot).len(), 1);
+        assert_eq!(query.run(&snapshot)[0].process_id, 100);
+        assert_eq!(ids(&query.windows(&snapshot)), vec![1, 2, 4, 5]);
+        assert!(ApplicationQuery::new()
+            .process_id(1)
+            .run(&snapshot)
+            .is_empty());
+        assert_eq!(
+            ApplicationQuery::new()
+                .bundle_identifier("com.apple.Terminal")
+                .process_id(200)
+                .run(&snapshot)
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+
+use super::{
+    sc_display::SCDisplay, sc_running_application::SCRunningApplication,
+    sc_shareable_content::SCShareableContent, sc_window::SCWindow,
+};
+
+/// An owned copy of a `CGRect`, in points.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width.max(0.0) * self.height.max(0.0)
+    }
+
+    /// Returns `true` if the rectangles share an area larger than zero.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+impl From<CGRect> for Rect {
+    fn from(rect: CGRect) -> Self {
+        Self::new(
+            rect.origin.x,
+            rect.origin.y,
+            rect.size.width,
+            rect.size.height,
+        )
+    }
+}
+
+impl From<Rect> for CGRect {
+    fn from(rect: Rect) -> Self {
+        Self {
+            origin: CGPoint::new(rect.x, rect.y),
+            size: CGSize::new(rect.width, rect.height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayInfo {
+    pub display_id: u32,
+    pub frame: Rect,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<&SCDisplay> for DisplayInfo {
+    fn from(display: &SCDisplay) -> Self {
+        Self {
+            display_id: display.display_id(),
+            frame: display.frame().into(),
+            width: display.width(),
+            height: display.height(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApplicationInfo {
+    pub process_id: i32,
+    pub application_name: String,
+    pub bundle_identifier: String,
+}
+
+impl From<&SCRunningApplication> for ApplicationInfo {
+    fn from(application: &SCRunningApplication) -> Self {
+        Self {
+            process_id: application.process_id(),
+            application_name: application.application_name(),
+            bundle_identifier: application.bundle_identifier(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowInfo {
+    pub window_id: u32,
+    pub title: String,
+    pub frame: Rect,
+    pub window_layer: u32,
+    pub is_on_screen: bool,
+    pub is_active: bool,
+    /// The owning application, or the default for windows without one.
+    pub owning_application: ApplicationInfo,
+}
+
+impl From<&SCWindow> for WindowInfo {
+    fn from(window: &SCWindow) -> Self {
+        Self {
+            window_id: window.window_id(),
+            title: window.title(),
+            frame: window.get_frame().into(),
+            window_layer: window.window_layer(),
+            is_on_screen: window.is_on_screen(),
+            is_active: window.is_active(),
+            owning_application: window
+                .try_owning_application()
+                .map(|application| (&application).into())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A change between two [`ContentSnapshot`]s.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContentChange {
+    DisplayAttached(DisplayInfo),
+    DisplayDetached(DisplayInfo),
+    WindowOpened(WindowInfo),
+    WindowClosed(WindowInfo),
+    /// The window was moved or resized.
+    WindowMoved {
+        window_id: u32,
+        from: Rect,
+        to: Rect,
+    },
+    WindowRetitled {
+        window_id: u32,
+        from: String,
+        to: String,
+    },
+    WindowActivated {
+        window_id: u32,
+    },
+    WindowDeactivated {
+        window_id: u32,
+    },
+}
+
+/// An owned, plain data copy of the displays, windows and applications available for capture.
+///
+/// Unlike [`SCShareableContent`], reading a snapshot sends no messages, so it can be cloned,
+/// compared, serialized with the `serde` feature and sent across threads freely.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContentSnapshot {
+    pub displays: Vec<DisplayInfo>,
+    pub windows: Vec<WindowInfo>,
+    pub applications: Vec<ApplicationInfo>,
+}
+
+impl From<&SCShareableContent> for ContentSnapshot {
+    fn from(content: &SCShareableContent) -> Self {
+        Self::from_parts(
+            &content.displays(),
+            &content.windows(),
+            &content.applications(),
+        )
+    }
+}
+
+impl ContentSnapshot {
+    pub fn from_parts(
+        displays: &[SCDisplay],
+        windows: &[SCWindow],
+        applications: &[SCRunningApplication],
+    ) -> Self {
+        Self {
+            displays: displays.iter().map(DisplayInfo::from).collect(),
+            windows: windows.iter().map(WindowInfo::from).collect(),
+            applications: applications.iter().map(ApplicationInfo::from).collect(),
+        }
+    }
+
+    pub fn display(&self, display_id: u32) -> Option<&DisplayInfo> {
+        self.displays
+            .iter()
+            .find(|display| display.display_id == display_id)
+    }
+
+    pub fn window(&self, window_id: u32) -> Option<&WindowInfo> {
+        self.windows
+            .iter()
+            .find(|window| window.window_id == window_id)
+    }
+
+    /// Returns the windows frontmost first: higher layers before lower ones, and within a
+    /// layer in the order `ScreenCaptureKit` lists them.
+    pub fn windows_in_z_order(&self) -> Vec<&WindowInfo> {
+        let mut windows: Vec<_> = self.windows.iter().collect();
+        // A stable sort keeps the listed order within a layer.
+        windows.sort_by_key(|window| Reverse(window.window_layer));
+        windows
+    }
+
+    /// Returns the changes from this snapshot to `newer`.
+    ///
+    /// Displays attached or detached come first, then windows closed, then windows opened
+    /// and finally the changes to windows present in both, each in the order of the snapshot
+    /// they are found in.
+    pub fn diff(&self, newer: &Self) -> Vec<ContentChange> {
+        // Indexes both sides once so that each lookup below takes constant time.
+        let old_displays: HashMap<_, _> = self
+            .displays
+            .iter()
+            .map(|display| (display.display_id, display))
+            .collect();
+        let new_displays: HashMap<_, _> = newer
+            .displays
+            .iter()
+            .map(|display| (display.display_id, display))
+            .collect();
+        let old_windows: HashMap<_, _> = self
+            .windows
+            .iter()
+            .map(|window| (window.window_id, window))
+            .collect();
+        let new_windows: HashMap<_, _> = newer
+            .windows
+            .iter()
+            .map(|window| (window.window_id, window))
+            .collect();
+
+        let mut changes = Vec::new();
+        for display in &self.displays {
+            if !new_displays.contains_key(&display.display_id) {
+                changes.push(ContentChange::DisplayDetached(display.clone()));
+            }
+        }
+        for display in &newer.displays {
+            if !old_displays.contains_key(&display.display_id) {
+                changes.push(ContentChange::DisplayAttached(display.clone()));
+            }
+        }
+        for window in &self.windows {
+            if !new_windows.contains_key(&window.window_id) {
+                changes.push(ContentChange::WindowClosed(window.clone()));
+            }
+        }
+        for window in &newer.windows {
+            if !old_windows.contains_key(&window.window_id) {
+                changes.push(ContentChange::WindowOpened(window.clone()));
+            }
+        }
+        for window in &newer.windows {
+            let Some(old) = old_windows.get(&window.window_id) else {
+                continue;
+            };
+            let window_id = window.window_id;
+            if old.frame != window.frame {
+                changes.push(ContentChange::WindowMoved {
+                    window_id,
+                    from: old.frame,
+                    to: window.frame,
+                });
+            }
+            if old.title != window.title {
+                changes.push(ContentChange::WindowRetitled {
+                    window_id,
+                    from: old.title.clone(),
+                    to: window.title.clone(),
+                });
+            }
+            match (old.is_active, window.is_active) {
+                (false, true) => changes.push(ContentChange::WindowActivated { window_id }),
+                (true, false) => changes.push(ContentChange::WindowDeactivated { window_id }),
+                _ => {}
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod content_snapshot_test {
+    use super::{ContentChange, ContentSnapshot, DisplayInfo, Rect, WindowInfo};
+
+    fn display(display_id: u32) -> DisplayInfo {
+        DisplayInfo {
+            display_id,
+            frame: Rect::new(f64::from(display_id) * 1920.0, 0.0, 1920.0, 1080.0),
+            width: 1920,
+            height: 1080,
+        }
+    }
+
+    fn window(window_id: u32, title: &str) -> WindowInfo {
+        WindowInfo {
+            window_id,
+            title: title.to_owned(),
+            frame: Rect::new(10.0, 20.0, 300.0, 200.0),
+            is_on_screen: true,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(displays: Vec<DisplayInfo>, windows: Vec<WindowInfo>) -> ContentSnapshot {
+        ContentSnapshot {
+            displays,
+            windows,
+            applications: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let old = snapshot(vec![display(1)], vec![window(1, "a"), window(2, "b")]);
+        assert!(old.diff(&old.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_displays_attached_and_detached() {
+        let old = snapshot(vec![display(1), display(2)], vec![]);
+        let new = snapshot(vec![display(2), display(3)], vec![]);
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                ContentChange::DisplayDetached(display(1)),
+                ContentChange::DisplayAttached(display(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_windows_opened_and_closed() {
+        let old = snapshot(vec![], vec![window(1, "a"), window(2, "b")]);
+        let new = snapshot(vec![], vec![window(3, "c"), window(2, "b"), window(4, "d")]);
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                ContentChange::WindowClosed(window(1, "a")),
+                ContentChange::WindowOpened(window(3, "c")),
+                ContentChange::WindowOpened(window(4, "d")),
+            ]
+        );
+        assert_eq!(
+            new.diff(&old),
+            vec![
+                ContentChange::WindowClosed(window(3, "c")),
+                ContentChange::WindowClosed(window(4, "d")),
+                ContentChange::WindowOpened(window(1, "a")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_changes() {
+        let old = snapshot(vec![], vec![window(1, "a"), window(2, "b"), window(3, "c")]);
+        let mut new = old.clone();
+        new.windows[0].frame.x += 5.0;
+        new.windows[0].frame.height = 100.0;
+        new.windows[1].title = "b - edited".to_owned();
+        new.windows[1].is_active = true;
+        new.windows[2].is_on_screen = false;
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                ContentChange::WindowMoved {
+                    window_id: 1,
+                    from: Rect::new(10.0, 20.0, 300.0, 200.0),
+                    to: Rect::new(15.0, 20.0, 300.0, 100.0),
+                },
+                ContentChange::WindowRetitled {
+                    window_id: 2,
+                    from: "b".to_owned(),
+                    to: "b - edited".to_owned(),
+                },
+                ContentChange::WindowActivated { window_id: 2 },
+            ]
+        );
+        assert_eq!(
+            new.diff(&old)[2],
+            ContentChange::WindowDeactivated { window_id: 2 }
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let snapshot = snapshot(vec![display(7)], vec![window(9, "nine")]);
+        assert_eq!(snapshot.display(7), Some(&display(7)));
+        assert_eq!(snapshot.window(9).map(|w| w.title.as_str()), Some("nine"));
+        assert!(snapshot.window(8).is_none());
+    }
+}
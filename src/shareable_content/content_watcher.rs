@@ -0,0 +1,514 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use core_foundation::error::CFError;
+
+use super::{
+    content_snapshot::{ContentChange, ContentSnapshot},
+    sc_shareable_content::{SCShareableContent, SCShareableContentOptions},
+};
+
+/// `SCStreamErrorUserDeclined`, returned while screen recording permission is not granted.
+pub const SC_STREAM_ERROR_USER_DECLINED: isize = -3801;
+
+pub fn is_permission_error(error: &CFError) -> bool {
+    error.code() == SC_STREAM_ERROR_USER_DECLINED
+}
+
+/// A source of [`ContentSnapshot`]s for a [`ContentWatcher`].
+pub trait ContentProvider {
+    /// Fetches the current content.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error reported by the underlying query.
+    fn snapshot(&mut self) -> Result<ContentSnapshot, CFError>;
+}
+
+impl<F: FnMut() -> Result<ContentSnapshot, CFError>> ContentProvider for F {
+    fn snapshot(&mut self) -> Result<ContentSnapshot, CFError> {
+        self()
+    }
+}
+
+/// Queries `ScreenCaptureKit` through [`SCShareableContentOptions::get`].
+#[derive(Debug, Clone, Copy)]
+pub struct ShareableContentProvider {
+    options: fn() -> SCShareableContentOptions,
+}
+
+impl ShareableContentProvider {
+    /// Creates a provider building its query with `options` on every poll, e.g.
+    /// `|| SCShareableContent::with_options().on_screen_windows_only()`.
+    pub const fn new(options: fn() -> SCShareableContentOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Default for ShareableContentProvider {
+    fn default() -> Self {
+        Self::new(SCShareableContent::with_options)
+    }
+}
+
+impl ContentProvider for ShareableContentProvider {
+    fn snapshot(&mut self) -> Result<ContentSnapshot, CFError> {
+        (self.options)()
+            .get()
+            .map(|content| ContentSnapshot::from(&content))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentEvent {
+    /// The content changed. The first successful poll reports every display as attached
+    /// and every window as opened.
+    Changed {
+        changes: Vec<ContentChange>,
+        snapshot: ContentSnapshot,
+    },
+    /// Fetching the content failed and will be retried after `retry_in`.
+    Failed {
+        message: String,
+        is_permission_error: bool,
+        retry_in: Duration,
+    },
+}
+
+#[derive(Debug)]
+struct Pending {
+    snapshot: ContentSnapshot,
+    first_seen: Instant,
+}
+
+/// Polls a [`ContentProvider`] and turns the differences between snapshots into
+/// [`ContentEvent`]s.
+///
+/// A change is held back until a poll after the debounce period sees the same content,
+/// so a burst of changes, like a window being dragged, is reported once. Changes are never
+/// held back for longer than the polling interval. Permission errors double the time to
+/// the next poll, up to the maximum backoff, until a poll succeeds.
+///
+/// The watcher can be driven manually with [`ContentWatcher::poll_at`], or on a thread of its
+/// own with [`ContentWatcher::spawn`] or [`ContentWatcher::spawn_with_channel`].
+#[derive(Debug)]
+pub struct ContentWatcher<P> {
+    provider: P,
+    interval: Duration,
+    debounce: Duration,
+    max_backoff: Duration,
+    current: Option<ContentSnapshot>,
+    pending: Option<Pending>,
+    backoff: Duration,
+    next_poll: Option<Instant>,
+}
+
+impl<P: ContentProvider> ContentWatcher<P> {
+    pub const fn new(provider: P) -> Self {
+        Self {
+            provider,
+            interval: Duration::from_secs(1),
+            debounce: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(60),
+            current: None,
+            pending: None,
+            backoff: Duration::ZERO,
+            next_poll: None,
+        }
+    }
+
+    /// Sets the time between polls. Defaults to one second.
+    #[must_use]
+    pub const fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how long changed content has to stay the same before it is reported.
+    /// Defaults to 250 ms; zero reports every change at once.
+    #[must_use]
+    pub const fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Sets the longest time between polls while permission is denied. Defaults to 60 s.
+    #[must_use]
+    pub const fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns the content last reported.
+    pub const fn snapshot(&self) -> Option<&ContentSnapshot> {
+        self.current.as_ref()
+    }
+
+    /// Returns when the next poll is due, or `None` before the first poll.
+    pub const fn next_poll(&self) -> Option<Instant> {
+        self.next_poll
+    }
+
+    /// Polls the provider once, treating `now` as the current time.
+    pub fn poll_at(&mut self, now: Instant) -> Option<ContentEvent> {
+        let snapshot = match self.provider.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                let is_permission_error = is_permission_error(&error);
+                let retry_in = if is_permission_error {
+                    self.backoff = (self.backoff * 2)
+                        .max(self.interval)
+                        .min(self.max_backoff.max(self.interval));
+                    self.backoff
+                } else {
+                    self.interval
+                };
+                self.next_poll = Some(now + retry_in);
+                return Some(ContentEvent::Failed {
+                    message: error.to_string(),
+                    is_permission_error,
+                    retry_in,
+                });
+            }
+        };
+        self.backoff = Duration::ZERO;
+        self.next_poll = Some(now + self.interval);
+        let Some(current) = self.current.as_mut() else {
+            let changes = ContentSnapshot::default().diff(&snapshot);
+            self.current = Some(snapshot.clone());
+            return Some(ContentEvent::Changed { changes, snapshot });
+        };
+        if snapshot == *current {
+            self.pending = None;
+            return None;
+        }
+        let settled = match &self.pending {
+            Some(pending) => {
+                pending.snapshot == snapshot || now - pending.first_seen >= self.interval
+            }
+            None => self.debounce.is_zero(),
+        };
+        if !settled {
+            let first_seen = self
+                .pending
+                .as_ref()
+                .map_or(now, |pending| pending.first_seen);
+            self.pending = Some(Pending {
+                snapshot,
+                first_seen,
+            });
+            self.next_poll = Some(now + self.debounce.min(self.interval));
+            return None;
+        }
+        self.pending = None;
+        let changes = current.diff(&snapshot);
+        *current = snapshot.clone();
+        Some(ContentEvent::Changed { changes, snapshot })
+    }
+
+    /// Polls the provider if a poll is due at `now`.
+    pub fn poll_if_due(&mut self, now: Instant) -> Option<ContentEvent> {
+        if self.next_poll.is_some_and(|next_poll| now < next_poll) {
+            return None;
+        }
+        self.poll_at(now)
+    }
+}
+
+impl<P: ContentProvider + Send + 'static> ContentWatcher<P> {
+    /// Polls on a new thread, calling `callback` with every event until the returned handle
+    /// is stopped or dropped.
+    pub fn spawn(
+        mut self,
+        mut callback: impl FnMut(ContentEvent) + Send + 'static,
+    ) -> ContentWatcherHandle {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                while !wait_until(&stop, self.next_poll.unwrap_or_else(Instant::now)) {
+                    if let Some(event) = self.poll_at(Instant::now()) {
+                        callback(event);
+                    }
+                }
+            })
+        };
+        ContentWatcherHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Polls on a new thread, sending every event to the returned receiver.
+    pub fn spawn_with_channel(self) -> (ContentWatcherHandle, Receiver<ContentEvent>) {
+        let (sender, receiver) = channel();
+        let handle = self.spawn(move |event| {
+            // The receiver may be gone while the handle is still alive.
+            let _ = sender.send(event);
+        });
+        (handle, receiver)
+    }
+}
+
+/// Waits until `deadline` or until the flag is set, returning the flag.
+fn wait_until(stop: &(Mutex<bool>, Condvar), deadline: Instant) -> bool {
+    let (mutex, condvar) = stop;
+    let mut stopped = mutex.lock().expect("should lock");
+    loop {
+        let now = Instant::now();
+        if *stopped || now >= deadline {
+            return *stopped;
+        }
+        stopped = condvar
+            .wait_timeout(stopped, deadline - now)
+            .expect("should lock")
+            .0;
+    }
+}
+
+/// Stops the polling thread of a [`ContentWatcher`] when stopped or dropped.
+#[derive(Debug)]
+pub struct ContentWatcherHandle {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ContentWatcherHandle {
+    /// Stops polling and waits for the thread to exit.
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for ContentWatcherHandle {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        if let Ok(mut stopped) = stopped.lock() {
+            *stopped = true;
+        }
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod content_watcher_test {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use core_foundation::error::CFError;
+
+    use crate::{
+        shareable_content::content_snapshot::{ContentChange, ContentSnapshot, Rect, WindowInfo},
+        utils::error::{create_cf_error, create_sc_error},
+    };
+
+    use super::{ContentEvent, ContentWatcher, SC_STREAM_ERROR_USER_DECLINED};
+
+    type Script = Arc<Mutex<VecDeque<Result<ContentSnapshot, CFError>>>>;
+
+    /// Returns the scripted results in order, repeating the last one.
+    fn fake_provider(
+        results: Vec<Result<ContentSnapshot, CFError>>,
+    ) -> (Script, impl FnMut() -> Result<ContentSnapshot, CFError>) {
+        let script: Script = Arc::new(Mutex::new(results.into()));
+        let provider = {
+            let script = Arc::clone(&script);
+            move || {
+                let mut script = script.lock().unwrap();
+                if script.len() > 1 {
+                    script.pop_front().unwrap()
+                } else {
+                    script.front().unwrap().clone()
+                }
+            }
+        };
+        (script, provider)
+    }
+
+    fn snapshot(windows: &[(u32, f64)]) -> ContentSnapshot {
+        ContentSnapshot {
+            windows: windows
+                .iter()
+                .map(|&(window_id, x)| WindowInfo {
+                    window_id,
+                    frame: Rect::new(x, 0.0, 100.0, 100.0),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn permission_error() -> Result<ContentSnapshot, CFError> {
+        Err(create_cf_error(
+            "com.apple.ScreenCaptureKit.SCStreamErrorDomain",
+            SC_STREAM_ERROR_USER_DECLINED,
+        ))
+    }
+
+    fn changes(event: Option<ContentEvent>) -> Vec<ContentChange> {
+        match event {
+            Some(ContentEvent::Changed { changes, .. }) => changes,
+            other => panic!("expected changes, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_first_poll_reports_everything() {
+        let (_, provider) = fake_provider(vec![Ok(snapshot(&[(1, 0.0), (2, 0.0)]))]);
+        let mut watcher = ContentWatcher::new(provider);
+        let now = Instant::now();
+        assert!(watcher.next_poll().is_none());
+        let changes = changes(watcher.poll_at(now));
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(changes[0], ContentChange::WindowOpened(_)));
+        assert_eq!(watcher.next_poll(), Some(now + Duration::from_secs(1)));
+        assert!(watcher.poll_at(now + Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn test_debounces_bursts() {
+        let (_, provider) = fake_provider(vec![
+            Ok(snapshot(&[(1, 0.0)])),
+            Ok(snapshot(&[(1, 10.0)])),
+            Ok(snapshot(&[(1, 20.0)])),
+            Ok(snapshot(&[(1, 30.0)])),
+            Ok(snapshot(&[(1, 30.0)])),
+        ]);
+        let mut watcher = ContentWatcher::new(provider)
+            .with_interval(Duration::from_secs(1))
+            .with_debounce(Duration::from_millis(100));
+        let start = Instant::now();
+        changes(watcher.poll_at(start));
+
+        let mut now = start + Duration::from_secs(1);
+        for _ in 0..3 {
+            assert!(watcher.poll_if_due(now).is_none());
+            now = watcher.next_poll().unwrap();
+        }
+        assert_eq!(now, start + Duration::from_millis(1300));
+        assert_eq!(
+            changes(watcher.poll_if_due(now)),
+            vec![ContentChange::WindowMoved {
+                window_id: 1,
+                from: Rect::new(0.0, 0.0, 100.0, 100.0),
+                to: Rect::new(30.0, 0.0, 100.0, 100.0),
+            }]
+        );
+        assert_eq!(watcher.snapshot(), Some(&snapshot(&[(1, 30.0)])));
+    }
+
+    #[test]
+    fn test_continuous_changes_are_reported_every_interval() {
+        let mut x = 0.0;
+        let provider = move || {
+            x += 1.0;
+            Ok(snapshot(&[(1, x)]))
+        };
+        let mut watcher = ContentWatcher::new(provider).with_debounce(Duration::from_millis(300));
+        let start = Instant::now();
+        changes(watcher.poll_at(start));
+        let mut reported = Vec::new();
+        let mut now = start;
+        while now < start + Duration::from_secs(5) {
+            now = watcher.next_poll().unwrap();
+            if watcher.poll_at(now).is_some() {
+                reported.push(now - start);
+            }
+        }
+        assert_eq!(
+            reported,
+            vec![Duration::from_millis(2200), Duration::from_millis(4400)]
+        );
+    }
+
+    #[test]
+    fn test_zero_debounce_reports_at_once() {
+        let (_, provider) = fake_provider(vec![
+            Ok(snapshot(&[(1, 0.0)])),
+            Ok(snapshot(&[(1, 0.0), (2, 0.0)])),
+        ]);
+        let mut watcher = ContentWatcher::new(provider).with_debounce(Duration::ZERO);
+        let now = Instant::now();
+        changes(watcher.poll_at(now));
+        let changes = changes(watcher.poll_at(now + Duration::from_secs(1)));
+        assert!(
+            matches!(&changes[..], [ContentChange::WindowOpened(window)] if window.window_id == 2)
+        );
+    }
+
+    #[test]
+    fn test_backs_off_on_permission_errors() {
+        let (script, provider) = fake_provider(vec![permission_error()]);
+        let mut watcher = ContentWatcher::new(provider).with_max_backoff(Duration::from_secs(10));
+        let mut now = Instant::now();
+        let mut delays = Vec::new();
+        for _ in 0..6 {
+            match watcher.poll_if_due(now) {
+                Some(ContentEvent::Failed {
+                    is_permission_error: true,
+                    retry_in,
+                    ..
+                }) => delays.push(retry_in.as_secs()),
+                other => panic!("expected a permission error, got {other:?}"),
+            }
+            now = watcher.next_poll().unwrap();
+        }
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+
+        *script.lock().unwrap() = vec![Ok(snapshot(&[(1, 0.0)])), permission_error()].into();
+        changes(watcher.poll_if_due(now));
+        now = watcher.next_poll().unwrap();
+        assert!(matches!(
+            watcher.poll_if_due(now),
+            Some(ContentEvent::Failed { retry_in, .. }) if retry_in == Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn test_other_errors_retry_at_interval() {
+        let (_, provider) = fake_provider(vec![Err(create_sc_error("boom"))]);
+        let mut watcher = ContentWatcher::new(provider).with_interval(Duration::from_millis(500));
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(
+                watcher.poll_at(now),
+                Some(ContentEvent::Failed {
+                    message: create_sc_error("boom").to_string(),
+                    is_permission_error: false,
+                    retry_in: Duration::from_millis(500),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_spawned_watcher_sends_events() {
+        let (script, provider) = fake_provider(vec![Ok(snapshot(&[(1, 0.0)]))]);
+        let (handle, receiver) = ContentWatcher::new(provider)
+            .with_interval(Duration::from_millis(5))
+            .with_debounce(Duration::ZERO)
+            .spawn_with_channel();
+        let timeout = Duration::from_secs(5);
+        changes(receiver.recv_timeout(timeout).ok());
+        *script.lock().unwrap() = vec![Ok(snapshot(&[]))].into();
+        assert!(matches!(
+            &changes(receiver.recv_timeout(timeout).ok())[..],
+            [ContentChange::WindowClosed(_)]
+        ));
+        handle.stop();
+        assert!(receiver.recv().is_err());
+    }
+}
@@ -1,3 +1,6 @@
+pub mod content_query;
+pub mod content_snapshot;
+pub mod content_watcher;
 pub mod sc_display;
 pub mod sc_running_application;
 #[allow(clippy::module_name_repetitions)]
@@ -1,7 +1,10 @@
 use core::fmt;
 
 use core_foundation::base::UInt32;
-use core_graphics::geometry::CGRect;
+use core_graphics::{
+    display::CGDisplay,
+    geometry::{CGPoint, CGRect, CGSize},
+};
 pub use internal::{SCDisplay, SCDisplayRef};
 
 use objc::{sel, sel_impl};
@@ -13,10 +16,9 @@ mod internal {
     #![allow(non_snake_case)]
     use std::os::raw::c_void;
 
-    use core_foundation::{
-        base::{CFTypeID, TCFType},
-        declare_TCFType, impl_TCFType,
-    };
+    use core_foundation::{base::CFTypeID, declare_TCFType};
+
+    use crate::utils::macros::impl_TCFType_with_id;
 
     #[repr(C)]
     pub struct __SCDisplayRef(c_void);
@@ -26,7 +28,7 @@ mod internal {
     pub type SCDisplayRef = *mut __SCDisplayRef;
 
     declare_TCFType! {SCDisplay, SCDisplayRef}
-    impl_TCFType!(SCDisplay, SCDisplayRef, SCDisplayGetTypeID);
+    impl_TCFType_with_id!(SCDisplay, SCDisplayRef, SCDisplayGetTypeID, display_id);
 }
 
 impl fmt::Debug for SCDisplay {
@@ -36,6 +38,9 @@ impl fmt::Debug for SCDisplay {
             .field("frame", &self.frame())
             .field("width", &self.width())
             .field("height", &self.height())
+            .field("pixel_width", &self.pixel_width())
+            .field("pixel_height", &self.pixel_height())
+            .field("scale_factor", &self.scale_factor())
             .finish()
     }
 }
@@ -53,9 +58,40 @@ impl SCDisplay {
     pub fn width(&self) -> UInt32 {
         get_property(self, sel!(width))
     }
+    /// Returns the width of the current display mode in pixels, which is larger than
+    /// [`SCDisplay::width`] in points on Retina displays.
+    pub fn pixel_width(&self) -> u64 {
+        CGDisplay::new(self.display_id())
+            .display_mode()
+            .map_or_else(|| self.width().into(), |mode| mode.pixel_width())
+    }
+    /// Returns the height of the current display mode in pixels.
+    pub fn pixel_height(&self) -> u64 {
+        CGDisplay::new(self.display_id())
+            .display_mode()
+            .map_or_else(|| self.height().into(), |mode| mode.pixel_height())
+    }
+    /// Returns the number of pixels per point, e.g. `2.0` on Retina displays.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn scale_factor(&self) -> f64 {
+        match self.width() {
+            0 => 1.0,
+            width => self.pixel_width() as f64 / f64::from(width),
+        }
+    }
+    /// Returns [`SCDisplay::frame`] in pixels instead of points.
+    pub fn frame_in_pixels(&self) -> CGRect {
+        let frame = self.frame();
+        let scale = self.scale_factor();
+        CGRect {
+            origin: CGPoint::new(frame.origin.x * scale, frame.origin.y * scale),
+            size: CGSize::new(frame.size.width * scale, frame.size.height * scale),
+        }
+    }
 }
 #[cfg(test)]
 mod sc_display_test {
+    use std::collections::HashSet;
 
     use crate::shareable_content::sc_shareable_content::SCShareableContent;
 
@@ -69,4 +105,27 @@ mod sc_display_test {
             println!("Display: {d:#?}");
         }
     }
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_pixels() {
+        let content = SCShareableContent::get().expect("Should work");
+        for display in content.displays() {
+            assert!(display.pixel_width() >= u64::from(display.width()));
+            assert!(display.scale_factor() >= 1.0);
+            assert!(display.frame_in_pixels().size.width >= display.frame().size.width);
+        }
+    }
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_equal_by_id() {
+        let displays: HashSet<_> = SCShareableContent::get()
+            .expect("Should work")
+            .displays()
+            .into_iter()
+            .collect();
+        for display in SCShareableContent::get().expect("Should work").displays() {
+            assert!(displays.contains(&display));
+        }
+    }
 }
@@ -2,10 +2,9 @@ mod internal {
     #![allow(non_snake_case)]
     use std::os::raw::c_void;
 
-    use core_foundation::{
-        base::{CFTypeID, TCFType},
-        declare_TCFType, impl_TCFType,
-    };
+    use core_foundation::{base::CFTypeID, declare_TCFType};
+
+    use crate::utils::macros::impl_TCFType_with_id;
 
     #[repr(C)]
     pub struct __SCRunningApplicationRef(c_void);
@@ -15,10 +14,11 @@ mod internal {
     pub type SCRunningApplicationRef = *mut __SCRunningApplicationRef;
 
     declare_TCFType! {SCRunningApplication, SCRunningApplicationRef}
-    impl_TCFType!(
+    impl_TCFType_with_id!(
         SCRunningApplication,
         SCRunningApplicationRef,
-        SCRunningApplicationGetTypeID
+        SCRunningApplicationGetTypeID,
+        process_id
     );
 }
 use core::fmt;
@@ -51,19 +51,31 @@ impl fmt::Debug for SCRunningApplication {
     }
 }
 
-// #[cfg(test)]
-// mod sc_running_application_test {
-//
-//     use crate::shareable_content::sc_shareable_content::SCShareableContent;
-//
-//     #[test]
-//     #[cfg_attr(feature = "ci", ignore)]
-//     fn test_properties() {
-//         let content = SCShareableContent::get().expect("Should work");
-//         let applications = content.applications();
-//         assert!(!applications.is_empty());
-//         for application in applications {
-//             println!("Application: {application:#?}");
-//         }
-//     }
-// }
+#[cfg(test)]
+mod sc_running_application_test {
+    use std::collections::HashSet;
+
+    use crate::shareable_content::sc_shareable_content::SCShareableContent;
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_properties() {
+        let content = SCShareableContent::get().expect("Should work");
+        let applications = content.applications();
+        assert!(!applications.is_empty());
+        for application in applications {
+            println!("Application: {application:#?}");
+        }
+    }
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_equal_by_id() {
+        let content = SCShareableContent::get().expect("Should work");
+        let applications: HashSet<_> = content.applications().into_iter().collect();
+        for window in content.windows() {
+            if let Some(application) = window.try_owning_application() {
+                assert!(applications.contains(&application));
+            }
+        }
+    }
+}
@@ -2,10 +2,9 @@ mod internal {
     #![allow(non_snake_case)]
     use std::os::raw::c_void;
 
-    use core_foundation::{
-        base::{CFTypeID, TCFType},
-        declare_TCFType, impl_TCFType,
-    };
+    use core_foundation::{base::CFTypeID, declare_TCFType};
+
+    use crate::utils::macros::impl_TCFType_with_id;
 
     #[repr(C)]
     pub struct __SCWindowRef(c_void);
@@ -15,27 +14,42 @@ mod internal {
     pub type SCWindowRef = *mut __SCWindowRef;
 
     declare_TCFType! {SCWindow, SCWindowRef}
-    impl_TCFType!(SCWindow, SCWindowRef, SCWindowGetTypeID);
+    impl_TCFType_with_id!(SCWindow, SCWindowRef, SCWindowGetTypeID, window_id);
 }
 pub use internal::{SCWindow, SCWindowRef};
 use std::fmt::{self};
 
-use core_foundation::base::{TCFType, UInt32};
+use core_foundation::base::{SInt32, TCFType, UInt32};
 use core_graphics::geometry::CGRect;
 
 use objc::{msg_send, sel, sel_impl};
 
-use crate::utils::objc::{get_bool_property, get_property, get_string_property, MessageForTFType};
+use crate::utils::objc::{
+    get_bool_property, get_cftype_property, get_property, get_string_property, MessageForTFType,
+};
 
 use super::sc_running_application::{SCRunningApplication, SCRunningApplicationRef};
 
 impl SCWindow {
+    /// # Panics
+    ///
+    /// Panics if the window has no owning application, see
+    /// [`SCWindow::try_owning_application`].
     pub fn owning_application(&self) -> SCRunningApplication {
         unsafe {
             let ptr: SCRunningApplicationRef = msg_send![self.as_sendable(), owningApplication];
             SCRunningApplication::wrap_under_get_rule(ptr)
         }
     }
+    /// Returns the application owning the window, or `None` for windows without one.
+    pub fn try_owning_application(&self) -> Option<SCRunningApplication> {
+        get_cftype_property(self, sel!(owningApplication))
+    }
+    /// Returns the process ID of the application owning the window.
+    pub fn owning_process_id(&self) -> Option<SInt32> {
+        self.try_owning_application()
+            .map(|application| application.process_id())
+    }
     pub fn window_layer(&self) -> UInt32 {
         get_property(self, sel!(windowLayer))
     }
@@ -65,13 +79,15 @@ impl fmt::Debug for SCWindow {
             .field("window_layer", &self.window_layer())
             .field("is_on_screen", &self.is_on_screen())
             .field("is_active", &self.is_active())
-            .field("owning_application", &self.owning_application())
+            .field("frame", &self.get_frame())
+            .field("owning_application", &self.try_owning_application())
             .finish()
     }
 }
 
 #[cfg(test)]
 mod sc_window_test {
+    use std::collections::HashMap;
 
     use crate::shareable_content::{sc_shareable_content::SCShareableContent, sc_window::SCWindow};
 
@@ -85,4 +101,21 @@ mod sc_window_test {
             println!("Window: {window:#?}");
         }
     }
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_equal_by_id() {
+        let windows = SCShareableContent::get().expect("Should work").windows();
+        let ids: HashMap<SCWindow, u32> = windows
+            .iter()
+            .map(|window| (window.clone(), window.window_id()))
+            .collect();
+        assert_eq!(ids.len(), windows.len());
+        let refetched = SCShareableContent::get().expect("Should work").windows();
+        let found = refetched
+            .iter()
+            .filter(|window| ids.get(window) == Some(&window.window_id()))
+            .count();
+        assert!(found > 0);
+    }
 }
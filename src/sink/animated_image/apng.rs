@@ -0,0 +1,197 @@
+//! Animated PNG encoding: chunk framing, scanline filtering and frame control.
+use super::{zlib, Region};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const BYTES_PER_PIXEL: usize = 3;
+/// Offset of the animation control chunk, which is rewritten once the frame count is known.
+pub const ANIMATION_CONTROL_OFFSET: u64 = 33;
+
+#[allow(clippy::cast_possible_truncation)]
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            k += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+};
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn chunk(out: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Returns the signature, an 8 bit `RGB` image header and the animation control chunk.
+pub fn header(width: u32, height: u32, frame_count: u32, loop_count: u16) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    let mut image_header = Vec::with_capacity(13);
+    image_header.extend_from_slice(&width.to_be_bytes());
+    image_header.extend_from_slice(&height.to_be_bytes());
+    image_header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut out, *b"IHDR", &image_header);
+    out.extend(animation_control(frame_count, loop_count));
+    out
+}
+
+pub fn animation_control(frame_count: u32, loop_count: u16) -> Vec<u8> {
+    let mut out = Vec::with_capacity(20);
+    let mut data = frame_count.to_be_bytes().to_vec();
+    data.extend_from_slice(&u32::from(loop_count).to_be_bytes());
+    chunk(&mut out, *b"acTL", &data);
+    out
+}
+
+pub fn trailer() -> Vec<u8> {
+    let mut out = Vec::with_capacity(12);
+    chunk(&mut out, *b"IEND", &[]);
+    out
+}
+
+/// Numbers the frame control and frame data chunks, which share one sequence.
+#[derive(Debug, Default)]
+pub struct FrameSequence {
+    next: u32,
+}
+
+impl FrameSequence {
+    fn next(&mut self) -> [u8; 4] {
+        self.next += 1;
+        (self.next - 1).to_be_bytes()
+    }
+
+    /// Encodes the `RGB` pixels of `region`, replacing that part of the previous frame, for
+    /// `delay` milliseconds. The first frame is stored as the default image.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn frame(&mut self, rgb: &[u8], region: Region, delay: u16) -> Vec<u8> {
+        let is_first = self.next == 0;
+        let mut out = Vec::new();
+        let mut control = self.next().to_vec();
+        for value in [region.width, region.height, region.x, region.y] {
+            control.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        control.extend_from_slice(&delay.to_be_bytes());
+        control.extend_from_slice(&1000_u16.to_be_bytes());
+        // Leave the frame in place and overwrite rather than blend the region.
+        control.extend_from_slice(&[0, 0]);
+        chunk(&mut out, *b"fcTL", &control);
+
+        let compressed = zlib::compress(&filter(rgb, region.width));
+        if is_first {
+            chunk(&mut out, *b"IDAT", &compressed);
+        } else {
+            let mut data = self.next().to_vec();
+            data.extend_from_slice(&compressed);
+            chunk(&mut out, *b"fdAT", &data);
+        }
+        out
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |value: u8| (estimate - i16::from(value)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Prefixes every scanline with the filter type minimising the sum of its residuals.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+fn filter(rgb: &[u8], width: usize) -> Vec<u8> {
+    let stride = width * BYTES_PER_PIXEL;
+    let zero = vec![0; stride];
+    let mut out = Vec::with_capacity(rgb.len() + rgb.len() / stride.max(1));
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+    for (y, row) in rgb.chunks_exact(stride).enumerate() {
+        let up = if y == 0 {
+            &zero
+        } else {
+            &rgb[(y - 1) * stride..y * stride]
+        };
+        let mut best_filter = 0;
+        let mut best_cost = u64::MAX;
+        for filter_type in 0..5_u8 {
+            for x in 0..stride {
+                let left = if x < BYTES_PER_PIXEL {
+                    0
+                } else {
+                    row[x - BYTES_PER_PIXEL]
+                };
+                let up_left = if x < BYTES_PER_PIXEL {
+                    0
+                } else {
+                    up[x - BYTES_PER_PIXEL]
+                };
+                let prediction = match filter_type {
+                    0 => 0,
+                    1 => left,
+                    2 => up[x],
+                    3 => ((u16::from(left) + u16::from(up[x])) / 2) as u8,
+                    _ => paeth(left, up[x], up_left),
+                };
+                candidate[x] = row[x].wrapping_sub(prediction);
+            }
+            let cost = candidate
+                .iter()
+                .map(|value| u64::from((*value as i8).unsigned_abs()))
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_filter = filter_type;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        out.push(best_filter);
+        out.extend_from_slice(&best);
+    }
+    out
+}
+
+#[cfg(test)]
+mod apng_test {
+    use super::{crc32, filter, zlib::adler32};
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_filter_prefers_up_for_repeated_rows() {
+        let rgb = [10, 200, 30, 250, 5, 90].repeat(4);
+        let filtered = filter(&rgb, 2);
+        assert_eq!(filtered.len(), 4 * 7);
+        for row in filtered.chunks_exact(7).skip(1) {
+            assert_eq!(row, [2, 0, 0, 0, 0, 0, 0]);
+        }
+    }
+}
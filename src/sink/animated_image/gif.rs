@@ -0,0 +1,289 @@
+//! `GIF89a` encoding: palette quantisation, LZW compression and block framing.
+use std::collections::HashMap;
+
+use super::Region;
+
+const MAX_CODE: u16 = 4095;
+pub const TRAILER: u8 = 0x3B;
+
+/// Returns the logical screen descriptor and a looping application extension.
+pub fn header(width: u16, height: u16, loop_count: u16) -> Vec<u8> {
+    let mut out = b"GIF89a".to_vec();
+    out.extend_from_slice(&width.to_le_bytes());
+    out.extend_from_slice(&height.to_le_bytes());
+    // No global color table, every frame carries its own palette.
+    out.extend_from_slice(&[0, 0, 0]);
+    out.extend_from_slice(&[0x21, 0xFF, 11]);
+    out.extend_from_slice(b"NETSCAPE2.0");
+    out.extend_from_slice(&[3, 1]);
+    out.extend_from_slice(&loop_count.to_le_bytes());
+    out.push(0);
+    out
+}
+
+/// Encodes the `RGB` pixels of `region` as an image drawn over the previous one for
+/// `delay` hundredths of a second.
+#[allow(clippy::cast_possible_truncation)]
+pub fn frame(rgb: &[u8], region: Region, delay: u16) -> Vec<u8> {
+    let (palette, indices) = quantize(rgb);
+    let table_bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(1);
+
+    // A graphic control extension leaving the frame in place when the next one is drawn.
+    let mut out = vec![0x21, 0xF9, 4, 1 << 2];
+    out.extend_from_slice(&delay.to_le_bytes());
+    out.extend_from_slice(&[0, 0]);
+
+    out.push(0x2C);
+    for value in [region.x, region.y, region.width, region.height] {
+        out.extend_from_slice(&(value as u16).to_le_bytes());
+    }
+    out.push(0x80 | (table_bits - 1) as u8);
+    for index in 0..1 << table_bits {
+        out.extend_from_slice(&palette.get(index).copied().unwrap_or_default());
+    }
+
+    let min_code_size = table_bits.max(2) as u8;
+    out.push(min_code_size);
+    for block in lzw_encode(&indices, min_code_size).chunks(255) {
+        out.push(block.len() as u8);
+        out.extend_from_slice(block);
+    }
+    out.push(0);
+    out
+}
+
+/// Reduces `RGB` pixels to at most 256 colors, returning the palette and an index per pixel.
+///
+/// Screen content rarely has more distinct colors than fit a palette, in which case they
+/// are kept exactly. Otherwise the most frequent colors at 5 bits per channel are chosen
+/// and every pixel is mapped to its nearest palette entry.
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(rgb: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut exact: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(rgb.len() / 3);
+    for pixel in rgb.chunks_exact(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let next = exact.len();
+        if next == 256 && !exact.contains_key(&color) {
+            return quantize_popular(rgb);
+        }
+        indices.push(*exact.entry(color).or_insert_with(|| {
+            palette.push(color);
+            next as u8
+        }));
+    }
+    if palette.is_empty() {
+        palette.push([0, 0, 0]);
+    }
+    (palette, indices)
+}
+
+const fn bucket(pixel: &[u8]) -> usize {
+    (pixel[0] as usize >> 3) << 10 | (pixel[1] as usize >> 3) << 5 | pixel[2] as usize >> 3
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn quantize_popular(rgb: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    // Pixel count and channel sums per bucket, so palette entries are the bucket means.
+    let mut histogram = vec![[0_u32; 4]; 1 << 15];
+    for pixel in rgb.chunks_exact(3) {
+        let entry = &mut histogram[bucket(pixel)];
+        entry[0] += 1;
+        for channel in 0..3 {
+            entry[channel + 1] += u32::from(pixel[channel]);
+        }
+    }
+    let mut buckets: Vec<usize> = (0..histogram.len())
+        .filter(|b| histogram[*b][0] > 0)
+        .collect();
+    buckets.sort_by_key(|b| std::cmp::Reverse(histogram[*b][0]));
+    let palette: Vec<[u8; 3]> = buckets
+        .iter()
+        .take(256)
+        .map(|b| {
+            let [count, r, g, b] = histogram[*b];
+            [(r / count) as u8, (g / count) as u8, (b / count) as u8]
+        })
+        .collect();
+
+    let mut nearest = vec![None; histogram.len()];
+    let indices = rgb
+        .chunks_exact(3)
+        .map(|pixel| {
+            *nearest[bucket(pixel)].get_or_insert_with(|| {
+                (0..palette.len())
+                    .min_by_key(|index| {
+                        palette[*index]
+                            .iter()
+                            .zip(pixel)
+                            .map(|(a, b)| (i32::from(*a) - i32::from(*b)).pow(2))
+                            .sum::<i32>()
+                    })
+                    .unwrap_or_default() as u8
+            })
+        })
+        .collect();
+    (palette, indices)
+}
+
+struct CodeWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl CodeWriter {
+    #[allow(clippy::cast_possible_truncation)]
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= u32::from(code) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+}
+
+/// Compresses palette indices with GIF's variable length LZW, resetting the dictionary when full.
+#[allow(clippy::cast_possible_truncation)]
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1_u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter {
+        out: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    let mut size = u32::from(min_code_size) + 1;
+    let mut next = end + 1;
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    writer.write(clear, size);
+
+    let mut iter = indices.iter();
+    if let Some(first) = iter.next() {
+        let mut prefix = u16::from(*first);
+        for index in iter {
+            if let Some(code) = dictionary.get(&(prefix, *index)) {
+                prefix = *code;
+                continue;
+            }
+            writer.write(prefix, size);
+            if next >= 1 << size && size < 12 {
+                size += 1;
+            }
+            if next >= MAX_CODE {
+                writer.write(clear, size);
+                dictionary.clear();
+                size = u32::from(min_code_size) + 1;
+                next = end + 1;
+            } else {
+                dictionary.insert((prefix, *index), next);
+                next += 1;
+            }
+            prefix = u16::from(*index);
+        }
+        writer.write(prefix, size);
+        if next >= 1 << size && size < 12 {
+            size += 1;
+        }
+    }
+    writer.write(end, size);
+    writer.write(0, 7);
+    writer.out
+}
+
+#[cfg(test)]
+mod gif_test {
+    use super::{lzw_encode, quantize};
+
+    /// A straightforward decoder following the GIF specification.
+    #[allow(clippy::cast_possible_truncation)]
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1_usize << min_code_size;
+        let mut bit = 0;
+        let mut read = |size: usize| {
+            let mut code = 0;
+            for i in 0..size {
+                let byte = data[(bit + i) / 8];
+                code |= usize::from(byte >> ((bit + i) % 8) & 1) << i;
+            }
+            bit += size;
+            code
+        };
+        let mut out = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = usize::from(min_code_size) + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = read(size);
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.extend([vec![], vec![]]);
+                size = usize::from(min_code_size) + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("invalid code"),
+            };
+            out.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([previous, vec![entry[0]]].concat());
+                }
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn test_lzw_round_trip() {
+        let mut state = 7_u32;
+        let noisy: Vec<u8> = (0..50_000)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i % 3 == 0 {
+                    (state >> 24) as u8
+                } else {
+                    (i / 100 % 256) as u8
+                }
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noisy, 8), 8), noisy);
+
+        let few: Vec<u8> = (0..10_000).map(|i| (i % 7 % 4) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&few, 2), 2), few);
+    }
+
+    #[test]
+    fn test_quantize_keeps_exact_colors() {
+        let rgb = [[255, 0, 0], [0, 255, 0], [255, 0, 0], [1, 2, 3]].concat();
+        let (palette, indices) = quantize(&rgb);
+        assert_eq!(palette, vec![[255, 0, 0], [0, 255, 0], [1, 2, 3]]);
+        assert_eq!(indices, vec![0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_quantize_many_colors() {
+        let rgb: Vec<u8> = (0..=255_u8)
+            .flat_map(|r| (0..4_u8).flat_map(move |g| [r, g * 60, 0]))
+            .collect();
+        let (palette, indices) = quantize(&rgb);
+        assert_eq!(palette.len(), 32 * 4);
+        for (pixel, index) in rgb.chunks_exact(3).zip(indices) {
+            let color = palette[usize::from(index)];
+            assert!(color.iter().zip(pixel).all(|(a, b)| a.abs_diff(*b) <= 16));
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Animated GIF and APNG recording for short clips.
+mod apng;
+mod gif;
+pub mod recorder;
+mod zlib;
+
+pub use recorder::{AnimatedImageFormat, AnimatedImageRecorder};
+
+/// The rectangle of the canvas a frame replaces, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
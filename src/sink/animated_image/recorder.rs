@@ -0,0 +1,477 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::media::{
+    media_time::MediaTime,
+    video_frame::{PixelFormat, VideoFrame},
+};
+
+use super::{apng, gif, Region};
+
+/// Frames closer together than this are merged, as viewers slow down shorter GIF delays.
+const MIN_FRAME_INTERVAL: MediaTime = MediaTime::new(20, 1000);
+/// How long the last frame is shown when the clip ends on it.
+const LAST_FRAME_DELAY: MediaTime = MediaTime::new(100, 1000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimatedImageFormat {
+    /// A GIF with a palette of up to 256 colors per frame.
+    Gif,
+    /// An animated PNG keeping full color.
+    Apng,
+}
+
+impl AnimatedImageFormat {
+    /// The number of delay units per second the format stores.
+    const fn timescale(self) -> i32 {
+        match self {
+            Self::Gif => 100,
+            Self::Apng => 1000,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum FormatState {
+    Gif,
+    Apng(apng::FrameSequence),
+}
+
+#[derive(Debug)]
+struct PendingFrame {
+    rgb: Vec<u8>,
+    start: MediaTime,
+}
+
+/// Records captured frames into an animated GIF or APNG, e.g. to attach a short clip to a
+/// bug report.
+///
+/// Frames are downscaled to fit the configured size and only the part that changed since
+/// the previous frame is stored. Frames whose status is not `Complete` or `Started`, like
+/// `Idle` frames sent while the screen is unchanged, and frames identical to the previous
+/// one extend the previous frame's delay instead of being encoded again.
+///
+/// The file is written as frames complete. Once a size budget is set, the first frame that
+/// would exceed it ends the recording, so the finished file always stays within budget.
+#[derive(Debug)]
+pub struct AnimatedImageRecorder<W: Write + Seek> {
+    inner: W,
+    format: AnimatedImageFormat,
+    state: FormatState,
+    max_width: u32,
+    max_height: u32,
+    max_bytes: Option<u64>,
+    loop_count: u16,
+    start_position: u64,
+    size: Option<(usize, usize)>,
+    first_pts: Option<MediaTime>,
+    last_pts: Option<MediaTime>,
+    pending: Option<PendingFrame>,
+    previous: Option<Vec<u8>>,
+    frame_count: u32,
+    bytes_written: u64,
+    is_full: bool,
+}
+
+impl<W: Write + Seek> AnimatedImageRecorder<W> {
+    pub fn new(inner: W, format: AnimatedImageFormat) -> Self {
+        Self {
+            inner,
+            format,
+            state: match format {
+                AnimatedImageFormat::Gif => FormatState::Gif,
+                AnimatedImageFormat::Apng => FormatState::Apng(apng::FrameSequence::default()),
+            },
+            max_width: 640,
+            max_height: 480,
+            max_bytes: None,
+            loop_count: 0,
+            start_position: 0,
+            size: None,
+            first_pts: None,
+            last_pts: None,
+            pending: None,
+            previous: None,
+            frame_count: 0,
+            bytes_written: 0,
+            is_full: false,
+        }
+    }
+
+    /// Sets the size frames are downscaled to fit, keeping their aspect ratio. Defaults to 640x480.
+    #[must_use]
+    pub const fn with_max_size(mut self, max_width: u32, max_height: u32) -> Self {
+        self.max_width = max_width;
+        self.max_height = max_height;
+        self
+    }
+
+    /// Sets the maximum size of the finished file in bytes.
+    #[must_use]
+    pub const fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Sets how many times the clip is played, where 0, the default, loops forever.
+    #[must_use]
+    pub const fn with_loop_count(mut self, loop_count: u16) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    /// Returns the number of frames written so far.
+    pub const fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Returns `true` once a frame did not fit the size budget and the recording has ended.
+    pub const fn is_full(&self) -> bool {
+        self.is_full
+    }
+
+    /// Adds a captured frame, returning `false` once the size budget is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the frame is not BGRA or the underlying writer fails.
+    pub fn push_frame(&mut self, frame: &VideoFrame) -> io::Result<bool> {
+        if self.is_full {
+            return Ok(false);
+        }
+        if frame.pixel_format != PixelFormat::Bgra {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "animated images can only be recorded from BGRA frames",
+            ));
+        }
+        self.last_pts = Some(self.last_pts.map_or(frame.pts, |last| last.max(frame.pts)));
+        if !frame.has_content() {
+            return Ok(true);
+        }
+
+        let (width, height) = *self
+            .size
+            .get_or_insert_with(|| fit(frame.width, frame.height, self.max_width, self.max_height));
+        let rgb = downscale(frame, width, height);
+        let Some(mut pending) = self.pending.take() else {
+            self.pending = Some(PendingFrame {
+                rgb,
+                start: frame.pts,
+            });
+            return Ok(true);
+        };
+        if pending.rgb == rgb {
+            self.pending = Some(pending);
+            return Ok(true);
+        }
+        if frame.pts - pending.start < MIN_FRAME_INTERVAL {
+            pending.rgb = rgb;
+            self.pending = Some(pending);
+            return Ok(true);
+        }
+        if !self.write_frame(&pending, frame.pts)? {
+            self.is_full = true;
+            return Ok(false);
+        }
+        self.pending = Some(PendingFrame {
+            rgb,
+            start: frame.pts,
+        });
+        Ok(true)
+    }
+
+    /// Writes the remaining frame and the trailer and returns the underlying writer.
+    ///
+    /// Nothing is written if no frame fit the size budget.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying writer fails.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(pending) = self.pending.take() {
+            if !self.is_full {
+                let end = self
+                    .last_pts
+                    .filter(|last| *last > pending.start)
+                    .unwrap_or(pending.start + LAST_FRAME_DELAY);
+                self.write_frame(&pending, end)?;
+            }
+        }
+        if self.frame_count == 0 {
+            return Ok(self.inner);
+        }
+        match self.format {
+            AnimatedImageFormat::Gif => self.inner.write_all(&[gif::TRAILER])?,
+            AnimatedImageFormat::Apng => {
+                self.inner.write_all(&apng::trailer())?;
+                let end = self.inner.stream_position()?;
+                self.inner.seek(SeekFrom::Start(
+                    self.start_position + apng::ANIMATION_CONTROL_OFFSET,
+                ))?;
+                self.inner
+                    .write_all(&apng::animation_control(self.frame_count, self.loop_count))?;
+                self.inner.seek(SeekFrom::Start(end))?;
+            }
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    const fn trailer_len(&self) -> u64 {
+        match self.format {
+            AnimatedImageFormat::Gif => 1,
+            AnimatedImageFormat::Apng => 12,
+        }
+    }
+
+    /// Writes `frame` shown until `end`, returning `false` without writing if it exceeds the budget.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn write_frame(&mut self, frame: &PendingFrame, end: MediaTime) -> io::Result<bool> {
+        let (width, height) = self.size.unwrap_or_default();
+        let first_pts = *self.first_pts.get_or_insert(frame.start);
+        let timescale = self.format.timescale();
+        let ticks = |time: MediaTime| (time - first_pts).rescale(timescale).value;
+        let delay = (ticks(end) - ticks(frame.start)).clamp(1, u16::MAX.into()) as u16;
+
+        let region = self
+            .previous
+            .as_ref()
+            .map_or(
+                Some(Region {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                }),
+                |previous| changed_region(previous, &frame.rgb, width),
+            )
+            .unwrap_or(Region {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            });
+        let rgb = crop(&frame.rgb, width, region);
+
+        let mut bytes = if self.frame_count == 0 {
+            self.start_position = self.inner.stream_position()?;
+            match self.format {
+                AnimatedImageFormat::Gif => {
+                    gif::header(width as u16, height as u16, self.loop_count)
+                }
+                AnimatedImageFormat::Apng => {
+                    apng::header(width as u32, height as u32, 0, self.loop_count)
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        match &mut self.state {
+            FormatState::Gif => bytes.extend(gif::frame(&rgb, region, delay)),
+            FormatState::Apng(sequence) => bytes.extend(sequence.frame(&rgb, region, delay)),
+        }
+
+        let total = self.bytes_written + bytes.len() as u64 + self.trailer_len();
+        if self.max_bytes.is_some_and(|max_bytes| total > max_bytes) {
+            return Ok(false);
+        }
+        self.inner.write_all(&bytes)?;
+        self.bytes_written += bytes.len() as u64;
+        self.frame_count += 1;
+        self.previous = Some(frame.rgb.clone());
+        Ok(true)
+    }
+}
+
+/// Returns the largest size with the aspect ratio of `width` by `height` fitting the maximum
+/// size, without upscaling.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn fit(width: u32, height: u32, max_width: u32, max_height: u32) -> (usize, usize) {
+    let scale = (f64::from(max_width) / f64::from(width))
+        .min(f64::from(max_height) / f64::from(height))
+        .min(1.0);
+    let scaled = |value: u32| ((f64::from(value) * scale).round() as usize).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// Averages the BGRA pixels covered by each output pixel into tightly packed `RGB`.
+#[allow(clippy::cast_possible_truncation)]
+fn downscale(frame: &VideoFrame, width: usize, height: usize) -> Vec<u8> {
+    let (source_width, source_height) = (frame.width as usize, frame.height as usize);
+    let span = |index: usize, size: usize, source_size: usize| {
+        let start = index * source_size / size;
+        start..((index + 1) * source_size / size).max(start + 1)
+    };
+    let plane = &frame.planes[0];
+    let mut out = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let rows = span(y, height, source_height);
+        for x in 0..width {
+            let columns = span(x, width, source_width);
+            let mut sum = [0_u32; 3];
+            for row in rows.clone() {
+                let row = plane.row(row);
+                for column in columns.clone() {
+                    let pixel = &row[column * 4..column * 4 + 3];
+                    for channel in 0..3 {
+                        sum[channel] += u32::from(pixel[2 - channel]);
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as u32;
+            out.extend(sum.map(|channel| ((channel + count / 2) / count) as u8));
+        }
+    }
+    out
+}
+
+/// Returns the bounding box of the pixels that differ between two frames of the same size.
+fn changed_region(previous: &[u8], current: &[u8], width: usize) -> Option<Region> {
+    let stride = width * 3;
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (y, (a, b)) in previous
+        .chunks_exact(stride)
+        .zip(current.chunks_exact(stride))
+        .enumerate()
+    {
+        if a == b {
+            continue;
+        }
+        let first = a
+            .chunks_exact(3)
+            .zip(b.chunks_exact(3))
+            .position(|(a, b)| a != b)?;
+        let last = width
+            - 1
+            - a.chunks_exact(3)
+                .rev()
+                .zip(b.chunks_exact(3).rev())
+                .position(|(a, b)| a != b)?;
+        bounds = Some(bounds.map_or((first, y, last, y), |(left, top, right, _)| {
+            (left.min(first), top, right.max(last), y)
+        }));
+    }
+    bounds.map(|(left, top, right, bottom)| Region {
+        x: left,
+        y: top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    })
+}
+
+fn crop(rgb: &[u8], width: usize, region: Region) -> Vec<u8> {
+    let stride = width * 3;
+    (region.y..region.y + region.height)
+        .flat_map(|y| &rgb[y * stride + region.x * 3..y * stride + (region.x + region.width) * 3])
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod recorder_test {
+    use std::io::{self, Cursor};
+
+    use crate::{
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+        output::sc_stream_frame_info::SCFrameStatus,
+    };
+
+    use super::{changed_region, AnimatedImageFormat, AnimatedImageRecorder, Region};
+
+    fn frame(color: u8, pts_ms: i64) -> VideoFrame {
+        let mut frame =
+            VideoFrame::filled_bgra(64, 32, [0, 0, 0, 255], MediaTime::from_millis(pts_ms));
+        for y in 0..8 {
+            frame.planes[0].row_mut(y)[..32].fill(color);
+        }
+        frame
+    }
+
+    /// Returns the delays of the graphic control extensions in a GIF written by the recorder.
+    fn gif_delays(data: &[u8]) -> Vec<u16> {
+        data.windows(4)
+            .enumerate()
+            .filter(|(_, window)| window == &[0x21, 0xF9, 4, 4])
+            .map(|(i, _)| u16::from_le_bytes([data[i + 4], data[i + 5]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_idle_frames_extend_delay() -> io::Result<()> {
+        let mut recorder =
+            AnimatedImageRecorder::new(Cursor::new(Vec::new()), AnimatedImageFormat::Gif)
+                .with_max_size(32, 32);
+        recorder.push_frame(&frame(10, 0).with_status(SCFrameStatus::Started))?;
+        for pts in [100, 200, 300] {
+            recorder.push_frame(&frame(10, pts).with_status(SCFrameStatus::Idle))?;
+        }
+        recorder.push_frame(&frame(10, 400))?;
+        recorder.push_frame(&frame(200, 500))?;
+        recorder.push_frame(&frame(90, 510))?;
+        recorder.push_frame(&frame(90, 900).with_status(SCFrameStatus::Idle))?;
+        assert_eq!(recorder.frame_count(), 1);
+
+        let data = recorder.finish()?.into_inner();
+        assert_eq!(&data[..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([data[6], data[7]]), 32);
+        assert_eq!(u16::from_le_bytes([data[8], data[9]]), 16);
+        assert_eq!(gif_delays(&data), vec![50, 40]);
+        assert_eq!(data.last(), Some(&0x3B));
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn test_size_budget() -> io::Result<()> {
+        let budget = 2_000;
+        let mut recorder =
+            AnimatedImageRecorder::new(Cursor::new(Vec::new()), AnimatedImageFormat::Apng)
+                .with_max_size(64, 32)
+                .with_max_bytes(budget);
+        let mut accepted = 0;
+        for i in 0..100_u8 {
+            let mut frame = frame(0, i64::from(i) * 100);
+            for (j, byte) in frame.planes[0].data.iter_mut().enumerate() {
+                *byte = (j as u8).wrapping_mul(i).wrapping_add(j as u8 / 7);
+            }
+            if !recorder.push_frame(&frame)? {
+                break;
+            }
+            accepted += 1;
+        }
+        assert!(recorder.is_full());
+        assert!(accepted < 100);
+        let frame_count = recorder.frame_count();
+        let data = recorder.finish()?.into_inner();
+        assert!(data.len() as u64 <= budget);
+        assert_eq!(&data[37..41], b"acTL");
+        assert_eq!(
+            u32::from_be_bytes(data[41..45].try_into().unwrap()),
+            frame_count
+        );
+        assert_eq!(&data[data.len() - 8..data.len() - 4], b"IEND");
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_region() {
+        let previous = vec![0; 4 * 3 * 3];
+        let mut current = previous.clone();
+        current[(4 + 1) * 3] = 1;
+        current[(2 * 4 + 2) * 3 + 2] = 1;
+        assert_eq!(
+            changed_region(&previous, &current, 4),
+            Some(Region {
+                x: 1,
+                y: 1,
+                width: 2,
+                height: 2
+            })
+        );
+        assert_eq!(changed_region(&previous, &previous, 4), None);
+    }
+}
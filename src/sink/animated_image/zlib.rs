@@ -0,0 +1,170 @@
+//! Just enough zlib to compress PNG image data: greedy LZ77 matching coded with the fixed
+//! Huffman tables of a single deflate block.
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 64;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const NONE: u32 = u32::MAX;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    #[allow(clippy::cast_possible_truncation)]
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASE.partition_point(|base| usize::from(*base) <= length) - 1;
+        self.write_symbol(257 + index as u32);
+        self.write(
+            (length - usize::from(LENGTH_BASE[index])) as u32,
+            LENGTH_EXTRA[index].into(),
+        );
+        let index = DISTANCE_BASE.partition_point(|base| usize::from(*base) <= distance) - 1;
+        self.write_code(index as u32, 5);
+        self.write(
+            (distance - usize::from(DISTANCE_BASE[index])) as u32,
+            DISTANCE_EXTRA[index].into(),
+        );
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    let key = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn insert(data: &[u8], position: usize, head: &mut [u32], previous: &mut [u32]) {
+    if position + MIN_MATCH <= data.len() {
+        let slot = &mut head[hash(&data[position..])];
+        previous[position] = *slot;
+        *slot = position as u32;
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += u32::from(*byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Compresses `data` into a zlib stream.
+#[allow(clippy::cast_possible_truncation)]
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        out: vec![0x78, 0x01],
+        bits: 0,
+        count: 0,
+    };
+    // A final block using the fixed Huffman codes.
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut previous = vec![NONE; data.len()];
+
+    let mut position = 0;
+    while position < data.len() {
+        let mut best = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(&data[position..])];
+            let mut chain = 0;
+            while candidate != NONE
+                && position - candidate as usize <= WINDOW_SIZE
+                && chain < MAX_CHAIN
+            {
+                let start = candidate as usize;
+                let length = data[start..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, position - start);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[start];
+                chain += 1;
+            }
+        }
+        if best.0 >= MIN_MATCH {
+            writer.write_match(best.0, best.1);
+            for offset in 0..best.0 {
+                insert(data, position + offset, &mut head, &mut previous);
+            }
+            position += best.0;
+        } else {
+            writer.write_symbol(data[position].into());
+            insert(data, position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+    writer.write_symbol(256);
+
+    let mut out = writer.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
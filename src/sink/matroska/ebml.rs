@@ -0,0 +1,247 @@
+use std::io::{self, Read};
+
+pub const EBML: u32 = 0x1A45_DFA3;
+pub const EBML_VERSION: u32 = 0x4286;
+pub const EBML_READ_VERSION: u32 = 0x42F7;
+pub const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+pub const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+pub const DOC_TYPE: u32 = 0x4282;
+pub const DOC_TYPE_VERSION: u32 = 0x4287;
+pub const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+pub const VOID: u32 = 0xEC;
+
+pub const SEGMENT: u32 = 0x1853_8067;
+pub const SEEK_HEAD: u32 = 0x114D_9B74;
+pub const SEEK: u32 = 0x4DBB;
+pub const SEEK_ID: u32 = 0x53AB;
+pub const SEEK_POSITION: u32 = 0x53AC;
+
+pub const INFO: u32 = 0x1549_A966;
+pub const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+pub const DURATION: u32 = 0x4489;
+pub const MUXING_APP: u32 = 0x4D80;
+pub const WRITING_APP: u32 = 0x5741;
+
+pub const TRACKS: u32 = 0x1654_AE6B;
+pub const TRACK_ENTRY: u32 = 0xAE;
+pub const TRACK_NUMBER: u32 = 0xD7;
+pub const TRACK_UID: u32 = 0x73C5;
+pub const TRACK_TYPE: u32 = 0x83;
+pub const FLAG_LACING: u32 = 0x9C;
+pub const NAME: u32 = 0x536E;
+pub const LANGUAGE: u32 = 0x22_B59C;
+pub const CODEC_ID: u32 = 0x86;
+pub const CODEC_PRIVATE: u32 = 0x63A2;
+pub const CODEC_DELAY: u32 = 0x56AA;
+pub const SEEK_PRE_ROLL: u32 = 0x56BB;
+pub const VIDEO: u32 = 0xE0;
+pub const PIXEL_WIDTH: u32 = 0xB0;
+pub const PIXEL_HEIGHT: u32 = 0xBA;
+pub const AUDIO: u32 = 0xE1;
+pub const SAMPLING_FREQUENCY: u32 = 0xB5;
+pub const CHANNELS: u32 = 0x9F;
+pub const BIT_DEPTH: u32 = 0x6264;
+
+pub const CLUSTER: u32 = 0x1F43_B675;
+pub const TIMESTAMP: u32 = 0xE7;
+pub const SIMPLE_BLOCK: u32 = 0xA3;
+
+pub const CUES: u32 = 0x1C53_BB6B;
+pub const CUE_POINT: u32 = 0xBB;
+pub const CUE_TIME: u32 = 0xB3;
+pub const CUE_TRACK_POSITIONS: u32 = 0xB7;
+pub const CUE_TRACK: u32 = 0xF7;
+pub const CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+pub const TAGS: u32 = 0x1254_C367;
+pub const TAG: u32 = 0x7373;
+pub const TARGETS: u32 = 0x63C0;
+pub const TARGET_TYPE_VALUE: u32 = 0x68CA;
+pub const TAG_TRACK_UID: u32 = 0x63C5;
+pub const SIMPLE_TAG: u32 = 0x67C8;
+pub const TAG_NAME: u32 = 0x45A3;
+pub const TAG_STRING: u32 = 0x4487;
+
+/// The reserved "unknown size" value for an eight byte size field.
+pub const UNKNOWN_SIZE: u64 = 0x00FF_FFFF_FFFF_FFFF;
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn write_id(buf: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(3);
+    buf.extend_from_slice(&bytes[skip..]);
+}
+
+/// Writes `size` as a variable length integer of the smallest possible width.
+pub fn write_size(buf: &mut Vec<u8>, size: u64) {
+    let width = (1..=8)
+        .find(|width| size < (1 << (7 * width)) - 1)
+        .unwrap_or(8);
+    write_size_with_width(buf, size, width);
+}
+
+/// Writes `size` as a variable length integer of exactly `width` bytes.
+pub fn write_size_with_width(buf: &mut Vec<u8>, size: u64, width: usize) {
+    let marked = size | (1 << (7 * width));
+    buf.extend_from_slice(&marked.to_be_bytes()[8 - width..]);
+}
+
+pub fn write_element(buf: &mut Vec<u8>, id: u32, data: &[u8]) {
+    write_id(buf, id);
+    write_size(buf, data.len() as u64);
+    buf.extend_from_slice(data);
+}
+
+pub fn write_master(buf: &mut Vec<u8>, id: u32, build: impl FnOnce(&mut Vec<u8>)) {
+    let mut children = Vec::new();
+    build(&mut children);
+    write_element(buf, id, &children);
+}
+
+pub fn write_uint(buf: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    write_element(buf, id, &bytes[skip..]);
+}
+
+/// Writes an unsigned integer padded to eight bytes, so it can be patched in place later.
+pub fn write_fixed_uint(buf: &mut Vec<u8>, id: u32, value: u64) {
+    write_element(buf, id, &value.to_be_bytes());
+}
+
+pub fn write_float(buf: &mut Vec<u8>, id: u32, value: f64) {
+    write_element(buf, id, &value.to_be_bytes());
+}
+
+pub fn write_string(buf: &mut Vec<u8>, id: u32, value: &str) {
+    write_element(buf, id, value.as_bytes());
+}
+
+/// Writes a Void element occupying exactly `total` bytes, header included.
+pub fn write_void(buf: &mut Vec<u8>, total: usize) {
+    debug_assert!(total >= 2, "a void element needs at least two bytes");
+    let width = if total - 1 > 127 { 8 } else { 1 };
+    let payload = total - 1 - width;
+    write_id(buf, VOID);
+    write_size_with_width(buf, payload as u64, width);
+    buf.resize(buf.len() + payload, 0);
+}
+
+/// The position and extent of an element header read from a stream.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementHeader {
+    pub id: u32,
+    /// `None` when the element was written with an unknown size.
+    pub size: Option<u64>,
+    pub header_len: u64,
+}
+
+pub fn read_id(reader: &mut impl Read) -> io::Result<(u32, u64)> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first)?;
+    let width = first[0].leading_zeros() + 1;
+    if width > 4 {
+        return Err(invalid_data("invalid EBML element id"));
+    }
+    let mut id = u32::from(first[0]);
+    for _ in 1..width {
+        reader.read_exact(&mut first)?;
+        id = (id << 8) | u32::from(first[0]);
+    }
+    Ok((id, u64::from(width)))
+}
+
+pub fn read_vint(reader: &mut impl Read) -> io::Result<(Option<u64>, u64)> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    let width = byte[0].leading_zeros() + 1;
+    if width > 8 {
+        return Err(invalid_data("invalid EBML variable length integer"));
+    }
+    let mut value = u64::from(byte[0]) & ((1 << (8 - width)) - 1);
+    let mut all_ones = value == (1 << (8 - width)) - 1;
+    for _ in 1..width {
+        reader.read_exact(&mut byte)?;
+        value = (value << 8) | u64::from(byte[0]);
+        all_ones &= byte[0] == 0xFF;
+    }
+    Ok(((!all_ones).then_some(value), u64::from(width)))
+}
+
+pub fn read_header(reader: &mut impl Read) -> io::Result<ElementHeader> {
+    let (id, id_len) = read_id(reader)?;
+    let (size, size_len) = read_vint(reader)?;
+    Ok(ElementHeader {
+        id,
+        size,
+        header_len: id_len + size_len,
+    })
+}
+
+pub fn read_uint(data: &[u8]) -> u64 {
+    data.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b))
+}
+
+/// Iterates over the direct children of an in-memory master element payload.
+pub fn children(mut data: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    std::iter::from_fn(move || {
+        let mut cursor = data;
+        let header = read_header(&mut cursor).ok()?;
+        let size = usize::try_from(header.size?).ok()?;
+        let payload = cursor.get(..size)?;
+        data = &cursor[size..];
+        Some((header.id, payload))
+    })
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod ebml_test {
+    use super::{
+        children, read_header, read_uint, write_master, write_size, write_uint, write_void,
+        UNKNOWN_SIZE,
+    };
+
+    #[test]
+    fn test_size_round_trip() {
+        for size in [0, 1, 126, 127, 16_382, 16_383, 1 << 30, UNKNOWN_SIZE - 1] {
+            let mut buf = vec![0xEC];
+            write_size(&mut buf, size);
+            let header = read_header(&mut buf.as_slice()).expect("should parse");
+            assert_eq!(header.id, 0xEC);
+            assert_eq!(header.size, Some(size));
+            assert_eq!(header.header_len, buf.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_unknown_size() {
+        let buf = [
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        let header = read_header(&mut buf.as_slice()).expect("should parse");
+        assert_eq!(header.id, 0x1853_8067);
+        assert_eq!(header.size, None);
+        assert_eq!(header.header_len, 12);
+    }
+
+    #[test]
+    fn test_master_children() {
+        let mut buf = Vec::new();
+        write_master(&mut buf, 0xAE, |b| {
+            write_uint(b, 0xD7, 1);
+            write_uint(b, 0x73C5, 0x1234_5678);
+            write_void(b, 10);
+        });
+        let header = read_header(&mut buf.as_slice()).expect("should parse");
+        let payload = &buf[usize::try_from(header.header_len).unwrap()..];
+        let elements: Vec<_> = children(payload).collect();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(read_uint(elements[0].1), 1);
+        assert_eq!(read_uint(elements[1].1), 0x1234_5678);
+        assert_eq!(elements[2].1.len(), 8);
+    }
+}
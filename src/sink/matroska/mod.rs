@@ -0,0 +1,9 @@
+//! A Matroska and `WebM` muxer for encoded capture output.
+mod ebml;
+pub mod recovery;
+pub mod track;
+pub mod writer;
+
+pub use recovery::{recover, RecoveryReport};
+pub use track::{AudioCodec, AudioTrack, DocType, TrackMetadata, VideoCodec, VideoTrack};
+pub use writer::{MatroskaWriter, TrackNumber};
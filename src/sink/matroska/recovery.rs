@@ -0,0 +1,328 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::{
+    ebml::{self, children, invalid_data, read_header, read_uint},
+    writer::{finalize, CuePoint, SegmentLayout, SEEK_HEAD_RESERVED},
+};
+
+/// What [`recover`] found and repaired in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Number of complete clusters kept.
+    pub clusters: usize,
+    /// Timestamp of the last block kept, in milliseconds.
+    pub duration_ms: u64,
+    /// Bytes of incomplete trailing data that were removed.
+    pub truncated_bytes: u64,
+    /// `true` if the file had already been finalized before recovery.
+    pub was_finalized: bool,
+}
+
+/// Repairs a file written by [`super::writer::MatroskaWriter`] that was never finished.
+///
+/// Incomplete trailing data is truncated, and cues, the seek head, the duration and the
+/// segment size are rebuilt from the complete clusters. Running it on a finished file
+/// rewrites the same values.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be opened or was not written by
+/// [`super::writer::MatroskaWriter`].
+pub fn recover(path: impl AsRef<Path>) -> io::Result<RecoveryReport> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    recover_file(&mut file)
+}
+
+/// Repairs an already opened file, see [`recover`].
+///
+/// # Errors
+///
+/// This function will return an error if the file was not written by
+/// [`super::writer::MatroskaWriter`] or can not be read or written.
+pub fn recover_file(file: &mut File) -> io::Result<RecoveryReport> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let header = read_header(file)?;
+    if header.id != ebml::EBML {
+        return Err(invalid_data("not an EBML file"));
+    }
+    let header_size = header
+        .size
+        .ok_or_else(|| invalid_data("invalid EBML header"))?;
+    file.seek(SeekFrom::Current(
+        i64::try_from(header_size).map_err(|_| invalid_data("invalid EBML header"))?,
+    ))?;
+
+    let (segment_id, id_len) = ebml::read_id(file)?;
+    if segment_id != ebml::SEGMENT {
+        return Err(invalid_data("missing segment"));
+    }
+    let segment_size_pos = file.stream_position()?;
+    let (segment_size, _) = ebml::read_vint(file)?;
+    let data_start = file.stream_position()?;
+    debug_assert_eq!(segment_size_pos, header.header_len + header_size + id_len);
+
+    let mut scan = Scan::default();
+    let mut position = data_start;
+    // Existing cues are rebuilt, so they are cut but not reported as truncated data.
+    let (cut, kept) = loop {
+        if position >= file_len {
+            break (file_len, file_len);
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let Ok(element) = read_header(file) else {
+            break (position, position);
+        };
+        let Some(size) = element.size else {
+            break (position, position);
+        };
+        let end = position + element.header_len + size;
+        if end > file_len {
+            break (position, position);
+        }
+        if element.id == ebml::CUES {
+            scan.was_finalized = segment_size.is_some();
+            break (position, end);
+        }
+        let payload_pos = position + element.header_len;
+        match element.id {
+            ebml::VOID | ebml::SEEK_HEAD if scan.seek_head_pos.is_none() => {
+                scan.seek_head_pos = Some(position);
+            }
+            ebml::INFO => {
+                let payload = read_payload(file, size)?;
+                let duration = children(&payload)
+                    .find(|(id, _)| *id == ebml::DURATION)
+                    .ok_or_else(|| invalid_data("segment info has no duration"))?;
+                let offset = duration.1.as_ptr() as usize - payload.as_ptr() as usize;
+                scan.info_pos = Some(position);
+                scan.duration_pos = Some(payload_pos + offset as u64);
+            }
+            ebml::TRACKS => {
+                let payload = read_payload(file, size)?;
+                scan.tracks_pos = Some(position);
+                scan.video_tracks = video_tracks(&payload);
+            }
+            ebml::TAGS => scan.tags_pos = Some(position),
+            ebml::CLUSTER => {
+                let payload = read_payload(file, size)?;
+                scan.add_cluster(&payload, position - data_start);
+            }
+            _ => {}
+        }
+        position = end;
+    };
+
+    let (Some(seek_head_pos), Some(info_pos), Some(tracks_pos), Some(duration_pos)) = (
+        scan.seek_head_pos,
+        scan.info_pos,
+        scan.tracks_pos,
+        scan.duration_pos,
+    ) else {
+        return Err(invalid_data("segment is missing its header elements"));
+    };
+    if info_pos - seek_head_pos != SEEK_HEAD_RESERVED as u64 {
+        return Err(invalid_data("segment has no reserved seek head"));
+    }
+
+    file.set_len(cut)?;
+    let layout = SegmentLayout {
+        segment_size_pos,
+        data_start,
+        seek_head_pos,
+        info_pos,
+        tracks_pos,
+        tags_pos: scan.tags_pos,
+        duration_pos,
+    };
+    #[allow(clippy::cast_precision_loss)]
+    finalize(file, &layout, &scan.cues, scan.duration as f64)?;
+
+    Ok(RecoveryReport {
+        clusters: scan.clusters,
+        duration_ms: scan.duration,
+        truncated_bytes: file_len.saturating_sub(kept),
+        was_finalized: scan.was_finalized,
+    })
+}
+
+#[derive(Debug, Default)]
+struct Scan {
+    seek_head_pos: Option<u64>,
+    info_pos: Option<u64>,
+    duration_pos: Option<u64>,
+    tracks_pos: Option<u64>,
+    tags_pos: Option<u64>,
+    video_tracks: HashSet<u64>,
+    cues: Vec<CuePoint>,
+    clusters: usize,
+    duration: u64,
+    was_finalized: bool,
+}
+
+impl Scan {
+    fn add_cluster(&mut self, payload: &[u8], cluster_position: u64) {
+        let mut timestamp = 0;
+        let mut cue = None;
+        for (id, data) in children(payload) {
+            match id {
+                ebml::TIMESTAMP => timestamp = read_uint(data),
+                ebml::SIMPLE_BLOCK => {
+                    let Some((track, relative, is_keyframe)) = parse_block_header(data) else {
+                        continue;
+                    };
+                    let time = timestamp.saturating_add_signed(relative.into());
+                    self.duration = self.duration.max(time);
+                    let is_cut_point = if self.video_tracks.is_empty() {
+                        true
+                    } else {
+                        is_keyframe && self.video_tracks.contains(&track)
+                    };
+                    if is_cut_point && cue.is_none() {
+                        cue = Some(CuePoint {
+                            time,
+                            track,
+                            cluster_position,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.cues.extend(cue);
+        self.clusters += 1;
+    }
+}
+
+fn read_payload(file: &mut File, size: u64) -> io::Result<Vec<u8>> {
+    let mut payload =
+        vec![0; usize::try_from(size).map_err(|_| invalid_data("element too large"))?];
+    file.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+fn video_tracks(tracks: &[u8]) -> HashSet<u64> {
+    children(tracks)
+        .filter(|(id, _)| *id == ebml::TRACK_ENTRY)
+        .filter_map(|(_, entry)| {
+            let mut number = None;
+            let mut is_video = false;
+            for (id, data) in children(entry) {
+                match id {
+                    ebml::TRACK_NUMBER => number = Some(read_uint(data)),
+                    ebml::TRACK_TYPE => is_video = read_uint(data) == 1,
+                    _ => {}
+                }
+            }
+            number.filter(|_| is_video)
+        })
+        .collect()
+}
+
+fn parse_block_header(mut data: &[u8]) -> Option<(u64, i16, bool)> {
+    let (track, _) = ebml::read_vint(&mut data).ok()?;
+    let relative = i16::from_be_bytes([*data.first()?, *data.get(1)?]);
+    let flags = *data.get(2)?;
+    Some((track?, relative, flags & 0x80 != 0))
+}
+
+#[cfg(test)]
+mod recovery_test {
+    use std::{
+        fs::{self, File},
+        io::{self, Write},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use crate::{
+        media::{encoded_packet::EncodedPacket, media_time::MediaTime},
+        sink::matroska::{
+            track::{DocType, VideoCodec, VideoTrack},
+            writer::MatroskaWriter,
+        },
+    };
+
+    use super::recover;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}.mkv", std::process::id()))
+    }
+
+    fn write_frames(
+        writer: &mut MatroskaWriter<File>,
+        range: std::ops::Range<i64>,
+    ) -> io::Result<()> {
+        let track = super::super::writer::TrackNumber(1);
+        for i in range {
+            let packet = EncodedPacket::new(vec![0xAB; 32], MediaTime::new(i, 10), i % 10 == 0);
+            writer.write_packet(track, &packet)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_crashed_recording() -> io::Result<()> {
+        let path = temp_path("recover-crashed");
+        let mut writer = MatroskaWriter::new(File::create(&path)?, DocType::Matroska)
+            .with_cluster_duration(Duration::from_secs(1));
+        writer.add_video_track(VideoTrack::new(VideoCodec::Other("V_QOI".into()), 4, 4))?;
+        write_frames(&mut writer, 0..25)?;
+        writer.flush()?;
+        drop(writer);
+
+        // Simulate a cluster that was cut off half way through.
+        let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[0x1F, 0x43, 0xB6, 0x75, 0x84, 0x00, 0x10, 0x00])?;
+        drop(file);
+
+        let report = recover(&path)?;
+        assert_eq!(report.clusters, 3);
+        assert_eq!(report.duration_ms, 2400);
+        assert_eq!(report.truncated_bytes, 8);
+        assert!(!report.was_finalized);
+
+        let again = recover(&path)?;
+        assert_eq!(again.clusters, 3);
+        assert_eq!(again.truncated_bytes, 0);
+        assert!(again.was_finalized);
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_recover_matches_finished_file() -> io::Result<()> {
+        let finished = temp_path("recover-finished");
+        let crashed = temp_path("recover-unfinished");
+        for path in [&finished, &crashed] {
+            let mut writer = MatroskaWriter::new(File::create(path)?, DocType::Matroska)
+                .with_cluster_duration(Duration::from_secs(1));
+            writer.add_video_track(VideoTrack::new(VideoCodec::Vp8, 4, 4))?;
+            write_frames(&mut writer, 0..20)?;
+            if path == &finished {
+                writer.finish()?;
+            } else {
+                writer.flush()?;
+            }
+        }
+        let recovered = recover(&crashed)?;
+        let (finished_len, recovered_len) = (
+            fs::metadata(&finished)?.len(),
+            fs::metadata(&crashed)?.len(),
+        );
+        let reference = recover(&finished)?;
+        assert!(reference.was_finalized);
+        assert_eq!(reference.truncated_bytes, 0);
+        assert_eq!(reference.clusters, recovered.clusters);
+        assert_eq!(reference.duration_ms, recovered.duration_ms);
+        assert_eq!(fs::metadata(&finished)?.len(), finished_len);
+        assert!(recovered_len.abs_diff(finished_len) < 16);
+        fs::remove_file(finished)?;
+        fs::remove_file(crashed)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::shareable_content::{
+    sc_display::SCDisplay, sc_running_application::SCRunningApplication, sc_window::SCWindow,
+};
+
+/// The EBML document type of the file being written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocType {
+    #[default]
+    Matroska,
+    /// The `WebM` subset of Matroska, restricted to VP8/VP9/AV1 video and Opus audio.
+    WebM,
+}
+
+impl DocType {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Matroska => "matroska",
+            Self::WebM => "webm",
+        }
+    }
+    pub const fn version(self) -> u64 {
+        match self {
+            Self::Matroska => 4,
+            Self::WebM => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VideoCodec {
+    Vp8,
+    Vp9,
+    Av1,
+    H264,
+    Hevc,
+    /// Any other Matroska codec id, e.g. `V_MS/VFW/FOURCC`.
+    Other(String),
+}
+
+impl VideoCodec {
+    pub fn codec_id(&self) -> &str {
+        match self {
+            Self::Vp8 => "V_VP8",
+            Self::Vp9 => "V_VP9",
+            Self::Av1 => "V_AV1",
+            Self::H264 => "V_MPEG4/ISO/AVC",
+            Self::Hevc => "V_MPEGH/ISO/HEVC",
+            Self::Other(id) => id,
+        }
+    }
+    pub const fn is_webm_compatible(&self) -> bool {
+        matches!(self, Self::Vp8 | Self::Vp9 | Self::Av1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    /// Little endian signed integer PCM.
+    PcmInt {
+        bit_depth: u8,
+    },
+    /// 32 bit IEEE float PCM, the native sample format of `SCStream` audio.
+    PcmFloat,
+    Opus,
+}
+
+impl AudioCodec {
+    pub const fn codec_id(self) -> &'static str {
+        match self {
+            Self::PcmInt { .. } => "A_PCM/INT/LIT",
+            Self::PcmFloat => "A_PCM/FLOAT/IEEE",
+            Self::Opus => "A_OPUS",
+        }
+    }
+    pub const fn bit_depth(self) -> Option<u8> {
+        match self {
+            Self::PcmInt { bit_depth } => Some(bit_depth),
+            Self::PcmFloat => Some(32),
+            Self::Opus => None,
+        }
+    }
+    pub const fn is_webm_compatible(self) -> bool {
+        matches!(self, Self::Opus)
+    }
+}
+
+/// Descriptive metadata stored with a track as its name and a set of simple tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackMetadata {
+    pub name: Option<String>,
+    pub language: Option<String>,
+    pub tags: Vec<(String, String)>,
+}
+
+impl TrackMetadata {
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+    #[must_use]
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+    #[must_use]
+    pub fn with_tag(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((name.into(), value.into()));
+        self
+    }
+
+    /// Describes a track captured from `display`.
+    pub fn from_display(display: &SCDisplay) -> Self {
+        let frame = display.frame();
+        Self::default()
+            .with_name(format!("Display {}", display.display_id()))
+            .with_tag("DISPLAY_ID", display.display_id().to_string())
+            .with_tag("DISPLAY_WIDTH", display.width().to_string())
+            .with_tag("DISPLAY_HEIGHT", display.height().to_string())
+            .with_tag(
+                "DISPLAY_FRAME",
+                format!(
+                    "{},{},{},{}",
+                    frame.origin.x, frame.origin.y, frame.size.width, frame.size.height
+                ),
+            )
+    }
+
+    /// Describes a track captured from `window`, named after its title or owning application,
+    /// or `Window {id}` for untitled windows without one.
+    pub fn from_window(window: &SCWindow) -> Self {
+        let application = window.try_owning_application();
+        let application_name = application
+            .as_ref()
+            .map(SCRunningApplication::application_name)
+            .unwrap_or_default();
+        let title = window.title();
+        let name = if !title.is_empty() {
+            title.clone()
+        } else if !application_name.is_empty() {
+            application_name.clone()
+        } else {
+            format!("Window {}", window.window_id())
+        };
+        let metadata = Self::default()
+            .with_name(name)
+            .with_tag("WINDOW_ID", window.window_id().to_string())
+            .with_tag("WINDOW_TITLE", title);
+        match application {
+            Some(application) => metadata
+                .with_tag("APPLICATION_NAME", application_name)
+                .with_tag("BUNDLE_IDENTIFIER", application.bundle_identifier()),
+            None => metadata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoTrack {
+    pub codec: VideoCodec,
+    pub width: u32,
+    pub height: u32,
+    pub codec_private: Option<Vec<u8>>,
+    pub metadata: TrackMetadata,
+}
+
+impl VideoTrack {
+    pub const fn new(codec: VideoCodec, width: u32, height: u32) -> Self {
+        Self {
+            codec,
+            width,
+            height,
+            codec_private: None,
+            metadata: TrackMetadata {
+                name: None,
+                language: None,
+                tags: Vec::new(),
+            },
+        }
+    }
+    #[must_use]
+    pub fn with_codec_private(mut self, codec_private: Vec<u8>) -> Self {
+        self.codec_private = Some(codec_private);
+        self
+    }
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: TrackMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTrack {
+    pub codec: AudioCodec,
+    pub sample_rate: f64,
+    pub channels: u8,
+    pub codec_private: Option<Vec<u8>>,
+    pub metadata: TrackMetadata,
+}
+
+impl AudioTrack {
+    pub const fn new(codec: AudioCodec, sample_rate: f64, channels: u8) -> Self {
+        Self {
+            codec,
+            sample_rate,
+            channels,
+            codec_private: None,
+            metadata: TrackMetadata {
+                name: None,
+                language: None,
+                tags: Vec::new(),
+            },
+        }
+    }
+    #[must_use]
+    pub fn with_codec_private(mut self, codec_private: Vec<u8>) -> Self {
+        self.codec_private = Some(codec_private);
+        self
+    }
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: TrackMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Builds the `OpusHead` identification header used as Opus codec private data.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn opus_head(&self) -> Vec<u8> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        head.push(self.channels);
+        head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+        head.extend_from_slice(&(self.sample_rate as u32).to_le_bytes());
+        head.extend_from_slice(&0_i16.to_le_bytes());
+        head.push(0);
+        head
+    }
+}
+
+/// Samples at 48 kHz an Opus decoder discards at the start of a stream.
+pub(crate) const OPUS_PRE_SKIP: u16 = 312;
@@ -0,0 +1,585 @@
+use std::{
+    io::{self, Seek, SeekFrom, Write},
+    time::{Duration, SystemTime},
+};
+
+use crate::{media::encoded_packet::EncodedPacket, utils::hash::hash};
+
+use super::{
+    ebml::{
+        self, write_element, write_fixed_uint, write_float, write_id, write_master,
+        write_size_with_width, write_string, write_uint, write_void,
+    },
+    track::{AudioTrack, DocType, TrackMetadata, VideoTrack, OPUS_PRE_SKIP},
+};
+
+/// Bytes reserved after the segment header for the seek head written on finalization.
+pub(super) const SEEK_HEAD_RESERVED: usize = 128;
+/// Nanoseconds per block timestamp unit, i.e. millisecond precision.
+const TIMESTAMP_SCALE: u64 = 1_000_000;
+const MAX_CLUSTER_BYTES: usize = 32 << 20;
+const APPLICATION: &str = concat!("screencapturekit-rs ", env!("CARGO_PKG_VERSION"));
+
+/// The number identifying a track within a file, as returned when adding it to a [`MatroskaWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackNumber(pub u64);
+
+#[derive(Debug)]
+enum TrackEntry {
+    Video(VideoTrack),
+    Audio(AudioTrack),
+}
+
+impl TrackEntry {
+    const fn metadata(&self) -> &TrackMetadata {
+        match self {
+            Self::Video(track) => &track.metadata,
+            Self::Audio(track) => &track.metadata,
+        }
+    }
+    const fn is_video(&self) -> bool {
+        matches!(self, Self::Video(_))
+    }
+}
+
+/// Absolute file offsets of the parts of a segment that are patched when the file is finalized.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SegmentLayout {
+    pub segment_size_pos: u64,
+    pub data_start: u64,
+    pub seek_head_pos: u64,
+    pub info_pos: u64,
+    pub tracks_pos: u64,
+    pub tags_pos: Option<u64>,
+    pub duration_pos: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct CuePoint {
+    pub time: u64,
+    pub track: u64,
+    pub cluster_position: u64,
+}
+
+#[derive(Debug)]
+struct OpenCluster {
+    timestamp: i64,
+    data: Vec<u8>,
+    cue: Option<(u64, u64)>,
+}
+
+/// Writes encoded audio and video packets to a Matroska or `WebM` file.
+///
+/// Clusters are written in full as they are closed, while the segment size, seek head,
+/// cues and duration are only filled in by [`MatroskaWriter::finish`]. A file whose writer
+/// never finished, e.g. because the recording crashed, can be completed with
+/// [`super::recovery::recover`].
+#[derive(Debug)]
+pub struct MatroskaWriter<W: Write + Seek> {
+    inner: W,
+    doc_type: DocType,
+    cluster_duration: Duration,
+    tracks: Vec<(u64, TrackEntry)>,
+    layout: Option<SegmentLayout>,
+    cluster: Option<OpenCluster>,
+    cues: Vec<CuePoint>,
+    end: i64,
+}
+
+impl<W: Write + Seek> MatroskaWriter<W> {
+    pub const fn new(inner: W, doc_type: DocType) -> Self {
+        Self {
+            inner,
+            doc_type,
+            cluster_duration: Duration::from_secs(5),
+            tracks: Vec::new(),
+            layout: None,
+            cluster: None,
+            cues: Vec::new(),
+            end: 0,
+        }
+    }
+
+    /// Sets the minimum duration of a cluster before a new one is started at the next keyframe.
+    ///
+    /// Shorter clusters lose less data on a crash at the cost of a slightly larger file.
+    #[must_use]
+    pub const fn with_cluster_duration(mut self, cluster_duration: Duration) -> Self {
+        self.cluster_duration = cluster_duration;
+        self
+    }
+
+    /// Adds a video track. Tracks must be added before the first packet is written.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if packets have already been written or the codec is not allowed in a `WebM` file.
+    pub fn add_video_track(&mut self, track: VideoTrack) -> io::Result<TrackNumber> {
+        if self.doc_type == DocType::WebM && !track.codec.is_webm_compatible() {
+            return Err(invalid_input("WebM only supports VP8, VP9 and AV1 video"));
+        }
+        self.add_track(TrackEntry::Video(track))
+    }
+
+    /// Adds an audio track. Tracks must be added before the first packet is written.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if packets have already been written or the codec is not allowed in a `WebM` file.
+    pub fn add_audio_track(&mut self, track: AudioTrack) -> io::Result<TrackNumber> {
+        if self.doc_type == DocType::WebM && !track.codec.is_webm_compatible() {
+            return Err(invalid_input("WebM only supports Opus audio"));
+        }
+        self.add_track(TrackEntry::Audio(track))
+    }
+
+    fn add_track(&mut self, track: TrackEntry) -> io::Result<TrackNumber> {
+        if self.layout.is_some() {
+            return Err(invalid_input("tracks must be added before writing packets"));
+        }
+        let number = self.tracks.len() as u64 + 1;
+        self.tracks.push((number, track));
+        Ok(TrackNumber(number))
+    }
+
+    /// Writes a packet to `track`. Packets should be written in decode order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the track is unknown, the packet has a negative
+    /// timestamp or the underlying writer fails.
+    pub fn write_packet(&mut self, track: TrackNumber, packet: &EncodedPacket) -> io::Result<()> {
+        let is_video = self
+            .tracks
+            .iter()
+            .find(|(number, _)| *number == track.0)
+            .map(|(_, entry)| entry.is_video())
+            .ok_or_else(|| invalid_input("unknown track number"))?;
+        let timestamp = packet.pts.as_millis();
+        if timestamp < 0 {
+            return Err(invalid_input("packet timestamps must not be negative"));
+        }
+        self.ensure_header()?;
+
+        let is_keyframe = packet.is_keyframe || !is_video;
+        let has_video = self.tracks.iter().any(|(_, entry)| entry.is_video());
+        let is_cut_point = if has_video {
+            is_video && is_keyframe
+        } else {
+            true
+        };
+        if self.should_start_cluster(timestamp, is_cut_point) {
+            self.close_cluster()?;
+        }
+        let cluster = self.cluster.get_or_insert_with(|| OpenCluster {
+            timestamp,
+            data: Vec::new(),
+            cue: None,
+        });
+        if is_cut_point && cluster.cue.is_none() {
+            cluster.cue = Some((timestamp.unsigned_abs(), track.0));
+        }
+        write_simple_block(cluster, track.0, timestamp, is_keyframe, &packet.data);
+
+        let end = packet.end().map_or(timestamp, |end| end.as_millis());
+        self.end = self.end.max(end);
+        Ok(())
+    }
+
+    fn should_start_cluster(&self, timestamp: i64, is_cut_point: bool) -> bool {
+        let Some(cluster) = &self.cluster else {
+            return false;
+        };
+        let relative = timestamp - cluster.timestamp;
+        let elapsed = Duration::from_millis(relative.unsigned_abs());
+        i16::try_from(relative).is_err()
+            || cluster.data.len() >= MAX_CLUSTER_BYTES
+            || (is_cut_point && relative >= 0 && elapsed >= self.cluster_duration)
+    }
+
+    /// Writes the open cluster and flushes the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying writer fails.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.ensure_header()?;
+        self.close_cluster()?;
+        self.inner.flush()
+    }
+
+    /// Writes the remaining data, cues, seek head and duration and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying writer fails.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn finish(mut self) -> io::Result<W> {
+        let layout = self.ensure_header()?;
+        self.close_cluster()?;
+        finalize(&mut self.inner, &layout, &self.cues, self.end as f64)?;
+        Ok(self.inner)
+    }
+
+    fn close_cluster(&mut self) -> io::Result<()> {
+        let Some(cluster) = self.cluster.take() else {
+            return Ok(());
+        };
+        let layout = self.ensure_header()?;
+        let position = self.inner.seek(SeekFrom::End(0))?;
+        let mut buf = Vec::with_capacity(cluster.data.len() + 16);
+        write_master(&mut buf, ebml::CLUSTER, |b| {
+            write_uint(b, ebml::TIMESTAMP, cluster.timestamp.unsigned_abs());
+            b.extend_from_slice(&cluster.data);
+        });
+        self.inner.write_all(&buf)?;
+        if let Some((time, track)) = cluster.cue {
+            self.cues.push(CuePoint {
+                time,
+                track,
+                cluster_position: position - layout.data_start,
+            });
+        }
+        Ok(())
+    }
+
+    fn ensure_header(&mut self) -> io::Result<SegmentLayout> {
+        if let Some(layout) = self.layout {
+            return Ok(layout);
+        }
+        let start = self.inner.stream_position()?;
+        let mut buf = Vec::new();
+        write_master(&mut buf, ebml::EBML, |b| {
+            write_uint(b, ebml::EBML_VERSION, 1);
+            write_uint(b, ebml::EBML_READ_VERSION, 1);
+            write_uint(b, ebml::EBML_MAX_ID_LENGTH, 4);
+            write_uint(b, ebml::EBML_MAX_SIZE_LENGTH, 8);
+            write_string(b, ebml::DOC_TYPE, self.doc_type.as_str());
+            write_uint(b, ebml::DOC_TYPE_VERSION, self.doc_type.version());
+            write_uint(b, ebml::DOC_TYPE_READ_VERSION, 2);
+        });
+        write_id(&mut buf, ebml::SEGMENT);
+        let segment_size_pos = start + buf.len() as u64;
+        write_size_with_width(&mut buf, ebml::UNKNOWN_SIZE, 8);
+        let data_start = start + buf.len() as u64;
+
+        let seek_head_pos = start + buf.len() as u64;
+        write_void(&mut buf, SEEK_HEAD_RESERVED);
+
+        let info_pos = start + buf.len() as u64;
+        let mut info = Vec::new();
+        write_uint(&mut info, ebml::TIMESTAMP_SCALE, TIMESTAMP_SCALE);
+        write_string(&mut info, ebml::MUXING_APP, APPLICATION);
+        write_string(&mut info, ebml::WRITING_APP, APPLICATION);
+        let duration_offset = info.len() + 3;
+        write_float(&mut info, ebml::DURATION, 0.0);
+        let header_len = buf.len();
+        write_element(&mut buf, ebml::INFO, &info);
+        let info_header_len = buf.len() - header_len - info.len();
+        let duration_pos = info_pos + (info_header_len + duration_offset) as u64;
+
+        let tracks_pos = start + buf.len() as u64;
+        let uids: Vec<u64> = self
+            .tracks
+            .iter()
+            .map(|(number, _)| track_uid(*number))
+            .collect();
+        write_master(&mut buf, ebml::TRACKS, |b| {
+            for ((number, entry), uid) in self.tracks.iter().zip(&uids) {
+                write_track_entry(b, *number, *uid, entry);
+            }
+        });
+
+        let has_tags = self
+            .tracks
+            .iter()
+            .any(|(_, entry)| !entry.metadata().tags.is_empty());
+        let tags_pos = has_tags.then_some(start + buf.len() as u64);
+        if has_tags {
+            write_master(&mut buf, ebml::TAGS, |b| {
+                for ((_, entry), uid) in self.tracks.iter().zip(&uids) {
+                    write_track_tags(b, *uid, entry.metadata());
+                }
+            });
+        }
+
+        self.inner.write_all(&buf)?;
+        let layout = SegmentLayout {
+            segment_size_pos,
+            data_start,
+            seek_head_pos,
+            info_pos,
+            tracks_pos,
+            tags_pos,
+            duration_pos,
+        };
+        self.layout = Some(layout);
+        Ok(layout)
+    }
+}
+
+fn track_uid(number: u64) -> u64 {
+    hash((number, SystemTime::now())) | 1
+}
+
+fn write_track_entry(buf: &mut Vec<u8>, number: u64, uid: u64, entry: &TrackEntry) {
+    write_master(buf, ebml::TRACK_ENTRY, |b| {
+        write_uint(b, ebml::TRACK_NUMBER, number);
+        write_uint(b, ebml::TRACK_UID, uid);
+        write_uint(b, ebml::FLAG_LACING, 0);
+        let metadata = entry.metadata();
+        if let Some(name) = &metadata.name {
+            write_string(b, ebml::NAME, name);
+        }
+        if let Some(language) = &metadata.language {
+            write_string(b, ebml::LANGUAGE, language);
+        }
+        match entry {
+            TrackEntry::Video(track) => {
+                write_uint(b, ebml::TRACK_TYPE, 1);
+                write_string(b, ebml::CODEC_ID, track.codec.codec_id());
+                if let Some(private) = &track.codec_private {
+                    write_element(b, ebml::CODEC_PRIVATE, private);
+                }
+                write_master(b, ebml::VIDEO, |v| {
+                    write_uint(v, ebml::PIXEL_WIDTH, track.width.into());
+                    write_uint(v, ebml::PIXEL_HEIGHT, track.height.into());
+                });
+            }
+            TrackEntry::Audio(track) => {
+                write_uint(b, ebml::TRACK_TYPE, 2);
+                write_string(b, ebml::CODEC_ID, track.codec.codec_id());
+                if track.codec == super::track::AudioCodec::Opus {
+                    let private = track
+                        .codec_private
+                        .clone()
+                        .unwrap_or_else(|| track.opus_head());
+                    write_element(b, ebml::CODEC_PRIVATE, &private);
+                    let pre_skip = u64::from(OPUS_PRE_SKIP) * 1_000_000_000 / 48_000;
+                    write_uint(b, ebml::CODEC_DELAY, pre_skip);
+                    write_uint(b, ebml::SEEK_PRE_ROLL, 80_000_000);
+                } else if let Some(private) = &track.codec_private {
+                    write_element(b, ebml::CODEC_PRIVATE, private);
+                }
+                write_master(b, ebml::AUDIO, |a| {
+                    write_float(a, ebml::SAMPLING_FREQUENCY, track.sample_rate);
+                    write_uint(a, ebml::CHANNELS, track.channels.into());
+                    if let Some(bit_depth) = track.codec.bit_depth() {
+                        write_uint(a, ebml::BIT_DEPTH, bit_depth.into());
+                    }
+                });
+            }
+        }
+    });
+}
+
+fn write_track_tags(buf: &mut Vec<u8>, uid: u64, metadata: &TrackMetadata) {
+    if metadata.tags.is_empty() {
+        return;
+    }
+    write_master(buf, ebml::TAG, |b| {
+        write_master(b, ebml::TARGETS, |t| {
+            write_uint(t, ebml::TARGET_TYPE_VALUE, 50);
+            write_uint(t, ebml::TAG_TRACK_UID, uid);
+        });
+        for (name, value) in &metadata.tags {
+            write_master(b, ebml::SIMPLE_TAG, |s| {
+                write_string(s, ebml::TAG_NAME, name);
+                write_string(s, ebml::TAG_STRING, value);
+            });
+        }
+    });
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_simple_block(
+    cluster: &mut OpenCluster,
+    track: u64,
+    timestamp: i64,
+    is_keyframe: bool,
+    data: &[u8],
+) {
+    let mut header = Vec::with_capacity(4);
+    ebml::write_size(&mut header, track);
+    header.extend_from_slice(&((timestamp - cluster.timestamp) as i16).to_be_bytes());
+    header.push(if is_keyframe { 0x80 } else { 0 });
+    write_id(&mut cluster.data, ebml::SIMPLE_BLOCK);
+    ebml::write_size(&mut cluster.data, (header.len() + data.len()) as u64);
+    cluster.data.extend_from_slice(&header);
+    cluster.data.extend_from_slice(data);
+}
+
+/// Appends cues and patches the seek head, duration and segment size of a segment.
+pub(super) fn finalize<W: Write + Seek>(
+    inner: &mut W,
+    layout: &SegmentLayout,
+    cues: &[CuePoint],
+    duration: f64,
+) -> io::Result<()> {
+    let cues_pos = inner.seek(SeekFrom::End(0))?;
+    if !cues.is_empty() {
+        let mut buf = Vec::new();
+        write_master(&mut buf, ebml::CUES, |b| {
+            for cue in cues {
+                write_master(b, ebml::CUE_POINT, |p| {
+                    write_uint(p, ebml::CUE_TIME, cue.time);
+                    write_master(p, ebml::CUE_TRACK_POSITIONS, |t| {
+                        write_uint(t, ebml::CUE_TRACK, cue.track);
+                        write_uint(t, ebml::CUE_CLUSTER_POSITION, cue.cluster_position);
+                    });
+                });
+            }
+        });
+        inner.write_all(&buf)?;
+    }
+    let end = inner.stream_position()?;
+
+    let mut entries = vec![
+        (ebml::INFO, layout.info_pos),
+        (ebml::TRACKS, layout.tracks_pos),
+    ];
+    if let Some(tags_pos) = layout.tags_pos {
+        entries.push((ebml::TAGS, tags_pos));
+    }
+    if !cues.is_empty() {
+        entries.push((ebml::CUES, cues_pos));
+    }
+    let mut seek_head = Vec::with_capacity(SEEK_HEAD_RESERVED);
+    write_master(&mut seek_head, ebml::SEEK_HEAD, |b| {
+        for (id, position) in entries {
+            write_master(b, ebml::SEEK, |s| {
+                let mut id_bytes = Vec::new();
+                write_id(&mut id_bytes, id);
+                write_element(s, ebml::SEEK_ID, &id_bytes);
+                write_fixed_uint(s, ebml::SEEK_POSITION, position - layout.data_start);
+            });
+        }
+    });
+    let padding = SEEK_HEAD_RESERVED - seek_head.len();
+    write_void(&mut seek_head, padding);
+    inner.seek(SeekFrom::Start(layout.seek_head_pos))?;
+    inner.write_all(&seek_head)?;
+
+    inner.seek(SeekFrom::Start(layout.duration_pos))?;
+    inner.write_all(&duration.to_be_bytes())?;
+
+    let mut size = Vec::with_capacity(8);
+    write_size_with_width(&mut size, end - layout.data_start, 8);
+    inner.seek(SeekFrom::Start(layout.segment_size_pos))?;
+    inner.write_all(&size)?;
+
+    inner.seek(SeekFrom::Start(end))?;
+    inner.flush()
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod writer_test {
+    use std::{
+        io::{self, Cursor},
+        time::Duration,
+    };
+
+    use crate::{
+        media::{encoded_packet::EncodedPacket, media_time::MediaTime},
+        sink::matroska::{
+            ebml::{self, children, read_header, read_uint},
+            track::{AudioCodec, AudioTrack, DocType, TrackMetadata, VideoCodec, VideoTrack},
+        },
+    };
+
+    use super::MatroskaWriter;
+
+    fn segment_children(file: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        let mut cursor = file;
+        let header = read_header(&mut cursor).expect("should have EBML header");
+        assert_eq!(header.id, ebml::EBML);
+        cursor = &cursor[usize::try_from(header.size.unwrap()).unwrap()..];
+        let segment = read_header(&mut cursor).expect("should have segment");
+        assert_eq!(segment.id, ebml::SEGMENT);
+        assert_eq!(segment.size, Some(cursor.len() as u64));
+        children(cursor)
+            .map(|(id, payload)| (id, payload.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_write_webm() -> io::Result<()> {
+        let mut writer = MatroskaWriter::new(Cursor::new(Vec::new()), DocType::WebM)
+            .with_cluster_duration(Duration::from_secs(1));
+        let video = writer.add_video_track(
+            VideoTrack::new(VideoCodec::Vp9, 320, 240).with_metadata(
+                TrackMetadata::default()
+                    .with_name("Display 1")
+                    .with_tag("DISPLAY_ID", "1"),
+            ),
+        )?;
+        let audio = writer.add_audio_track(AudioTrack::new(AudioCodec::Opus, 48_000.0, 2))?;
+        for i in 0..90 {
+            let pts = MediaTime::new(i, 30);
+            let packet = EncodedPacket::new(vec![u8::try_from(i).unwrap(); 16], pts, i % 30 == 0)
+                .with_duration(MediaTime::new(1, 30));
+            writer.write_packet(video, &packet)?;
+            writer.write_packet(audio, &EncodedPacket::new(vec![0; 8], pts, true))?;
+        }
+        let file = writer.finish()?.into_inner();
+
+        let elements = segment_children(&file);
+        let ids: Vec<u32> = elements.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids[0], ebml::SEEK_HEAD);
+        assert_eq!(ids.iter().filter(|id| **id == ebml::CLUSTER).count(), 3);
+        assert_eq!(ids.last(), Some(&ebml::CUES));
+        assert!(ids.contains(&ebml::TAGS));
+
+        let (_, info) = elements.iter().find(|(id, _)| *id == ebml::INFO).unwrap();
+        let (_, duration) = children(info)
+            .find(|(id, _)| *id == ebml::DURATION)
+            .unwrap();
+        let duration = f64::from_be_bytes(duration.try_into().unwrap());
+        assert!((duration - 3000.0).abs() < f64::EPSILON);
+
+        let (_, cues) = elements.iter().find(|(id, _)| *id == ebml::CUES).unwrap();
+        let times: Vec<u64> = children(cues)
+            .map(|(_, point)| {
+                let (_, time) = children(point)
+                    .find(|(id, _)| *id == ebml::CUE_TIME)
+                    .unwrap();
+                read_uint(time)
+            })
+            .collect();
+        assert_eq!(times, vec![0, 1000, 2000]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_webm_rejects_pcm() {
+        let mut writer = MatroskaWriter::new(Cursor::new(Vec::new()), DocType::WebM);
+        assert!(writer
+            .add_audio_track(AudioTrack::new(AudioCodec::PcmFloat, 48_000.0, 2))
+            .is_err());
+        assert!(writer
+            .add_video_track(VideoTrack::new(VideoCodec::H264, 320, 240))
+            .is_err());
+    }
+
+    #[test]
+    fn test_tracks_locked_after_first_packet() -> io::Result<()> {
+        let mut writer = MatroskaWriter::new(Cursor::new(Vec::new()), DocType::Matroska);
+        let audio = writer.add_audio_track(AudioTrack::new(
+            AudioCodec::PcmInt { bit_depth: 16 },
+            48_000.0,
+            1,
+        ))?;
+        writer.write_packet(
+            audio,
+            &EncodedPacket::new(vec![0; 4], MediaTime::ZERO, true),
+        )?;
+        assert!(writer
+            .add_video_track(VideoTrack::new(VideoCodec::Vp8, 2, 2))
+            .is_err());
+        Ok(())
+    }
+}
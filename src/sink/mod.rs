@@ -0,0 +1,6 @@
+pub mod animated_image;
+pub mod matroska;
+#[cfg(feature = "preview")]
+pub mod preview_server;
+pub mod replay_buffer;
+pub mod shared_memory;
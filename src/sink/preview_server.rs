@@ -0,0 +1,791 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    os::fd::AsRawFd,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use core_media_rs::cm_sample_buffer::CMSampleBuffer;
+
+use crate::{
+    encoder::jpeg_encoder::JpegEncoder,
+    media::video_frame::VideoFrame,
+    session::{backend::FrameHandler, screen_capture::SampleBufferConverter},
+    stream::{
+        sc_stream_output_trait::SCStreamOutputTrait, sc_stream_output_type::SCStreamOutputType,
+    },
+};
+
+const BOUNDARY: &str = "frame";
+/// The longest request head a client may send.
+const MAX_REQUEST_SIZE: u64 = 8192;
+/// How often idle streams and the acceptor check whether the server stopped.
+const IDLE_INTERVAL: Duration = Duration::from_millis(250);
+/// How many connections may be open besides the streaming clients, e.g. while they send
+/// their request.
+const MAX_PENDING_CONNECTIONS: usize = 16;
+
+/// The settings of a [`PreviewServer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MjpegPreview {
+    frame_rate: f64,
+    quality: u8,
+    client_timeout: Duration,
+    max_clients: usize,
+}
+
+impl MjpegPreview {
+    /// Creates a preview streaming up to 10 frames per second at quality 75 to up to 8
+    /// clients, dropping clients that don't take a frame within 2 seconds.
+    pub const fn new() -> Self {
+        Self {
+            frame_rate: 10.0,
+            quality: 75,
+            client_timeout: Duration::from_secs(2),
+            max_clients: 8,
+        }
+    }
+
+    /// Sets the most frames per second sent to each client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_rate` is not positive.
+    #[must_use]
+    pub fn with_frame_rate(mut self, frame_rate: f64) -> Self {
+        assert!(frame_rate > 0.0, "the frame rate must be positive");
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    /// Sets the JPEG quality, from 1 to 100.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quality` is not between 1 and 100.
+    #[must_use]
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        assert!(
+            (1..=100).contains(&quality),
+            "the quality must be between 1 and 100"
+        );
+        self.quality = quality;
+        self
+    }
+
+    /// Sets how long a client may take to send its request, or to take each frame, before
+    /// it's dropped, however slowly it keeps sending or reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `client_timeout` is zero.
+    #[must_use]
+    pub const fn with_client_timeout(mut self, client_timeout: Duration) -> Self {
+        assert!(
+            !client_timeout.is_zero(),
+            "the client timeout must not be zero"
+        );
+        self.client_timeout = client_timeout;
+        self
+    }
+
+    /// Sets how many clients may stream at once. More are turned away with a 503, as are
+    /// connections beyond 16 more than that.
+    #[must_use]
+    pub const fn with_max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    /// Starts serving on `address`, e.g. `127.0.0.1:8080`, or port 0 for any free port.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address can't be bound.
+    pub fn serve(self, address: impl ToSocketAddrs) -> io::Result<PreviewServer> {
+        let listener = TcpListener::bind(address)?;
+        // Accepts without blocking, so the acceptor always gets to see the server stopped.
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            latest: Mutex::new(Latest::default()),
+            changed: Condvar::new(),
+            encoder: JpegEncoder::new(self.quality),
+            frame_interval: Duration::from_secs_f64(1.0 / self.frame_rate),
+            client_timeout: self.client_timeout,
+            max_clients: self.max_clients,
+            clients: AtomicUsize::new(0),
+            connections: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let accepting = Arc::clone(&shared);
+        let acceptor = thread::Builder::new()
+            .name("preview-server".into())
+            .spawn(move || accept(&listener, &accepting))?;
+        Ok(PreviewServer {
+            local_addr,
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+impl Default for MjpegPreview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A frame's JPEG, or `None` if the frame can't be encoded.
+type Jpeg = Option<Arc<Vec<u8>>>;
+
+#[derive(Default)]
+struct Latest {
+    frame: Option<Arc<VideoFrame>>,
+    /// Counts the published frames.
+    generation: u64,
+    /// The JPEG of a generation, encoded once for every client, failures included.
+    jpeg: Option<(u64, Jpeg)>,
+    /// The generation a client is encoding, which other clients wait for.
+    encoding: Option<u64>,
+}
+
+struct Shared {
+    latest: Mutex<Latest>,
+    changed: Condvar,
+    encoder: JpegEncoder,
+    frame_interval: Duration,
+    client_timeout: Duration,
+    max_clients: usize,
+    clients: AtomicUsize,
+    /// Counts the open connections, streaming or not.
+    connections: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Latest> {
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn publish(&self, frame: VideoFrame) {
+        let mut latest = self.lock();
+        latest.frame = Some(Arc::new(frame));
+        latest.generation += 1;
+        drop(latest);
+        self.changed.notify_all();
+    }
+
+    /// Waits up to `timeout` for a frame newer than generation `seen`, and returns it
+    /// with its generation as a JPEG, or `None` in its place if it can't be encoded. A frame
+    /// another client is encoding is waited for rather than encoded again.
+    fn next_jpeg(&self, seen: u64, timeout: Duration) -> Option<(u64, Jpeg)> {
+        let (latest, _) = self
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |latest| {
+                latest.generation <= seen && !self.stopped.load(Ordering::Acquire)
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        if latest.generation <= seen {
+            return None;
+        }
+        let mut latest = self
+            .changed
+            .wait_while(latest, |latest| latest.encoding == Some(latest.generation))
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((generation, jpeg)) = &latest.jpeg {
+            if *generation == latest.generation {
+                return Some((*generation, jpeg.clone()));
+            }
+        }
+        let generation = latest.generation;
+        let frame = Arc::clone(latest.frame.as_ref()?);
+        latest.encoding = Some(generation);
+        drop(latest);
+
+        // Encoded unlocked, so publishing never waits for the encoder.
+        let jpeg = self.encoder.encode_frame(&frame).ok().map(Arc::new);
+        let mut latest = self.lock();
+        if latest
+            .jpeg
+            .as_ref()
+            .map_or(true, |(cached, _)| *cached < generation)
+        {
+            latest.jpeg = Some((generation, jpeg.clone()));
+        }
+        if latest.encoding == Some(generation) {
+            latest.encoding = None;
+        }
+        drop(latest);
+        self.changed.notify_all();
+        Some((generation, jpeg))
+    }
+}
+
+/// A local HTTP server previewing the most recent frame, as an MJPEG stream at `/` and
+/// `/stream.mjpeg` and as a single JPEG at `/frame.jpg`.
+///
+/// Frames come from [`Self::handler`] for capture backends, [`Self::output`] for an
+/// `SCStream`, or [`Self::publish`]. Only the latest frame is kept, and it's only
+/// encoded when a client asks for it, at most once. Streaming clients get new frames at
+/// up to the configured frame rate, skipping frames they're too slow for, and are
+/// dropped if sending a frame takes longer than the client timeout.
+///
+/// Dropping the server stops it. Clients still streaming are disconnected once they wait
+/// for their next frame.
+pub struct PreviewServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for PreviewServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreviewServer")
+            .field("local_addr", &self.local_addr)
+            .field("client_count", &self.client_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PreviewServer {
+    /// Returns the address the server listens on, with the port picked for port 0.
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the number of clients streaming.
+    pub fn client_count(&self) -> usize {
+        self.shared.clients.load(Ordering::Acquire)
+    }
+
+    /// Replaces the frame being previewed.
+    pub fn publish(&self, frame: VideoFrame) {
+        self.shared.publish(frame);
+    }
+
+    /// Returns a handler previewing the frames of a capture backend's stream.
+    pub fn handler(&self) -> FrameHandler {
+        let shared = Arc::clone(&self.shared);
+        Arc::new(move |frame| shared.publish(frame))
+    }
+
+    /// Returns an `SCStream` output handler previewing the screen samples it receives,
+    /// converted with `convert`, e.g. [`sample_buffer::video_frame`].
+    ///
+    /// [`sample_buffer::video_frame`]: crate::session::sample_buffer::video_frame
+    pub fn output(&self, convert: SampleBufferConverter) -> PreviewOutput {
+        PreviewOutput {
+            convert,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Release);
+        self.shared.changed.notify_all();
+        // Wakes the acceptor up early. Otherwise it notices within `IDLE_INTERVAL`.
+        let mut wake = self.local_addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(if wake.is_ipv4() {
+                Ipv4Addr::LOCALHOST.into()
+            } else {
+                Ipv6Addr::LOCALHOST.into()
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, IDLE_INTERVAL);
+        // The listener is closed once the acceptor returns.
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+/// Previews the screen samples of an `SCStream`, see [`PreviewServer::output`].
+pub struct PreviewOutput {
+    convert: SampleBufferConverter,
+    shared: Arc<Shared>,
+}
+
+impl SCStreamOutputTrait for PreviewOutput {
+    fn did_output_sample_buffer(&self, sample_buffer: CMSampleBuffer, of_type: SCStreamOutputType) {
+        if of_type != SCStreamOutputType::Screen {
+            return;
+        }
+        if let Some(frame) = (self.convert)(&sample_buffer) {
+            self.shared.publish(frame);
+        }
+    }
+}
+
+fn accept(listener: &TcpListener, shared: &Arc<Shared>) {
+    while !shared.stopped.load(Ordering::Acquire) {
+        let Ok((stream, _)) = listener.accept() else {
+            wait_for_connection(listener, IDLE_INTERVAL);
+            continue;
+        };
+        // Accepted sockets inherit the listener's non-blocking mode on macOS.
+        if stream.set_nonblocking(false).is_err() {
+            continue;
+        }
+        // Counted before spawning, so a flood of connections can't pile up threads.
+        let limit = shared.max_clients.saturating_add(MAX_PENDING_CONNECTIONS);
+        let claimed =
+            shared
+                .connections
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |connections| {
+                    (connections < limit).then_some(connections + 1)
+                });
+        if claimed.is_err() {
+            // Refused without blocking the acceptor, so the response may be cut short.
+            let _ = stream.set_nonblocking(true);
+            let _ = respond(
+                &stream,
+                shared.client_timeout,
+                "503 Service Unavailable",
+                "text/plain",
+                b"too many connections\n",
+            );
+            continue;
+        }
+        let serving = Arc::clone(shared);
+        let spawned = thread::Builder::new()
+            .name("preview-client".into())
+            .spawn(move || {
+                let _slot = ClientSlot(&serving.connections);
+                let _ = serve_client(&stream, &serving);
+            });
+        if spawned.is_err() {
+            shared.connections.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Waits up to `timeout` for a connection to accept.
+fn wait_for_connection(listener: &TcpListener, timeout: Duration) {
+    let mut poll = libc::pollfd {
+        fd: listener.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+    // Failures, like interruptions, only make the acceptor look again sooner.
+    unsafe { libc::poll(&mut poll, 1, timeout) };
+}
+
+/// Reads or writes a stream until `deadline`, however slowly the peer keeps up, by
+/// shortening the socket timeout before each call.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> Deadline<'a> {
+    fn after(stream: &'a TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    fn remaining(&self) -> io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        Ok(remaining)
+    }
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+impl Write for Deadline<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        let mut stream = self.stream;
+        stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut stream = self.stream;
+        stream.flush()
+    }
+}
+
+fn serve_client(stream: &TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut request_line = String::new();
+    let mut head =
+        BufReader::new(Deadline::after(stream, shared.client_timeout).take(MAX_REQUEST_SIZE));
+    head.read_line(&mut request_line)?;
+    // The headers don't matter, but are read so closing doesn't reset the connection.
+    let mut line = String::new();
+    while head.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let path = path.split('?').next().unwrap_or_default();
+    if method != "GET" {
+        return respond(
+            stream,
+            shared.client_timeout,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"GET only\n",
+        );
+    }
+    match path {
+        "/" | "/stream.mjpeg" => stream_frames(stream, shared),
+        "/frame.jpg" => match shared.next_jpeg(0, Duration::ZERO) {
+            Some((_, Some(jpeg))) => {
+                respond(stream, shared.client_timeout, "200 OK", "image/jpeg", &jpeg)
+            }
+            Some((_, None)) => respond(
+                stream,
+                shared.client_timeout,
+                "500 Internal Server Error",
+                "text/plain",
+                b"the frame can't be encoded\n",
+            ),
+            None => respond(
+                stream,
+                shared.client_timeout,
+                "503 Service Unavailable",
+                "text/plain",
+                b"no frame yet\n",
+            ),
+        },
+        _ => respond(
+            stream,
+            shared.client_timeout,
+            "404 Not Found",
+            "text/plain",
+            b"not found\n",
+        ),
+    }
+}
+
+fn respond(
+    stream: &TcpStream,
+    timeout: Duration,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    let mut stream = Deadline::after(stream, timeout);
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Counts a connection or streaming client for as long as it's alive.
+struct ClientSlot<'a>(&'a AtomicUsize);
+
+impl Drop for ClientSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn stream_frames(stream: &TcpStream, shared: &Shared) -> io::Result<()> {
+    let claimed = shared
+        .clients
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |clients| {
+            (clients < shared.max_clients).then_some(clients + 1)
+        });
+    if claimed.is_err() {
+        return respond(
+            stream,
+            shared.client_timeout,
+            "503 Service Unavailable",
+            "text/plain",
+            b"too many clients\n",
+        );
+    }
+    let _slot = ClientSlot(&shared.clients);
+    write!(
+        Deadline::after(stream, shared.client_timeout),
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\n\
+         Cache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut seen = 0;
+    let mut next = Instant::now();
+    while !shared.stopped.load(Ordering::Acquire) {
+        let wait = next.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+        let Some((generation, jpeg)) = shared.next_jpeg(seen, IDLE_INTERVAL) else {
+            continue;
+        };
+        // Frames that can't be encoded are skipped rather than retried.
+        seen = generation;
+        let Some(jpeg) = jpeg else {
+            continue;
+        };
+        // A client that doesn't take the whole frame within the timeout is dropped here.
+        let mut stream = Deadline::after(stream, shared.client_timeout);
+        write!(
+            stream,
+            "--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            jpeg.len()
+        )?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+        next = Instant::now() + shared.frame_interval;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod preview_server_test {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use core_graphics::geometry::CGRect;
+
+    use crate::{
+        geometry::{display_layout::DisplayGeometry, rect::RectExt},
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+        session::{backend::CaptureBackend, synthetic::SyntheticBackend},
+    };
+
+    use super::{MjpegPreview, PreviewServer};
+
+    fn start(server: &PreviewServer) -> SyntheticBackend {
+        let mut backend = SyntheticBackend::new();
+        let display = DisplayGeometry::new(1, CGRect::from_edges(0.0, 0.0, 32.0, 16.0), 1.0);
+        backend
+            .start_display(&display, 32, 16, server.handler())
+            .unwrap();
+        backend
+    }
+
+    fn request(address: SocketAddr, request: &str) -> BufReader<TcpStream> {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        BufReader::new(stream)
+    }
+
+    /// Reads a response or part head, returning its first line and content length.
+    fn read_head(reader: &mut BufReader<TcpStream>) -> (String, usize) {
+        let mut first = String::new();
+        reader.read_line(&mut first).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                return (first.trim().to_string(), length);
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+    }
+
+    fn get(address: SocketAddr, path: &str) -> (String, Vec<u8>) {
+        let mut reader = request(address, &format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n"));
+        let (status, length) = read_head(&mut reader);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        (status, body)
+    }
+
+    /// Returns the size in the `SOF0` segment of a JPEG image.
+    fn jpeg_size(jpeg: &[u8]) -> (u16, u16) {
+        assert_eq!(&jpeg[..2], [0xFF, 0xD8]);
+        let sof = jpeg
+            .windows(2)
+            .position(|pair| pair == [0xFF, 0xC0])
+            .unwrap();
+        let size = |i: usize| u16::from_be_bytes([jpeg[sof + i], jpeg[sof + i + 1]]);
+        (size(7), size(5))
+    }
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn test_serves_frames() {
+        let server = MjpegPreview::new()
+            .with_frame_rate(100.0)
+            .with_quality(60)
+            .with_max_clients(1)
+            .serve("127.0.0.1:0")
+            .unwrap();
+        let address = server.local_addr();
+        let backend = start(&server);
+
+        assert!(get(address, "/frame.jpg").0.contains("503"));
+        assert!(backend.emit(1, [0, 0, 255, 255], MediaTime::ZERO));
+        let (status, jpeg) = get(address, "/frame.jpg?t=1");
+        assert!(status.contains("200"));
+        assert_eq!(jpeg_size(&jpeg), (32, 16));
+        assert!(get(address, "/missing").0.contains("404"));
+        let mut post = request(address, "POST / HTTP/1.1\r\n\r\n");
+        assert!(read_head(&mut post).0.contains("405"));
+
+        let mut stream = request(address, "GET /stream.mjpeg HTTP/1.1\r\n\r\n");
+        let (status, _) = read_head(&mut stream);
+        assert!(status.contains("200"));
+        wait_until(|| server.client_count() == 1);
+        assert!(get(address, "/").0.contains("503"));
+        let mut parts = Vec::new();
+        for bgra in [[255, 0, 0, 255], [0, 255, 0, 255]] {
+            // The latest frame is sent first, then each new one.
+            let (boundary, length) = read_head(&mut stream);
+            assert_eq!(boundary, "--frame");
+            let mut part = vec![0; length + 2];
+            stream.read_exact(&mut part).unwrap();
+            assert_eq!(jpeg_size(&part), (32, 16));
+            parts.push(part);
+            backend.emit(1, bgra, MediaTime::ZERO);
+        }
+        assert_ne!(parts[0], parts[1]);
+        assert_eq!(parts[0][..parts[0].len() - 2], jpeg);
+
+        // A frame that can't be encoded is reported, and skipped by streams.
+        server.publish(VideoFrame::new_bgra(0, 0, Vec::new(), MediaTime::ZERO));
+        assert!(get(address, "/frame.jpg").0.contains("500"));
+        backend.emit(1, [0, 0, 0, 255], MediaTime::ZERO);
+        let (boundary, length) = read_head(&mut stream);
+        assert_eq!(boundary, "--frame");
+        let mut part = vec![0; length + 2];
+        stream.read_exact(&mut part).unwrap();
+        assert_eq!(jpeg_size(&part), (32, 16));
+
+        // A client that went away is noticed at the next frame.
+        drop(stream);
+        for i in 0..50 {
+            backend.emit(1, [i, i, i, 255], MediaTime::ZERO);
+            if server.client_count() == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(server.client_count(), 0);
+
+        // Dropping the server closes its listener.
+        drop(server);
+        TcpListener::bind(address).unwrap();
+    }
+
+    #[test]
+    fn test_drops_slow_clients() {
+        let server = MjpegPreview::new()
+            .with_frame_rate(1000.0)
+            .with_quality(100)
+            .with_client_timeout(Duration::from_millis(100))
+            .serve("127.0.0.1:0")
+            .unwrap();
+        // Connects, but never reads a frame.
+        let _stalled = request(server.local_addr(), "GET / HTTP/1.1\r\n\r\n");
+        // Keeps reading, but too slowly to take a frame within the timeout.
+        let mut draining = request(server.local_addr(), "GET / HTTP/1.1\r\n\r\n");
+        let dropped = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&dropped);
+        let drainer = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            while !stop.load(Ordering::Acquire)
+                && draining.read(&mut buf).is_ok_and(|read| read > 0)
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        wait_until(|| server.client_count() == 2);
+
+        // Noise barely compresses, so the socket buffers fill up quickly.
+        let mut state = 0x2545_F491_u32;
+        for i in 0..2000 {
+            let noise = (0..256 * 256 * 4)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state.to_le_bytes()[0]
+                })
+                .collect();
+            server.publish(VideoFrame::new_bgra(256, 256, noise, MediaTime::new(i, 30)));
+            if server.client_count() == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(server.client_count(), 0);
+        dropped.store(true, Ordering::Release);
+        drainer.join().unwrap();
+    }
+
+    #[test]
+    fn test_limits_connections() {
+        let server = MjpegPreview::new()
+            .with_client_timeout(Duration::from_secs(1))
+            .with_max_clients(1)
+            .serve("127.0.0.1:0")
+            .unwrap();
+        let address = server.local_addr();
+
+        // A request trickling in is dropped once the timeout passes.
+        let mut dripping = TcpStream::connect(address).unwrap();
+        for byte in b"GET / HTTP/1.1\r\n\r\n" {
+            let _ = dripping.write_all(&[*byte]);
+            thread::sleep(Duration::from_millis(100));
+        }
+        dripping
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = dripping.read_to_end(&mut response);
+        assert!(response.is_empty());
+
+        // Connections beyond the streaming clients and 16 more are turned away.
+        let idle: Vec<_> = (0..17)
+            .map(|_| TcpStream::connect(address).unwrap())
+            .collect();
+        let mut refused = request(address, "");
+        assert!(read_head(&mut refused).0.contains("503"));
+        drop(idle);
+        wait_until(|| get(address, "/missing").0.contains("404"));
+    }
+}
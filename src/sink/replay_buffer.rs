@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::media::{encoded_packet::EncodedPacket, media_time::MediaTime};
+
+/// The contents of a [`ReplayBuffer`] ready to be muxed, with timestamps rebased so the
+/// first video keyframe is presented at zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaySnapshot {
+    /// Video packets in decode order, starting with a keyframe.
+    pub video: Vec<EncodedPacket>,
+    /// Audio packets presented at or after the first video frame.
+    pub audio: Vec<EncodedPacket>,
+    /// The original presentation time that was rebased to zero.
+    pub start: MediaTime,
+}
+
+impl ReplaySnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.video.is_empty() && self.audio.is_empty()
+    }
+    /// Returns the time from the start of the snapshot to the end of its last packet.
+    pub fn duration(&self) -> MediaTime {
+        self.video
+            .iter()
+            .chain(&self.audio)
+            .map(|packet| packet.end().unwrap_or(packet.pts))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Keeps the most recently captured audio and video packets in memory to save an instant
+/// replay on demand.
+///
+/// Packets older than the configured duration are evicted, as are the oldest packets once
+/// the buffer holds more than the configured number of bytes. Video is always evicted up
+/// to the next keyframe and audio up to the first remaining video frame, so whatever is
+/// left can be decoded from the start.
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    max_duration: MediaTime,
+    max_bytes: usize,
+    video: VecDeque<EncodedPacket>,
+    audio: VecDeque<EncodedPacket>,
+    newest: Option<MediaTime>,
+    bytes: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(max_duration: Duration, max_bytes: usize) -> Self {
+        Self {
+            max_duration: max_duration.into(),
+            max_bytes,
+            video: VecDeque::new(),
+            audio: VecDeque::new(),
+            newest: None,
+            bytes: 0,
+        }
+    }
+
+    /// Returns the number of payload bytes currently buffered.
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the time between the oldest buffered packet and the end of the newest one.
+    pub fn duration(&self) -> MediaTime {
+        match (self.oldest(), self.newest) {
+            (Some(oldest), Some(newest)) => newest - oldest,
+            _ => MediaTime::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.video.is_empty() && self.audio.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.video.clear();
+        self.audio.clear();
+        self.newest = None;
+        self.bytes = 0;
+    }
+
+    /// Adds a video packet. Packets should be pushed in decode order.
+    pub fn push_video(&mut self, packet: EncodedPacket) {
+        self.push(packet, true);
+    }
+
+    /// Adds an audio packet. Packets should be pushed in presentation order.
+    pub fn push_audio(&mut self, packet: EncodedPacket) {
+        self.push(packet, false);
+    }
+
+    /// Copies the buffered packets, starting at the first video keyframe.
+    ///
+    /// Without buffered video the snapshot starts at the first audio packet.
+    pub fn snapshot(&self) -> ReplaySnapshot {
+        let keyframe = self.video.iter().position(|packet| packet.is_keyframe);
+        let start = match keyframe {
+            Some(index) => self.video[index].pts,
+            None => match self.audio.front() {
+                Some(packet) => packet.pts,
+                None => return ReplaySnapshot::default(),
+            },
+        };
+        let rebase = |packet: &EncodedPacket| EncodedPacket {
+            pts: packet.pts - start,
+            dts: packet.dts - start,
+            ..packet.clone()
+        };
+        ReplaySnapshot {
+            video: keyframe.map_or_else(Vec::new, |index| {
+                self.video.iter().skip(index).map(rebase).collect()
+            }),
+            audio: self
+                .audio
+                .iter()
+                .filter(|packet| packet.pts >= start)
+                .map(rebase)
+                .collect(),
+            start,
+        }
+    }
+
+    fn oldest(&self) -> Option<MediaTime> {
+        let video = self.video.front().map(|packet| packet.pts);
+        let audio = self.audio.front().map(|packet| packet.pts);
+        video.into_iter().chain(audio).min()
+    }
+
+    fn push(&mut self, packet: EncodedPacket, is_video: bool) {
+        let end = packet.end().unwrap_or(packet.pts);
+        self.newest = Some(self.newest.map_or(end, |newest| newest.max(end)));
+        self.bytes += packet.data.len();
+        if is_video {
+            self.video.push_back(packet);
+        } else {
+            self.audio.push_back(packet);
+        }
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        if let Some(newest) = self.newest {
+            let cutoff = newest - self.max_duration;
+            while self.video.front().is_some_and(|packet| packet.pts < cutoff) {
+                self.pop_video();
+            }
+            while self.audio.front().is_some_and(|packet| packet.pts < cutoff) {
+                self.pop_audio();
+            }
+        }
+        loop {
+            self.align();
+            if self.bytes <= self.max_bytes {
+                break;
+            }
+            let video = self.video.front().map(|packet| packet.pts);
+            let audio = self.audio.front().map(|packet| packet.pts);
+            match (video, audio) {
+                (Some(video), Some(audio)) if audio < video => self.pop_audio(),
+                (Some(_), _) => self.pop_video(),
+                (None, Some(_)) => self.pop_audio(),
+                (None, None) => break,
+            }
+        }
+    }
+
+    /// Drops video up to the next keyframe and audio presented before the first video frame.
+    fn align(&mut self) {
+        while self.video.front().is_some_and(|packet| !packet.is_keyframe) {
+            self.pop_video();
+        }
+        if let Some(start) = self.video.front().map(|packet| packet.pts) {
+            while self.audio.front().is_some_and(|packet| packet.pts < start) {
+                self.pop_audio();
+            }
+        }
+    }
+
+    fn pop_video(&mut self) {
+        if let Some(packet) = self.video.pop_front() {
+            self.bytes -= packet.data.len();
+        }
+    }
+
+    fn pop_audio(&mut self) {
+        if let Some(packet) = self.audio.pop_front() {
+            self.bytes -= packet.data.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod replay_buffer_test {
+    use std::time::Duration;
+
+    use crate::media::{encoded_packet::EncodedPacket, media_time::MediaTime};
+
+    use super::ReplayBuffer;
+
+    /// Pushes `seconds` of 10 fps video with a keyframe every second and 50 ms audio packets.
+    fn fill(buffer: &mut ReplayBuffer, from_ms: i64, seconds: i64) {
+        for ms in (from_ms..from_ms + seconds * 1000).step_by(50) {
+            if ms % 100 == 0 {
+                let packet =
+                    EncodedPacket::new(vec![0; 100], MediaTime::from_millis(ms), ms % 1000 == 0)
+                        .with_duration(MediaTime::from_millis(100));
+                buffer.push_video(packet);
+            }
+            let packet = EncodedPacket::new(vec![0; 10], MediaTime::new(ms * 48, 48_000), true)
+                .with_duration(MediaTime::new(2400, 48_000));
+            buffer.push_audio(packet);
+        }
+    }
+
+    #[test]
+    fn test_evicts_by_time() {
+        let mut buffer = ReplayBuffer::new(Duration::from_millis(4500), usize::MAX);
+        fill(&mut buffer, 0, 12);
+        assert!(buffer.duration() <= MediaTime::from_millis(4500));
+
+        let snapshot = buffer.snapshot();
+        assert_eq!(snapshot.start, MediaTime::from_millis(8000));
+        assert!(snapshot.video[0].is_keyframe);
+        assert_eq!(snapshot.video[0].pts, MediaTime::ZERO);
+        assert_eq!(snapshot.video.len(), 40);
+        assert_eq!(snapshot.audio[0].pts, MediaTime::ZERO);
+        assert_eq!(snapshot.audio.len(), 80);
+        assert_eq!(snapshot.duration(), MediaTime::from_millis(4000));
+    }
+
+    #[test]
+    fn test_evicts_by_bytes() {
+        let mut buffer = ReplayBuffer::new(Duration::from_secs(60), 2500);
+        fill(&mut buffer, 0, 10);
+        assert!(buffer.bytes() <= 2500);
+
+        let snapshot = buffer.snapshot();
+        assert_eq!(snapshot.start, MediaTime::from_millis(8000));
+        assert_eq!(snapshot.video.len(), 20);
+        assert_eq!(snapshot.audio.len(), 40);
+        assert_eq!(buffer.bytes(), 2400);
+    }
+
+    #[test]
+    fn test_audio_aligned_to_keyframe() {
+        let mut buffer = ReplayBuffer::new(Duration::from_secs(60), usize::MAX);
+        buffer.push_audio(EncodedPacket::new(
+            vec![0; 10],
+            MediaTime::from_millis(900),
+            true,
+        ));
+        buffer.push_video(EncodedPacket::new(
+            vec![0; 10],
+            MediaTime::from_millis(950),
+            false,
+        ));
+        buffer.push_audio(EncodedPacket::new(
+            vec![0; 10],
+            MediaTime::from_millis(980),
+            true,
+        ));
+        buffer.push_video(EncodedPacket::new(
+            vec![0; 10],
+            MediaTime::from_millis(1000),
+            true,
+        ));
+        buffer.push_audio(EncodedPacket::new(
+            vec![0; 10],
+            MediaTime::from_millis(1010),
+            true,
+        ));
+
+        let snapshot = buffer.snapshot();
+        assert_eq!(snapshot.video.len(), 1);
+        assert_eq!(snapshot.audio.len(), 1);
+        assert_eq!(snapshot.audio[0].pts, MediaTime::from_millis(10));
+        assert_eq!(buffer.bytes(), 20);
+    }
+}
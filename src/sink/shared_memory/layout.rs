@@ -0,0 +1,458 @@
+//! The memory layout of a frame ring, shared by the writer and the reader.
+//!
+//! The file starts with a [`HEADER_SIZE`] header, followed by `slot_count` slots of
+//! `slot_size` bytes. Each slot is a [`SLOT_HEADER_SIZE`] header describing the frame,
+//! followed by its planes. All integers are stored in native byte order, as both ends of
+//! the ring run on the same machine.
+use std::{
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU32, AtomicU64},
+};
+
+use crate::{
+    media::{
+        media_time::MediaTime,
+        video_frame::{FrameInfo, PixelFormat, PixelRect, VideoFrame},
+    },
+    output::sc_stream_frame_info::SCFrameStatus,
+};
+
+pub const MAGIC: [u8; 8] = *b"SCKFRAME";
+pub const VERSION: u32 = 1;
+
+pub const HEADER_SIZE: usize = 4096;
+pub const MAGIC_OFFSET: usize = 0;
+pub const VERSION_OFFSET: usize = 8;
+pub const SLOT_COUNT_OFFSET: usize = 12;
+pub const SLOT_SIZE_OFFSET: usize = 16;
+/// The number of frames published so far, i.e. the index of the next frame.
+pub const WRITE_INDEX_OFFSET: usize = 24;
+pub const PRODUCER_PID_OFFSET: usize = 32;
+/// Non-zero once the writer was dropped.
+pub const CLOSED_OFFSET: usize = 36;
+pub const SOCKET_PATH_LEN_OFFSET: usize = 40;
+pub const SOCKET_PATH_OFFSET: usize = 48;
+/// The longest socket path `sockaddr_un` holds on every platform.
+pub const SOCKET_PATH_MAX: usize = 103;
+/// The consumer table, [`MAX_CONSUMERS`] entries of a process ID and the index of the
+/// next frame that consumer reads.
+pub const CONSUMERS_OFFSET: usize = 160;
+pub const CONSUMER_SIZE: usize = 16;
+pub const MAX_CONSUMERS: usize = 32;
+
+pub const SLOT_HEADER_SIZE: usize = 512;
+/// Twice the index of the frame in the slot plus one while it's written, plus two once
+/// it's complete.
+const SEQUENCE_OFFSET: usize = 0;
+/// Where the fields written without atomics start.
+pub const FIELDS_OFFSET: usize = 8;
+const WIDTH_OFFSET: usize = FIELDS_OFFSET;
+const HEIGHT_OFFSET: usize = 12;
+const FORMAT_OFFSET: usize = 16;
+const PLANE_COUNT_OFFSET: usize = 20;
+/// Up to two planes of a byte offset into the slot data, bytes per row and length.
+const PLANES_OFFSET: usize = 24;
+const PTS_VALUE_OFFSET: usize = 72;
+const PTS_TIMESCALE_OFFSET: usize = 80;
+const STATUS_OFFSET: usize = 84;
+const DISPLAY_TIME_OFFSET: usize = 88;
+const HAS_DISPLAY_TIME_OFFSET: usize = 96;
+/// The number of dirty rects, or `u32::MAX` if they're unknown.
+const DIRTY_COUNT_OFFSET: usize = 100;
+const DIRTY_RECTS_OFFSET: usize = 104;
+/// Frames with more dirty rects are stored without them, i.e. as fully changed.
+pub const MAX_DIRTY_RECTS: usize = 24;
+
+/// Returns the size of a slot holding `capacity` bytes of planes, aligned to a cache line.
+pub const fn slot_size(capacity: usize) -> usize {
+    (SLOT_HEADER_SIZE + capacity + 63) / 64 * 64
+}
+
+/// Returns the offset of the slot that holds frame `index`.
+#[allow(clippy::cast_possible_truncation)]
+pub const fn slot_offset(index: u64, slot_count: u32, slot_size: usize) -> usize {
+    HEADER_SIZE + (index % slot_count as u64) as usize * slot_size
+}
+
+pub const fn consumer_offset(consumer: usize) -> usize {
+    CONSUMERS_OFFSET + consumer * CONSUMER_SIZE
+}
+
+/// The sequence number of slot of frame `index` once the frame is complete.
+pub const fn complete_sequence(index: u64) -> u64 {
+    2 * index + 2
+}
+
+pub const fn sequence_offset(slot: usize) -> usize {
+    slot + SEQUENCE_OFFSET
+}
+
+/// A shared, read-write mapping of a ring file.
+#[derive(Debug)]
+pub struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// The mapping is plain memory, every access goes through atomics or bounds checked copies.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Maps the first `len` bytes of `file`.
+    pub fn new(file: &File, len: usize) -> io::Result<Self> {
+        // SAFETY: a fresh shared mapping of a file we hold open, checked for failure.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        NonNull::new(ptr.cast())
+            .map(|ptr| Self { ptr, len })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "mmap returned null"))
+    }
+
+    fn check(&self, offset: usize, len: usize, align: usize) {
+        assert!(
+            offset % align == 0 && offset.checked_add(len).is_some_and(|end| end <= self.len),
+            "out of bounds access to the frame ring"
+        );
+    }
+
+    #[allow(clippy::cast_ptr_alignment)]
+    pub fn atomic_u64(&self, offset: usize) -> &AtomicU64 {
+        self.check(offset, 8, 8);
+        // SAFETY: in bounds and aligned, as the mapping is page aligned.
+        unsafe { &*self.ptr.as_ptr().add(offset).cast::<AtomicU64>() }
+    }
+
+    #[allow(clippy::cast_ptr_alignment)]
+    pub fn atomic_u32(&self, offset: usize) -> &AtomicU32 {
+        self.check(offset, 4, 4);
+        // SAFETY: in bounds and aligned, as the mapping is page aligned.
+        unsafe { &*self.ptr.as_ptr().add(offset).cast::<AtomicU32>() }
+    }
+
+    /// Copies bytes out of the mapping. They may be torn by a concurrent write, which
+    /// readers detect with the slot's sequence number.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) {
+        self.check(offset, buf.len(), 1);
+        // SAFETY: in bounds, and `buf` can't overlap the mapping.
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr.as_ptr().add(offset), buf.as_mut_ptr(), buf.len());
+        }
+    }
+
+    pub fn write(&self, offset: usize, data: &[u8]) {
+        self.check(offset, data.len(), 1);
+        // SAFETY: in bounds, and `data` can't overlap the mapping.
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.as_ptr().add(offset), data.len());
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `new` mapped, nothing borrows from it anymore.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+/// Where a plane is stored in the slot data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    pub offset: usize,
+    pub bytes_per_row: usize,
+    pub len: usize,
+}
+
+/// Everything about a frame but its pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotHeader {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    pub planes: Vec<PlaneLayout>,
+    pub pts: MediaTime,
+    pub info: FrameInfo,
+}
+
+impl SlotHeader {
+    /// Describes `frame` with its planes stored back to back.
+    pub fn of(frame: &VideoFrame) -> Self {
+        let mut offset = 0;
+        let planes = frame
+            .planes
+            .iter()
+            .map(|plane| {
+                let layout = PlaneLayout {
+                    offset,
+                    bytes_per_row: plane.bytes_per_row,
+                    len: plane.data.len(),
+                };
+                offset += plane.data.len();
+                layout
+            })
+            .collect();
+        Self {
+            width: frame.width,
+            height: frame.height,
+            pixel_format: frame.pixel_format,
+            planes,
+            pts: frame.pts,
+            info: frame.info.clone(),
+        }
+    }
+
+    /// Returns the number of bytes of plane data.
+    pub fn data_len(&self) -> usize {
+        self.planes
+            .iter()
+            .map(|plane| plane.offset + plane.len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Encodes the header, leaving the sequence number zeroed.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn encode(&self) -> [u8; SLOT_HEADER_SIZE] {
+        let mut buf = [0; SLOT_HEADER_SIZE];
+        put(&mut buf, WIDTH_OFFSET, &self.width.to_ne_bytes());
+        put(&mut buf, HEIGHT_OFFSET, &self.height.to_ne_bytes());
+        put(
+            &mut buf,
+            FORMAT_OFFSET,
+            &self.pixel_format.four_char_code().to_ne_bytes(),
+        );
+        put(
+            &mut buf,
+            PLANE_COUNT_OFFSET,
+            &(self.planes.len() as u32).to_ne_bytes(),
+        );
+        for (i, plane) in self.planes.iter().enumerate() {
+            let offset = PLANES_OFFSET + i * 24;
+            put(&mut buf, offset, &(plane.offset as u64).to_ne_bytes());
+            put(
+                &mut buf,
+                offset + 8,
+                &(plane.bytes_per_row as u64).to_ne_bytes(),
+            );
+            put(&mut buf, offset + 16, &(plane.len as u64).to_ne_bytes());
+        }
+        put(&mut buf, PTS_VALUE_OFFSET, &self.pts.value.to_ne_bytes());
+        put(
+            &mut buf,
+            PTS_TIMESCALE_OFFSET,
+            &self.pts.timescale.to_ne_bytes(),
+        );
+        put(
+            &mut buf,
+            STATUS_OFFSET,
+            &(self.info.status as i32).to_ne_bytes(),
+        );
+        if let Some(display_time) = self.info.display_time {
+            put(&mut buf, DISPLAY_TIME_OFFSET, &display_time.to_ne_bytes());
+            put(&mut buf, HAS_DISPLAY_TIME_OFFSET, &1u32.to_ne_bytes());
+        }
+        match &self.info.dirty_rects {
+            Some(rects) if rects.len() <= MAX_DIRTY_RECTS => {
+                put(
+                    &mut buf,
+                    DIRTY_COUNT_OFFSET,
+                    &(rects.len() as u32).to_ne_bytes(),
+                );
+                for (i, rect) in rects.iter().enumerate() {
+                    let offset = DIRTY_RECTS_OFFSET + i * 16;
+                    for (j, value) in [rect.x, rect.y, rect.width, rect.height]
+                        .into_iter()
+                        .enumerate()
+                    {
+                        put(&mut buf, offset + j * 4, &value.to_ne_bytes());
+                    }
+                }
+            }
+            _ => put(&mut buf, DIRTY_COUNT_OFFSET, &u32::MAX.to_ne_bytes()),
+        }
+        buf
+    }
+
+    /// Decodes a header, checking its planes fit in `capacity` bytes.
+    pub fn decode(buf: &[u8; SLOT_HEADER_SIZE], capacity: usize) -> io::Result<Self> {
+        let pixel_format = match get_u32(buf, FORMAT_OFFSET) {
+            code if code == PixelFormat::Bgra.four_char_code() => PixelFormat::Bgra,
+            code if code == PixelFormat::Nv12.four_char_code() => PixelFormat::Nv12,
+            _ => return Err(invalid_data("unknown pixel format")),
+        };
+        let plane_count = get_u32(buf, PLANE_COUNT_OFFSET) as usize;
+        if plane_count != pixel_format.plane_count() {
+            return Err(invalid_data("invalid plane count"));
+        }
+        let width = get_u32(buf, WIDTH_OFFSET);
+        let height = get_u32(buf, HEIGHT_OFFSET);
+        let (width_px, height_px) = (width as usize, height as usize);
+        // The bytes of a row's pixels and the number of rows of each plane.
+        let sizes = match pixel_format {
+            PixelFormat::Bgra => vec![(width_px.checked_mul(4), height_px)],
+            PixelFormat::Nv12 => vec![
+                (Some(width_px), height_px),
+                (Some((width_px + 1) / 2 * 2), (height_px + 1) / 2),
+            ],
+        };
+        let planes = (0..plane_count)
+            .zip(sizes)
+            .map(|(i, (row_bytes, rows))| {
+                let offset = PLANES_OFFSET + i * 24;
+                let plane = PlaneLayout {
+                    offset: get_usize(buf, offset)?,
+                    bytes_per_row: get_usize(buf, offset + 8)?,
+                    len: get_usize(buf, offset + 16)?,
+                };
+                match plane.offset.checked_add(plane.len) {
+                    Some(end) if end <= capacity => {}
+                    _ => return Err(invalid_data("plane out of bounds")),
+                }
+                // Rows must hold the frame's pixels, and the plane all of its rows, so
+                // readers can't be handed a frame whose rows are out of bounds.
+                match (row_bytes, plane.bytes_per_row.checked_mul(rows)) {
+                    (Some(row_bytes), Some(size))
+                        if plane.bytes_per_row >= row_bytes && size <= plane.len =>
+                    {
+                        Ok(plane)
+                    }
+                    _ => Err(invalid_data("plane too small for the frame")),
+                }
+            })
+            .collect::<io::Result<_>>()?;
+        let status = match get_u32(buf, STATUS_OFFSET) {
+            0 => SCFrameStatus::Complete,
+            1 => SCFrameStatus::Idle,
+            2 => SCFrameStatus::Blank,
+            3 => SCFrameStatus::Suspended,
+            4 => SCFrameStatus::Started,
+            5 => SCFrameStatus::Stopped,
+            _ => return Err(invalid_data("unknown frame status")),
+        };
+        let dirty_rects = match get_u32(buf, DIRTY_COUNT_OFFSET) as usize {
+            count if count <= MAX_DIRTY_RECTS => Some(
+                (0..count)
+                    .map(|i| {
+                        let offset = DIRTY_RECTS_OFFSET + i * 16;
+                        PixelRect::new(
+                            get_u32(buf, offset),
+                            get_u32(buf, offset + 4),
+                            get_u32(buf, offset + 8),
+                            get_u32(buf, offset + 12),
+                        )
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Ok(Self {
+            width,
+            height,
+            pixel_format,
+            planes,
+            pts: MediaTime {
+                value: i64::from_ne_bytes(get(buf, PTS_VALUE_OFFSET)),
+                timescale: i32::from_ne_bytes(get(buf, PTS_TIMESCALE_OFFSET)),
+            },
+            info: FrameInfo {
+                status,
+                display_time: (get_u32(buf, HAS_DISPLAY_TIME_OFFSET) != 0)
+                    .then(|| u64::from_ne_bytes(get(buf, DISPLAY_TIME_OFFSET))),
+                dirty_rects,
+            },
+        })
+    }
+}
+
+fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+    buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn get<const N: usize>(buf: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0; N];
+    bytes.copy_from_slice(&buf[offset..offset + N]);
+    bytes
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(get(buf, offset))
+}
+
+fn get_usize(buf: &[u8], offset: usize) -> io::Result<usize> {
+    usize::try_from(u64::from_ne_bytes(get(buf, offset)))
+        .map_err(|_| invalid_data("plane out of bounds"))
+}
+
+/// Returns whether process `pid` still exists, even if it's a zombie.
+pub fn process_alive(pid: i32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists.
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod layout_test {
+    use crate::{
+        media::{
+            media_time::MediaTime,
+            video_frame::{FrameInfo, PixelRect, VideoFrame},
+        },
+        output::sc_stream_frame_info::SCFrameStatus,
+    };
+
+    use super::{SlotHeader, MAX_DIRTY_RECTS};
+
+    #[test]
+    fn test_slot_header() {
+        let frame = VideoFrame::new_nv12(3, 3, vec![16; 9], vec![128; 8], MediaTime::new(7, 30))
+            .with_info(FrameInfo {
+                status: SCFrameStatus::Started,
+                display_time: Some(123_456_789),
+                dirty_rects: Some(vec![PixelRect::new(1, 2, 3, 4)]),
+            });
+        let header = SlotHeader::of(&frame);
+        assert_eq!(header.data_len(), 17);
+        assert_eq!(header.planes[1].offset, 9);
+        let decoded = SlotHeader::decode(&header.encode(), 17).unwrap();
+        assert_eq!(decoded, header);
+        assert!(SlotHeader::decode(&header.encode(), 16).is_err());
+        for (plane, bytes_per_row) in [(0, 0), (0, 2), (0, 4), (1, 2)] {
+            let mut corrupt = header.clone();
+            corrupt.planes[plane].bytes_per_row = bytes_per_row;
+            assert!(SlotHeader::decode(&corrupt.encode(), 17).is_err());
+        }
+
+        let mut many = frame.info.clone();
+        many.dirty_rects = Some(vec![PixelRect::new(0, 0, 1, 1); MAX_DIRTY_RECTS + 1]);
+        let header = SlotHeader::of(&frame.with_info(many));
+        assert_eq!(
+            SlotHeader::decode(&header.encode(), 17)
+                .unwrap()
+                .info
+                .dirty_rects,
+            None
+        );
+        assert!(SlotHeader::decode(&[0; super::SLOT_HEADER_SIZE], 17).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+//! Frame transport to other processes through shared memory, e.g. to feed an inference
+//! process without encoding.
+//!
+//! The transport only relies on Unix shared memory and sockets, but the rest of the crate
+//! binds macOS frameworks, so it and its two process tests only build on macOS for now.
+mod layout;
+mod notify;
+pub mod reader;
+pub mod writer;
+
+pub use reader::{SharedFrame, SharedFrameReader};
+pub use writer::{ConsumerInfo, SharedFrameRing, SharedFrameWriter};
@@ -0,0 +1,108 @@
+//! Wake-ups for readers blocked on a frame ring, over a Unix domain socket.
+//!
+//! The writer sends one byte to every connected reader per published frame. The bytes only
+//! wake readers up, the ring itself says which frames are available, so notifications
+//! that don't fit a slow reader's socket buffer are simply dropped.
+use std::{
+    fs, io,
+    os::{
+        fd::AsRawFd,
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const SEND_FLAGS: libc::c_int = 0;
+
+#[derive(Debug)]
+pub struct Notifier {
+    listener: UnixListener,
+    path: PathBuf,
+    readers: Vec<UnixStream>,
+}
+
+impl Notifier {
+    /// Listens on `path`, replacing a socket a previous writer left behind.
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        match fs::remove_file(path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            readers: Vec::new(),
+        })
+    }
+
+    pub fn reader_count(&self) -> usize {
+        self.readers.len()
+    }
+
+    /// Accepts readers that connected since the last call.
+    pub fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    no_sigpipe(&stream)?;
+                    self.readers.push(stream);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Wakes every reader up, dropping the ones that disconnected.
+    pub fn notify(&mut self) -> io::Result<()> {
+        self.accept()?;
+        self.readers.retain(|stream| {
+            // SAFETY: sends one byte from a live buffer on a socket we own.
+            let sent =
+                unsafe { libc::send(stream.as_raw_fd(), [1u8].as_ptr().cast(), 1, SEND_FLAGS) };
+            sent == 1 || io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock
+        });
+        Ok(())
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Keeps writes to a reader that died from raising `SIGPIPE` where `MSG_NOSIGNAL` isn't
+/// available.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[allow(clippy::cast_possible_truncation)]
+fn no_sigpipe(stream: &UnixStream) -> io::Result<()> {
+    let on: libc::c_int = 1;
+    // SAFETY: sets an integer socket option from a live value.
+    let result = unsafe {
+        libc::setsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_NOSIGPIPE,
+            std::ptr::addr_of!(on).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+#[allow(clippy::unnecessary_wraps)]
+const fn no_sigpipe(_stream: &UnixStream) -> io::Result<()> {
+    Ok(())
+}
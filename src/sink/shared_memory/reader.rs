@@ -0,0 +1,390 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read},
+    mem,
+    os::unix::{ffi::OsStrExt, net::UnixStream},
+    path::Path,
+    sync::atomic::{fence, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::media::video_frame::{VideoFrame, VideoPlane};
+
+use super::layout::{
+    complete_sequence, consumer_offset, invalid_data, process_alive, sequence_offset, slot_offset,
+    Mapping, SlotHeader, CLOSED_OFFSET, FIELDS_OFFSET, HEADER_SIZE, MAGIC, MAGIC_OFFSET,
+    MAX_CONSUMERS, PRODUCER_PID_OFFSET, SLOT_COUNT_OFFSET, SLOT_HEADER_SIZE, SLOT_SIZE_OFFSET,
+    SOCKET_PATH_LEN_OFFSET, SOCKET_PATH_MAX, SOCKET_PATH_OFFSET, VERSION, VERSION_OFFSET,
+    WRITE_INDEX_OFFSET,
+};
+
+/// How often readers without a notification socket check for new frames.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// A frame read from a [`SharedFrameReader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedFrame {
+    /// The index of the frame, counting every frame the writer published.
+    pub index: u64,
+    /// The number of frames skipped since the previous read, because the writer
+    /// overwrote them before they were read.
+    pub skipped: u64,
+    pub frame: VideoFrame,
+}
+
+/// Reads the frames a [`SharedFrameWriter`](super::SharedFrameWriter) publishes in
+/// another process.
+///
+/// A reader starts at the most recent frame, so readers attaching late get the current
+/// frame right away, and then reads every frame that follows. Reading never blocks the
+/// writer, a reader that falls behind skips the frames that were overwritten, see
+/// [`SharedFrame::skipped`].
+#[derive(Debug)]
+pub struct SharedFrameReader {
+    mapping: Mapping,
+    slot_count: u32,
+    slot_size: usize,
+    producer_pid: i32,
+    consumer: usize,
+    position: u64,
+    skipped: u64,
+    socket: Option<UnixStream>,
+}
+
+impl SharedFrameReader {
+    /// Attaches to the ring at `path`, and to its notification socket if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ring can't be opened, isn't a frame ring, or already has
+    /// the maximum number of readers attached.
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options().read(true).write(true).open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| invalid_data("the frame ring is too large"))?;
+        if len < HEADER_SIZE {
+            return Err(invalid_data("not a frame ring"));
+        }
+        let mapping = Mapping::new(&file, len)?;
+        let mut magic = [0; 8];
+        mapping.read(MAGIC_OFFSET, &mut magic);
+        if magic != MAGIC || read_u32(&mapping, VERSION_OFFSET) != VERSION {
+            return Err(invalid_data("not a frame ring"));
+        }
+        let slot_count = read_u32(&mapping, SLOT_COUNT_OFFSET);
+        let mut slot_size = [0; 8];
+        mapping.read(SLOT_SIZE_OFFSET, &mut slot_size);
+        let slot_size = usize::try_from(u64::from_ne_bytes(slot_size))
+            .map_err(|_| invalid_data("invalid slot size"))?;
+        let fits = slot_size
+            .checked_mul(slot_count as usize)
+            .and_then(|slots| slots.checked_add(HEADER_SIZE))
+            .is_some_and(|end| end <= len);
+        if slot_count == 0 || slot_size < SLOT_HEADER_SIZE || slot_size % 8 != 0 || !fits {
+            return Err(invalid_data("invalid frame ring size"));
+        }
+
+        let process_id = std::process::id();
+        let consumer = (0..MAX_CONSUMERS)
+            .find(|&consumer| {
+                mapping
+                    .atomic_u32(consumer_offset(consumer))
+                    .compare_exchange(0, process_id, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "too many readers are attached"))?;
+        let position = mapping
+            .atomic_u64(WRITE_INDEX_OFFSET)
+            .load(Ordering::Acquire)
+            .saturating_sub(1);
+        mapping
+            .atomic_u64(consumer_offset(consumer) + 8)
+            .store(position, Ordering::Relaxed);
+
+        let socket_path_len =
+            (read_u32(&mapping, SOCKET_PATH_LEN_OFFSET) as usize).min(SOCKET_PATH_MAX);
+        let mut socket_path = vec![0; socket_path_len];
+        mapping.read(SOCKET_PATH_OFFSET, &mut socket_path);
+        // Without the socket, e.g. because the writer just exited, the reader polls.
+        let socket = (!socket_path.is_empty())
+            .then(|| UnixStream::connect(OsStr::from_bytes(&socket_path)).ok())
+            .flatten();
+        Ok(Self {
+            producer_pid: read_u32(&mapping, PRODUCER_PID_OFFSET) as i32,
+            mapping,
+            slot_count,
+            slot_size,
+            consumer,
+            position,
+            skipped: 0,
+            socket,
+        })
+    }
+
+    /// Returns the index of the next frame to read.
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the number of frames the writer published so far.
+    pub fn published(&self) -> u64 {
+        self.mapping
+            .atomic_u64(WRITE_INDEX_OFFSET)
+            .load(Ordering::Acquire)
+    }
+
+    /// Returns `false` once the writer was dropped or its process died.
+    pub fn is_writer_alive(&self) -> bool {
+        self.mapping
+            .atomic_u32(CLOSED_OFFSET)
+            .load(Ordering::Acquire)
+            == 0
+            && process_alive(self.producer_pid)
+    }
+
+    /// Returns the next frame if the writer published one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ring is corrupted.
+    pub fn try_read(&mut self) -> io::Result<Option<SharedFrame>> {
+        loop {
+            let published = self.published();
+            if self.position >= published {
+                return Ok(None);
+            }
+            let oldest = published.saturating_sub(u64::from(self.slot_count));
+            if self.position < oldest {
+                self.skipped += oldest - self.position;
+                self.position = oldest;
+            }
+            let frame = self.read_slot(self.position)?;
+            let index = self.position;
+            self.position += 1;
+            self.mapping
+                .atomic_u64(consumer_offset(self.consumer) + 8)
+                .store(self.position, Ordering::Relaxed);
+            match frame {
+                Some(frame) => {
+                    return Ok(Some(SharedFrame {
+                        index,
+                        skipped: mem::take(&mut self.skipped),
+                        frame,
+                    }))
+                }
+                // Overwritten while it was read.
+                None => self.skipped += 1,
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for the next frame. Returns `None` if none was published in
+    /// time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::UnexpectedEof`] error once the writer is gone and every
+    /// frame left in the ring was read, or an error if the ring is corrupted.
+    pub fn read(&mut self, timeout: Duration) -> io::Result<Option<SharedFrame>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Checked first so frames published right before the writer closed are read.
+            let alive = self.is_writer_alive();
+            if let Some(frame) = self.try_read()? {
+                return Ok(Some(frame));
+            }
+            if !alive {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the writer closed the frame ring",
+                ));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            self.wait(deadline - now)?;
+        }
+    }
+
+    /// Blocks until the writer publishes a frame, at most for `timeout`.
+    fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+        let Some(socket) = &mut self.socket else {
+            thread::sleep(timeout.min(POLL_INTERVAL));
+            return Ok(());
+        };
+        // A zero timeout means blocking forever.
+        socket.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+        let mut wakeups = [0; 64];
+        match socket.read(&mut wakeups) {
+            // The writer closed the socket, poll from now on.
+            Ok(0) => self.socket = None,
+            Ok(_) => {}
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) => {}
+            Err(error) => return Err(error),
+        }
+        Ok(())
+    }
+
+    /// Copies frame `index` out of its slot, or returns `None` if it was overwritten.
+    fn read_slot(&self, index: u64) -> io::Result<Option<VideoFrame>> {
+        let slot = slot_offset(index, self.slot_count, self.slot_size);
+        let sequence = self.mapping.atomic_u64(sequence_offset(slot));
+        let expected = complete_sequence(index);
+        if sequence.load(Ordering::Acquire) != expected {
+            return Ok(None);
+        }
+        let mut buf = [0; SLOT_HEADER_SIZE];
+        self.mapping
+            .read(slot + FIELDS_OFFSET, &mut buf[FIELDS_OFFSET..]);
+        // A torn header may not decode, which only matters if the slot wasn't overwritten.
+        let header = SlotHeader::decode(&buf, self.slot_size - SLOT_HEADER_SIZE);
+        let planes: Vec<VideoPlane> = header
+            .as_ref()
+            .map(|header| {
+                header
+                    .planes
+                    .iter()
+                    .map(|layout| {
+                        let mut data = vec![0; layout.len];
+                        self.mapping
+                            .read(slot + SLOT_HEADER_SIZE + layout.offset, &mut data);
+                        VideoPlane::new(data, layout.bytes_per_row)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        fence(Ordering::Acquire);
+        if sequence.load(Ordering::Relaxed) != expected {
+            return Ok(None);
+        }
+        let header = header?;
+        Ok(Some(VideoFrame {
+            width: header.width,
+            height: header.height,
+            pixel_format: header.pixel_format,
+            planes,
+            pts: header.pts,
+            info: header.info,
+        }))
+    }
+}
+
+impl Drop for SharedFrameReader {
+    fn drop(&mut self) {
+        self.mapping
+            .atomic_u32(consumer_offset(self.consumer))
+            .store(0, Ordering::Release);
+    }
+}
+
+fn read_u32(mapping: &Mapping, offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    mapping.read(offset, &mut bytes);
+    u32::from_ne_bytes(bytes)
+}
+
+#[cfg(test)]
+mod reader_test {
+    use std::{env, io, path::PathBuf, process::Command, thread, time::Duration};
+
+    use crate::{
+        media::{media_time::MediaTime, video_frame::VideoFrame},
+        sink::shared_memory::SharedFrameRing,
+    };
+
+    use super::SharedFrameReader;
+
+    const RING_ENV: &str = "SCREENCAPTUREKIT_TEST_RING";
+    const ROLE_ENV: &str = "SCREENCAPTUREKIT_TEST_ROLE";
+    const FRAME_COUNT: u8 = 20;
+
+    fn frame(index: u8) -> VideoFrame {
+        VideoFrame::filled_bgra(8, 8, [index, 1, 2, 255], MediaTime::new(index.into(), 30))
+    }
+
+    /// Runs this test again in a child process, as one of the roles of [`child`].
+    fn spawn(path: &PathBuf, role: &str) -> std::process::Child {
+        let (_, module) = module_path!().split_once("::").unwrap();
+        Command::new(env::current_exe().unwrap())
+            .args([
+                &format!("{module}::test_two_processes"),
+                "--exact",
+                "--nocapture",
+            ])
+            .env(RING_ENV, path)
+            .env(ROLE_ENV, role)
+            .spawn()
+            .unwrap()
+    }
+
+    fn child(path: &str, role: &str) {
+        let mut reader = SharedFrameReader::open(path).unwrap();
+        if role == "crash" {
+            // Exits without detaching.
+            std::process::exit(0);
+        }
+        let mut last = None;
+        loop {
+            match reader.read(Duration::from_secs(10)) {
+                Ok(Some(shared)) => {
+                    assert!(last.map_or(true, |last| shared.index > last));
+                    assert_eq!(shared.frame, frame(u8::try_from(shared.index).unwrap()));
+                    last = Some(shared.index);
+                }
+                Ok(None) => panic!("timed out waiting for a frame"),
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => panic!("{error}"),
+            }
+        }
+        assert_eq!(last, Some(u64::from(FRAME_COUNT - 1)));
+    }
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        for _ in 0..1000 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn test_two_processes() {
+        if let (Ok(path), Ok(role)) = (env::var(RING_ENV), env::var(ROLE_ENV)) {
+            return child(&path, &role);
+        }
+        let id = std::process::id();
+        let path = env::temp_dir().join(format!("sck-reader-{id}.ring"));
+        let mut writer = SharedFrameRing::for_size(8, 8)
+            .with_notifications(env::temp_dir().join(format!("sck-reader-{id}.sock")))
+            .create(&path)
+            .unwrap();
+
+        // A reader that dies is dropped from the consumers and the socket.
+        let status = spawn(&path, "crash").wait().unwrap();
+        assert!(status.success());
+        assert!(writer.consumers().is_empty());
+        writer.write(&frame(0)).unwrap();
+        assert_eq!(writer.notified_count().unwrap(), 0);
+
+        let mut reader = spawn(&path, "read");
+        wait_until(|| writer.consumers().len() == 1 && writer.notified_count().unwrap() == 1);
+        for index in 1..FRAME_COUNT {
+            writer.write(&frame(index)).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+        drop(writer);
+        assert!(reader.wait().unwrap().success());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::atomic::{fence, Ordering},
+};
+
+use crate::media::video_frame::VideoFrame;
+
+use super::{
+    layout::{
+        complete_sequence, consumer_offset, process_alive, sequence_offset, slot_offset, slot_size,
+        Mapping, SlotHeader, CLOSED_OFFSET, FIELDS_OFFSET, HEADER_SIZE, MAGIC, MAGIC_OFFSET,
+        MAX_CONSUMERS, PRODUCER_PID_OFFSET, SLOT_COUNT_OFFSET, SLOT_HEADER_SIZE, SLOT_SIZE_OFFSET,
+        SOCKET_PATH_LEN_OFFSET, SOCKET_PATH_MAX, SOCKET_PATH_OFFSET, VERSION, VERSION_OFFSET,
+        WRITE_INDEX_OFFSET,
+    },
+    notify::Notifier,
+};
+
+/// The shape of a frame ring, see [`SharedFrameWriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedFrameRing {
+    frame_capacity: usize,
+    slot_count: u32,
+    socket_path: Option<PathBuf>,
+}
+
+impl SharedFrameRing {
+    /// Creates a ring of 4 slots holding frames of up to `frame_capacity` bytes of pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_capacity` is zero.
+    pub fn new(frame_capacity: usize) -> Self {
+        assert!(frame_capacity > 0, "the frame capacity must not be zero");
+        Self {
+            frame_capacity,
+            slot_count: 4,
+            socket_path: None,
+        }
+    }
+
+    /// Creates a ring for BGRA or NV12 frames of up to `width` by `height` pixels without
+    /// row padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero.
+    pub fn for_size(width: u32, height: u32) -> Self {
+        Self::new(width as usize * height as usize * 4)
+    }
+
+    /// Sets the number of slots, i.e. how many frames a reader can fall behind before it
+    /// skips frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot_count` is less than 2.
+    #[must_use]
+    pub fn with_slot_count(mut self, slot_count: u32) -> Self {
+        assert!(slot_count >= 2, "a frame ring needs at least 2 slots");
+        self.slot_count = slot_count;
+        self
+    }
+
+    /// Wakes readers up over a Unix domain socket at `socket_path` whenever a frame is
+    /// published. Without it, readers poll.
+    #[must_use]
+    pub fn with_notifications(mut self, socket_path: impl Into<PathBuf>) -> Self {
+        self.socket_path = Some(socket_path.into());
+        self
+    }
+
+    /// Creates the ring at `path`, replacing a ring a previous writer left behind. Use a
+    /// path on a memory backed file system, like `/dev/shm` on Linux, to keep frames off
+    /// the disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket path is too long, or the ring or the socket can't
+    /// be created.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn create(self, path: impl AsRef<Path>) -> io::Result<SharedFrameWriter> {
+        let path = path.as_ref();
+        let socket_path = self
+            .socket_path
+            .as_ref()
+            .map_or(&[][..], |socket_path| socket_path.as_os_str().as_bytes());
+        if socket_path.len() > SOCKET_PATH_MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the socket path is too long",
+            ));
+        }
+        let slot_size = slot_size(self.frame_capacity);
+        let len = HEADER_SIZE + self.slot_count as usize * slot_size;
+
+        // Readers only ever see the ring once it's initialized.
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        let temporary = PathBuf::from(temporary);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temporary)?;
+        let initialized = (|| {
+            file.set_len(len as u64)?;
+            let mapping = Mapping::new(&file, len)?;
+            mapping.write(MAGIC_OFFSET, &MAGIC);
+            mapping.write(VERSION_OFFSET, &VERSION.to_ne_bytes());
+            mapping.write(SLOT_COUNT_OFFSET, &self.slot_count.to_ne_bytes());
+            mapping.write(SLOT_SIZE_OFFSET, &(slot_size as u64).to_ne_bytes());
+            mapping
+                .atomic_u32(PRODUCER_PID_OFFSET)
+                .store(std::process::id(), Ordering::Relaxed);
+            mapping.write(
+                SOCKET_PATH_LEN_OFFSET,
+                &(socket_path.len() as u32).to_ne_bytes(),
+            );
+            mapping.write(SOCKET_PATH_OFFSET, socket_path);
+            let notifier = self
+                .socket_path
+                .as_deref()
+                .map(Notifier::bind)
+                .transpose()?;
+            fs::rename(&temporary, path)?;
+            Ok((mapping, notifier))
+        })();
+        let (mapping, notifier) = initialized.map_err(|error: io::Error| {
+            let _ = fs::remove_file(&temporary);
+            error
+        })?;
+        Ok(SharedFrameWriter {
+            mapping,
+            path: path.to_path_buf(),
+            slot_count: self.slot_count,
+            slot_size,
+            published: 0,
+            notifier,
+        })
+    }
+}
+
+/// A reader attached to a [`SharedFrameWriter`]'s ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsumerInfo {
+    pub process_id: i32,
+    /// The index of the next frame the reader reads.
+    pub position: u64,
+}
+
+/// Publishes frames to other processes through a ring of frame slots in a shared memory
+/// file, read with [`SharedFrameReader`](super::SharedFrameReader).
+///
+/// The writer never waits for readers: each frame overwrites the oldest slot, and readers
+/// that fall more than a ring behind skip ahead to the oldest frame still in it. Readers
+/// that die are dropped from [`Self::consumers`] and from the notification socket, so
+/// they can't stall or leak into the writer.
+///
+/// Dropping the writer marks the ring as closed and removes its files. Readers that are
+/// still attached keep their mapping and see the frames that were left in it.
+#[derive(Debug)]
+pub struct SharedFrameWriter {
+    mapping: Mapping,
+    path: PathBuf,
+    slot_count: u32,
+    slot_size: usize,
+    published: u64,
+    notifier: Option<Notifier>,
+}
+
+impl SharedFrameWriter {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the number of bytes of pixels a slot holds.
+    pub const fn frame_capacity(&self) -> usize {
+        self.slot_size - SLOT_HEADER_SIZE
+    }
+
+    pub const fn slot_count(&self) -> u32 {
+        self.slot_count
+    }
+
+    /// Returns the number of frames written, i.e. the index of the next frame.
+    pub const fn published(&self) -> u64 {
+        self.published
+    }
+
+    /// Publishes `frame` and wakes readers up. Returns the index of the frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame's planes don't fit in a slot, or the notification
+    /// socket fails.
+    pub fn write(&mut self, frame: &VideoFrame) -> io::Result<u64> {
+        let header = SlotHeader::of(frame);
+        if header.data_len() > self.frame_capacity() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the frame doesn't fit in a slot",
+            ));
+        }
+        let index = self.published;
+        let slot = slot_offset(index, self.slot_count, self.slot_size);
+        let sequence = self.mapping.atomic_u64(sequence_offset(slot));
+        sequence.store(complete_sequence(index) - 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.mapping
+            .write(slot + FIELDS_OFFSET, &header.encode()[FIELDS_OFFSET..]);
+        for (plane, layout) in frame.planes.iter().zip(&header.planes) {
+            self.mapping
+                .write(slot + SLOT_HEADER_SIZE + layout.offset, &plane.data);
+        }
+        sequence.store(complete_sequence(index), Ordering::Release);
+        self.published += 1;
+        self.mapping
+            .atomic_u64(WRITE_INDEX_OFFSET)
+            .store(self.published, Ordering::Release);
+
+        self.prune();
+        if let Some(notifier) = &mut self.notifier {
+            notifier.notify()?;
+        }
+        Ok(index)
+    }
+
+    /// Returns the attached readers.
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn consumers(&self) -> Vec<ConsumerInfo> {
+        self.prune();
+        (0..MAX_CONSUMERS)
+            .filter_map(|consumer| {
+                let offset = consumer_offset(consumer);
+                let process_id = self.mapping.atomic_u32(offset).load(Ordering::Acquire);
+                (process_id != 0).then(|| ConsumerInfo {
+                    process_id: process_id as i32,
+                    position: self.mapping.atomic_u64(offset + 8).load(Ordering::Relaxed),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of readers connected to the notification socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if pending connections can't be accepted.
+    pub fn notified_count(&mut self) -> io::Result<usize> {
+        self.notifier.as_mut().map_or(Ok(0), |notifier| {
+            notifier.accept()?;
+            Ok(notifier.reader_count())
+        })
+    }
+
+    /// Frees the consumer entries of readers whose process died.
+    #[allow(clippy::cast_possible_wrap)]
+    fn prune(&self) {
+        for consumer in 0..MAX_CONSUMERS {
+            let entry = self.mapping.atomic_u32(consumer_offset(consumer));
+            let process_id = entry.load(Ordering::Acquire);
+            if process_id != 0 && !process_alive(process_id as i32) {
+                let _ = entry.compare_exchange(process_id, 0, Ordering::AcqRel, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for SharedFrameWriter {
+    fn drop(&mut self) {
+        self.mapping
+            .atomic_u32(CLOSED_OFFSET)
+            .store(1, Ordering::Release);
+        // Closing the sockets wakes blocked readers up.
+        self.notifier = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod writer_test {
+    use std::{io, path::PathBuf, time::Duration};
+
+    use crate::{
+        media::{
+            media_time::MediaTime,
+            video_frame::{FrameInfo, PixelRect, VideoFrame},
+        },
+        output::sc_stream_frame_info::SCFrameStatus,
+        sink::shared_memory::SharedFrameReader,
+    };
+
+    use super::SharedFrameRing;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}", std::process::id()))
+    }
+
+    fn frame(index: u8) -> VideoFrame {
+        VideoFrame::filled_bgra(4, 4, [index, 0, 0, 255], MediaTime::new(index.into(), 30))
+    }
+
+    #[test]
+    fn test_ring() {
+        let path = temp_path("sck-writer.ring");
+        let mut writer = SharedFrameRing::for_size(4, 4)
+            .with_slot_count(3)
+            .with_notifications(temp_path("sck-writer.sock"))
+            .create(&path)
+            .unwrap();
+        let mut early = SharedFrameReader::open(&path).unwrap();
+        for index in 0..2 {
+            assert_eq!(writer.write(&frame(index)).unwrap(), index.into());
+        }
+        let first = early.try_read().unwrap().unwrap();
+        assert_eq!((first.index, first.skipped, first.frame), (0, 0, frame(0)));
+        assert_eq!(early.try_read().unwrap().unwrap().index, 1);
+        assert_eq!(early.try_read().unwrap(), None);
+
+        // A late reader starts at the current frame.
+        let mut late = SharedFrameReader::open(&path).unwrap();
+        assert_eq!(late.try_read().unwrap().unwrap().frame, frame(1));
+        assert_eq!(writer.notified_count().unwrap(), 2);
+
+        // Falling behind skips what was overwritten.
+        for index in 2..7 {
+            writer.write(&frame(index)).unwrap();
+        }
+        let next = early.try_read().unwrap().unwrap();
+        assert_eq!((next.index, next.skipped), (4, 2));
+        let positions: Vec<u64> = writer.consumers().iter().map(|c| c.position).collect();
+        assert_eq!(positions, [5, 2]);
+
+        let nv12 = VideoFrame::new_nv12(4, 4, vec![16; 16], vec![128; 8], MediaTime::ZERO)
+            .with_info(FrameInfo {
+                status: SCFrameStatus::Idle,
+                display_time: Some(42),
+                dirty_rects: Some(vec![PixelRect::new(0, 0, 2, 2)]),
+            });
+        writer.write(&nv12).unwrap();
+        let too_large = VideoFrame::filled_bgra(5, 4, [0; 4], MediaTime::ZERO);
+        let error = writer.write(&too_large).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(writer.published(), 8);
+
+        drop(late);
+        assert_eq!(writer.consumers().len(), 1);
+        writer.write(&frame(8)).unwrap();
+        assert_eq!(writer.notified_count().unwrap(), 1);
+
+        drop(writer);
+        assert!(!path.exists());
+        assert!(!early.is_writer_alive());
+        let read = early.read(Duration::from_secs(1)).unwrap().unwrap();
+        assert_eq!((read.index, read.skipped), (6, 1));
+        assert_eq!(
+            early.read(Duration::from_secs(1)).unwrap().unwrap().frame,
+            nv12
+        );
+        assert_eq!(
+            early.read(Duration::from_secs(1)).unwrap().unwrap().frame,
+            frame(8)
+        );
+        let error = early.read(Duration::from_secs(1)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -107,6 +107,20 @@ impl SCStream {
                 .map_err(|_| create_sc_error("Could not receive from completion handler"))?
         }
     }
+    /// Replaces the content filter of the running [`SCStream`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the stream rejects the filter.
+    pub fn internal_update_content_filter(&self, filter: &SCContentFilter) -> Result<(), CFError> {
+        unsafe {
+            let CompletionHandler(handler, rx) = new_void_completion_handler();
+            let _: () = msg_send![self.as_CFTypeRef().cast::<Object>(), updateContentFilter: filter.clone().as_CFTypeRef() completionHandler: handler];
+
+            rx.recv()
+                .map_err(|_| create_sc_error("Could not receive from completion handler"))?
+        }
+    }
     /// Returns the internal stop capture of this [`SCStream`].
     ///
     /// # Errors
@@ -47,6 +47,15 @@ impl SCStream {
     pub fn start_capture(&self) -> Result<(), CFError> {
         self.internal_start_capture()
     }
+    /// Switches the running [`SCStream`] to capture the content of `filter`, keeping its
+    /// configuration and output handlers.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the stream rejects the filter.
+    pub fn update_content_filter(&self, filter: &SCContentFilter) -> Result<(), CFError> {
+        self.internal_update_content_filter(filter)
+    }
     /// Returns the stop capture of this [`SCStream`].
     ///
     /// # Errors
@@ -35,6 +35,7 @@ mod internal {
 }
 
 use core_foundation::{boolean::CFBoolean, error::CFError};
+use core_media_rs::cm_time::CMTime;
 pub use internal::SCStreamConfiguration;
 use objc::{sel, sel_impl};
 
@@ -77,6 +78,20 @@ impl SCStreamConfiguration {
     pub fn get_captures_audio(&self) -> bool {
         get_property(self, sel!(capturesAudio))
     }
+    /// Sets showsCursor of this [`SCStreamConfiguration`]. Turn it off to record the cursor
+    /// separately with a [`CursorRecorder`](crate::pipeline::cursor::CursorRecorder).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `showsCursor` property could not be set.
+    pub fn set_shows_cursor(mut self, shows_cursor: bool) -> Result<Self, CFError> {
+        set_property(&mut self, sel!(setShowsCursor:), shows_cursor)?;
+        Ok(self)
+    }
+    /// Returns showsCursor of this [`SCStreamConfiguration`].
+    pub fn get_shows_cursor(&self) -> bool {
+        get_property(self, sel!(showsCursor))
+    }
     /// Sets capturesAudio of this [`SCStreamConfiguration`].
     ///
     /// # Errors
@@ -105,6 +120,36 @@ impl SCStreamConfiguration {
     pub fn get_channel_count(&self) -> u8 {
         get_property(self, sel!(channelCount))
     }
+    /// Sets the audio sample rate of this [`SCStreamConfiguration`], one of 8000, 16000,
+    /// 24000 and 48000 Hz.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `sampleRate` property could not be set.
+    pub fn set_sample_rate(mut self, sample_rate: u32) -> Result<Self, CFError> {
+        set_property(&mut self, sel!(setSampleRate:), i64::from(sample_rate))?;
+        Ok(self)
+    }
+    /// Returns the audio sample rate of this [`SCStreamConfiguration`], in Hz.
+    pub fn get_sample_rate(&self) -> u32 {
+        let sample_rate: i64 = get_property(self, sel!(sampleRate));
+        u32::try_from(sample_rate).unwrap_or_default()
+    }
+    /// Sets the shortest time between frames of this [`SCStreamConfiguration`], which caps
+    /// the frame rate.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `minimumFrameInterval` property could not be
+    /// set.
+    pub fn set_minimum_frame_interval(mut self, interval: CMTime) -> Result<Self, CFError> {
+        set_property(&mut self, sel!(setMinimumFrameInterval:), interval)?;
+        Ok(self)
+    }
+    /// Returns the shortest time between frames of this [`SCStreamConfiguration`].
+    pub fn get_minimum_frame_interval(&self) -> CMTime {
+        get_property(self, sel!(minimumFrameInterval))
+    }
 }
 
 impl Default for SCStreamConfiguration {
@@ -116,6 +161,7 @@ impl Default for SCStreamConfiguration {
 #[cfg(test)]
 mod sc_stream_configuration_test {
     use core_foundation::error::CFError;
+    use core_media_rs::cm_time::CMTime;
 
     use super::SCStreamConfiguration;
 
@@ -123,6 +169,14 @@ mod sc_stream_configuration_test {
     fn test_setters() -> Result<(), CFError> {
         SCStreamConfiguration::new()
             .set_captures_audio(true)?
+            .set_shows_cursor(false)?
+            .set_sample_rate(16_000)?
+            .set_minimum_frame_interval(CMTime {
+                value: 1,
+                timescale: 2,
+                flags: 1,
+                epoch: 0,
+            })?
             .set_width(100)?
             .set_height(100)?;
         Ok(())
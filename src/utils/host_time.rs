@@ -0,0 +1,32 @@
+//! The host clock of `mach_absolute_time`, which display times and sample times count in.
+use std::ptr;
+
+#[repr(C)]
+#[derive(Default)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+extern "C" {
+    fn mach_absolute_time() -> u64;
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+}
+
+/// Returns the current host time.
+pub fn now() -> u64 {
+    unsafe { mach_absolute_time() }
+}
+
+/// Returns the ratio from host time units to nanoseconds as `(numer, denom)`.
+pub fn timebase() -> (u32, u32) {
+    let mut timebase = MachTimebaseInfo::default();
+    unsafe { mach_timebase_info(ptr::addr_of_mut!(timebase)) };
+    (timebase.numer, timebase.denom.max(1))
+}
+
+/// Converts a host time to nanoseconds.
+pub fn host_time_to_nanos(host_time: u64) -> u128 {
+    let (numer, denom) = timebase();
+    u128::from(host_time) * u128::from(numer) / u128::from(denom)
+}
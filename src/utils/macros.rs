@@ -0,0 +1,94 @@
+/// Like `core_foundation::impl_TCFType!`, but compares and hashes by the ID returned from
+/// the given method instead of by object identity.
+///
+/// Every call to `SCShareableContent::get` returns new objects for the same windows,
+/// displays and applications, so only their IDs are stable enough to key maps with. Each
+/// `eq` and `hash` call sends the ID method as an Objective-C message, so look the ID up once
+/// when comparing in a loop.
+macro_rules! impl_TCFType_with_id {
+    ($ty:ident, $ty_ref:ident, $ty_id:ident, $id:ident) => {
+        impl ::core_foundation::base::TCFType for $ty {
+            type Ref = $ty_ref;
+
+            #[allow(non_snake_case)]
+            #[inline]
+            fn as_concrete_TypeRef(&self) -> $ty_ref {
+                self.0
+            }
+
+            #[inline]
+            unsafe fn wrap_under_get_rule(reference: $ty_ref) -> Self {
+                assert!(!reference.is_null(), "Attempted to create a NULL object.");
+                let reference =
+                    ::core_foundation::base::CFRetain(reference as *const ::core::ffi::c_void)
+                        as $ty_ref;
+                ::core_foundation::base::TCFType::wrap_under_create_rule(reference)
+            }
+
+            #[allow(non_snake_case)]
+            #[inline]
+            fn as_CFTypeRef(&self) -> ::core_foundation::base::CFTypeRef {
+                self.as_concrete_TypeRef() as ::core_foundation::base::CFTypeRef
+            }
+
+            #[inline]
+            unsafe fn wrap_under_create_rule(reference: $ty_ref) -> Self {
+                assert!(!reference.is_null(), "Attempted to create a NULL object.");
+                $ty(reference)
+            }
+
+            #[inline]
+            fn type_id() -> ::core_foundation::base::CFTypeID {
+                unsafe { $ty_id() }
+            }
+        }
+
+        unsafe impl ::core_foundation::ConcreteCFType for $ty {}
+
+        impl Clone for $ty {
+            #[inline]
+            fn clone(&self) -> Self {
+                unsafe { ::core_foundation::base::TCFType::wrap_under_get_rule(self.0) }
+            }
+        }
+
+        impl PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                self.$id() == other.$id()
+            }
+        }
+
+        impl Eq for $ty {}
+
+        #[allow(unused_imports)]
+        unsafe impl<'a> ::core_foundation::base::ToVoid<$ty> for &'a $ty {
+            fn to_void(&self) -> *const ::core::ffi::c_void {
+                use ::core_foundation::base::{TCFType, TCFTypeRef};
+                self.as_concrete_TypeRef().as_void_ptr()
+            }
+        }
+
+        #[allow(unused_imports)]
+        unsafe impl ::core_foundation::base::ToVoid<$ty> for $ty {
+            fn to_void(&self) -> *const ::core::ffi::c_void {
+                use ::core_foundation::base::{TCFType, TCFTypeRef};
+                self.as_concrete_TypeRef().as_void_ptr()
+            }
+        }
+
+        #[allow(unused_imports)]
+        unsafe impl ::core_foundation::base::ToVoid<$ty> for $ty_ref {
+            fn to_void(&self) -> *const ::core::ffi::c_void {
+                use ::core_foundation::base::TCFTypeRef;
+                self.as_void_ptr()
+            }
+        }
+
+        impl ::std::hash::Hash for $ty {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                ::std::hash::Hash::hash(&self.$id(), state);
+            }
+        }
+    };
+}
+pub(crate) use impl_TCFType_with_id;
@@ -1,6 +1,8 @@
 // pub mod as_ptr;
 pub mod block;
-// pub mod macros;
+pub mod macros;
 pub mod error;
 pub mod hash;
+#[cfg(target_os = "macos")]
+pub(crate) mod host_time;
 pub mod objc;